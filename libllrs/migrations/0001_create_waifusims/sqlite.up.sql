@@ -0,0 +1,38 @@
+CREATE TABLE Author (
+    AuthorID INTEGER PRIMARY KEY,
+    AuthorName TEXT NOT NULL
+);
+
+CREATE TABLE Manga (
+    MangaID INTEGER PRIMARY KEY,
+    MangaName TEXT NOT NULL,
+    AuthorID INTEGER NOT NULL REFERENCES Author (AuthorID),
+    CoverImageURL TEXT,
+    PurchaseURL TEXT
+);
+
+CREATE TABLE MangaChapter (
+    MangaID INTEGER NOT NULL REFERENCES Manga (MangaID),
+    ChapterIndex INTEGER NOT NULL,
+    ChapterNumber TEXT NOT NULL,
+    ChapterName TEXT NOT NULL,
+    DateCreated TEXT NOT NULL,
+    DateReleased TEXT,
+    PRIMARY KEY (MangaID, ChapterIndex),
+    UNIQUE (MangaID, ChapterNumber)
+);
+
+CREATE TABLE Page (
+    PageID INTEGER PRIMARY KEY,
+    MangaID INTEGER NOT NULL,
+    ChapterIndex INTEGER NOT NULL,
+    PageNumber INTEGER NOT NULL,
+    FOREIGN KEY (MangaID, ChapterIndex) REFERENCES MangaChapter (MangaID, ChapterIndex)
+);
+
+CREATE TABLE PageURL (
+    PageID INTEGER NOT NULL REFERENCES Page (PageID),
+    URL TEXT NOT NULL,
+    Priority INTEGER NOT NULL,
+    PRIMARY KEY (PageID, Priority)
+);
@@ -0,0 +1,5 @@
+DROP TABLE PageURL;
+DROP TABLE Page;
+DROP TABLE MangaChapter;
+DROP TABLE Manga;
+DROP TABLE Author;
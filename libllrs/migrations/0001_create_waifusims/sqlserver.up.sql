@@ -0,0 +1,39 @@
+CREATE TABLE Author (
+    AuthorID INT IDENTITY(1, 1) NOT NULL CONSTRAINT PK_Author PRIMARY KEY,
+    AuthorName NVARCHAR(255) NOT NULL
+);
+
+CREATE TABLE Manga (
+    MangaID INT IDENTITY(1, 1) NOT NULL CONSTRAINT PK_Manga PRIMARY KEY,
+    MangaName NVARCHAR(255) NOT NULL,
+    AuthorID INT NOT NULL CONSTRAINT FK_Manga_Author REFERENCES Author (AuthorID),
+    CoverImageURL NVARCHAR(2048) NULL,
+    PurchaseURL NVARCHAR(2048) NULL
+);
+
+CREATE TABLE MangaChapter (
+    MangaID INT NOT NULL CONSTRAINT FK_MangaChapter_Manga REFERENCES Manga (MangaID),
+    ChapterIndex INT NOT NULL,
+    ChapterNumber NVARCHAR(32) NOT NULL,
+    ChapterName NVARCHAR(255) NOT NULL,
+    DateCreated DATETIME2 NOT NULL,
+    DateReleased DATETIME2 NULL,
+    CONSTRAINT PK_MangaChapter PRIMARY KEY (MangaID, ChapterIndex),
+    CONSTRAINT UQ_MangaChapter_ChapterNumber UNIQUE (MangaID, ChapterNumber)
+);
+
+CREATE TABLE Page (
+    PageID INT IDENTITY(1, 1) NOT NULL CONSTRAINT PK_Page PRIMARY KEY,
+    MangaID INT NOT NULL,
+    ChapterIndex INT NOT NULL,
+    PageNumber INT NOT NULL,
+    CONSTRAINT FK_Page_MangaChapter FOREIGN KEY (MangaID, ChapterIndex)
+        REFERENCES MangaChapter (MangaID, ChapterIndex)
+);
+
+CREATE TABLE PageURL (
+    PageID INT NOT NULL CONSTRAINT FK_PageURL_Page REFERENCES Page (PageID),
+    URL NVARCHAR(2048) NOT NULL,
+    Priority INT NOT NULL,
+    CONSTRAINT PK_PageURL PRIMARY KEY (PageID, Priority)
+);
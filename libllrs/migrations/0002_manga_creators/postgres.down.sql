@@ -0,0 +1,14 @@
+-- Manga without an Author credit are left with a NULL AuthorID
+ALTER TABLE Manga ADD COLUMN AuthorID INTEGER REFERENCES Author (AuthorID);
+
+UPDATE Manga m
+SET AuthorID = (
+    SELECT c.AuthorID
+    FROM MangaCreator c
+    WHERE c.MangaID = m.MangaID
+        AND c.Role = 'Author'
+    ORDER BY c.CreditOrder
+    LIMIT 1
+);
+
+DROP TABLE MangaCreator;
@@ -0,0 +1,14 @@
+-- Manga.AuthorID becomes the first Author credit of each manga
+CREATE TABLE MangaCreator (
+    MangaID INTEGER NOT NULL REFERENCES Manga (MangaID),
+    AuthorID INTEGER NOT NULL REFERENCES Author (AuthorID),
+    Role TEXT NOT NULL CHECK (Role IN ('Author', 'Artist')),
+    CreditOrder INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY (MangaID, AuthorID, Role)
+);
+
+INSERT INTO MangaCreator (MangaID, AuthorID, Role, CreditOrder)
+SELECT MangaID, AuthorID, 'Author', 0
+FROM Manga;
+
+ALTER TABLE Manga DROP COLUMN AuthorID;
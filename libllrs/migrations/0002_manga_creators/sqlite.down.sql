@@ -0,0 +1,30 @@
+-- Manga without an Author credit are left with a NULL AuthorID
+CREATE TABLE OldManga (
+    MangaID INTEGER PRIMARY KEY,
+    MangaName TEXT NOT NULL,
+    AuthorID INTEGER REFERENCES Author (AuthorID),
+    CoverImageURL TEXT,
+    PurchaseURL TEXT
+);
+
+INSERT INTO OldManga (MangaID, MangaName, AuthorID, CoverImageURL, PurchaseURL)
+SELECT
+    m.MangaID,
+    m.MangaName,
+    (
+        SELECT c.AuthorID
+        FROM MangaCreator c
+        WHERE c.MangaID = m.MangaID
+            AND c.Role = 'Author'
+        ORDER BY c.CreditOrder
+        LIMIT 1
+    ),
+    m.CoverImageURL,
+    m.PurchaseURL
+FROM Manga m;
+
+DROP TABLE MangaCreator;
+
+DROP TABLE Manga;
+
+ALTER TABLE OldManga RENAME TO Manga;
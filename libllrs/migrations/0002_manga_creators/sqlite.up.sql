@@ -0,0 +1,28 @@
+-- Manga.AuthorID becomes the first Author credit of each manga
+CREATE TABLE MangaCreator (
+    MangaID INTEGER NOT NULL REFERENCES Manga (MangaID),
+    AuthorID INTEGER NOT NULL REFERENCES Author (AuthorID),
+    Role TEXT NOT NULL CHECK (Role IN ('Author', 'Artist')),
+    CreditOrder INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY (MangaID, AuthorID, Role)
+);
+
+INSERT INTO MangaCreator (MangaID, AuthorID, Role, CreditOrder)
+SELECT MangaID, AuthorID, 'Author', 0
+FROM Manga;
+
+-- SQLite can't drop a column with a foreign key, so Manga is rebuilt without it
+CREATE TABLE NewManga (
+    MangaID INTEGER PRIMARY KEY,
+    MangaName TEXT NOT NULL,
+    CoverImageURL TEXT,
+    PurchaseURL TEXT
+);
+
+INSERT INTO NewManga (MangaID, MangaName, CoverImageURL, PurchaseURL)
+SELECT MangaID, MangaName, CoverImageURL, PurchaseURL
+FROM Manga;
+
+DROP TABLE Manga;
+
+ALTER TABLE NewManga RENAME TO Manga;
@@ -0,0 +1,15 @@
+-- Manga without an Author credit are left with a NULL AuthorID
+ALTER TABLE Manga ADD AuthorID INT NULL CONSTRAINT FK_Manga_Author REFERENCES Author (AuthorID);
+GO
+
+UPDATE m
+SET AuthorID = (
+    SELECT TOP 1 c.AuthorID
+    FROM MangaCreator c
+    WHERE c.MangaID = m.MangaID
+        AND c.Role = N'Author'
+    ORDER BY c.CreditOrder
+)
+FROM Manga m;
+
+DROP TABLE MangaCreator;
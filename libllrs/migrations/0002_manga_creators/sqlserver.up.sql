@@ -0,0 +1,26 @@
+-- Manga.AuthorID becomes the first Author credit of each manga
+CREATE TABLE MangaCreator (
+    MangaID INT NOT NULL CONSTRAINT FK_MangaCreator_Manga REFERENCES Manga (MangaID),
+    AuthorID INT NOT NULL CONSTRAINT FK_MangaCreator_Author REFERENCES Author (AuthorID),
+    Role NVARCHAR(16) NOT NULL CONSTRAINT CK_MangaCreator_Role CHECK (Role IN (N'Author', N'Artist')),
+    CreditOrder INT NOT NULL CONSTRAINT DF_MangaCreator_CreditOrder DEFAULT 0,
+    CONSTRAINT PK_MangaCreator PRIMARY KEY (MangaID, AuthorID, Role)
+);
+
+INSERT INTO MangaCreator (MangaID, AuthorID, Role, CreditOrder)
+SELECT MangaID, AuthorID, N'Author', 0
+FROM Manga;
+
+-- Databases created before migrations may have a system named foreign key,
+-- so it's found by its column rather than assuming FK_Manga_Author
+DECLARE @drop_foreign_key NVARCHAR(MAX) = (
+    SELECT TOP 1 N'ALTER TABLE Manga DROP CONSTRAINT ' + QUOTENAME(fk.name)
+    FROM sys.foreign_keys fk
+    JOIN sys.foreign_key_columns fkc ON fkc.constraint_object_id = fk.object_id
+    WHERE fk.parent_object_id = OBJECT_ID(N'Manga')
+        AND COL_NAME(fkc.parent_object_id, fkc.parent_column_id) = N'AuthorID'
+);
+IF @drop_foreign_key IS NOT NULL
+    EXEC sp_executesql @drop_foreign_key;
+
+ALTER TABLE Manga DROP COLUMN AuthorID;
@@ -0,0 +1,3 @@
+ALTER TABLE Manga
+    DROP COLUMN DateCreated,
+    DROP COLUMN Status;
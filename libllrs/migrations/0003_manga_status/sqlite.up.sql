@@ -0,0 +1,5 @@
+-- Existing manga have no known creation date
+ALTER TABLE Manga ADD COLUMN Status TEXT NOT NULL DEFAULT 'Ongoing'
+    CHECK (Status IN ('Ongoing', 'Completed', 'Hiatus', 'Cancelled'));
+
+ALTER TABLE Manga ADD COLUMN DateCreated TEXT;
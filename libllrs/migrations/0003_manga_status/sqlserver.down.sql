@@ -0,0 +1,3 @@
+ALTER TABLE Manga DROP CONSTRAINT CK_Manga_Status, DF_Manga_Status;
+
+ALTER TABLE Manga DROP COLUMN DateCreated, Status;
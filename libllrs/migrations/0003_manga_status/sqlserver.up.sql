@@ -0,0 +1,6 @@
+-- Existing manga have no known creation date
+ALTER TABLE Manga ADD
+    Status NVARCHAR(16) NOT NULL
+        CONSTRAINT DF_Manga_Status DEFAULT N'Ongoing'
+        CONSTRAINT CK_Manga_Status CHECK (Status IN (N'Ongoing', N'Completed', N'Hiatus', N'Cancelled')),
+    DateCreated DATETIME2 NULL;
@@ -0,0 +1,5 @@
+CREATE TABLE MangaAlternateTitle (
+    MangaID INTEGER NOT NULL REFERENCES Manga (MangaID),
+    Title TEXT NOT NULL,
+    PRIMARY KEY (MangaID, Title)
+);
@@ -0,0 +1,5 @@
+CREATE TABLE MangaAlternateTitle (
+    MangaID INT NOT NULL CONSTRAINT FK_MangaAlternateTitle_Manga REFERENCES Manga (MangaID),
+    Title NVARCHAR(255) NOT NULL,
+    CONSTRAINT PK_MangaAlternateTitle PRIMARY KEY (MangaID, Title)
+);
@@ -0,0 +1,3 @@
+ALTER TABLE MangaChapter DROP COLUMN UpdatedDate;
+
+ALTER TABLE Manga DROP COLUMN UpdatedDate;
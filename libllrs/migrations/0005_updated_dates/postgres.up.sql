@@ -0,0 +1,8 @@
+-- Earlier changes weren't recorded, so existing rows count as changed now
+ALTER TABLE Manga ADD COLUMN UpdatedDate TIMESTAMP;
+
+ALTER TABLE MangaChapter ADD COLUMN UpdatedDate TIMESTAMP;
+
+UPDATE Manga SET UpdatedDate = date_trunc('second', timezone('utc', now()));
+
+UPDATE MangaChapter SET UpdatedDate = date_trunc('second', timezone('utc', now()));
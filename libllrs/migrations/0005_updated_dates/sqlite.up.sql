@@ -0,0 +1,8 @@
+-- Earlier changes weren't recorded, so existing rows count as changed now
+ALTER TABLE Manga ADD COLUMN UpdatedDate TEXT;
+
+ALTER TABLE MangaChapter ADD COLUMN UpdatedDate TEXT;
+
+UPDATE Manga SET UpdatedDate = strftime('%Y-%m-%d %H:%M:%S', 'now');
+
+UPDATE MangaChapter SET UpdatedDate = strftime('%Y-%m-%d %H:%M:%S', 'now');
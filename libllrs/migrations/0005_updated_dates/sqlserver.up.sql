@@ -0,0 +1,9 @@
+-- Earlier changes weren't recorded, so existing rows count as changed now
+ALTER TABLE Manga ADD UpdatedDate DATETIME2 NULL;
+
+ALTER TABLE MangaChapter ADD UpdatedDate DATETIME2 NULL;
+GO
+
+UPDATE Manga SET UpdatedDate = CAST(SYSUTCDATETIME() AS DATETIME2(0));
+
+UPDATE MangaChapter SET UpdatedDate = CAST(SYSUTCDATETIME() AS DATETIME2(0));
@@ -0,0 +1,640 @@
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use chrono::NaiveDateTime;
+
+use crate::{
+    Chapter, Creator, Manga, MangaListQuery, MangaListing, MangaService, NewChapter, NewManga,
+    Page, RecentChapter, Result, SearchResult,
+};
+
+/// How long and how many of each kind of result a `MangaCache` keeps.
+/// A zero TTL or size turns caching off for that kind.
+#[derive(Debug, Clone)]
+pub struct CacheConfig {
+    /// `get_all_manga_titles`, `list_manga` and `get_manga` results
+    pub manga_list_ttl: Duration,
+    pub max_manga_lists: usize,
+    pub max_mangas: usize,
+    pub chapter_list_ttl: Duration,
+    pub max_chapter_lists: usize,
+    /// Pages of released chapters rarely change, so these can be kept much longer
+    pub page_list_ttl: Duration,
+    pub max_page_lists: usize,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        CacheConfig {
+            manga_list_ttl: Duration::from_secs(60),
+            max_manga_lists: 256,
+            max_mangas: 1024,
+            chapter_list_ttl: Duration::from_secs(60),
+            max_chapter_lists: 1024,
+            page_list_ttl: Duration::from_secs(60 * 60),
+            max_page_lists: 4096,
+        }
+    }
+}
+
+/// Entries expire after `ttl`, the oldest entry is evicted once `capacity` is reached.
+#[derive(Debug)]
+struct TtlMap<K, V> {
+    entries: HashMap<K, (Instant, V)>,
+    ttl: Duration,
+    capacity: usize,
+}
+
+impl<K: Eq + Hash + Clone, V: Clone> TtlMap<K, V> {
+    fn new(ttl: Duration, capacity: usize) -> TtlMap<K, V> {
+        TtlMap {
+            entries: HashMap::new(),
+            ttl,
+            capacity,
+        }
+    }
+
+    fn get(&self, key: &K, now: Instant) -> Option<V> {
+        match self.entries.get(key) {
+            Some((inserted, value)) if now.duration_since(*inserted) < self.ttl => {
+                Some(value.clone())
+            }
+            _ => None,
+        }
+    }
+
+    fn insert(&mut self, key: K, value: V, now: Instant) {
+        if self.capacity == 0 || self.ttl.is_zero() {
+            return;
+        }
+        if self.entries.len() >= self.capacity && !self.entries.contains_key(&key) {
+            let ttl = self.ttl;
+            self.entries
+                .retain(|_, (inserted, _)| now.duration_since(*inserted) < ttl);
+        }
+        if self.entries.len() >= self.capacity && !self.entries.contains_key(&key) {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, (inserted, _))| *inserted)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.entries.insert(key, (now, value));
+    }
+
+    fn retain(&mut self, mut keep: impl FnMut(&K) -> bool) {
+        self.entries.retain(|key, _| keep(key));
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum MangaListKey {
+    All,
+    Query(MangaListQuery),
+}
+
+#[derive(Debug, Clone)]
+enum MangaList {
+    All(Vec<Manga>),
+    Listing(MangaListing),
+}
+
+#[derive(Debug)]
+struct Entries {
+    manga_lists: TtlMap<MangaListKey, MangaList>,
+    /// `None` for IDs without a manga, creating one invalidates them like any other manga
+    mangas: TtlMap<i32, Option<Manga>>,
+    chapter_lists: TtlMap<i32, Vec<Chapter>>,
+    page_lists: TtlMap<(i32, String), Vec<Page>>,
+    /// Bumped by every invalidation, so a read that raced a write doesn't cache what it read
+    generation: u64,
+}
+
+/// Results cached by `CachedMangaService`, cloning shares the same entries.
+#[derive(Debug, Clone)]
+pub struct MangaCache {
+    entries: Arc<Mutex<Entries>>,
+}
+
+impl MangaCache {
+    pub fn new(config: CacheConfig) -> MangaCache {
+        let entries = Entries {
+            manga_lists: TtlMap::new(config.manga_list_ttl, config.max_manga_lists),
+            mangas: TtlMap::new(config.manga_list_ttl, config.max_mangas),
+            chapter_lists: TtlMap::new(config.chapter_list_ttl, config.max_chapter_lists),
+            page_lists: TtlMap::new(config.page_list_ttl, config.max_page_lists),
+            generation: 0,
+        };
+        MangaCache {
+            entries: Arc::new(Mutex::new(entries)),
+        }
+    }
+
+    // Never held across an await, a panic can't leave entries half written
+    fn entries(&self) -> MutexGuard<'_, Entries> {
+        self.entries
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Drops every cached result, eg: after editing the database directly.
+    pub fn invalidate_all(&self) {
+        let mut entries = self.entries();
+        entries.generation += 1;
+        entries.manga_lists.clear();
+        entries.mangas.clear();
+        entries.chapter_lists.clear();
+        entries.page_lists.clear();
+    }
+
+    /// Drops every manga list and listing, their order and filters span all manga,
+    /// along with every cached manga.
+    pub fn invalidate_manga_lists(&self) {
+        let mut entries = self.entries();
+        entries.generation += 1;
+        entries.manga_lists.clear();
+        entries.mangas.clear();
+    }
+
+    /// Drops the manga's chapter list and the page lists of all of its chapters.
+    pub fn invalidate_manga(&self, manga_id: i32) {
+        let mut entries = self.entries();
+        entries.generation += 1;
+        entries
+            .chapter_lists
+            .retain(|cached_manga_id| *cached_manga_id != manga_id);
+        entries
+            .page_lists
+            .retain(|(cached_manga_id, _)| *cached_manga_id != manga_id);
+    }
+
+    /// Drops the page list of one chapter.
+    pub fn invalidate_chapter(&self, manga_id: i32, chapter_number: &str) {
+        let mut entries = self.entries();
+        entries.generation += 1;
+        entries
+            .page_lists
+            .retain(|(cached_manga_id, cached_chapter_number)| {
+                !(*cached_manga_id == manga_id && cached_chapter_number == chapter_number)
+            });
+    }
+
+    fn generation(&self) -> u64 {
+        self.entries().generation
+    }
+
+    /// Caches what a read returned unless the cache was invalidated while reading.
+    fn insert_since(&self, generation: u64, insert: impl FnOnce(&mut Entries, Instant)) {
+        let mut entries = self.entries();
+        if entries.generation == generation {
+            insert(&mut entries, Instant::now());
+        }
+    }
+}
+
+impl Default for MangaCache {
+    fn default() -> Self {
+        MangaCache::new(CacheConfig::default())
+    }
+}
+
+/// Serves manga, chapter and page lists from a `MangaCache` in front of another `MangaService`.
+///
+/// Writes through this service invalidate what they change.
+/// Writes made any other way are only seen once their entries expire,
+/// unless the cache is invalidated explicitly.
+#[derive(Debug, Clone)]
+pub struct CachedMangaService<S> {
+    inner: S,
+    cache: MangaCache,
+}
+
+impl<S> CachedMangaService<S> {
+    pub fn new(inner: S, cache: MangaCache) -> CachedMangaService<S> {
+        CachedMangaService { inner, cache }
+    }
+
+    pub fn cache(&self) -> &MangaCache {
+        &self.cache
+    }
+
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+#[async_trait]
+impl<S: MangaService<i32> + Send> MangaService<i32> for CachedMangaService<S> {
+    async fn get_all_manga_titles(&mut self) -> Result<Vec<Manga>> {
+        let key = MangaListKey::All;
+        if let Some(MangaList::All(mangas)) =
+            self.cache.entries().manga_lists.get(&key, Instant::now())
+        {
+            return Ok(mangas);
+        }
+        let generation = self.cache.generation();
+        let mangas = self.inner.get_all_manga_titles().await?;
+        self.cache.insert_since(generation, |entries, now| {
+            entries
+                .manga_lists
+                .insert(key, MangaList::All(mangas.clone()), now)
+        });
+        Ok(mangas)
+    }
+
+    async fn list_manga(&mut self, query: &MangaListQuery) -> Result<MangaListing> {
+        let key = MangaListKey::Query(query.clone());
+        if let Some(MangaList::Listing(listing)) =
+            self.cache.entries().manga_lists.get(&key, Instant::now())
+        {
+            return Ok(listing);
+        }
+        let generation = self.cache.generation();
+        let listing = self.inner.list_manga(query).await?;
+        self.cache.insert_since(generation, |entries, now| {
+            entries
+                .manga_lists
+                .insert(key, MangaList::Listing(listing.clone()), now)
+        });
+        Ok(listing)
+    }
+
+    async fn search(&mut self, query: &str, limit: u32) -> Result<Vec<SearchResult>> {
+        self.inner.search(query, limit).await
+    }
+
+    async fn get_manga(&mut self, manga_id: i32) -> Result<Option<Manga>> {
+        if let Some(manga) = self.cache.entries().mangas.get(&manga_id, Instant::now()) {
+            return Ok(manga);
+        }
+        let generation = self.cache.generation();
+        let manga = self.inner.get_manga(manga_id).await?;
+        self.cache.insert_since(generation, |entries, now| {
+            entries.mangas.insert(manga_id, manga.clone(), now)
+        });
+        Ok(manga)
+    }
+
+    async fn get_manga_creators(&mut self, manga_id: i32) -> Result<Vec<Creator>> {
+        self.inner.get_manga_creators(manga_id).await
+    }
+
+    async fn get_manga_chapters(&mut self, manga_id: i32) -> Result<Vec<Chapter>> {
+        if let Some(chapters) = self
+            .cache
+            .entries()
+            .chapter_lists
+            .get(&manga_id, Instant::now())
+        {
+            return Ok(chapters);
+        }
+        let generation = self.cache.generation();
+        let chapters = self.inner.get_manga_chapters(manga_id).await?;
+        self.cache.insert_since(generation, |entries, now| {
+            entries
+                .chapter_lists
+                .insert(manga_id, chapters.clone(), now)
+        });
+        Ok(chapters)
+    }
+
+    /// Only the manga without a cached chapter list are queried, in one batch.
+    async fn get_mangas_chapters(&mut self, manga_ids: &[i32]) -> Result<Vec<Vec<Chapter>>> {
+        let mut chapter_lists = {
+            let entries = self.cache.entries();
+            let now = Instant::now();
+            manga_ids
+                .iter()
+                .map(|manga_id| entries.chapter_lists.get(manga_id, now))
+                .collect::<Vec<Option<Vec<Chapter>>>>()
+        };
+        let missing = manga_ids
+            .iter()
+            .zip(&chapter_lists)
+            .filter(|(_, chapters)| chapters.is_none())
+            .map(|(manga_id, _)| *manga_id)
+            .collect::<Vec<i32>>();
+        if !missing.is_empty() {
+            let generation = self.cache.generation();
+            let fetched = self.inner.get_mangas_chapters(&missing).await?;
+            self.cache.insert_since(generation, |entries, now| {
+                for (manga_id, chapters) in missing.iter().zip(&fetched) {
+                    entries
+                        .chapter_lists
+                        .insert(*manga_id, chapters.clone(), now);
+                }
+            });
+            let mut fetched = fetched.into_iter();
+            for chapters in chapter_lists
+                .iter_mut()
+                .filter(|chapters| chapters.is_none())
+            {
+                *chapters = fetched.next();
+            }
+        }
+        Ok(chapter_lists
+            .into_iter()
+            .map(Option::unwrap_or_default)
+            .collect())
+    }
+
+    /// Picked out of a cached chapter list when there is one.
+    async fn get_chapter(
+        &mut self,
+        manga_id: i32,
+        chapter_number: &str,
+    ) -> Result<Option<Chapter>> {
+        let chapters = self
+            .cache
+            .entries()
+            .chapter_lists
+            .get(&manga_id, Instant::now());
+        match chapters {
+            Some(chapters) => Ok(chapters
+                .into_iter()
+                .find(|chapter| chapter.chapter_number.as_str() == chapter_number)),
+            None => self.inner.get_chapter(manga_id, chapter_number).await,
+        }
+    }
+
+    /// Not cached, `since` differs between almost every poll.
+    async fn get_recent_chapters(
+        &mut self,
+        limit: u32,
+        since: Option<NaiveDateTime>,
+    ) -> Result<Vec<RecentChapter>> {
+        self.inner.get_recent_chapters(limit, since).await
+    }
+
+    async fn get_pages(&mut self, manga_id: i32, chapter_number: &str) -> Result<Vec<Page>> {
+        let key = (manga_id, chapter_number.to_owned());
+        if let Some(pages) = self.cache.entries().page_lists.get(&key, Instant::now()) {
+            return Ok(pages);
+        }
+        let generation = self.cache.generation();
+        let pages = self.inner.get_pages(manga_id, chapter_number).await?;
+        self.cache.insert_since(generation, |entries, now| {
+            entries.page_lists.insert(key, pages.clone(), now)
+        });
+        Ok(pages)
+    }
+
+    /// Only the chapters without a cached page list are queried, in one batch.
+    async fn get_chapters_pages(&mut self, chapters: &[(i32, String)]) -> Result<Vec<Vec<Page>>> {
+        let mut page_lists = {
+            let entries = self.cache.entries();
+            let now = Instant::now();
+            chapters
+                .iter()
+                .map(|key| entries.page_lists.get(key, now))
+                .collect::<Vec<Option<Vec<Page>>>>()
+        };
+        let missing = chapters
+            .iter()
+            .zip(&page_lists)
+            .filter(|(_, pages)| pages.is_none())
+            .map(|(key, _)| key.clone())
+            .collect::<Vec<(i32, String)>>();
+        if !missing.is_empty() {
+            let generation = self.cache.generation();
+            let fetched = self.inner.get_chapters_pages(&missing).await?;
+            self.cache.insert_since(generation, |entries, now| {
+                for (key, pages) in missing.into_iter().zip(&fetched) {
+                    entries.page_lists.insert(key, pages.clone(), now);
+                }
+            });
+            let mut fetched = fetched.into_iter();
+            for pages in page_lists.iter_mut().filter(|pages| pages.is_none()) {
+                *pages = fetched.next();
+            }
+        }
+        Ok(page_lists
+            .into_iter()
+            .map(Option::unwrap_or_default)
+            .collect())
+    }
+
+    // Writes invalidate even when they fail, a failed write may still have been applied
+
+    async fn create_manga(&mut self, manga: NewManga) -> Result<Manga> {
+        let result = self.inner.create_manga(manga).await;
+        self.cache.invalidate_manga_lists();
+        result
+    }
+
+    async fn update_manga(&mut self, manga_id: i32, manga: NewManga) -> Result<Option<Manga>> {
+        let result = self.inner.update_manga(manga_id, manga).await;
+        self.cache.invalidate_manga_lists();
+        result
+    }
+
+    async fn delete_manga(&mut self, manga_id: i32) -> Result<bool> {
+        let result = self.inner.delete_manga(manga_id).await;
+        self.cache.invalidate_manga_lists();
+        self.cache.invalidate_manga(manga_id);
+        result
+    }
+
+    async fn create_chapter(
+        &mut self,
+        manga_id: i32,
+        chapter: NewChapter,
+    ) -> Result<Option<Chapter>> {
+        let result = self.inner.create_chapter(manga_id, chapter).await;
+        // Listings sort by newest chapter
+        self.cache.invalidate_manga_lists();
+        self.cache.invalidate_manga(manga_id);
+        result
+    }
+
+    async fn update_chapter(
+        &mut self,
+        manga_id: i32,
+        chapter_number: &str,
+        chapter: NewChapter,
+    ) -> Result<Option<Chapter>> {
+        let result = self
+            .inner
+            .update_chapter(manga_id, chapter_number, chapter)
+            .await;
+        self.cache.invalidate_manga_lists();
+        self.cache.invalidate_manga(manga_id);
+        result
+    }
+
+    async fn delete_chapter(&mut self, manga_id: i32, chapter_number: &str) -> Result<bool> {
+        let result = self.inner.delete_chapter(manga_id, chapter_number).await;
+        self.cache.invalidate_manga_lists();
+        self.cache.invalidate_manga(manga_id);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{InMemoryMangaService, MangaFixture, MangaStatus, NewPage};
+
+    fn new_manga(manga_name: &str) -> NewManga {
+        NewManga {
+            manga_name: manga_name.to_owned(),
+            creators: vec![],
+            cover_image_url: None,
+            purchase_url: None,
+            status: MangaStatus::default(),
+            alternate_titles: vec![],
+        }
+    }
+
+    fn new_chapter(chapter_number: &str) -> NewChapter {
+        NewChapter {
+            chapter_number: chapter_number.into(),
+            chapter_name: format!("Chapter {}", chapter_number),
+            release_date: None,
+            pages: vec![NewPage {
+                page_number: 1,
+                urls: vec![format!("{}.png", chapter_number)],
+            }],
+        }
+    }
+
+    /// A cached service and a clone of the backend it wraps, writes through the clone bypass the cache.
+    fn cached_service() -> (
+        CachedMangaService<InMemoryMangaService>,
+        InMemoryMangaService,
+    ) {
+        let inner = InMemoryMangaService::new(MangaFixture::default());
+        let mut service = CachedMangaService::new(inner.clone(), MangaCache::default());
+        let manga = tokio_test::block_on(service.create_manga(new_manga("First"))).unwrap();
+        tokio_test::block_on(service.create_chapter(manga.manga_id, new_chapter("1"))).unwrap();
+        (service, inner)
+    }
+
+    fn manga_names(service: &mut CachedMangaService<InMemoryMangaService>) -> Vec<String> {
+        tokio_test::block_on(service.get_all_manga_titles())
+            .unwrap()
+            .into_iter()
+            .map(|manga| manga.manga_name)
+            .collect()
+    }
+
+    fn page_urls(service: &mut CachedMangaService<InMemoryMangaService>) -> Vec<String> {
+        tokio_test::block_on(service.get_pages(1, "1"))
+            .unwrap()
+            .into_iter()
+            .map(|page| page.url_string)
+            .collect()
+    }
+
+    #[test]
+    fn serves_cached_lists_until_invalidated() {
+        let (mut service, mut bypass) = cached_service();
+        assert_eq!(manga_names(&mut service), vec!["First"]);
+        assert_eq!(page_urls(&mut service), vec!["1.png"]);
+        tokio_test::block_on(bypass.create_manga(new_manga("Second"))).unwrap();
+        let mut chapter = new_chapter("1");
+        chapter.pages[0].urls = vec!["replaced.png".to_owned()];
+        tokio_test::block_on(bypass.update_chapter(1, "1", chapter)).unwrap();
+
+        assert_eq!(manga_names(&mut service), vec!["First"]);
+        assert_eq!(page_urls(&mut service), vec!["1.png"]);
+        service.cache().invalidate_manga_lists();
+        assert_eq!(manga_names(&mut service), vec!["First", "Second"]);
+        assert_eq!(page_urls(&mut service), vec!["1.png"]);
+        service.cache().invalidate_chapter(1, "1");
+        assert_eq!(page_urls(&mut service), vec!["replaced.png"]);
+    }
+
+    #[test]
+    fn batches_only_what_is_not_cached() {
+        let (mut service, mut bypass) = cached_service();
+        assert_eq!(page_urls(&mut service), vec!["1.png"]);
+        tokio_test::block_on(service.get_manga_chapters(1)).unwrap();
+        tokio_test::block_on(bypass.create_chapter(1, new_chapter("2"))).unwrap();
+
+        let chapters = tokio_test::block_on(service.get_mangas_chapters(&[2, 1])).unwrap();
+        assert_eq!(chapters.len(), 2);
+        assert!(chapters[0].is_empty());
+        // Still the cached list without the chapter added behind the cache's back
+        assert_eq!(chapters[1].len(), 1);
+        let key = |chapter_number: &str| (1, chapter_number.to_owned());
+        let pages =
+            tokio_test::block_on(service.get_chapters_pages(&[key("2"), key("1")])).unwrap();
+        assert_eq!(pages[0][0].url_string, "2.png");
+        assert_eq!(pages[1][0].url_string, "1.png");
+        // Cached by the batch like a single lookup
+        tokio_test::block_on(bypass.delete_chapter(1, "2")).unwrap();
+        let pages = tokio_test::block_on(service.get_pages(1, "2")).unwrap();
+        assert_eq!(pages.len(), 1);
+    }
+
+    #[test]
+    fn writes_invalidate_what_they_change() {
+        let (mut service, _) = cached_service();
+        assert_eq!(manga_names(&mut service), vec!["First"]);
+        assert_eq!(page_urls(&mut service), vec!["1.png"]);
+        let chapters = tokio_test::block_on(service.get_manga_chapters(1)).unwrap();
+        assert_eq!(chapters.len(), 1);
+
+        let manga = tokio_test::block_on(service.get_manga(1)).unwrap();
+        assert_eq!(manga.unwrap().manga_name, "First");
+        assert_eq!(tokio_test::block_on(service.get_manga(2)).unwrap(), None);
+
+        tokio_test::block_on(service.update_manga(1, new_manga("Renamed"))).unwrap();
+        assert_eq!(manga_names(&mut service), vec!["Renamed"]);
+        let manga = tokio_test::block_on(service.get_manga(1)).unwrap();
+        assert_eq!(manga.unwrap().manga_name, "Renamed");
+        tokio_test::block_on(service.create_manga(new_manga("Second"))).unwrap();
+        assert!(tokio_test::block_on(service.get_manga(2))
+            .unwrap()
+            .is_some());
+        tokio_test::block_on(service.create_chapter(1, new_chapter("2"))).unwrap();
+        let chapters = tokio_test::block_on(service.get_manga_chapters(1)).unwrap();
+        assert_eq!(chapters.len(), 2);
+        let chapter = tokio_test::block_on(service.get_chapter(1, "2")).unwrap();
+        assert_eq!(chapter.unwrap().chapter_name, "Chapter 2");
+        tokio_test::block_on(service.delete_chapter(1, "1")).unwrap();
+        assert!(page_urls(&mut service).is_empty());
+        assert_eq!(
+            tokio_test::block_on(service.get_chapter(1, "1")).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn expires_and_evicts_entries() {
+        let start = Instant::now();
+        let mut map = TtlMap::new(Duration::from_secs(10), 2);
+        map.insert(1, "one", start);
+        map.insert(2, "two", start + Duration::from_secs(1));
+        assert_eq!(map.get(&1, start + Duration::from_secs(9)), Some("one"));
+        assert_eq!(map.get(&1, start + Duration::from_secs(10)), None);
+
+        // Full, so the oldest entry makes room
+        map.insert(3, "three", start + Duration::from_secs(2));
+        assert_eq!(map.entries.len(), 2);
+        assert_eq!(map.get(&1, start + Duration::from_secs(2)), None);
+        assert_eq!(map.get(&2, start + Duration::from_secs(2)), Some("two"));
+
+        // Expired entries make room before live ones
+        map.insert(4, "four", start + Duration::from_secs(11));
+        assert_eq!(map.get(&3, start + Duration::from_secs(11)), Some("three"));
+        assert_eq!(map.get(&4, start + Duration::from_secs(11)), Some("four"));
+
+        let mut disabled = TtlMap::new(Duration::from_secs(10), 0);
+        disabled.insert(1, "one", start);
+        assert_eq!(disabled.get(&1, start), None);
+    }
+}
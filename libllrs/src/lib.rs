@@ -1,58 +1,442 @@
-use std::cmp::Ordering;
+use std::{
+    any::type_name,
+    collections::{HashMap, HashSet},
+    str::FromStr,
+};
 
 use async_trait::async_trait;
-use chrono::NaiveDateTime;
+use chrono::{NaiveDateTime, Timelike, Utc};
 use futures::{AsyncRead, AsyncWrite};
 use log::warn;
-use serde::{Deserialize, Serialize};
 use thiserror::Error;
-use tiberius::{AuthMethod, Client, Config as SqlSrvConfig};
+use tiberius::{AuthMethod, Client, Config as SqlSrvConfig, FromSql, Row, ToSql};
 use tokio::net::TcpStream;
 use tokio_util::compat::{Compat, TokioAsyncWriteCompatExt};
 
-// Should redesign DB
-#[derive(Debug, Serialize, Deserialize)]
-pub struct Manga {
-    pub manga_id: i32,
-    pub manga_name: String,
-    // May make sense to have author/artist per chapter instead (anthologies)?
-    // and then this returns all associated
-    pub author_names: Vec<String>,
-    pub artist_names: Vec<String>,
-    pub cover_image_url: String,
-    pub purchase_url: String,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct Chapter {
-    pub chapter_number: String,
-    // not at the chapter level in current db
-    // pub author_name: String,
-    // pub artist_name: String,
-    pub chapter_name: String,
-    pub creation_date: NaiveDateTime,
-    pub release_date: NaiveDateTime,
-    pub manga_id: i32,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct Page {
-    url_string: String,
-    page_number: i32,
-}
+mod cache;
+mod memory;
+mod migrations;
+mod pool;
+mod postgres;
+mod search;
+mod sqlite;
+
+pub use llrs_model::{
+    Chapter, ChapterNumber, Creator, CreatorRole, Manga, MangaListQuery, MangaListing, MangaSort,
+    MangaStatus, NewChapter, NewManga, NewPage, Page, RecentChapter, RecentChaptersQuery,
+    SearchField, SearchResult,
+};
+use search::{
+    candidates, like_pattern, rank, search_results, search_terms, SearchHit, MAX_CANDIDATES,
+};
+
+pub use cache::{CacheConfig, CachedMangaService, MangaCache};
+pub use memory::{FixtureAlternateTitle, FixturePage, InMemoryMangaService, MangaFixture};
+use migrations::sql_server_batches;
+pub use migrations::{AppliedMigration, Dialect, Direction, Migrate, Migration, MigrationStatus};
+pub use pool::{
+    ConnectionPool, PoolConfig, PostgresPool, WaifusimsConnectionManager, WaifusimsPool,
+};
+pub use postgres::{PostgresConnectionManager, PostgresWaifusims};
+pub use sqlite::SqliteWaifusims;
 
 pub type Result<T> = std::result::Result<T, Error>;
 
+/// Orders chapters by their `ChapterNumber`, specials like `Extra` sort first.
+/// Shared by every `MangaService` so all backends agree on chapter order.
+pub(crate) fn sort_chapters(chapters: &mut [Chapter]) {
+    chapters.sort_by(|a, b| a.chapter_number.cmp(&b.chapter_number));
+}
+
+/// Fills `author_names`/`artist_names` from `(manga_id, creator)` pairs in credit order.
+pub(crate) fn assign_creators(mangas: &mut [Manga], creators: Vec<(i32, Creator)>) {
+    let mut creators_by_manga: HashMap<i32, Vec<Creator>> = HashMap::new();
+    for (manga_id, creator) in creators {
+        creators_by_manga.entry(manga_id).or_default().push(creator);
+    }
+    for manga in mangas {
+        let creators = creators_by_manga
+            .remove(&manga.manga_id)
+            .unwrap_or_default();
+        for creator in creators {
+            match creator.role {
+                CreatorRole::Author => manga.author_names.push(creator.creator_name),
+                CreatorRole::Artist => manga.artist_names.push(creator.creator_name),
+            }
+        }
+    }
+}
+
+/// `(page_id, page_number, url)` row of a page's mirror
+pub(crate) type PageUrl = (i32, i32, String);
+
+/// Groups `(page_id, page_number, url)` rows, ordered by page and then by priority,
+/// into pages with their mirrors.
+pub(crate) fn group_page_urls(urls: Vec<PageUrl>) -> Vec<Page> {
+    let mut pages: Vec<(i32, Page)> = vec![];
+    for (page_id, page_number, url) in urls {
+        match pages.last_mut() {
+            Some((last_page_id, page)) if *last_page_id == page_id => page.mirror_urls.push(url),
+            _ => pages.push((
+                page_id,
+                Page {
+                    url_string: url,
+                    page_number,
+                    mirror_urls: vec![],
+                },
+            )),
+        }
+    }
+    pages.into_iter().map(|(_, page)| page).collect()
+}
+
+/// Splits the chapters of several manga by manga, in the order of `manga_ids`.
+pub(crate) fn group_manga_chapters(manga_ids: &[i32], chapters: Vec<Chapter>) -> Vec<Vec<Chapter>> {
+    let mut chapters_by_manga: HashMap<i32, Vec<Chapter>> = HashMap::new();
+    for chapter in chapters {
+        chapters_by_manga
+            .entry(chapter.manga_id)
+            .or_default()
+            .push(chapter);
+    }
+    manga_ids
+        .iter()
+        .map(|manga_id| {
+            let mut chapters = chapters_by_manga.get(manga_id).cloned().unwrap_or_default();
+            sort_chapters(&mut chapters);
+            chapters
+        })
+        .collect()
+}
+
+/// Splits `(manga_id, chapter_number, page_id, page_number, url)` rows of several chapters,
+/// ordered by page and then by priority, into the pages of each of `chapters` in order.
+pub(crate) fn group_chapter_page_urls(
+    chapters: &[(i32, String)],
+    urls: Vec<(i32, String, i32, i32, String)>,
+) -> Vec<Vec<Page>> {
+    let mut urls_by_chapter: HashMap<(i32, String), Vec<PageUrl>> = HashMap::new();
+    for (manga_id, chapter_number, page_id, page_number, url) in urls {
+        urls_by_chapter
+            .entry((manga_id, chapter_number))
+            .or_default()
+            .push((page_id, page_number, url));
+    }
+    chapters
+        .iter()
+        .map(|chapter| group_page_urls(urls_by_chapter.get(chapter).cloned().unwrap_or_default()))
+        .collect()
+}
+
+/// Reads a text column holding one of a fixed set of values, like a creator's `Role`.
+pub(crate) fn decode_parsed<T: FromStr<Err = String>>(
+    value: &str,
+    column: &'static str,
+    row_index: usize,
+) -> Result<T> {
+    value.parse().map_err(|reason| Error::Decode {
+        column,
+        row: row_index,
+        expected: type_name::<T>(),
+        reason,
+    })
+}
+
+/// `LIMIT` for a listing query, `None` lists every remaining manga.
+pub(crate) fn list_limit(query: &MangaListQuery) -> i64 {
+    query.limit.map_or(i64::from(i32::MAX), i64::from)
+}
+
+/// Rejects manga that would be stored incorrectly, checked before any writes.
+pub(crate) fn validate_manga(manga: &NewManga) -> Result<()> {
+    if manga.manga_name.trim().is_empty() {
+        return Err(Error::InvalidInput("manga_name is empty".to_owned()));
+    }
+    let mut credits = HashSet::new();
+    for creator in &manga.creators {
+        if creator.creator_name.trim().is_empty() {
+            return Err(Error::InvalidInput("creator_name is empty".to_owned()));
+        }
+        if !credits.insert((creator.creator_name.as_str(), creator.role)) {
+            return Err(Error::InvalidInput(format!(
+                "{} is credited as {} more than once",
+                creator.creator_name,
+                creator.role.as_str()
+            )));
+        }
+    }
+    let mut alternate_titles = HashSet::new();
+    for title in &manga.alternate_titles {
+        if title.trim().is_empty() {
+            return Err(Error::InvalidInput("alternate title is empty".to_owned()));
+        }
+        if !alternate_titles.insert(title) {
+            return Err(Error::InvalidInput(format!(
+                "alternate title {} is repeated",
+                title
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Rejects chapters that would be stored incorrectly, checked before any writes.
+pub(crate) fn validate_chapter(chapter: &NewChapter) -> Result<()> {
+    if chapter.chapter_number.as_str().trim().is_empty() {
+        return Err(Error::InvalidInput("chapter_number is empty".to_owned()));
+    }
+    let mut page_numbers = HashSet::new();
+    for page in &chapter.pages {
+        if page.page_number < 1 {
+            return Err(Error::InvalidInput(format!(
+                "page_number {} is less than 1",
+                page.page_number
+            )));
+        }
+        if !page_numbers.insert(page.page_number) {
+            return Err(Error::InvalidInput(format!(
+                "page_number {} is repeated",
+                page.page_number
+            )));
+        }
+        if page.urls.is_empty() {
+            return Err(Error::InvalidInput(format!(
+                "page_number {} has no urls",
+                page.page_number
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// The `Manga` a backend returns once `manga` has been stored as `manga_id`.
+pub(crate) fn stored_manga(
+    manga_id: i32,
+    manga: NewManga,
+    creation_date: Option<NaiveDateTime>,
+    updated_date: NaiveDateTime,
+) -> Manga {
+    let mut mangas = [Manga {
+        manga_id,
+        manga_name: manga.manga_name,
+        author_names: vec![],
+        artist_names: vec![],
+        cover_image_url: manga.cover_image_url,
+        purchase_url: manga.purchase_url,
+        status: manga.status,
+        creation_date,
+        updated_date: Some(updated_date),
+    }];
+    let creators = manga
+        .creators
+        .into_iter()
+        .map(|creator| (manga_id, creator))
+        .collect();
+    assign_creators(&mut mangas, creators);
+    let [manga] = mangas;
+    manga
+}
+
+/// The `Chapter` a backend returns once `chapter` has been stored.
+pub(crate) fn stored_chapter(
+    manga_id: i32,
+    chapter: NewChapter,
+    creation_date: NaiveDateTime,
+    updated_date: NaiveDateTime,
+) -> Chapter {
+    Chapter {
+        chapter_number: chapter.chapter_number,
+        chapter_name: chapter.chapter_name,
+        creation_date,
+        release_date: chapter.release_date,
+        updated_date: Some(updated_date),
+        manga_id,
+    }
+}
+
+/// Pairs chapters with their manga in order, dropping any whose manga has since been deleted.
+pub(crate) fn recent_chapters(chapters: Vec<Chapter>, mangas: Vec<Manga>) -> Vec<RecentChapter> {
+    let mangas = mangas
+        .into_iter()
+        .map(|manga| (manga.manga_id, manga))
+        .collect::<HashMap<i32, Manga>>();
+    chapters
+        .into_iter()
+        .filter_map(|chapter| {
+            Some(RecentChapter {
+                manga: mangas.get(&chapter.manga_id)?.clone(),
+                chapter,
+            })
+        })
+        .collect()
+}
+
+/// IDs of the manga `chapters` belong to, each once.
+pub(crate) fn chapter_manga_ids(chapters: &[Chapter]) -> Vec<i32> {
+    let mut manga_ids = chapters
+        .iter()
+        .map(|chapter| chapter.manga_id)
+        .collect::<Vec<i32>>();
+    manga_ids.sort_unstable();
+    manga_ids.dedup();
+    manga_ids
+}
+
+/// Creation or updated date for manga and chapters being written,
+/// whole seconds so every database stores it exactly.
+pub(crate) fn creation_date() -> NaiveDateTime {
+    let now = Utc::now().naive_utc();
+    now.with_nanosecond(0).unwrap_or(now)
+}
+
+/// Error for a chapter number that is already taken in the manga.
+pub(crate) fn chapter_exists(chapter_number: &ChapterNumber) -> Error {
+    Error::Conflict(format!("chapter {} already exists", chapter_number))
+}
+
 // TODO: Maybe get rid of i32, can generalize later if it ever becomes needed
 #[async_trait]
 pub trait MangaService<T> {
     async fn get_all_manga_titles(&mut self) -> Result<Vec<Manga>>;
+    /// Lists one slice of the manga matching `query`, with how many match in total.
+    async fn list_manga(&mut self, query: &MangaListQuery) -> Result<MangaListing>;
+    /// Finds manga by title, alternate title, creator or chapter name, best match first.
+    async fn search(&mut self, query: &str, limit: u32) -> Result<Vec<SearchResult>>;
+    /// The manga with its creators, `None` when there's no such manga.
+    async fn get_manga(&mut self, manga_id: T) -> Result<Option<Manga>>;
+    async fn get_manga_creators(&mut self, manga_id: T) -> Result<Vec<Creator>>;
     async fn get_manga_chapters(&mut self, manga_id: T) -> Result<Vec<Chapter>>;
+    /// Chapters of each of `manga_ids` in one query, in the order of `manga_ids`.
+    async fn get_mangas_chapters(&mut self, manga_ids: &[T]) -> Result<Vec<Vec<Chapter>>>;
+    /// One chapter's details without its pages, `None` when there's no such chapter.
+    async fn get_chapter(&mut self, manga_id: T, chapter_number: &str) -> Result<Option<Chapter>>;
+    /// Chapters released after `since` with their manga, newest first, at most `limit` of them.
+    /// Chapters without a release date count as released when they were added.
+    async fn get_recent_chapters(
+        &mut self,
+        limit: u32,
+        since: Option<NaiveDateTime>,
+    ) -> Result<Vec<RecentChapter>>;
     async fn get_pages(&mut self, manga_id: T, chapter_number: &str) -> Result<Vec<Page>>;
+    /// Pages of each `(manga_id, chapter_number)` in one query, in the order of `chapters`.
+    async fn get_chapters_pages(&mut self, chapters: &[(T, String)]) -> Result<Vec<Vec<Page>>>;
+
+    async fn create_manga(&mut self, manga: NewManga) -> Result<Manga>;
+    /// Replaces the manga's values and creators, `None` when there's no such manga.
+    async fn update_manga(&mut self, manga_id: T, manga: NewManga) -> Result<Option<Manga>>;
+    /// Deletes the manga with all of its chapters and pages, false when there's no such manga.
+    async fn delete_manga(&mut self, manga_id: T) -> Result<bool>;
+    /// Adds a chapter and its pages in one transaction, `None` when there's no such manga.
+    async fn create_chapter(&mut self, manga_id: T, chapter: NewChapter)
+        -> Result<Option<Chapter>>;
+    /// Replaces a chapter and all of its pages in one transaction,
+    /// `None` when there's no such chapter.
+    async fn update_chapter(
+        &mut self,
+        manga_id: T,
+        chapter_number: &str,
+        chapter: NewChapter,
+    ) -> Result<Option<Chapter>>;
+    /// Deletes a chapter and its pages, false when there's no such chapter.
+    async fn delete_chapter(&mut self, manga_id: T, chapter_number: &str) -> Result<bool>;
+}
+
+/// Lets a boxed service be wrapped, eg: in a `CachedMangaService`.
+#[async_trait]
+impl MangaService<i32> for Box<dyn MangaService<i32> + Send> {
+    async fn get_all_manga_titles(&mut self) -> Result<Vec<Manga>> {
+        (**self).get_all_manga_titles().await
+    }
+
+    async fn list_manga(&mut self, query: &MangaListQuery) -> Result<MangaListing> {
+        (**self).list_manga(query).await
+    }
+
+    async fn search(&mut self, query: &str, limit: u32) -> Result<Vec<SearchResult>> {
+        (**self).search(query, limit).await
+    }
+
+    async fn get_manga(&mut self, manga_id: i32) -> Result<Option<Manga>> {
+        (**self).get_manga(manga_id).await
+    }
+
+    async fn get_manga_creators(&mut self, manga_id: i32) -> Result<Vec<Creator>> {
+        (**self).get_manga_creators(manga_id).await
+    }
+
+    async fn get_manga_chapters(&mut self, manga_id: i32) -> Result<Vec<Chapter>> {
+        (**self).get_manga_chapters(manga_id).await
+    }
+
+    async fn get_mangas_chapters(&mut self, manga_ids: &[i32]) -> Result<Vec<Vec<Chapter>>> {
+        (**self).get_mangas_chapters(manga_ids).await
+    }
+
+    async fn get_chapter(
+        &mut self,
+        manga_id: i32,
+        chapter_number: &str,
+    ) -> Result<Option<Chapter>> {
+        (**self).get_chapter(manga_id, chapter_number).await
+    }
+
+    async fn get_recent_chapters(
+        &mut self,
+        limit: u32,
+        since: Option<NaiveDateTime>,
+    ) -> Result<Vec<RecentChapter>> {
+        (**self).get_recent_chapters(limit, since).await
+    }
+
+    async fn get_pages(&mut self, manga_id: i32, chapter_number: &str) -> Result<Vec<Page>> {
+        (**self).get_pages(manga_id, chapter_number).await
+    }
+
+    async fn get_chapters_pages(&mut self, chapters: &[(i32, String)]) -> Result<Vec<Vec<Page>>> {
+        (**self).get_chapters_pages(chapters).await
+    }
+
+    async fn create_manga(&mut self, manga: NewManga) -> Result<Manga> {
+        (**self).create_manga(manga).await
+    }
+
+    async fn update_manga(&mut self, manga_id: i32, manga: NewManga) -> Result<Option<Manga>> {
+        (**self).update_manga(manga_id, manga).await
+    }
+
+    async fn delete_manga(&mut self, manga_id: i32) -> Result<bool> {
+        (**self).delete_manga(manga_id).await
+    }
+
+    async fn create_chapter(
+        &mut self,
+        manga_id: i32,
+        chapter: NewChapter,
+    ) -> Result<Option<Chapter>> {
+        (**self).create_chapter(manga_id, chapter).await
+    }
+
+    async fn update_chapter(
+        &mut self,
+        manga_id: i32,
+        chapter_number: &str,
+        chapter: NewChapter,
+    ) -> Result<Option<Chapter>> {
+        (**self)
+            .update_chapter(manga_id, chapter_number, chapter)
+            .await
+    }
+
+    async fn delete_chapter(&mut self, manga_id: i32, chapter_number: &str) -> Result<bool> {
+        (**self).delete_chapter(manga_id, chapter_number).await
+    }
 }
 
 pub struct Waifusims<S: AsyncRead + AsyncWrite + Unpin + Send> {
     client: Client<S>,
+    /// Set from `BEGIN` until the commit or rollback completes, so a write future
+    /// dropped in between leaves it set and the pool discards the connection
+    transaction_open: bool,
 }
 
 #[derive(Debug, Error)]
@@ -61,6 +445,61 @@ pub enum Error {
     IoError(std::io::Error),
     #[error("IO error ${0:?}")]
     Tiberius(tiberius::error::Error),
+    #[error("SQLite error {0:?}")]
+    Sqlite(rusqlite::Error),
+    #[error("Postgres error {0:?}")]
+    Postgres(tokio_postgres::Error),
+    #[error("Blocking task failed {0:?}")]
+    Join(tokio::task::JoinError),
+    #[error("JSON error {0:?}")]
+    Json(serde_json::Error),
+    #[error("Timed out waiting for a pooled connection")]
+    PoolTimedOut,
+    /// Connecting to the database failed, wraps the cause
+    #[error("Database unavailable: {0}")]
+    Unavailable(Box<Error>),
+    #[error("Invalid input: {0}")]
+    InvalidInput(String),
+    #[error("Conflict: {0}")]
+    Conflict(String),
+    #[error("Migration failed: {0}")]
+    Migration(String),
+    #[error("Column {column} of row {row} was NULL, expected {expected}")]
+    UnexpectedNull {
+        column: &'static str,
+        row: usize,
+        expected: &'static str,
+    },
+    #[error("Column {column} of row {row} could not be read as {expected}: {reason}")]
+    Decode {
+        column: &'static str,
+        row: usize,
+        expected: &'static str,
+        reason: String,
+    },
+}
+
+impl Error {
+    fn unavailable(error: Error) -> Error {
+        Error::Unavailable(Box::new(error))
+    }
+
+    /// Whether the database couldn't be reached or was too busy,
+    /// as opposed to rejecting the request, so retrying later may succeed.
+    pub fn is_unavailable(&self) -> bool {
+        match self {
+            Error::IoError(_) | Error::PoolTimedOut | Error::Unavailable(_) => true,
+            Error::Tiberius(tiberius::error::Error::Io { .. }) => true,
+            Error::Postgres(err) => err.is_closed(),
+            Error::Sqlite(rusqlite::Error::SqliteFailure(err, _)) => matches!(
+                err.code,
+                rusqlite::ErrorCode::DatabaseBusy
+                    | rusqlite::ErrorCode::DatabaseLocked
+                    | rusqlite::ErrorCode::CannotOpen
+            ),
+            _ => false,
+        }
+    }
 }
 
 impl From<std::io::Error> for Error {
@@ -75,6 +514,30 @@ impl From<tiberius::error::Error> for Error {
     }
 }
 
+impl From<rusqlite::Error> for Error {
+    fn from(e: rusqlite::Error) -> Self {
+        Error::Sqlite(e)
+    }
+}
+
+impl From<tokio_postgres::Error> for Error {
+    fn from(e: tokio_postgres::Error) -> Self {
+        Error::Postgres(e)
+    }
+}
+
+impl From<tokio::task::JoinError> for Error {
+    fn from(e: tokio::task::JoinError) -> Self {
+        Error::Join(e)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::Json(e)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Config {
     pub auth: Auth,
@@ -114,7 +577,12 @@ impl From<Config> for SqlSrvConfig {
 
 // TODO: Maybe remove the strong typing
 impl Waifusims<Compat<TcpStream>> {
+    /// Connects to SQL Server, failures are `Error::Unavailable`.
     pub async fn new(config: Config) -> Result<Waifusims<Compat<TcpStream>>> {
+        Self::connect(config).await.map_err(Error::unavailable)
+    }
+
+    async fn connect(config: Config) -> Result<Waifusims<Compat<TcpStream>>> {
         let sql_cfg = SqlSrvConfig::from(config);
         let tcp = TcpStream::connect(sql_cfg.get_addr()).await?;
         tcp.set_nodelay(true)?;
@@ -122,7 +590,7 @@ impl Waifusims<Compat<TcpStream>> {
         let client = match Client::connect(sql_cfg.clone(), tcp.compat_write()).await {
             Ok(client) => client,
             Err(tiberius::error::Error::Routing { host, port }) => {
-                let mut sql_cfg = SqlSrvConfig::from(sql_cfg);
+                let mut sql_cfg = sql_cfg;
                 warn!("Rerouting to {}:{}", host, port);
                 sql_cfg.host(&host);
                 sql_cfg.port(port);
@@ -132,7 +600,13 @@ impl Waifusims<Compat<TcpStream>> {
             }
             Err(err) => Err(Error::Tiberius(err))?,
         };
-        Ok(Waifusims { client })
+        let mut waifusims = Waifusims {
+            client,
+            transaction_open: false,
+        };
+        // Any error in a transaction rolls all of it back, not just the failed statement
+        waifusims.execute_batch("SET XACT_ABORT ON").await?;
+        Ok(waifusims)
     }
 }
 
@@ -140,13 +614,159 @@ const SELECT_ALL_MANGA_QUERY: &str = "
 SELECT
     m.MangaID,
     m.MangaName,
+    m.CoverImageURL,
+    m.PurchaseURL,
+    m.Status,
+    m.DateCreated,
+    m.UpdatedDate
+FROM Manga m
+ORDER BY m.MangaID
+";
+
+/// Shared by a listing and its count, `@P1` is the status and `@P2` the author
+const MANGA_LIST_FILTER: &str = "
+WHERE (@P1 IS NULL OR m.Status = @P1)
+    AND (@P2 IS NULL OR EXISTS (
+        SELECT 1
+        FROM MangaCreator mc
+        JOIN Author a
+            ON mc.AuthorID = a.AuthorID
+        WHERE mc.MangaID = m.MangaID
+            AND mc.Role = 'Author'
+            AND a.AuthorName = @P2
+    ))
+";
+
+/// Lists a slice of manga, `@P3` is the offset and `@P4` the limit.
+/// SQL Server's default collation already compares names ignoring case.
+fn select_manga_list_query(sort: MangaSort) -> String {
+    let order = match sort {
+        MangaSort::Name => "m.MangaName, m.MangaID",
+        MangaSort::NewestChapter => {
+            "(SELECT MAX(c.DateCreated) FROM MangaChapter c WHERE c.MangaID = m.MangaID) DESC,
+    m.MangaID"
+        }
+        MangaSort::CreationDate => "m.DateCreated DESC, m.MangaID DESC",
+    };
+    format!(
+        "
+SELECT
+    m.MangaID,
+    m.MangaName,
+    m.CoverImageURL,
+    m.PurchaseURL,
+    m.Status,
+    m.DateCreated,
+    m.UpdatedDate
+FROM Manga m{}ORDER BY {}
+OFFSET @P3 ROWS FETCH NEXT @P4 ROWS ONLY
+",
+        MANGA_LIST_FILTER, order
+    )
+}
+
+fn count_manga_list_query() -> String {
+    format!(
+        "
+SELECT COUNT(*) AS Total
+FROM Manga m{}",
+        MANGA_LIST_FILTER
+    )
+}
+
+/// `@P1` is a JSON array of manga IDs
+const SELECT_LISTED_MANGA_CREATORS_QUERY: &str = "
+SELECT
+    mc.MangaID,
     a.AuthorName,
+    mc.Role
+FROM MangaCreator mc
+JOIN Author a
+    ON mc.AuthorID = a.AuthorID
+WHERE mc.MangaID IN (SELECT CAST(value AS INT) FROM OPENJSON(@P1))
+ORDER BY mc.MangaID, mc.CreditOrder, a.AuthorName
+";
+
+/// `@P1` is a JSON array of manga IDs
+const SELECT_MANGA_BY_IDS_QUERY: &str = "
+SELECT
+    m.MangaID,
+    m.MangaName,
+    m.CoverImageURL,
+    m.PurchaseURL,
+    m.Status,
+    m.DateCreated,
+    m.UpdatedDate
+FROM Manga m
+WHERE m.MangaID IN (SELECT CAST(value AS INT) FROM OPENJSON(@P1))
+";
+
+/// Search candidates, `@P1` is a `LIKE` pattern escaped with `\`
+/// Compared case insensitively whatever the database's collation,
+/// the first `@P2` in `search::candidates` order are returned
+const SEARCH_QUERY: &str = "
+SELECT TOP (@P2) MangaID, Field, Text, ChapterNumber
+FROM (
+    SELECT m.MangaID, N'Title' AS Field, m.MangaName AS Text, NULL AS ChapterNumber, 1 AS FieldOrder
+    FROM Manga m
+    WHERE m.MangaName COLLATE Latin1_General_100_CI_AS LIKE @P1 ESCAPE '\\'
+    UNION ALL
+    SELECT t.MangaID, N'AlternateTitle', t.Title, NULL, 2
+    FROM MangaAlternateTitle t
+    WHERE t.Title COLLATE Latin1_General_100_CI_AS LIKE @P1 ESCAPE '\\'
+    UNION ALL
+    SELECT mc.MangaID, N'Creator', a.AuthorName, NULL, 3
+    FROM MangaCreator mc
+    JOIN Author a
+        ON mc.AuthorID = a.AuthorID
+    WHERE a.AuthorName COLLATE Latin1_General_100_CI_AS LIKE @P1 ESCAPE '\\'
+    UNION ALL
+    SELECT c.MangaID, N'Chapter', c.ChapterName, c.ChapterNumber, 4
+    FROM MangaChapter c
+    WHERE c.ChapterName COLLATE Latin1_General_100_CI_AS LIKE @P1 ESCAPE '\\'
+) hits
+ORDER BY
+    FieldOrder,
+    LEN(Text),
+    MangaID,
+    Text COLLATE Latin1_General_100_BIN2,
+    ChapterNumber COLLATE Latin1_General_100_BIN2
+";
+
+const SELECT_MANGA_QUERY: &str = "
+SELECT
+    m.MangaID,
+    m.MangaName,
     m.CoverImageURL,
-    m.PurchaseURL
+    m.PurchaseURL,
+    m.Status,
+    m.DateCreated,
+    m.UpdatedDate
 FROM Manga m
+WHERE m.MangaID = @P1
+";
+
+const SELECT_ALL_MANGA_CREATORS_QUERY: &str = "
+SELECT
+    mc.MangaID,
+    a.AuthorName,
+    mc.Role
+FROM MangaCreator mc
 JOIN Author a
-    ON m.AuthorID = a.AuthorID
-ORDER BY m.MangaID
+    ON mc.AuthorID = a.AuthorID
+ORDER BY mc.MangaID, mc.CreditOrder, a.AuthorName
+";
+
+const SELECT_MANGA_CREATORS_QUERY: &str = "
+SELECT
+    mc.MangaID,
+    a.AuthorName,
+    mc.Role
+FROM MangaCreator mc
+JOIN Author a
+    ON mc.AuthorID = a.AuthorID
+WHERE mc.MangaID = @P1
+ORDER BY mc.CreditOrder, a.AuthorName
 ";
 
 const SELECT_MANGA_CHAPTERS_QUERY: &str = "
@@ -155,14 +775,57 @@ SELECT
     ChapterName,
     DateCreated,
     DateReleased,
+    UpdatedDate,
+    MangaID
+FROM MangaChapter
+WHERE MangaID = @P1
+";
+
+/// `@P1` is a JSON array of manga IDs
+const SELECT_MANGAS_CHAPTERS_QUERY: &str = "
+SELECT
+    ChapterNumber,
+    ChapterName,
+    DateCreated,
+    DateReleased,
+    UpdatedDate,
+    MangaID
+FROM MangaChapter
+WHERE MangaID IN (SELECT CAST(value AS INT) FROM OPENJSON(@P1))
+";
+
+const SELECT_CHAPTER_QUERY: &str = "
+SELECT
+    ChapterNumber,
+    ChapterName,
+    DateCreated,
+    DateReleased,
+    UpdatedDate,
     MangaID
 FROM MangaChapter
 WHERE MangaID = @P1
+    AND ChapterNumber = @P2
+";
+
+/// Newest releases first, `@P1` is the release to list chapters after and `@P2` the limit.
+/// Chapters without a release date count as released when they were added.
+const SELECT_RECENT_CHAPTERS_QUERY: &str = "
+SELECT TOP (@P2)
+    ChapterNumber,
+    ChapterName,
+    DateCreated,
+    DateReleased,
+    UpdatedDate,
+    MangaID
+FROM MangaChapter
+WHERE @P1 IS NULL OR COALESCE(DateReleased, DateCreated) > @P1
+ORDER BY COALESCE(DateReleased, DateCreated) DESC, MangaID, ChapterNumber
 ";
 
 const SELECT_CHAPTER_PAGES_QUERY: &str = "
 SELECT
     u.URL,
+    p.PageID,
     p.PageNumber
 FROM Page p
 JOIN PageURL u
@@ -171,46 +834,756 @@ JOIN MangaChapter mc
     ON mc.ChapterIndex = p.ChapterIndex
         AND mc.MangaID = p.MangaID
         AND mc.ChapterNumber = @P2
-WHERE u.Priority = 1
-    AND p.MangaID = @P1
-ORDER BY p.PageNumber
+WHERE p.MangaID = @P1
+ORDER BY p.PageNumber, p.PageID, u.Priority
+";
+
+/// `@P1` is a JSON array of `[manga_id, chapter_number]` pairs,
+/// each row carries the pair it matched as it was requested
+const SELECT_CHAPTERS_PAGES_QUERY: &str = "
+SELECT
+    k.MangaID,
+    k.ChapterNumber,
+    u.URL,
+    p.PageID,
+    p.PageNumber
+FROM OPENJSON(@P1) WITH (
+    MangaID INT '$[0]',
+    ChapterNumber NVARCHAR(MAX) '$[1]'
+) k
+JOIN MangaChapter mc
+    ON mc.MangaID = k.MangaID
+        AND mc.ChapterNumber = k.ChapterNumber
+JOIN Page p
+    ON p.ChapterIndex = mc.ChapterIndex
+        AND p.MangaID = mc.MangaID
+JOIN PageURL u
+    ON p.PageID = u.PageID
+ORDER BY p.PageNumber, p.PageID, u.Priority
+";
+
+const INSERT_MANGA_QUERY: &str = "
+INSERT INTO Manga (MangaName, CoverImageURL, PurchaseURL, Status, DateCreated, UpdatedDate)
+OUTPUT INSERTED.MangaID
+VALUES (@P1, @P2, @P3, @P4, @P5, @P5)
+";
+
+const UPDATE_MANGA_QUERY: &str = "
+UPDATE Manga
+SET MangaName = @P2,
+    CoverImageURL = @P3,
+    PurchaseURL = @P4,
+    Status = @P5,
+    UpdatedDate = @P6
+OUTPUT INSERTED.DateCreated
+WHERE MangaID = @P1
+";
+
+/// Marks the manga changed by a write to one of its chapters, `@P2` is when
+const UPDATE_MANGA_UPDATED_DATE_QUERY: &str = "
+UPDATE Manga
+SET UpdatedDate = @P2
+WHERE MangaID = @P1
+";
+
+const SELECT_MANGA_ID_QUERY: &str = "
+SELECT MangaID
+FROM Manga
+WHERE MangaID = @P1
+";
+
+const SELECT_AUTHOR_ID_QUERY: &str = "
+SELECT AuthorID
+FROM Author
+WHERE AuthorName = @P1
+";
+
+const INSERT_AUTHOR_QUERY: &str = "
+INSERT INTO Author (AuthorName)
+OUTPUT INSERTED.AuthorID
+VALUES (@P1)
+";
+
+const INSERT_MANGA_CREATOR_QUERY: &str = "
+INSERT INTO MangaCreator (MangaID, AuthorID, Role, CreditOrder)
+VALUES (@P1, @P2, @P3, @P4)
+";
+
+const DELETE_MANGA_CREATORS_QUERY: &str = "
+DELETE FROM MangaCreator
+WHERE MangaID = @P1
+";
+
+const INSERT_ALTERNATE_TITLE_QUERY: &str = "
+INSERT INTO MangaAlternateTitle (MangaID, Title)
+VALUES (@P1, @P2)
+";
+
+const DELETE_MANGA_ALTERNATE_TITLES_QUERY: &str = "
+DELETE FROM MangaAlternateTitle
+WHERE MangaID = @P1
+";
+
+const DELETE_MANGA_PAGE_URLS_QUERY: &str = "
+DELETE FROM PageURL
+WHERE PageID IN (SELECT PageID FROM Page WHERE MangaID = @P1)
+";
+
+const DELETE_MANGA_PAGES_QUERY: &str = "
+DELETE FROM Page
+WHERE MangaID = @P1
+";
+
+const DELETE_MANGA_CHAPTERS_QUERY: &str = "
+DELETE FROM MangaChapter
+WHERE MangaID = @P1
+";
+
+const DELETE_MANGA_QUERY: &str = "
+DELETE FROM Manga
+WHERE MangaID = @P1
+";
+
+const SELECT_CHAPTER_INDEX_QUERY: &str = "
+SELECT ChapterIndex
+FROM MangaChapter
+WHERE MangaID = @P1
+    AND ChapterNumber = @P2
+";
+
+// The locks keep concurrent inserts from picking the same ChapterIndex
+const INSERT_CHAPTER_QUERY: &str = "
+INSERT INTO MangaChapter (MangaID, ChapterIndex, ChapterNumber, ChapterName, DateCreated, DateReleased, UpdatedDate)
+OUTPUT INSERTED.ChapterIndex
+SELECT @P1, COALESCE(MAX(ChapterIndex), 0) + 1, @P2, @P3, @P4, @P5, @P4
+FROM MangaChapter WITH (UPDLOCK, HOLDLOCK)
+WHERE MangaID = @P1
+";
+
+const UPDATE_CHAPTER_QUERY: &str = "
+UPDATE MangaChapter
+SET ChapterNumber = @P3,
+    ChapterName = @P4,
+    DateReleased = @P5,
+    UpdatedDate = @P6
+OUTPUT INSERTED.DateCreated
+WHERE MangaID = @P1
+    AND ChapterIndex = @P2
+";
+
+const DELETE_CHAPTER_PAGE_URLS_QUERY: &str = "
+DELETE FROM PageURL
+WHERE PageID IN (SELECT PageID FROM Page WHERE MangaID = @P1 AND ChapterIndex = @P2)
+";
+
+const DELETE_CHAPTER_PAGES_QUERY: &str = "
+DELETE FROM Page
+WHERE MangaID = @P1
+    AND ChapterIndex = @P2
+";
+
+const DELETE_CHAPTER_QUERY: &str = "
+DELETE FROM MangaChapter
+WHERE MangaID = @P1
+    AND ChapterIndex = @P2
+";
+
+const INSERT_PAGE_QUERY: &str = "
+INSERT INTO Page (MangaID, ChapterIndex, PageNumber)
+OUTPUT INSERTED.PageID
+VALUES (@P1, @P2, @P3)
+";
+
+const INSERT_PAGE_URL_QUERY: &str = "
+INSERT INTO PageURL (PageID, URL, Priority)
+VALUES (@P1, @P2, @P3)
+";
+
+const CREATE_SCHEMA_MIGRATION_QUERY: &str = "
+IF OBJECT_ID(N'SchemaMigration', N'U') IS NULL
+CREATE TABLE SchemaMigration (
+    Version BIGINT NOT NULL CONSTRAINT PK_SchemaMigration PRIMARY KEY,
+    Name NVARCHAR(255) NOT NULL,
+    AppliedAt DATETIME2 NOT NULL
+)
+";
+
+const SELECT_SCHEMA_MIGRATIONS_QUERY: &str = "
+SELECT Version, Name, AppliedAt
+FROM SchemaMigration
+ORDER BY Version
+";
+
+const SELECT_HAS_WAIFUSIMS_TABLES_QUERY: &str = "
+SELECT CASE WHEN OBJECT_ID(N'Manga', N'U') IS NULL THEN 0 ELSE 1 END AS HasTables
+";
+
+const INSERT_SCHEMA_MIGRATION_QUERY: &str = "
+INSERT INTO SchemaMigration (Version, Name, AppliedAt)
+VALUES (@P1, @P2, @P3)
+";
+
+const DELETE_SCHEMA_MIGRATION_QUERY: &str = "
+DELETE FROM SchemaMigration
+WHERE Version = @P1
 ";
 
+/// Reads a NOT NULL column, `row_index` is only used to describe errors.
+fn decode<'a, R: FromSql<'a>>(row: &'a Row, row_index: usize, column: &'static str) -> Result<R> {
+    decode_nullable(row, row_index, column)?.ok_or(Error::UnexpectedNull {
+        column,
+        row: row_index,
+        expected: type_name::<R>(),
+    })
+}
+
+fn decode_nullable<'a, R: FromSql<'a>>(
+    row: &'a Row,
+    row_index: usize,
+    column: &'static str,
+) -> Result<Option<R>> {
+    row.try_get(column).map_err(|err| Error::Decode {
+        column,
+        row: row_index,
+        expected: type_name::<R>(),
+        reason: err.to_string(),
+    })
+}
+
+fn decode_manga(row: &Row, row_index: usize) -> Result<Manga> {
+    Ok(Manga {
+        manga_id: decode(row, row_index, "MangaID")?,
+        manga_name: decode::<&str>(row, row_index, "MangaName")?.to_owned(),
+        author_names: vec![],
+        artist_names: vec![],
+        cover_image_url: decode_nullable::<&str>(row, row_index, "CoverImageURL")?
+            .map(str::to_owned),
+        purchase_url: decode_nullable::<&str>(row, row_index, "PurchaseURL")?.map(str::to_owned),
+        status: decode_parsed(decode(row, row_index, "Status")?, "Status", row_index)?,
+        creation_date: decode_nullable(row, row_index, "DateCreated")?,
+        updated_date: decode_nullable(row, row_index, "UpdatedDate")?,
+    })
+}
+
+fn decode_search_hit(row: &Row, row_index: usize) -> Result<SearchHit> {
+    Ok(SearchHit {
+        manga_id: decode(row, row_index, "MangaID")?,
+        field: decode_parsed(decode(row, row_index, "Field")?, "Field", row_index)?,
+        text: decode::<&str>(row, row_index, "Text")?.to_owned(),
+        chapter_number: decode_nullable::<&str>(row, row_index, "ChapterNumber")?
+            .map(ChapterNumber::from),
+    })
+}
+
+fn decode_chapter(row: &Row, row_index: usize) -> Result<Chapter> {
+    Ok(Chapter {
+        manga_id: decode(row, row_index, "MangaID")?,
+        chapter_number: decode::<&str>(row, row_index, "ChapterNumber")?.into(),
+        chapter_name: decode::<&str>(row, row_index, "ChapterName")?.to_owned(),
+        creation_date: decode(row, row_index, "DateCreated")?,
+        release_date: decode_nullable(row, row_index, "DateReleased")?,
+        updated_date: decode_nullable(row, row_index, "UpdatedDate")?,
+    })
+}
+
+fn decode_creator(row: &Row, row_index: usize) -> Result<Creator> {
+    Ok(Creator {
+        creator_name: decode::<&str>(row, row_index, "AuthorName")?.to_owned(),
+        role: decode_parsed(decode(row, row_index, "Role")?, "Role", row_index)?,
+    })
+}
+
+/// Reads the single column of a row returned by an `OUTPUT`/`SELECT` of one ID.
+fn returned_id(row: Option<Row>, column: &'static str) -> Result<i32> {
+    match row {
+        Some(row) => decode(&row, 0, column),
+        None => Err(Error::UnexpectedNull {
+            column,
+            row: 0,
+            expected: type_name::<i32>(),
+        }),
+    }
+}
+
+// Writes run as helpers between `begin` and `finish` so any error rolls the transaction back
+impl Waifusims<Compat<TcpStream>> {
+    async fn begin(&mut self) -> Result<()> {
+        self.transaction_open = true;
+        self.client
+            .simple_query("BEGIN TRANSACTION")
+            .await?
+            .into_results()
+            .await?;
+        Ok(())
+    }
+
+    /// Commits if `result` is `Ok`, otherwise rolls back and returns the original error.
+    async fn finish<R>(&mut self, result: Result<R>) -> Result<R> {
+        match result {
+            Ok(value) => {
+                self.client
+                    .simple_query("COMMIT TRANSACTION")
+                    .await?
+                    .into_results()
+                    .await?;
+                self.transaction_open = false;
+                Ok(value)
+            }
+            Err(err) => {
+                let rollback = match self.client.simple_query("ROLLBACK TRANSACTION").await {
+                    Ok(stream) => stream.into_results().await.map(|_| ()),
+                    Err(rollback_err) => Err(rollback_err),
+                };
+                match rollback {
+                    Ok(()) => self.transaction_open = false,
+                    Err(rollback_err) => warn!("Rollback failed: {}", rollback_err),
+                }
+                Err(err)
+            }
+        }
+    }
+
+    async fn query_id(
+        &mut self,
+        query: &'static str,
+        params: &[&dyn ToSql],
+        column: &'static str,
+    ) -> Result<Option<i32>> {
+        let row = self.client.query(query, params).await?.into_row().await?;
+        row.map(|row| decode(&row, 0, column)).transpose()
+    }
+
+    /// Credits `creators` in order, adding any authors that don't exist yet.
+    async fn insert_creators(&mut self, manga_id: i32, creators: &[Creator]) -> Result<()> {
+        for (credit_order, creator) in (0..).zip(creators) {
+            let name = creator.creator_name.as_str();
+            let author_id = match self
+                .query_id(SELECT_AUTHOR_ID_QUERY, &[&name], "AuthorID")
+                .await?
+            {
+                Some(author_id) => author_id,
+                None => {
+                    let stream = self.client.query(INSERT_AUTHOR_QUERY, &[&name]).await?;
+                    returned_id(stream.into_row().await?, "AuthorID")?
+                }
+            };
+            self.client
+                .execute(
+                    INSERT_MANGA_CREATOR_QUERY,
+                    &[&manga_id, &author_id, &creator.role.as_str(), &credit_order],
+                )
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn insert_alternate_titles(&mut self, manga_id: i32, titles: &[String]) -> Result<()> {
+        for title in titles {
+            self.client
+                .execute(INSERT_ALTERNATE_TITLE_QUERY, &[&manga_id, &title.as_str()])
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Fills in creators for manga fetched without them.
+    async fn assign_listed_creators(&mut self, mangas: &mut [Manga]) -> Result<()> {
+        let manga_ids = mangas
+            .iter()
+            .map(|manga| manga.manga_id)
+            .collect::<Vec<i32>>();
+        let stream = self
+            .client
+            .query(
+                SELECT_LISTED_MANGA_CREATORS_QUERY,
+                &[&serde_json::to_string(&manga_ids)?],
+            )
+            .await?;
+        let rows = stream.into_first_result().await?;
+        let creators = rows
+            .iter()
+            .enumerate()
+            .map(|(index, row)| Ok((decode(row, index, "MangaID")?, decode_creator(row, index)?)))
+            .collect::<Result<Vec<(i32, Creator)>>>()?;
+        assign_creators(mangas, creators);
+        Ok(())
+    }
+
+    async fn find_chapter_index(
+        &mut self,
+        manga_id: i32,
+        chapter_number: &str,
+    ) -> Result<Option<i32>> {
+        self.query_id(
+            SELECT_CHAPTER_INDEX_QUERY,
+            &[&manga_id, &chapter_number],
+            "ChapterIndex",
+        )
+        .await
+    }
+
+    /// Adds `pages`, each URL's priority follows its position in `urls`.
+    async fn insert_pages(
+        &mut self,
+        manga_id: i32,
+        chapter_index: i32,
+        pages: &[NewPage],
+    ) -> Result<()> {
+        for page in pages {
+            let stream = self
+                .client
+                .query(
+                    INSERT_PAGE_QUERY,
+                    &[&manga_id, &chapter_index, &page.page_number],
+                )
+                .await?;
+            let page_id = returned_id(stream.into_row().await?, "PageID")?;
+            for (priority, url) in (1..).zip(&page.urls) {
+                self.client
+                    .execute(INSERT_PAGE_URL_QUERY, &[&page_id, &url.as_str(), &priority])
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn delete_pages(&mut self, manga_id: i32, chapter_index: i32) -> Result<()> {
+        self.client
+            .execute(DELETE_CHAPTER_PAGE_URLS_QUERY, &[&manga_id, &chapter_index])
+            .await?;
+        self.client
+            .execute(DELETE_CHAPTER_PAGES_QUERY, &[&manga_id, &chapter_index])
+            .await?;
+        Ok(())
+    }
+
+    /// Marks the manga changed by a write to one of its chapters
+    async fn touch_manga(&mut self, manga_id: i32, updated_date: NaiveDateTime) -> Result<()> {
+        self.client
+            .execute(UPDATE_MANGA_UPDATED_DATE_QUERY, &[&manga_id, &updated_date])
+            .await?;
+        Ok(())
+    }
+
+    async fn insert_manga(&mut self, manga: NewManga) -> Result<Manga> {
+        let creation_date = creation_date();
+        let stream = self
+            .client
+            .query(
+                INSERT_MANGA_QUERY,
+                &[
+                    &manga.manga_name.as_str(),
+                    &manga.cover_image_url.as_deref(),
+                    &manga.purchase_url.as_deref(),
+                    &manga.status.as_str(),
+                    &creation_date,
+                ],
+            )
+            .await?;
+        let manga_id = returned_id(stream.into_row().await?, "MangaID")?;
+        self.insert_creators(manga_id, &manga.creators).await?;
+        self.insert_alternate_titles(manga_id, &manga.alternate_titles)
+            .await?;
+        Ok(stored_manga(
+            manga_id,
+            manga,
+            Some(creation_date),
+            creation_date,
+        ))
+    }
+
+    async fn replace_manga(&mut self, manga_id: i32, manga: NewManga) -> Result<Option<Manga>> {
+        let updated_date = creation_date();
+        let stream = self
+            .client
+            .query(
+                UPDATE_MANGA_QUERY,
+                &[
+                    &manga_id,
+                    &manga.manga_name.as_str(),
+                    &manga.cover_image_url.as_deref(),
+                    &manga.purchase_url.as_deref(),
+                    &manga.status.as_str(),
+                    &updated_date,
+                ],
+            )
+            .await?;
+        let creation_date = match stream.into_row().await? {
+            Some(row) => decode_nullable(&row, 0, "DateCreated")?,
+            None => return Ok(None),
+        };
+        for query in [
+            DELETE_MANGA_CREATORS_QUERY,
+            DELETE_MANGA_ALTERNATE_TITLES_QUERY,
+        ] {
+            self.client.execute(query, &[&manga_id]).await?;
+        }
+        self.insert_creators(manga_id, &manga.creators).await?;
+        self.insert_alternate_titles(manga_id, &manga.alternate_titles)
+            .await?;
+        Ok(Some(stored_manga(
+            manga_id,
+            manga,
+            creation_date,
+            updated_date,
+        )))
+    }
+
+    async fn remove_manga(&mut self, manga_id: i32) -> Result<bool> {
+        for query in [
+            DELETE_MANGA_PAGE_URLS_QUERY,
+            DELETE_MANGA_PAGES_QUERY,
+            DELETE_MANGA_CHAPTERS_QUERY,
+            DELETE_MANGA_CREATORS_QUERY,
+            DELETE_MANGA_ALTERNATE_TITLES_QUERY,
+        ] {
+            self.client.execute(query, &[&manga_id]).await?;
+        }
+        let deleted = self
+            .client
+            .execute(DELETE_MANGA_QUERY, &[&manga_id])
+            .await?
+            .total();
+        Ok(deleted > 0)
+    }
+
+    async fn insert_chapter(
+        &mut self,
+        manga_id: i32,
+        chapter: NewChapter,
+    ) -> Result<Option<Chapter>> {
+        if self
+            .query_id(SELECT_MANGA_ID_QUERY, &[&manga_id], "MangaID")
+            .await?
+            .is_none()
+        {
+            return Ok(None);
+        }
+        let chapter_number = chapter.chapter_number.as_str();
+        if self
+            .find_chapter_index(manga_id, chapter_number)
+            .await?
+            .is_some()
+        {
+            return Err(chapter_exists(&chapter.chapter_number));
+        }
+        let creation_date = creation_date();
+        let stream = self
+            .client
+            .query(
+                INSERT_CHAPTER_QUERY,
+                &[
+                    &manga_id,
+                    &chapter_number,
+                    &chapter.chapter_name.as_str(),
+                    &creation_date,
+                    &chapter.release_date,
+                ],
+            )
+            .await?;
+        let chapter_index = returned_id(stream.into_row().await?, "ChapterIndex")?;
+        self.insert_pages(manga_id, chapter_index, &chapter.pages)
+            .await?;
+        self.touch_manga(manga_id, creation_date).await?;
+        Ok(Some(stored_chapter(
+            manga_id,
+            chapter,
+            creation_date,
+            creation_date,
+        )))
+    }
+
+    async fn replace_chapter(
+        &mut self,
+        manga_id: i32,
+        chapter_number: &str,
+        chapter: NewChapter,
+    ) -> Result<Option<Chapter>> {
+        let chapter_index = match self.find_chapter_index(manga_id, chapter_number).await? {
+            Some(chapter_index) => chapter_index,
+            None => return Ok(None),
+        };
+        let new_chapter_number = chapter.chapter_number.as_str();
+        match self
+            .find_chapter_index(manga_id, new_chapter_number)
+            .await?
+        {
+            Some(other_index) if other_index != chapter_index => {
+                return Err(chapter_exists(&chapter.chapter_number))
+            }
+            _ => {}
+        }
+        let updated_date = creation_date();
+        let row = self
+            .client
+            .query(
+                UPDATE_CHAPTER_QUERY,
+                &[
+                    &manga_id,
+                    &chapter_index,
+                    &new_chapter_number,
+                    &chapter.chapter_name.as_str(),
+                    &chapter.release_date,
+                    &updated_date,
+                ],
+            )
+            .await?
+            .into_row()
+            .await?;
+        let creation_date = match row {
+            Some(row) => decode(&row, 0, "DateCreated")?,
+            None => return Ok(None),
+        };
+        self.delete_pages(manga_id, chapter_index).await?;
+        self.insert_pages(manga_id, chapter_index, &chapter.pages)
+            .await?;
+        self.touch_manga(manga_id, updated_date).await?;
+        Ok(Some(stored_chapter(
+            manga_id,
+            chapter,
+            creation_date,
+            updated_date,
+        )))
+    }
+
+    async fn remove_chapter(&mut self, manga_id: i32, chapter_number: &str) -> Result<bool> {
+        let chapter_index = match self.find_chapter_index(manga_id, chapter_number).await? {
+            Some(chapter_index) => chapter_index,
+            None => return Ok(false),
+        };
+        self.delete_pages(manga_id, chapter_index).await?;
+        self.client
+            .execute(DELETE_CHAPTER_QUERY, &[&manga_id, &chapter_index])
+            .await?;
+        self.touch_manga(manga_id, creation_date()).await?;
+        Ok(true)
+    }
+}
+
 // i32 as no u32 in SQL Server
 #[async_trait]
 impl MangaService<i32> for Waifusims<Compat<TcpStream>> {
     async fn get_all_manga_titles(&mut self) -> Result<Vec<Manga>> {
         let stream = self.client.simple_query(SELECT_ALL_MANGA_QUERY).await?;
-        // We only make one query, so one result
         // Take first result, as we only make one query
         let rows = stream.into_first_result().await?;
-        // map to Manga and return, should never fail
+        let mut mangas = rows
+            .iter()
+            .enumerate()
+            .map(|(index, row)| decode_manga(row, index))
+            .collect::<Result<Vec<Manga>>>()?;
+        let stream = self
+            .client
+            .simple_query(SELECT_ALL_MANGA_CREATORS_QUERY)
+            .await?;
+        let rows = stream.into_first_result().await?;
+        let creators = rows
+            .iter()
+            .enumerate()
+            .map(|(index, row)| Ok((decode(row, index, "MangaID")?, decode_creator(row, index)?)))
+            .collect::<Result<Vec<(i32, Creator)>>>()?;
+        assign_creators(&mut mangas, creators);
+        Ok(mangas)
+    }
+
+    async fn list_manga(&mut self, query: &MangaListQuery) -> Result<MangaListing> {
+        let status = query.status.map(|status| status.as_str());
+        let author = query.author.as_deref();
+        let stream = self
+            .client
+            .query(count_manga_list_query(), &[&status, &author])
+            .await?;
+        let total: i32 = match stream.into_row().await? {
+            Some(row) => decode(&row, 0, "Total")?,
+            None => 0,
+        };
+        let stream = self
+            .client
+            .query(
+                select_manga_list_query(query.sort),
+                &[
+                    &status,
+                    &author,
+                    &i64::from(query.offset),
+                    &list_limit(query),
+                ],
+            )
+            .await?;
+        let rows = stream.into_first_result().await?;
+        let mut mangas = rows
+            .iter()
+            .enumerate()
+            .map(|(index, row)| decode_manga(row, index))
+            .collect::<Result<Vec<Manga>>>()?;
+        self.assign_listed_creators(&mut mangas).await?;
+        Ok(MangaListing {
+            mangas,
+            total: total as u64,
+        })
+    }
+
+    async fn search(&mut self, query: &str, limit: u32) -> Result<Vec<SearchResult>> {
+        let pattern = match like_pattern(&search_terms(query)) {
+            Some(pattern) => pattern,
+            None => return Ok(vec![]),
+        };
+        let stream = self
+            .client
+            .query(SEARCH_QUERY, &[&pattern.as_str(), &MAX_CANDIDATES])
+            .await?;
+        let rows = stream.into_first_result().await?;
+        let hits = rows
+            .iter()
+            .enumerate()
+            .map(|(index, row)| decode_search_hit(row, index))
+            .collect::<Result<Vec<SearchHit>>>()?;
+        let ranked = rank(query, hits, limit as usize);
+        let manga_ids = ranked
+            .iter()
+            .map(|(hit, _)| hit.manga_id)
+            .collect::<Vec<i32>>();
+        let stream = self
+            .client
+            .query(
+                SELECT_MANGA_BY_IDS_QUERY,
+                &[&serde_json::to_string(&manga_ids)?],
+            )
+            .await?;
+        let rows = stream.into_first_result().await?;
+        let mut mangas = rows
+            .iter()
+            .enumerate()
+            .map(|(index, row)| decode_manga(row, index))
+            .collect::<Result<Vec<Manga>>>()?;
+        self.assign_listed_creators(&mut mangas).await?;
+        Ok(search_results(ranked, mangas))
+    }
+
+    async fn get_manga(&mut self, manga_id: i32) -> Result<Option<Manga>> {
+        let row = self
+            .client
+            .query(SELECT_MANGA_QUERY, &[&manga_id])
+            .await?
+            .into_row()
+            .await?;
+        let mut mangas = row
+            .map(|row| decode_manga(&row, 0))
+            .transpose()?
+            .into_iter()
+            .collect::<Vec<Manga>>();
+        self.assign_listed_creators(&mut mangas).await?;
+        Ok(mangas.pop())
+    }
+
+    async fn get_manga_creators(&mut self, manga_id: i32) -> Result<Vec<Creator>> {
+        let stream = self
+            .client
+            .query(SELECT_MANGA_CREATORS_QUERY, &[&manga_id])
+            .await?;
+        let rows = stream.into_first_result().await?;
         rows.iter()
-            .map(|row| {
-                Ok(Manga {
-                    manga_id: row.get("MangaID").expect("MangaID is NOT NULL"),
-                    manga_name: row
-                        .get::<&str, _>("MangaName")
-                        .expect("MangaName is NOT NULL")
-                        .to_owned(),
-                    author_names: vec![row
-                        .get::<&str, _>("AuthorName")
-                        .expect("AuthorName is NOT NULL")
-                        .to_owned()],
-                    artist_names: vec![row
-                        .get::<&str, _>("AuthorName")
-                        .expect("AuthorName is NOT NULL")
-                        .to_owned()],
-                    cover_image_url: row
-                        .get::<&str, _>("CoverImageURL")
-                        .expect("CoverImageURL is hopefully NOT NULL but IDR")
-                        .to_owned(),
-                    purchase_url: row
-                        .get::<&str, _>("PurchaseURL")
-                        .expect("PurchaseURL is hopefully NOT NULL but IDR")
-                        .to_owned(),
-                })
-            })
+            .enumerate()
+            .map(|(index, row)| decode_creator(row, index))
             .collect()
     }
 
@@ -221,37 +1594,78 @@ impl MangaService<i32> for Waifusims<Compat<TcpStream>> {
             .await?;
         let rows = stream.into_first_result().await?;
         let mut chapters = rows
-            .into_iter()
-            .map(|row| Chapter {
-                manga_id: row.get("MangaID").expect("MangaID is NOT NULL"),
-                chapter_number: row
-                    .get::<&str, _>("ChapterNumber")
-                    .expect("ChapterNumber is NOT NULL")
-                    .to_owned(),
-                chapter_name: row
-                    .get::<&str, _>("ChapterName")
-                    .expect("ChapterName is NOT NULL")
-                    .to_owned(),
-                creation_date: row
-                    .get::<NaiveDateTime, _>("DateCreated")
-                    .expect("DateCreated is NOT NULL")
-                    .to_owned(),
-                release_date: row
-                    .get::<NaiveDateTime, _>("DateReleased")
-                    .expect("DateReleased is hopefully NOT NULL but IDR")
-                    .to_owned(),
-            })
-            .collect::<Vec<Chapter>>();
-        chapters.sort_by(|a, b| {
-            let chapter_number_a: f64 = a.chapter_number.parse().unwrap_or(0f64);
-            let chapter_number_b: f64 = b.chapter_number.parse().unwrap_or(0f64);
-            chapter_number_a
-                .partial_cmp(&chapter_number_b)
-                .unwrap_or(Ordering::Equal)
-        });
+            .iter()
+            .enumerate()
+            .map(|(index, row)| decode_chapter(row, index))
+            .collect::<Result<Vec<Chapter>>>()?;
+        sort_chapters(&mut chapters);
         Ok(chapters)
     }
 
+    async fn get_mangas_chapters(&mut self, manga_ids: &[i32]) -> Result<Vec<Vec<Chapter>>> {
+        let stream = self
+            .client
+            .query(
+                SELECT_MANGAS_CHAPTERS_QUERY,
+                &[&serde_json::to_string(manga_ids)?],
+            )
+            .await?;
+        let rows = stream.into_first_result().await?;
+        let chapters = rows
+            .iter()
+            .enumerate()
+            .map(|(index, row)| decode_chapter(row, index))
+            .collect::<Result<Vec<Chapter>>>()?;
+        Ok(group_manga_chapters(manga_ids, chapters))
+    }
+
+    async fn get_chapter(
+        &mut self,
+        manga_id: i32,
+        chapter_number: &str,
+    ) -> Result<Option<Chapter>> {
+        let row = self
+            .client
+            .query(SELECT_CHAPTER_QUERY, &[&manga_id, &chapter_number])
+            .await?
+            .into_row()
+            .await?;
+        row.map(|row| decode_chapter(&row, 0)).transpose()
+    }
+
+    async fn get_recent_chapters(
+        &mut self,
+        limit: u32,
+        since: Option<NaiveDateTime>,
+    ) -> Result<Vec<RecentChapter>> {
+        let stream = self
+            .client
+            .query(SELECT_RECENT_CHAPTERS_QUERY, &[&since, &i64::from(limit)])
+            .await?;
+        let rows = stream.into_first_result().await?;
+        let chapters = rows
+            .iter()
+            .enumerate()
+            .map(|(index, row)| decode_chapter(row, index))
+            .collect::<Result<Vec<Chapter>>>()?;
+        let manga_ids = chapter_manga_ids(&chapters);
+        let stream = self
+            .client
+            .query(
+                SELECT_MANGA_BY_IDS_QUERY,
+                &[&serde_json::to_string(&manga_ids)?],
+            )
+            .await?;
+        let rows = stream.into_first_result().await?;
+        let mut mangas = rows
+            .iter()
+            .enumerate()
+            .map(|(index, row)| decode_manga(row, index))
+            .collect::<Result<Vec<Manga>>>()?;
+        self.assign_listed_creators(&mut mangas).await?;
+        Ok(recent_chapters(chapters, mangas))
+    }
+
     async fn get_pages(&mut self, manga_id: i32, chapter_number: &str) -> Result<Vec<Page>> {
         // Quick test seems to imply that query is safe to injections
         let stream = self
@@ -259,19 +1673,188 @@ impl MangaService<i32> for Waifusims<Compat<TcpStream>> {
             .query(SELECT_CHAPTER_PAGES_QUERY, &[&manga_id, &chapter_number])
             .await?;
         let rows = stream.into_first_result().await?;
+        let urls = rows
+            .iter()
+            .enumerate()
+            .map(|(index, row)| {
+                Ok((
+                    decode(row, index, "PageID")?,
+                    decode(row, index, "PageNumber")?,
+                    decode::<&str>(row, index, "URL")?.to_owned(),
+                ))
+            })
+            .collect::<Result<Vec<(i32, i32, String)>>>()?;
+        Ok(group_page_urls(urls))
+    }
+
+    async fn get_chapters_pages(&mut self, chapters: &[(i32, String)]) -> Result<Vec<Vec<Page>>> {
+        let stream = self
+            .client
+            .query(
+                SELECT_CHAPTERS_PAGES_QUERY,
+                &[&serde_json::to_string(chapters)?],
+            )
+            .await?;
+        let rows = stream.into_first_result().await?;
+        let urls = rows
+            .iter()
+            .enumerate()
+            .map(|(index, row)| {
+                Ok((
+                    decode(row, index, "MangaID")?,
+                    decode::<&str>(row, index, "ChapterNumber")?.to_owned(),
+                    decode(row, index, "PageID")?,
+                    decode(row, index, "PageNumber")?,
+                    decode::<&str>(row, index, "URL")?.to_owned(),
+                ))
+            })
+            .collect::<Result<Vec<(i32, String, i32, i32, String)>>>()?;
+        Ok(group_chapter_page_urls(chapters, urls))
+    }
+
+    async fn create_manga(&mut self, manga: NewManga) -> Result<Manga> {
+        validate_manga(&manga)?;
+        self.begin().await?;
+        let result = self.insert_manga(manga).await;
+        self.finish(result).await
+    }
+
+    async fn update_manga(&mut self, manga_id: i32, manga: NewManga) -> Result<Option<Manga>> {
+        validate_manga(&manga)?;
+        self.begin().await?;
+        let result = self.replace_manga(manga_id, manga).await;
+        self.finish(result).await
+    }
+
+    async fn delete_manga(&mut self, manga_id: i32) -> Result<bool> {
+        self.begin().await?;
+        let result = self.remove_manga(manga_id).await;
+        self.finish(result).await
+    }
+
+    async fn create_chapter(
+        &mut self,
+        manga_id: i32,
+        chapter: NewChapter,
+    ) -> Result<Option<Chapter>> {
+        validate_chapter(&chapter)?;
+        self.begin().await?;
+        let result = self.insert_chapter(manga_id, chapter).await;
+        self.finish(result).await
+    }
+
+    async fn update_chapter(
+        &mut self,
+        manga_id: i32,
+        chapter_number: &str,
+        chapter: NewChapter,
+    ) -> Result<Option<Chapter>> {
+        validate_chapter(&chapter)?;
+        self.begin().await?;
+        let result = self
+            .replace_chapter(manga_id, chapter_number, chapter)
+            .await;
+        self.finish(result).await
+    }
+
+    async fn delete_chapter(&mut self, manga_id: i32, chapter_number: &str) -> Result<bool> {
+        self.begin().await?;
+        let result = self.remove_chapter(manga_id, chapter_number).await;
+        self.finish(result).await
+    }
+}
+
+impl Waifusims<Compat<TcpStream>> {
+    async fn execute_batch(&mut self, batch: &str) -> Result<()> {
+        self.client
+            .simple_query(batch)
+            .await?
+            .into_results()
+            .await?;
+        Ok(())
+    }
+
+    async fn record_migration(&mut self, migration: &Migration) -> Result<()> {
+        self.client
+            .execute(
+                INSERT_SCHEMA_MIGRATION_QUERY,
+                &[&migration.version, &migration.name, &creation_date()],
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn apply_migration(&mut self, migration: &Migration, direction: Direction) -> Result<()> {
+        for batch in sql_server_batches(migration.script(direction)) {
+            self.execute_batch(&batch).await?;
+        }
+        match direction {
+            Direction::Up => self.record_migration(migration).await,
+            Direction::Down => {
+                self.client
+                    .execute(DELETE_SCHEMA_MIGRATION_QUERY, &[&migration.version])
+                    .await?;
+                Ok(())
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Migrate for Waifusims<Compat<TcpStream>> {
+    fn dialect(&self) -> Dialect {
+        Dialect::SqlServer
+    }
+
+    async fn applied_migrations(&mut self) -> Result<Vec<AppliedMigration>> {
+        self.execute_batch(CREATE_SCHEMA_MIGRATION_QUERY).await?;
+        let rows = self
+            .client
+            .simple_query(SELECT_SCHEMA_MIGRATIONS_QUERY)
+            .await?
+            .into_first_result()
+            .await?;
         rows.iter()
-            .map(|row| {
-                Ok(Page {
-                    page_number: row
-                        .get::<i32, _>("PageNumber")
-                        .expect("PageNumber is NOT NULL")
-                        .to_owned(),
-                    url_string: row
-                        .get::<&str, _>("URL")
-                        .expect("URL is NOT NULL")
-                        .to_owned(),
+            .enumerate()
+            .map(|(index, row)| {
+                Ok(AppliedMigration {
+                    version: decode(row, index, "Version")?,
+                    name: decode::<&str>(row, index, "Name")?.to_owned(),
+                    applied_at: decode(row, index, "AppliedAt")?,
                 })
             })
             .collect()
     }
+
+    async fn has_waifusims_tables(&mut self) -> Result<bool> {
+        let row = self
+            .client
+            .simple_query(SELECT_HAS_WAIFUSIMS_TABLES_QUERY)
+            .await?
+            .into_row()
+            .await?;
+        Ok(returned_id(row, "HasTables")? == 1)
+    }
+
+    async fn run_migration(
+        &mut self,
+        migration: &'static Migration,
+        direction: Direction,
+    ) -> Result<()> {
+        self.begin().await?;
+        let result = self.apply_migration(migration, direction).await;
+        self.finish(result).await
+    }
+
+    async fn record_migrations(&mut self, migrations: &'static [Migration]) -> Result<()> {
+        self.begin().await?;
+        let mut result = Ok(());
+        for migration in migrations {
+            result = self.record_migration(migration).await;
+            if result.is_err() {
+                break;
+            }
+        }
+        self.finish(result).await
+    }
 }
@@ -0,0 +1,679 @@
+use std::{
+    cmp::Reverse,
+    collections::HashMap,
+    fs::File,
+    io::BufReader,
+    path::Path,
+    sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard},
+};
+
+use async_trait::async_trait;
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    candidates, chapter_exists, creation_date, group_manga_chapters, rank, recent_chapters,
+    search_results, search_terms, sort_chapters, stored_chapter, stored_manga, validate_chapter,
+    validate_manga, Chapter, Creator, CreatorRole, Manga, MangaListQuery, MangaListing,
+    MangaService, MangaSort, NewChapter, NewManga, NewPage, Page, RecentChapter, Result,
+    SearchField, SearchHit, SearchResult,
+};
+
+/// Seed data for an `InMemoryMangaService`, shaped like the Waifusims tables.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MangaFixture {
+    #[serde(default)]
+    pub mangas: Vec<Manga>,
+    #[serde(default)]
+    pub chapters: Vec<Chapter>,
+    #[serde(default)]
+    pub pages: Vec<FixturePage>,
+    #[serde(default)]
+    pub alternate_titles: Vec<FixtureAlternateTitle>,
+    /// ID the next created manga gets, raised past the fixture's mangas when it's served.
+    /// Like an identity column, a deleted manga's ID isn't handed out again.
+    #[serde(default)]
+    pub next_manga_id: i32,
+}
+
+/// A `Page` along with the chapter it belongs to.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FixturePage {
+    pub manga_id: i32,
+    pub chapter_number: String,
+    #[serde(flatten)]
+    pub page: Page,
+}
+
+/// Another title a manga is known by, eg: its romanized or translated name.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FixtureAlternateTitle {
+    pub manga_id: i32,
+    pub title: String,
+}
+
+/// Serves manga from values held in memory, for tests and demos.
+///
+/// Cloning shares the same fixture, writes are seen by every clone.
+#[derive(Debug, Clone)]
+pub struct InMemoryMangaService {
+    fixture: Arc<RwLock<MangaFixture>>,
+}
+
+impl Default for InMemoryMangaService {
+    fn default() -> Self {
+        InMemoryMangaService::new(MangaFixture::default())
+    }
+}
+
+impl InMemoryMangaService {
+    pub fn new(mut fixture: MangaFixture) -> InMemoryMangaService {
+        let max_manga_id = fixture
+            .mangas
+            .iter()
+            .map(|manga| manga.manga_id)
+            .max()
+            .unwrap_or(0);
+        fixture.next_manga_id = fixture.next_manga_id.max(max_manga_id + 1);
+        // Fixtures may have changed since they were last served
+        let loaded = creation_date();
+        for manga in &mut fixture.mangas {
+            manga.updated_date.get_or_insert(loaded);
+        }
+        for chapter in &mut fixture.chapters {
+            chapter.updated_date.get_or_insert(loaded);
+        }
+        InMemoryMangaService {
+            fixture: Arc::new(RwLock::new(fixture)),
+        }
+    }
+
+    // Every write happens under one lock, so a panic can't leave a change half applied
+    fn fixture(&self) -> RwLockReadGuard<'_, MangaFixture> {
+        self.fixture
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn fixture_mut(&self) -> RwLockWriteGuard<'_, MangaFixture> {
+        self.fixture
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Loads a JSON serialized `MangaFixture`.
+    pub fn from_json_file<P: AsRef<Path>>(path: P) -> Result<InMemoryMangaService> {
+        let reader = BufReader::new(File::open(path)?);
+        let fixture: MangaFixture = serde_json::from_reader(reader)?;
+        Ok(InMemoryMangaService::new(fixture))
+    }
+}
+
+impl MangaFixture {
+    fn newest_chapter_dates(&self) -> HashMap<i32, NaiveDateTime> {
+        let mut newest = HashMap::new();
+        for chapter in &self.chapters {
+            newest
+                .entry(chapter.manga_id)
+                .and_modify(|date: &mut NaiveDateTime| *date = (*date).max(chapter.creation_date))
+                .or_insert(chapter.creation_date);
+        }
+        newest
+    }
+
+    /// Marks the manga changed by a write to one of its chapters.
+    fn touch_manga(&mut self, manga_id: i32, updated_date: NaiveDateTime) {
+        if let Some(manga) = self
+            .mangas
+            .iter_mut()
+            .find(|manga| manga.manga_id == manga_id)
+        {
+            manga.updated_date = Some(updated_date);
+        }
+    }
+
+    fn set_alternate_titles(&mut self, manga_id: i32, titles: &[String]) {
+        self.alternate_titles
+            .retain(|alternate_title| alternate_title.manga_id != manga_id);
+        let titles = titles.iter().map(|title| FixtureAlternateTitle {
+            manga_id,
+            title: title.to_owned(),
+        });
+        self.alternate_titles.extend(titles);
+    }
+
+    /// Every searchable text, `rank` does the matching.
+    fn search_hits(&self) -> Vec<SearchHit> {
+        let hit = |manga_id, field, text: &str| SearchHit {
+            manga_id,
+            field,
+            text: text.to_owned(),
+            chapter_number: None,
+        };
+        let mut hits = vec![];
+        for manga in &self.mangas {
+            hits.push(hit(manga.manga_id, SearchField::Title, &manga.manga_name));
+            for name in manga.author_names.iter().chain(&manga.artist_names) {
+                hits.push(hit(manga.manga_id, SearchField::Creator, name));
+            }
+        }
+        for alternate_title in &self.alternate_titles {
+            hits.push(hit(
+                alternate_title.manga_id,
+                SearchField::AlternateTitle,
+                &alternate_title.title,
+            ));
+        }
+        for chapter in &self.chapters {
+            hits.push(SearchHit {
+                chapter_number: Some(chapter.chapter_number.clone()),
+                ..hit(
+                    chapter.manga_id,
+                    SearchField::Chapter,
+                    &chapter.chapter_name,
+                )
+            });
+        }
+        hits
+    }
+
+    fn chapter_position(&self, manga_id: i32, chapter_number: &str) -> Option<usize> {
+        self.chapters.iter().position(|chapter| {
+            chapter.manga_id == manga_id && chapter.chapter_number.as_str() == chapter_number
+        })
+    }
+
+    fn remove_pages(&mut self, manga_id: i32, chapter_number: &str) {
+        self.pages
+            .retain(|page| !(page.manga_id == manga_id && page.chapter_number == chapter_number));
+    }
+
+    fn add_pages(&mut self, manga_id: i32, chapter_number: &str, pages: Vec<NewPage>) {
+        let pages = pages.into_iter().map(|page| {
+            let mut urls = page.urls.into_iter();
+            FixturePage {
+                manga_id,
+                chapter_number: chapter_number.to_owned(),
+                page: Page {
+                    url_string: urls.next().unwrap_or_default(),
+                    page_number: page.page_number,
+                    mirror_urls: urls.collect(),
+                },
+            }
+        });
+        self.pages.extend(pages);
+    }
+}
+
+impl From<MangaFixture> for InMemoryMangaService {
+    fn from(fixture: MangaFixture) -> Self {
+        InMemoryMangaService::new(fixture)
+    }
+}
+
+#[async_trait]
+impl MangaService<i32> for InMemoryMangaService {
+    async fn get_all_manga_titles(&mut self) -> Result<Vec<Manga>> {
+        let mut mangas = self.fixture().mangas.clone();
+        mangas.sort_by_key(|manga| manga.manga_id);
+        Ok(mangas)
+    }
+
+    async fn list_manga(&mut self, query: &MangaListQuery) -> Result<MangaListing> {
+        let fixture = self.fixture();
+        let author = query.author.as_ref().map(|author| author.to_lowercase());
+        let mut mangas = fixture
+            .mangas
+            .iter()
+            .filter(|manga| query.status.is_none_or(|status| manga.status == status))
+            .filter(|manga| {
+                author.as_ref().is_none_or(|author| {
+                    manga
+                        .author_names
+                        .iter()
+                        .any(|name| name.to_lowercase() == *author)
+                })
+            })
+            .cloned()
+            .collect::<Vec<Manga>>();
+        // Reversing an Option puts None last
+        match query.sort {
+            MangaSort::Name => {
+                mangas.sort_by_cached_key(|manga| (manga.manga_name.to_lowercase(), manga.manga_id))
+            }
+            MangaSort::NewestChapter => {
+                let newest = fixture.newest_chapter_dates();
+                mangas.sort_by_key(|manga| {
+                    (
+                        Reverse(newest.get(&manga.manga_id).copied()),
+                        manga.manga_id,
+                    )
+                })
+            }
+            MangaSort::CreationDate => {
+                mangas.sort_by_key(|manga| Reverse((manga.creation_date, manga.manga_id)))
+            }
+        }
+        let total = mangas.len() as u64;
+        let mangas = mangas
+            .into_iter()
+            .skip(query.offset as usize)
+            .take(query.limit.map_or(usize::MAX, |limit| limit as usize))
+            .collect();
+        Ok(MangaListing { mangas, total })
+    }
+
+    async fn search(&mut self, query: &str, limit: u32) -> Result<Vec<SearchResult>> {
+        let fixture = self.fixture();
+        let hits = candidates(&search_terms(query), fixture.search_hits());
+        let ranked = rank(query, hits, limit as usize);
+        Ok(search_results(ranked, fixture.mangas.clone()))
+    }
+
+    async fn get_manga(&mut self, manga_id: i32) -> Result<Option<Manga>> {
+        Ok(self
+            .fixture()
+            .mangas
+            .iter()
+            .find(|manga| manga.manga_id == manga_id)
+            .cloned())
+    }
+
+    async fn get_manga_creators(&mut self, manga_id: i32) -> Result<Vec<Creator>> {
+        let fixture = self.fixture();
+        let manga = fixture
+            .mangas
+            .iter()
+            .find(|manga| manga.manga_id == manga_id);
+        // Fixtures only keep names, so authors are credited ahead of artists
+        Ok(manga
+            .map(|manga| {
+                let authors = manga
+                    .author_names
+                    .iter()
+                    .map(|name| (name, CreatorRole::Author));
+                let artists = manga
+                    .artist_names
+                    .iter()
+                    .map(|name| (name, CreatorRole::Artist));
+                authors
+                    .chain(artists)
+                    .map(|(name, role)| Creator {
+                        creator_name: name.to_owned(),
+                        role,
+                    })
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+
+    async fn get_manga_chapters(&mut self, manga_id: i32) -> Result<Vec<Chapter>> {
+        let mut chapters = self
+            .fixture()
+            .chapters
+            .iter()
+            .filter(|chapter| chapter.manga_id == manga_id)
+            .cloned()
+            .collect::<Vec<Chapter>>();
+        sort_chapters(&mut chapters);
+        Ok(chapters)
+    }
+
+    async fn get_mangas_chapters(&mut self, manga_ids: &[i32]) -> Result<Vec<Vec<Chapter>>> {
+        let chapters = self
+            .fixture()
+            .chapters
+            .iter()
+            .filter(|chapter| manga_ids.contains(&chapter.manga_id))
+            .cloned()
+            .collect::<Vec<Chapter>>();
+        Ok(group_manga_chapters(manga_ids, chapters))
+    }
+
+    async fn get_chapter(
+        &mut self,
+        manga_id: i32,
+        chapter_number: &str,
+    ) -> Result<Option<Chapter>> {
+        let fixture = self.fixture();
+        Ok(fixture
+            .chapter_position(manga_id, chapter_number)
+            .map(|position| fixture.chapters[position].clone()))
+    }
+
+    async fn get_recent_chapters(
+        &mut self,
+        limit: u32,
+        since: Option<NaiveDateTime>,
+    ) -> Result<Vec<RecentChapter>> {
+        let fixture = self.fixture();
+        let released = |chapter: &Chapter| chapter.release_date.unwrap_or(chapter.creation_date);
+        let mut chapters = fixture
+            .chapters
+            .iter()
+            .filter(|chapter| since.is_none_or(|since| released(chapter) > since))
+            .cloned()
+            .collect::<Vec<Chapter>>();
+        chapters.sort_by(|a, b| {
+            released(b)
+                .cmp(&released(a))
+                .then(a.manga_id.cmp(&b.manga_id))
+                .then_with(|| a.chapter_number.as_str().cmp(b.chapter_number.as_str()))
+        });
+        chapters.truncate(limit as usize);
+        Ok(recent_chapters(chapters, fixture.mangas.clone()))
+    }
+
+    async fn get_pages(&mut self, manga_id: i32, chapter_number: &str) -> Result<Vec<Page>> {
+        let mut pages = self
+            .fixture()
+            .pages
+            .iter()
+            .filter(|page| page.manga_id == manga_id && page.chapter_number == chapter_number)
+            .map(|page| page.page.clone())
+            .collect::<Vec<Page>>();
+        pages.sort_by_key(|page| page.page_number);
+        Ok(pages)
+    }
+
+    async fn get_chapters_pages(&mut self, chapters: &[(i32, String)]) -> Result<Vec<Vec<Page>>> {
+        let mut pages = Vec::with_capacity(chapters.len());
+        for (manga_id, chapter_number) in chapters {
+            pages.push(self.get_pages(*manga_id, chapter_number).await?);
+        }
+        Ok(pages)
+    }
+
+    async fn create_manga(&mut self, manga: NewManga) -> Result<Manga> {
+        validate_manga(&manga)?;
+        let mut fixture = self.fixture_mut();
+        let manga_id = fixture.next_manga_id;
+        fixture.next_manga_id += 1;
+        fixture.set_alternate_titles(manga_id, &manga.alternate_titles);
+        let creation_date = creation_date();
+        let manga = stored_manga(manga_id, manga, Some(creation_date), creation_date);
+        fixture.mangas.push(manga.clone());
+        Ok(manga)
+    }
+
+    async fn update_manga(&mut self, manga_id: i32, manga: NewManga) -> Result<Option<Manga>> {
+        validate_manga(&manga)?;
+        let mut fixture = self.fixture_mut();
+        let stored = match fixture
+            .mangas
+            .iter_mut()
+            .find(|manga| manga.manga_id == manga_id)
+        {
+            Some(stored) => stored,
+            None => return Ok(None),
+        };
+        let alternate_titles = manga.alternate_titles.clone();
+        *stored = stored_manga(manga_id, manga, stored.creation_date, creation_date());
+        let stored = stored.clone();
+        fixture.set_alternate_titles(manga_id, &alternate_titles);
+        Ok(Some(stored))
+    }
+
+    async fn delete_manga(&mut self, manga_id: i32) -> Result<bool> {
+        let mut fixture = self.fixture_mut();
+        let manga_count = fixture.mangas.len();
+        fixture.mangas.retain(|manga| manga.manga_id != manga_id);
+        fixture
+            .chapters
+            .retain(|chapter| chapter.manga_id != manga_id);
+        fixture.pages.retain(|page| page.manga_id != manga_id);
+        fixture
+            .alternate_titles
+            .retain(|alternate_title| alternate_title.manga_id != manga_id);
+        Ok(fixture.mangas.len() != manga_count)
+    }
+
+    async fn create_chapter(
+        &mut self,
+        manga_id: i32,
+        chapter: NewChapter,
+    ) -> Result<Option<Chapter>> {
+        validate_chapter(&chapter)?;
+        let mut fixture = self.fixture_mut();
+        if !fixture
+            .mangas
+            .iter()
+            .any(|manga| manga.manga_id == manga_id)
+        {
+            return Ok(None);
+        }
+        let chapter_number = chapter.chapter_number.as_str().to_owned();
+        if fixture
+            .chapter_position(manga_id, &chapter_number)
+            .is_some()
+        {
+            return Err(chapter_exists(&chapter.chapter_number));
+        }
+        let pages = chapter.pages.clone();
+        let creation_date = creation_date();
+        let chapter = stored_chapter(manga_id, chapter, creation_date, creation_date);
+        fixture.chapters.push(chapter.clone());
+        fixture.touch_manga(manga_id, creation_date);
+        fixture.add_pages(manga_id, &chapter_number, pages);
+        Ok(Some(chapter))
+    }
+
+    async fn update_chapter(
+        &mut self,
+        manga_id: i32,
+        chapter_number: &str,
+        chapter: NewChapter,
+    ) -> Result<Option<Chapter>> {
+        validate_chapter(&chapter)?;
+        let mut fixture = self.fixture_mut();
+        let position = match fixture.chapter_position(manga_id, chapter_number) {
+            Some(position) => position,
+            None => return Ok(None),
+        };
+        let new_chapter_number = chapter.chapter_number.as_str().to_owned();
+        match fixture.chapter_position(manga_id, &new_chapter_number) {
+            Some(other_position) if other_position != position => {
+                return Err(chapter_exists(&chapter.chapter_number))
+            }
+            _ => {}
+        }
+        let pages = chapter.pages.clone();
+        let updated_date = creation_date();
+        let creation_date = fixture.chapters[position].creation_date;
+        let chapter = stored_chapter(manga_id, chapter, creation_date, updated_date);
+        fixture.chapters[position] = chapter.clone();
+        fixture.touch_manga(manga_id, updated_date);
+        fixture.remove_pages(manga_id, chapter_number);
+        fixture.add_pages(manga_id, &new_chapter_number, pages);
+        Ok(Some(chapter))
+    }
+
+    async fn delete_chapter(&mut self, manga_id: i32, chapter_number: &str) -> Result<bool> {
+        let mut fixture = self.fixture_mut();
+        let position = match fixture.chapter_position(manga_id, chapter_number) {
+            Some(position) => position,
+            None => return Ok(false),
+        };
+        fixture.chapters.remove(position);
+        fixture.remove_pages(manga_id, chapter_number);
+        fixture.touch_manga(manga_id, creation_date());
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MangaStatus;
+
+    const FIXTURE_JSON: &str = r#"{
+        "mangas": [{
+            "manga_id": 1,
+            "manga_name": "Manga",
+            "author_names": ["Author"],
+            "artist_names": ["Author"],
+            "cover_image_url": "cover.png",
+            "purchase_url": ""
+        }],
+        "chapters": [{
+            "chapter_number": "1",
+            "chapter_name": "One",
+            "creation_date": "2021-01-01T00:00:00",
+            "release_date": "2021-01-01T00:00:00",
+            "manga_id": 1
+        }],
+        "pages": [
+            { "manga_id": 1, "chapter_number": "1", "url_string": "1-2.png", "page_number": 2 },
+            { "manga_id": 1, "chapter_number": "1", "url_string": "1-1.png", "page_number": 1 },
+            { "manga_id": 2, "chapter_number": "1", "url_string": "other.png", "page_number": 1 }
+        ]
+    }"#;
+
+    #[test]
+    fn serves_json_fixture() {
+        let fixture: MangaFixture = serde_json::from_str(FIXTURE_JSON).unwrap();
+        let mut service = InMemoryMangaService::new(fixture);
+
+        let mangas = tokio_test::block_on(service.get_all_manga_titles()).unwrap();
+        assert_eq!(mangas.len(), 1);
+        let creators = tokio_test::block_on(service.get_manga_creators(1)).unwrap();
+        assert_eq!(creators.len(), 2);
+        assert_eq!(creators[1].role, CreatorRole::Artist);
+        let chapters = tokio_test::block_on(service.get_manga_chapters(1)).unwrap();
+        assert_eq!(chapters[0].chapter_name, "One");
+        let pages = tokio_test::block_on(service.get_pages(1, "1")).unwrap();
+        let urls = pages
+            .iter()
+            .map(|page| page.url_string.as_str())
+            .collect::<Vec<&str>>();
+        assert_eq!(urls, vec!["1-1.png", "1-2.png"]);
+    }
+
+    #[test]
+    fn lists_manga_by_newest_chapter() {
+        let fixture: MangaFixture = serde_json::from_str(FIXTURE_JSON).unwrap();
+        let mut service = InMemoryMangaService::new(fixture);
+        let newer = tokio_test::block_on(service.create_manga(NewManga {
+            manga_name: "newer".to_owned(),
+            creators: vec![],
+            cover_image_url: None,
+            purchase_url: None,
+            status: MangaStatus::Completed,
+            alternate_titles: vec![],
+        }))
+        .unwrap();
+
+        let query = MangaListQuery {
+            sort: MangaSort::NewestChapter,
+            ..MangaListQuery::default()
+        };
+        let listing = tokio_test::block_on(service.list_manga(&query)).unwrap();
+        let manga_ids = listing
+            .mangas
+            .iter()
+            .map(|manga| manga.manga_id)
+            .collect::<Vec<i32>>();
+        assert_eq!(manga_ids, vec![1, newer.manga_id]);
+
+        let query = MangaListQuery {
+            sort: MangaSort::CreationDate,
+            status: Some(MangaStatus::Completed),
+            ..MangaListQuery::default()
+        };
+        let listing = tokio_test::block_on(service.list_manga(&query)).unwrap();
+        assert_eq!(listing.total, 1);
+        assert_eq!(listing.mangas[0].manga_id, newer.manga_id);
+    }
+
+    #[test]
+    fn never_reuses_deleted_manga_ids() {
+        let fixture: MangaFixture = serde_json::from_str(FIXTURE_JSON).unwrap();
+        let mut service = InMemoryMangaService::new(fixture);
+        let new_manga = || NewManga {
+            manga_name: "Manga".to_owned(),
+            creators: vec![],
+            cover_image_url: None,
+            purchase_url: None,
+            status: MangaStatus::default(),
+            alternate_titles: vec![],
+        };
+        let created = tokio_test::block_on(service.create_manga(new_manga())).unwrap();
+        assert_eq!(created.manga_id, 2);
+        assert!(tokio_test::block_on(service.delete_manga(created.manga_id)).unwrap());
+
+        let created = tokio_test::block_on(service.create_manga(new_manga())).unwrap();
+        assert_eq!(created.manga_id, 3);
+    }
+
+    #[test]
+    fn chapter_writes_update_the_manga() {
+        let mut fixture: MangaFixture = serde_json::from_str(FIXTURE_JSON).unwrap();
+        let date = fixture.chapters[0].creation_date;
+        fixture.mangas[0].updated_date = Some(date);
+        let mut service = InMemoryMangaService::new(fixture);
+        let manga = tokio_test::block_on(service.get_manga(1)).unwrap().unwrap();
+        assert_eq!(manga.updated_date, Some(date));
+        let chapters = tokio_test::block_on(service.get_manga_chapters(1)).unwrap();
+        assert!(chapters[0].updated_date > Some(date));
+
+        assert!(tokio_test::block_on(service.delete_chapter(1, "1")).unwrap());
+        let manga = tokio_test::block_on(service.get_manga(1)).unwrap().unwrap();
+        assert!(manga.updated_date > Some(date));
+    }
+
+    #[test]
+    fn unknown_manga_is_empty() {
+        let mut service = InMemoryMangaService::default();
+        assert!(tokio_test::block_on(service.get_manga_chapters(1))
+            .unwrap()
+            .is_empty());
+        assert!(tokio_test::block_on(service.get_manga_creators(1))
+            .unwrap()
+            .is_empty());
+        assert!(tokio_test::block_on(service.get_pages(1, "1"))
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn writes_are_shared_between_clones() {
+        let mut service = InMemoryMangaService::default();
+        let mut clone = service.clone();
+        let manga = tokio_test::block_on(service.create_manga(NewManga {
+            manga_name: "Manga".to_owned(),
+            creators: vec![],
+            cover_image_url: None,
+            purchase_url: None,
+            status: MangaStatus::default(),
+            alternate_titles: vec!["Shared".to_owned()],
+        }))
+        .unwrap();
+        let chapter = NewChapter {
+            chapter_number: "1".into(),
+            chapter_name: "One".to_owned(),
+            release_date: None,
+            pages: vec![NewPage {
+                page_number: 1,
+                urls: vec!["1-1.png".to_owned(), "1-1-mirror.png".to_owned()],
+            }],
+        };
+        assert!(
+            tokio_test::block_on(clone.create_chapter(manga.manga_id, chapter))
+                .unwrap()
+                .is_some()
+        );
+        let pages = tokio_test::block_on(service.get_pages(manga.manga_id, "1")).unwrap();
+        assert_eq!(pages[0].url_string, "1-1.png");
+        assert_eq!(pages[0].mirror_urls, vec!["1-1-mirror.png"]);
+        let results = tokio_test::block_on(clone.search("shared", 10)).unwrap();
+        assert_eq!(results[0].field, SearchField::AlternateTitle);
+
+        assert!(tokio_test::block_on(clone.delete_manga(manga.manga_id)).unwrap());
+        assert!(tokio_test::block_on(service.search("shared", 10))
+            .unwrap()
+            .is_empty());
+        assert!(tokio_test::block_on(service.get_all_manga_titles())
+            .unwrap()
+            .is_empty());
+    }
+}
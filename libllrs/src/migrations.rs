@@ -0,0 +1,291 @@
+//! Versioned Waifusims schema, embedded so a binary can stand up or upgrade its own database.
+//!
+//! Each migration is a directory under `libllrs/migrations` holding an up and a down script
+//! per dialect. Applied versions are recorded in a `SchemaMigration` table.
+//! SQL Server scripts may be split into batches with `GO` lines, as in `sqlcmd`,
+//! so later statements can use columns added earlier in the script.
+//!
+//! Databases created before migrations are baselined instead of migrated from scratch.
+//! Baselining only records versions, so the tables must already match what those
+//! migrations create: before `baseline 1`, compare the database with 0001's script for
+//! its dialect and bring any differing columns or types in line by hand. Constraint
+//! names may differ, migrations that drop one of 0001's constraints find it by column.
+
+use async_trait::async_trait;
+use chrono::NaiveDateTime;
+
+use crate::{Error, Result};
+
+/// One schema change, `up` applies it and `down` reverts it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Migration {
+    pub version: i64,
+    pub name: &'static str,
+    pub up: &'static str,
+    pub down: &'static str,
+}
+
+impl Migration {
+    pub fn script(&self, direction: Direction) -> &'static str {
+        match direction {
+            Direction::Up => self.up,
+            Direction::Down => self.down,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Up,
+    Down,
+}
+
+/// SQL flavour of a Waifusims database, each has its own scripts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dialect {
+    SqlServer,
+    Postgres,
+    Sqlite,
+}
+
+macro_rules! embed_migrations {
+    ($dialect:literal: $(($version:literal, $name:literal)),* $(,)?) => {
+        &[$(Migration {
+            version: $version,
+            name: $name,
+            up: include_str!(concat!("../migrations/", $name, "/", $dialect, ".up.sql")),
+            down: include_str!(concat!("../migrations/", $name, "/", $dialect, ".down.sql")),
+        }),*]
+    };
+}
+
+// Every dialect lists the same migrations, oldest first
+macro_rules! dialect_migrations {
+    ($dialect:literal) => {
+        embed_migrations!($dialect:
+            (1, "0001_create_waifusims"),
+            (2, "0002_manga_creators"),
+            (3, "0003_manga_status"),
+            (4, "0004_manga_alternate_titles"),
+            (5, "0005_updated_dates"),
+        )
+    };
+}
+
+const SQL_SERVER_MIGRATIONS: &[Migration] = dialect_migrations!("sqlserver");
+const POSTGRES_MIGRATIONS: &[Migration] = dialect_migrations!("postgres");
+const SQLITE_MIGRATIONS: &[Migration] = dialect_migrations!("sqlite");
+
+impl Dialect {
+    /// Every embedded migration, oldest first.
+    pub fn migrations(self) -> &'static [Migration] {
+        match self {
+            Dialect::SqlServer => SQL_SERVER_MIGRATIONS,
+            Dialect::Postgres => POSTGRES_MIGRATIONS,
+            Dialect::Sqlite => SQLITE_MIGRATIONS,
+        }
+    }
+}
+
+/// A version recorded in `SchemaMigration`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AppliedMigration {
+    pub version: i64,
+    pub name: String,
+    pub applied_at: NaiveDateTime,
+}
+
+/// A migration known to this build or to the database, `applied_at` is `None` while pending.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MigrationStatus {
+    pub version: i64,
+    pub name: String,
+    pub applied_at: Option<NaiveDateTime>,
+    /// `false` for versions applied by a newer build
+    pub embedded: bool,
+}
+
+/// Applies and reverts the embedded migrations.
+///
+/// Backends only provide the primitives, the provided methods decide what to run.
+#[async_trait]
+pub trait Migrate: Send {
+    fn dialect(&self) -> Dialect;
+
+    /// Creates `SchemaMigration` if it's missing and lists what it records, oldest first.
+    async fn applied_migrations(&mut self) -> Result<Vec<AppliedMigration>>;
+
+    /// Whether Waifusims tables already exist, used to catch databases that predate migrations.
+    async fn has_waifusims_tables(&mut self) -> Result<bool>;
+
+    /// Runs one script of `migration` and records it, in a single transaction.
+    async fn run_migration(
+        &mut self,
+        migration: &'static Migration,
+        direction: Direction,
+    ) -> Result<()>;
+
+    /// Records `migrations` as applied without running them.
+    async fn record_migrations(&mut self, migrations: &'static [Migration]) -> Result<()>;
+
+    async fn migration_status(&mut self) -> Result<Vec<MigrationStatus>> {
+        let mut applied = self.applied_migrations().await?;
+        let mut statuses = self
+            .dialect()
+            .migrations()
+            .iter()
+            .map(|migration| {
+                let applied_at = applied
+                    .iter()
+                    .position(|applied| applied.version == migration.version)
+                    .map(|index| applied.remove(index).applied_at);
+                MigrationStatus {
+                    version: migration.version,
+                    name: migration.name.to_owned(),
+                    applied_at,
+                    embedded: true,
+                }
+            })
+            .collect::<Vec<MigrationStatus>>();
+        statuses.extend(applied.into_iter().map(|applied| MigrationStatus {
+            version: applied.version,
+            name: applied.name,
+            applied_at: Some(applied.applied_at),
+            embedded: false,
+        }));
+        statuses.sort_by_key(|status| status.version);
+        Ok(statuses)
+    }
+
+    /// Applies pending migrations up to and including `target`, every one when `None`.
+    /// Returns what was applied, in order.
+    async fn migrate_up(&mut self, target: Option<i64>) -> Result<Vec<&'static Migration>> {
+        let migrations = self.dialect().migrations();
+        let applied = self.applied_migrations().await?;
+        check_embedded(migrations, &applied)?;
+        if applied.is_empty() && self.has_waifusims_tables().await? {
+            return Err(Error::Migration(
+                "the database has Waifusims tables but no migration history, \
+                 baseline it at the version its schema matches"
+                    .to_owned(),
+            ));
+        }
+        let pending = migrations
+            .iter()
+            .filter(|migration| target.is_none_or(|target| migration.version <= target))
+            .filter(|migration| !applied.iter().any(|a| a.version == migration.version))
+            .collect::<Vec<&'static Migration>>();
+        for migration in &pending {
+            self.run_migration(migration, Direction::Up).await?;
+        }
+        Ok(pending)
+    }
+
+    /// Reverts applied migrations newer than `target`, `0` reverts every one.
+    /// Returns what was reverted, newest first.
+    async fn migrate_down(&mut self, target: i64) -> Result<Vec<&'static Migration>> {
+        let migrations = self.dialect().migrations();
+        let applied = self.applied_migrations().await?;
+        check_embedded(migrations, &applied)?;
+        let reverted = migrations
+            .iter()
+            .rev()
+            .filter(|migration| migration.version > target)
+            .filter(|migration| applied.iter().any(|a| a.version == migration.version))
+            .collect::<Vec<&'static Migration>>();
+        for migration in &reverted {
+            self.run_migration(migration, Direction::Down).await?;
+        }
+        Ok(reverted)
+    }
+
+    /// Marks migrations up to `version` as applied on a database created before migrations,
+    /// its schema must already match that version. Nothing is checked, see the module docs.
+    async fn baseline(&mut self, version: i64) -> Result<Vec<&'static Migration>> {
+        let migrations = self.dialect().migrations();
+        if !self.applied_migrations().await?.is_empty() {
+            return Err(Error::Migration(
+                "only a database without migration history can be baselined".to_owned(),
+            ));
+        }
+        let count = match migrations.iter().position(|m| m.version == version) {
+            Some(index) => index + 1,
+            None => {
+                return Err(Error::Migration(format!(
+                    "there is no migration {}",
+                    version
+                )))
+            }
+        };
+        self.record_migrations(&migrations[..count]).await?;
+        Ok(migrations[..count].iter().collect())
+    }
+}
+
+/// Refuses to migrate a database that a newer build has already migrated further.
+fn check_embedded(migrations: &[Migration], applied: &[AppliedMigration]) -> Result<()> {
+    match applied
+        .iter()
+        .find(|applied| !migrations.iter().any(|m| m.version == applied.version))
+    {
+        Some(unknown) => Err(Error::Migration(format!(
+            "migration {} {} was applied by a newer build",
+            unknown.version, unknown.name
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Splits a SQL Server script on lines holding only `GO`.
+pub(crate) fn sql_server_batches(script: &str) -> Vec<String> {
+    let mut batches = vec![];
+    let mut batch = String::new();
+    for line in script.lines() {
+        if line.trim().eq_ignore_ascii_case("go") {
+            batches.push(std::mem::take(&mut batch));
+        } else {
+            batch.push_str(line);
+            batch.push('\n');
+        }
+    }
+    batches.push(batch);
+    batches.retain(|batch| !batch.trim().is_empty());
+    batches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dialects_embed_the_same_versions_in_order() {
+        let versions = |dialect: Dialect| {
+            dialect
+                .migrations()
+                .iter()
+                .map(|migration| (migration.version, migration.name))
+                .collect::<Vec<(i64, &str)>>()
+        };
+        let sqlite = versions(Dialect::Sqlite);
+        assert_eq!(sqlite, versions(Dialect::Postgres));
+        assert_eq!(sqlite, versions(Dialect::SqlServer));
+        assert!(sqlite.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        for dialect in [Dialect::SqlServer, Dialect::Postgres, Dialect::Sqlite] {
+            for migration in dialect.migrations() {
+                assert!(!migration.up.trim().is_empty(), "{}", migration.name);
+                assert!(!migration.down.trim().is_empty(), "{}", migration.name);
+            }
+        }
+    }
+
+    #[test]
+    fn splits_sql_server_batches() {
+        let batches =
+            sql_server_batches("ALTER TABLE a ADD b INT;\n  go\nUPDATE a SET b = 1;\nGO\n");
+        assert_eq!(
+            batches,
+            vec!["ALTER TABLE a ADD b INT;\n", "UPDATE a SET b = 1;\n"]
+        );
+        assert_eq!(sql_server_batches("SELECT 1;").len(), 1);
+    }
+}
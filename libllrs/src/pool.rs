@@ -0,0 +1,381 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use bb8::{ManageConnection, Pool, PooledConnection, RunError};
+use chrono::NaiveDateTime;
+use tokio::net::TcpStream;
+use tokio_util::compat::Compat;
+
+use crate::{
+    Chapter, Config, Creator, Error, Manga, MangaListQuery, MangaListing, MangaService, NewChapter,
+    NewManga, Page, PostgresConnectionManager, RecentChapter, Result, SearchResult, Waifusims,
+};
+
+/// Checks the connection still answers, and rolls back any transaction left open
+/// by a failed commit or rollback
+const HEALTH_CHECK_QUERY: &str = "
+IF @@TRANCOUNT > 0 ROLLBACK TRANSACTION;
+SELECT 1
+";
+
+/// Sizing and health check settings for a `ConnectionPool`.
+#[derive(Debug, Clone)]
+pub struct PoolConfig {
+    pub max_size: u32,
+    /// Connections kept open even when idle, none are kept when `None`
+    pub min_idle: Option<u32>,
+    /// Idle connections above `min_idle` are closed after this long
+    pub idle_timeout: Option<Duration>,
+    /// How long a request waits for a free connection before failing
+    pub connection_timeout: Duration,
+    /// Runs a `SELECT 1` on each connection before handing it out,
+    /// on SQL Server this also rolls back any transaction it was left in
+    pub health_check: bool,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        PoolConfig {
+            max_size: 10,
+            min_idle: None,
+            idle_timeout: Some(Duration::from_secs(10 * 60)),
+            connection_timeout: Duration::from_secs(30),
+            health_check: true,
+        }
+    }
+}
+
+/// Opens and health checks SQL Server connections for bb8.
+#[derive(Debug, Clone)]
+pub struct WaifusimsConnectionManager {
+    config: Config,
+}
+
+impl WaifusimsConnectionManager {
+    pub fn new(config: Config) -> WaifusimsConnectionManager {
+        WaifusimsConnectionManager { config }
+    }
+}
+
+#[async_trait]
+impl ManageConnection for WaifusimsConnectionManager {
+    type Connection = Waifusims<Compat<TcpStream>>;
+    type Error = Error;
+
+    async fn connect(&self) -> Result<Self::Connection> {
+        Waifusims::new(self.config.clone()).await
+    }
+
+    async fn is_valid(&self, conn: &mut Self::Connection) -> Result<()> {
+        conn.client
+            .simple_query(HEALTH_CHECK_QUERY)
+            .await?
+            .into_row()
+            .await?;
+        conn.transaction_open = false;
+        Ok(())
+    }
+
+    /// A connection whose write was dropped mid-transaction still holds that transaction
+    /// and its locks, closing it is what makes SQL Server roll them back.
+    /// tiberius doesn't expose the socket state, is_valid catches dead connections.
+    fn has_broken(&self, conn: &mut Self::Connection) -> bool {
+        conn.transaction_open
+    }
+}
+
+/// Pool of database connections opened by `M`, cloning shares the same pool.
+#[derive(Clone)]
+pub struct ConnectionPool<M: ManageConnection> {
+    pool: Pool<M>,
+}
+
+/// Pool of SQL Server connections.
+pub type WaifusimsPool = ConnectionPool<WaifusimsConnectionManager>;
+
+/// Pool of Postgres connections.
+pub type PostgresPool = ConnectionPool<PostgresConnectionManager>;
+
+impl<M: ManageConnection<Error = Error>> ConnectionPool<M> {
+    /// Creates the pool without waiting on the database,
+    /// `min_idle` connections are opened in the background.
+    pub fn with_manager(manager: M, pool_config: PoolConfig) -> ConnectionPool<M> {
+        let pool = Pool::builder()
+            .max_size(pool_config.max_size)
+            .min_idle(pool_config.min_idle)
+            .idle_timeout(pool_config.idle_timeout)
+            .connection_timeout(pool_config.connection_timeout)
+            .test_on_check_out(pool_config.health_check)
+            .build_unchecked(manager);
+        ConnectionPool { pool }
+    }
+
+    pub async fn get(&self) -> Result<PooledConnection<'_, M>> {
+        self.pool.get().await.map_err(|err| match err {
+            RunError::User(err) => err,
+            RunError::TimedOut => Error::PoolTimedOut,
+        })
+    }
+}
+
+impl WaifusimsPool {
+    pub fn new(config: Config, pool_config: PoolConfig) -> WaifusimsPool {
+        ConnectionPool::with_manager(WaifusimsConnectionManager::new(config), pool_config)
+    }
+}
+
+impl PostgresPool {
+    /// Takes a libpq style connection string, see `PostgresWaifusims::new`.
+    pub fn new(connection_string: &str, pool_config: PoolConfig) -> PostgresPool {
+        ConnectionPool::with_manager(
+            PostgresConnectionManager::new(connection_string),
+            pool_config,
+        )
+    }
+}
+
+#[async_trait]
+impl<M> MangaService<i32> for ConnectionPool<M>
+where
+    M: ManageConnection<Error = Error>,
+    M::Connection: MangaService<i32>,
+{
+    async fn get_all_manga_titles(&mut self) -> Result<Vec<Manga>> {
+        self.get().await?.get_all_manga_titles().await
+    }
+
+    async fn list_manga(&mut self, query: &MangaListQuery) -> Result<MangaListing> {
+        self.get().await?.list_manga(query).await
+    }
+
+    async fn search(&mut self, query: &str, limit: u32) -> Result<Vec<SearchResult>> {
+        self.get().await?.search(query, limit).await
+    }
+
+    async fn get_manga(&mut self, manga_id: i32) -> Result<Option<Manga>> {
+        self.get().await?.get_manga(manga_id).await
+    }
+
+    async fn get_manga_creators(&mut self, manga_id: i32) -> Result<Vec<Creator>> {
+        self.get().await?.get_manga_creators(manga_id).await
+    }
+
+    async fn get_manga_chapters(&mut self, manga_id: i32) -> Result<Vec<Chapter>> {
+        self.get().await?.get_manga_chapters(manga_id).await
+    }
+
+    async fn get_mangas_chapters(&mut self, manga_ids: &[i32]) -> Result<Vec<Vec<Chapter>>> {
+        self.get().await?.get_mangas_chapters(manga_ids).await
+    }
+
+    async fn get_chapter(
+        &mut self,
+        manga_id: i32,
+        chapter_number: &str,
+    ) -> Result<Option<Chapter>> {
+        self.get()
+            .await?
+            .get_chapter(manga_id, chapter_number)
+            .await
+    }
+
+    async fn get_recent_chapters(
+        &mut self,
+        limit: u32,
+        since: Option<NaiveDateTime>,
+    ) -> Result<Vec<RecentChapter>> {
+        self.get().await?.get_recent_chapters(limit, since).await
+    }
+
+    async fn get_pages(&mut self, manga_id: i32, chapter_number: &str) -> Result<Vec<Page>> {
+        self.get().await?.get_pages(manga_id, chapter_number).await
+    }
+
+    async fn get_chapters_pages(&mut self, chapters: &[(i32, String)]) -> Result<Vec<Vec<Page>>> {
+        self.get().await?.get_chapters_pages(chapters).await
+    }
+
+    async fn create_manga(&mut self, manga: NewManga) -> Result<Manga> {
+        self.get().await?.create_manga(manga).await
+    }
+
+    async fn update_manga(&mut self, manga_id: i32, manga: NewManga) -> Result<Option<Manga>> {
+        self.get().await?.update_manga(manga_id, manga).await
+    }
+
+    async fn delete_manga(&mut self, manga_id: i32) -> Result<bool> {
+        self.get().await?.delete_manga(manga_id).await
+    }
+
+    async fn create_chapter(
+        &mut self,
+        manga_id: i32,
+        chapter: NewChapter,
+    ) -> Result<Option<Chapter>> {
+        self.get().await?.create_chapter(manga_id, chapter).await
+    }
+
+    async fn update_chapter(
+        &mut self,
+        manga_id: i32,
+        chapter_number: &str,
+        chapter: NewChapter,
+    ) -> Result<Option<Chapter>> {
+        self.get()
+            .await?
+            .update_chapter(manga_id, chapter_number, chapter)
+            .await
+    }
+
+    async fn delete_chapter(&mut self, manga_id: i32, chapter_number: &str) -> Result<bool> {
+        self.get()
+            .await?
+            .delete_chapter(manga_id, chapter_number)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Auth;
+    use futures::FutureExt;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+
+    /// Hands out numbered connections without a database, counting how many it opened.
+    #[derive(Default)]
+    struct FakeConnectionManager {
+        opened: Arc<AtomicU32>,
+    }
+
+    struct FakeConnection {
+        id: u32,
+        broken: bool,
+    }
+
+    #[async_trait]
+    impl ManageConnection for FakeConnectionManager {
+        type Connection = FakeConnection;
+        type Error = Error;
+
+        async fn connect(&self) -> Result<Self::Connection> {
+            let id = self.opened.fetch_add(1, Ordering::SeqCst) + 1;
+            Ok(FakeConnection { id, broken: false })
+        }
+
+        async fn is_valid(&self, _conn: &mut Self::Connection) -> Result<()> {
+            Ok(())
+        }
+
+        fn has_broken(&self, conn: &mut Self::Connection) -> bool {
+            conn.broken
+        }
+    }
+
+    fn fake_pool(max_size: u32) -> (ConnectionPool<FakeConnectionManager>, Arc<AtomicU32>) {
+        let manager = FakeConnectionManager::default();
+        let opened = manager.opened.clone();
+        let pool_config = PoolConfig {
+            max_size,
+            connection_timeout: Duration::from_millis(50),
+            ..PoolConfig::default()
+        };
+        (ConnectionPool::with_manager(manager, pool_config), opened)
+    }
+
+    #[test]
+    fn waits_for_a_free_connection_up_to_max_size() {
+        tokio_test::block_on(async {
+            let (pool, opened) = fake_pool(2);
+            let first = pool.get().await.unwrap();
+            let second = pool.get().await.unwrap();
+            assert_ne!(first.id, second.id);
+            assert!(matches!(pool.get().await, Err(Error::PoolTimedOut)));
+
+            drop(first);
+            let third = pool.get().await.unwrap();
+            assert_eq!(third.id, 1);
+            assert_eq!(opened.load(Ordering::SeqCst), 2);
+        });
+    }
+
+    #[test]
+    fn replaces_broken_connections() {
+        tokio_test::block_on(async {
+            let (pool, opened) = fake_pool(1);
+            let mut connection = pool.get().await.unwrap();
+            connection.broken = true;
+            drop(connection);
+
+            let connection = pool.get().await.unwrap();
+            assert_eq!(connection.id, 2);
+            assert_eq!(opened.load(Ordering::SeqCst), 2);
+        });
+    }
+
+    /// SQL Server to test against from `LLRS_TEST_SQLSERVER`, eg: `sa:password@localhost:1433/waifusims`.
+    /// Tests that need one pass without it.
+    fn sql_server_config() -> Option<Config> {
+        let url = std::env::var("LLRS_TEST_SQLSERVER").ok()?;
+        let malformed = "LLRS_TEST_SQLSERVER should look like user:password@host:port/database";
+        let (credentials, address) = url.split_once('@').expect(malformed);
+        let (user, pass) = credentials.split_once(':').expect(malformed);
+        let (host, database) = address.split_once('/').expect(malformed);
+        let (host, port) = match host.split_once(':') {
+            Some((host, port)) => (host, Some(port.parse().expect(malformed))),
+            None => (host, None),
+        };
+        Some(Config {
+            auth: Auth::Sql {
+                user: user.to_owned(),
+                pass: pass.to_owned(),
+            },
+            database: Some(database.to_owned()),
+            host: host.to_owned(),
+            port,
+            trust_cert: true,
+        })
+    }
+
+    async fn transaction_count(pool: &WaifusimsPool) -> i32 {
+        let row = pool
+            .get()
+            .await
+            .unwrap()
+            .client
+            .simple_query("SELECT @@TRANCOUNT AS TransactionCount")
+            .await
+            .unwrap()
+            .into_row()
+            .await
+            .unwrap()
+            .unwrap();
+        row.get("TransactionCount").unwrap()
+    }
+
+    #[test]
+    fn discards_connections_dropped_mid_transaction() {
+        let config = match sql_server_config() {
+            Some(config) => config,
+            None => return,
+        };
+        tokio_test::block_on(async {
+            let pool_config = PoolConfig {
+                max_size: 1,
+                health_check: false,
+                ..PoolConfig::default()
+            };
+            let pool = WaifusimsPool::new(config, pool_config);
+            assert_eq!(transaction_count(&pool).await, 0);
+
+            // A client disconnecting drops the write after BEGIN was sent
+            let mut connection = pool.get().await.unwrap();
+            assert!(connection.delete_manga(-1).now_or_never().is_none());
+            assert!(connection.transaction_open);
+            drop(connection);
+
+            assert_eq!(transaction_count(&pool).await, 0);
+        });
+    }
+}
@@ -0,0 +1,1966 @@
+use std::any::type_name;
+
+use async_trait::async_trait;
+use bb8::ManageConnection;
+use chrono::NaiveDateTime;
+use log::error;
+use tokio_postgres::{error::SqlState, types::FromSql, Client, NoTls, Row, Transaction};
+
+use crate::{
+    assign_creators, chapter_exists, chapter_manga_ids, creation_date, decode_parsed,
+    group_chapter_page_urls, group_manga_chapters, group_page_urls, like_pattern, list_limit, rank,
+    recent_chapters, search_results, search_terms, sort_chapters, stored_chapter, stored_manga,
+    validate_chapter, validate_manga, Chapter, ChapterNumber, Creator, Error, Manga,
+    MangaListQuery, MangaListing, MangaService, MangaSort, NewChapter, NewManga, NewPage, Page,
+    RecentChapter, Result, SearchHit, SearchResult, MAX_CANDIDATES,
+};
+use crate::{AppliedMigration, Dialect, Direction, Migrate, Migration};
+
+const SELECT_ALL_MANGA_QUERY: &str = "
+SELECT
+    m.MangaID,
+    m.MangaName,
+    m.CoverImageURL,
+    m.PurchaseURL,
+    m.Status,
+    m.DateCreated,
+    m.UpdatedDate
+FROM Manga m
+ORDER BY m.MangaID
+";
+
+/// Shared by a listing and its count, `$1` is the status and `$2` the author
+const MANGA_LIST_FILTER: &str = "
+WHERE ($1::TEXT IS NULL OR m.Status = $1)
+    AND ($2::TEXT IS NULL OR EXISTS (
+        SELECT 1
+        FROM MangaCreator mc
+        JOIN Author a
+            ON mc.AuthorID = a.AuthorID
+        WHERE mc.MangaID = m.MangaID
+            AND mc.Role = 'Author'
+            AND LOWER(a.AuthorName) = LOWER($2)
+    ))
+";
+
+/// Lists a slice of manga, `$3` is the offset and `$4` the limit.
+fn select_manga_list_query(sort: MangaSort) -> String {
+    let order = match sort {
+        MangaSort::Name => "LOWER(m.MangaName), m.MangaID",
+        MangaSort::NewestChapter => {
+            "(SELECT MAX(c.DateCreated) FROM MangaChapter c WHERE c.MangaID = m.MangaID)
+        DESC NULLS LAST,
+    m.MangaID"
+        }
+        MangaSort::CreationDate => "m.DateCreated DESC NULLS LAST, m.MangaID DESC",
+    };
+    format!(
+        "
+SELECT
+    m.MangaID,
+    m.MangaName,
+    m.CoverImageURL,
+    m.PurchaseURL,
+    m.Status,
+    m.DateCreated,
+    m.UpdatedDate
+FROM Manga m{}ORDER BY {}
+LIMIT $4 OFFSET $3
+",
+        MANGA_LIST_FILTER, order
+    )
+}
+
+fn count_manga_list_query() -> String {
+    format!(
+        "
+SELECT COUNT(*) AS Total
+FROM Manga m{}",
+        MANGA_LIST_FILTER
+    )
+}
+
+const SELECT_LISTED_MANGA_CREATORS_QUERY: &str = "
+SELECT
+    mc.MangaID,
+    a.AuthorName,
+    mc.Role
+FROM MangaCreator mc
+JOIN Author a
+    ON mc.AuthorID = a.AuthorID
+WHERE mc.MangaID = ANY($1)
+ORDER BY mc.MangaID, mc.CreditOrder, a.AuthorName
+";
+
+const SELECT_MANGA_BY_IDS_QUERY: &str = "
+SELECT
+    m.MangaID,
+    m.MangaName,
+    m.CoverImageURL,
+    m.PurchaseURL,
+    m.Status,
+    m.DateCreated,
+    m.UpdatedDate
+FROM Manga m
+WHERE m.MangaID = ANY($1)
+";
+
+/// Search candidates, `$1` is a `LIKE` pattern escaped with `\`.
+/// The first `$2` in `search::candidates` order are returned.
+const SEARCH_QUERY: &str = "
+SELECT MangaID, Field, Text, ChapterNumber
+FROM (
+    SELECT m.MangaID, 'Title' AS Field, m.MangaName AS Text, NULL AS ChapterNumber, 1 AS FieldOrder
+    FROM Manga m
+    WHERE m.MangaName ILIKE $1 ESCAPE '\\'
+    UNION ALL
+    SELECT t.MangaID, 'AlternateTitle', t.Title, NULL, 2
+    FROM MangaAlternateTitle t
+    WHERE t.Title ILIKE $1 ESCAPE '\\'
+    UNION ALL
+    SELECT mc.MangaID, 'Creator', a.AuthorName, NULL, 3
+    FROM MangaCreator mc
+    JOIN Author a
+        ON mc.AuthorID = a.AuthorID
+    WHERE a.AuthorName ILIKE $1 ESCAPE '\\'
+    UNION ALL
+    SELECT c.MangaID, 'Chapter', c.ChapterName, c.ChapterNumber, 4
+    FROM MangaChapter c
+    WHERE c.ChapterName ILIKE $1 ESCAPE '\\'
+) hits
+ORDER BY FieldOrder, LENGTH(Text), MangaID, Text COLLATE \"C\", ChapterNumber COLLATE \"C\"
+LIMIT $2
+";
+
+const SELECT_MANGA_QUERY: &str = "
+SELECT
+    m.MangaID,
+    m.MangaName,
+    m.CoverImageURL,
+    m.PurchaseURL,
+    m.Status,
+    m.DateCreated,
+    m.UpdatedDate
+FROM Manga m
+WHERE m.MangaID = $1
+";
+
+const SELECT_ALL_MANGA_CREATORS_QUERY: &str = "
+SELECT
+    mc.MangaID,
+    a.AuthorName,
+    mc.Role
+FROM MangaCreator mc
+JOIN Author a
+    ON mc.AuthorID = a.AuthorID
+ORDER BY mc.MangaID, mc.CreditOrder, a.AuthorName
+";
+
+const SELECT_MANGA_CREATORS_QUERY: &str = "
+SELECT
+    mc.MangaID,
+    a.AuthorName,
+    mc.Role
+FROM MangaCreator mc
+JOIN Author a
+    ON mc.AuthorID = a.AuthorID
+WHERE mc.MangaID = $1
+ORDER BY mc.CreditOrder, a.AuthorName
+";
+
+const SELECT_MANGA_CHAPTERS_QUERY: &str = "
+SELECT
+    ChapterNumber,
+    ChapterName,
+    DateCreated,
+    DateReleased,
+    UpdatedDate,
+    MangaID
+FROM MangaChapter
+WHERE MangaID = $1
+";
+
+const SELECT_MANGAS_CHAPTERS_QUERY: &str = "
+SELECT
+    ChapterNumber,
+    ChapterName,
+    DateCreated,
+    DateReleased,
+    UpdatedDate,
+    MangaID
+FROM MangaChapter
+WHERE MangaID = ANY($1)
+";
+
+const SELECT_CHAPTER_QUERY: &str = "
+SELECT
+    ChapterNumber,
+    ChapterName,
+    DateCreated,
+    DateReleased,
+    UpdatedDate,
+    MangaID
+FROM MangaChapter
+WHERE MangaID = $1
+    AND ChapterNumber = $2
+";
+
+/// Newest releases first, `$1` is the release to list chapters after and `$2` the limit.
+/// Chapters without a release date count as released when they were added.
+const SELECT_RECENT_CHAPTERS_QUERY: &str = "
+SELECT
+    ChapterNumber,
+    ChapterName,
+    DateCreated,
+    DateReleased,
+    UpdatedDate,
+    MangaID
+FROM MangaChapter
+WHERE $1::TIMESTAMP IS NULL OR COALESCE(DateReleased, DateCreated) > $1
+ORDER BY COALESCE(DateReleased, DateCreated) DESC, MangaID, ChapterNumber
+LIMIT $2
+";
+
+const SELECT_CHAPTER_PAGES_QUERY: &str = "
+SELECT
+    u.URL,
+    p.PageID,
+    p.PageNumber
+FROM Page p
+JOIN PageURL u
+    ON p.PageID = u.PageID
+JOIN MangaChapter mc
+    ON mc.ChapterIndex = p.ChapterIndex
+        AND mc.MangaID = p.MangaID
+        AND mc.ChapterNumber = $2
+WHERE p.MangaID = $1
+ORDER BY p.PageNumber, p.PageID, u.Priority
+";
+
+/// `$1` and `$2` pair manga IDs with chapter numbers,
+/// each row carries the pair it matched as it was requested
+const SELECT_CHAPTERS_PAGES_QUERY: &str = "
+SELECT
+    k.MangaID,
+    k.ChapterNumber,
+    u.URL,
+    p.PageID,
+    p.PageNumber
+FROM UNNEST($1::INT[], $2::TEXT[]) AS k(MangaID, ChapterNumber)
+JOIN MangaChapter mc
+    ON mc.MangaID = k.MangaID
+        AND mc.ChapterNumber = k.ChapterNumber
+JOIN Page p
+    ON p.ChapterIndex = mc.ChapterIndex
+        AND p.MangaID = mc.MangaID
+JOIN PageURL u
+    ON p.PageID = u.PageID
+ORDER BY p.PageNumber, p.PageID, u.Priority
+";
+
+const INSERT_MANGA_QUERY: &str = "
+INSERT INTO Manga (MangaName, CoverImageURL, PurchaseURL, Status, DateCreated, UpdatedDate)
+VALUES ($1, $2, $3, $4, $5, $5)
+RETURNING MangaID
+";
+
+const UPDATE_MANGA_QUERY: &str = "
+UPDATE Manga
+SET MangaName = $2,
+    CoverImageURL = $3,
+    PurchaseURL = $4,
+    Status = $5,
+    UpdatedDate = $6
+WHERE MangaID = $1
+RETURNING DateCreated
+";
+
+/// Marks the manga changed by a write to one of its chapters, `$2` is when
+const UPDATE_MANGA_UPDATED_DATE_QUERY: &str = "
+UPDATE Manga
+SET UpdatedDate = $2
+WHERE MangaID = $1
+";
+
+/// Serializes chapter writes to the same manga, so concurrent writers don't pick the
+/// same ChapterIndex or both pass the duplicate chapter number check
+const LOCK_MANGA_QUERY: &str = "
+SELECT MangaID
+FROM Manga
+WHERE MangaID = $1
+FOR UPDATE
+";
+
+const SELECT_AUTHOR_ID_QUERY: &str = "
+SELECT AuthorID
+FROM Author
+WHERE AuthorName = $1
+";
+
+const INSERT_AUTHOR_QUERY: &str = "
+INSERT INTO Author (AuthorName)
+VALUES ($1)
+RETURNING AuthorID
+";
+
+const INSERT_MANGA_CREATOR_QUERY: &str = "
+INSERT INTO MangaCreator (MangaID, AuthorID, Role, CreditOrder)
+VALUES ($1, $2, $3, $4)
+";
+
+const DELETE_MANGA_CREATORS_QUERY: &str = "
+DELETE FROM MangaCreator
+WHERE MangaID = $1
+";
+
+const INSERT_ALTERNATE_TITLE_QUERY: &str = "
+INSERT INTO MangaAlternateTitle (MangaID, Title)
+VALUES ($1, $2)
+";
+
+const DELETE_MANGA_ALTERNATE_TITLES_QUERY: &str = "
+DELETE FROM MangaAlternateTitle
+WHERE MangaID = $1
+";
+
+const DELETE_MANGA_PAGE_URLS_QUERY: &str = "
+DELETE FROM PageURL
+WHERE PageID IN (SELECT PageID FROM Page WHERE MangaID = $1)
+";
+
+const DELETE_MANGA_PAGES_QUERY: &str = "
+DELETE FROM Page
+WHERE MangaID = $1
+";
+
+const DELETE_MANGA_CHAPTERS_QUERY: &str = "
+DELETE FROM MangaChapter
+WHERE MangaID = $1
+";
+
+const DELETE_MANGA_QUERY: &str = "
+DELETE FROM Manga
+WHERE MangaID = $1
+";
+
+const SELECT_CHAPTER_INDEX_QUERY: &str = "
+SELECT ChapterIndex
+FROM MangaChapter
+WHERE MangaID = $1
+    AND ChapterNumber = $2
+";
+
+const INSERT_CHAPTER_QUERY: &str = "
+INSERT INTO MangaChapter (MangaID, ChapterIndex, ChapterNumber, ChapterName, DateCreated, DateReleased, UpdatedDate)
+SELECT $1::INTEGER, COALESCE(MAX(ChapterIndex), 0) + 1, $2::TEXT, $3::TEXT, $4::TIMESTAMP, $5::TIMESTAMP, $4::TIMESTAMP
+FROM MangaChapter
+WHERE MangaID = $1
+RETURNING ChapterIndex
+";
+
+const UPDATE_CHAPTER_QUERY: &str = "
+UPDATE MangaChapter
+SET ChapterNumber = $3,
+    ChapterName = $4,
+    DateReleased = $5,
+    UpdatedDate = $6
+WHERE MangaID = $1
+    AND ChapterIndex = $2
+RETURNING DateCreated
+";
+
+const DELETE_CHAPTER_PAGE_URLS_QUERY: &str = "
+DELETE FROM PageURL
+WHERE PageID IN (SELECT PageID FROM Page WHERE MangaID = $1 AND ChapterIndex = $2)
+";
+
+const DELETE_CHAPTER_PAGES_QUERY: &str = "
+DELETE FROM Page
+WHERE MangaID = $1
+    AND ChapterIndex = $2
+";
+
+const DELETE_CHAPTER_QUERY: &str = "
+DELETE FROM MangaChapter
+WHERE MangaID = $1
+    AND ChapterIndex = $2
+";
+
+const INSERT_PAGE_QUERY: &str = "
+INSERT INTO Page (MangaID, ChapterIndex, PageNumber)
+VALUES ($1, $2, $3)
+RETURNING PageID
+";
+
+const INSERT_PAGE_URL_QUERY: &str = "
+INSERT INTO PageURL (PageID, URL, Priority)
+VALUES ($1, $2, $3)
+";
+
+const CREATE_SCHEMA_MIGRATION_QUERY: &str = "
+CREATE TABLE IF NOT EXISTS SchemaMigration (
+    Version BIGINT PRIMARY KEY,
+    Name TEXT NOT NULL,
+    AppliedAt TIMESTAMP NOT NULL
+)
+";
+
+const SELECT_SCHEMA_MIGRATIONS_QUERY: &str = "
+SELECT Version, Name, AppliedAt
+FROM SchemaMigration
+ORDER BY Version
+";
+
+const SELECT_HAS_WAIFUSIMS_TABLES_QUERY: &str = "
+SELECT to_regclass('Manga') IS NOT NULL AS HasTables
+";
+
+const INSERT_SCHEMA_MIGRATION_QUERY: &str = "
+INSERT INTO SchemaMigration (Version, Name, AppliedAt)
+VALUES ($1, $2, $3)
+";
+
+const DELETE_SCHEMA_MIGRATION_QUERY: &str = "
+DELETE FROM SchemaMigration
+WHERE Version = $1
+";
+
+/// Waifusims database hosted on Postgres.
+pub struct PostgresWaifusims {
+    client: Client,
+}
+
+impl PostgresWaifusims {
+    /// Connects with a libpq style connection string,
+    /// eg: `host=localhost user=llrs dbname=waifusims`, failures are `Error::Unavailable`.
+    // TODO: TLS, currently only suitable for a database on a trusted network
+    pub async fn new(connection_string: &str) -> Result<PostgresWaifusims> {
+        let (client, connection) = tokio_postgres::connect(connection_string, NoTls)
+            .await
+            .map_err(|err| Error::unavailable(err.into()))?;
+        // The connection performs the actual IO and resolves once the client is dropped
+        tokio::spawn(async move {
+            if let Err(err) = connection.await {
+                error!("Postgres connection error: {}", err);
+            }
+        });
+        Ok(PostgresWaifusims { client })
+    }
+}
+
+/// Opens and health checks Postgres connections for bb8.
+#[derive(Debug, Clone)]
+pub struct PostgresConnectionManager {
+    connection_string: String,
+}
+
+impl PostgresConnectionManager {
+    pub fn new(connection_string: &str) -> PostgresConnectionManager {
+        PostgresConnectionManager {
+            connection_string: connection_string.to_owned(),
+        }
+    }
+}
+
+#[async_trait]
+impl ManageConnection for PostgresConnectionManager {
+    type Connection = PostgresWaifusims;
+    type Error = Error;
+
+    async fn connect(&self) -> Result<Self::Connection> {
+        PostgresWaifusims::new(&self.connection_string).await
+    }
+
+    async fn is_valid(&self, conn: &mut Self::Connection) -> Result<()> {
+        conn.client.simple_query("SELECT 1").await?;
+        Ok(())
+    }
+
+    /// Transactions roll back when dropped, so only a closed connection is broken.
+    fn has_broken(&self, conn: &mut Self::Connection) -> bool {
+        conn.client.is_closed()
+    }
+}
+
+/// Reads a NOT NULL column, `row_index` is only used to describe errors.
+fn decode<'a, R: FromSql<'a>>(row: &'a Row, row_index: usize, column: &'static str) -> Result<R> {
+    decode_nullable(row, row_index, column)?.ok_or(Error::UnexpectedNull {
+        column,
+        row: row_index,
+        expected: type_name::<R>(),
+    })
+}
+
+fn decode_nullable<'a, R: FromSql<'a>>(
+    row: &'a Row,
+    row_index: usize,
+    column: &'static str,
+) -> Result<Option<R>> {
+    row.try_get(column).map_err(|err| Error::Decode {
+        column,
+        row: row_index,
+        expected: type_name::<R>(),
+        reason: err.to_string(),
+    })
+}
+
+fn decode_manga(row: &Row, row_index: usize) -> Result<Manga> {
+    Ok(Manga {
+        manga_id: decode(row, row_index, "MangaID")?,
+        manga_name: decode(row, row_index, "MangaName")?,
+        author_names: vec![],
+        artist_names: vec![],
+        cover_image_url: decode_nullable(row, row_index, "CoverImageURL")?,
+        purchase_url: decode_nullable(row, row_index, "PurchaseURL")?,
+        status: decode_parsed(decode(row, row_index, "Status")?, "Status", row_index)?,
+        creation_date: decode_nullable(row, row_index, "DateCreated")?,
+        updated_date: decode_nullable(row, row_index, "UpdatedDate")?,
+    })
+}
+
+fn decode_chapter(row: &Row, row_index: usize) -> Result<Chapter> {
+    Ok(Chapter {
+        manga_id: decode(row, row_index, "MangaID")?,
+        chapter_number: decode::<String>(row, row_index, "ChapterNumber")?.into(),
+        chapter_name: decode(row, row_index, "ChapterName")?,
+        creation_date: decode(row, row_index, "DateCreated")?,
+        release_date: decode_nullable(row, row_index, "DateReleased")?,
+        updated_date: decode_nullable(row, row_index, "UpdatedDate")?,
+    })
+}
+
+fn decode_creator(row: &Row, row_index: usize) -> Result<Creator> {
+    Ok(Creator {
+        creator_name: decode(row, row_index, "AuthorName")?,
+        role: decode_parsed(decode(row, row_index, "Role")?, "Role", row_index)?,
+    })
+}
+
+fn decode_search_hit(row: &Row, row_index: usize) -> Result<SearchHit> {
+    Ok(SearchHit {
+        manga_id: decode(row, row_index, "MangaID")?,
+        field: decode_parsed(decode(row, row_index, "Field")?, "Field", row_index)?,
+        text: decode(row, row_index, "Text")?,
+        chapter_number: decode_nullable::<String>(row, row_index, "ChapterNumber")?
+            .map(ChapterNumber::from),
+    })
+}
+
+/// Fills in creators for manga fetched without them.
+async fn assign_listed_creators(client: &Client, mangas: &mut [Manga]) -> Result<()> {
+    let manga_ids = mangas
+        .iter()
+        .map(|manga| manga.manga_id)
+        .collect::<Vec<i32>>();
+    let rows = client
+        .query(SELECT_LISTED_MANGA_CREATORS_QUERY, &[&manga_ids])
+        .await?;
+    let creators = rows
+        .iter()
+        .enumerate()
+        .map(|(index, row)| Ok((decode(row, index, "MangaID")?, decode_creator(row, index)?)))
+        .collect::<Result<Vec<(i32, Creator)>>>()?;
+    assign_creators(mangas, creators);
+    Ok(())
+}
+
+async fn insert_alternate_titles(
+    transaction: &Transaction<'_>,
+    manga_id: i32,
+    titles: &[String],
+) -> Result<()> {
+    for title in titles {
+        transaction
+            .execute(INSERT_ALTERNATE_TITLE_QUERY, &[&manga_id, title])
+            .await?;
+    }
+    Ok(())
+}
+
+/// Credits `creators` in order, adding any authors that don't exist yet.
+async fn insert_creators(
+    transaction: &Transaction<'_>,
+    manga_id: i32,
+    creators: &[Creator],
+) -> Result<()> {
+    for (credit_order, creator) in (0..).zip(creators) {
+        let author_id: i32 = match transaction
+            .query_opt(SELECT_AUTHOR_ID_QUERY, &[&creator.creator_name])
+            .await?
+        {
+            Some(row) => decode(&row, 0, "AuthorID")?,
+            None => {
+                let row = transaction
+                    .query_one(INSERT_AUTHOR_QUERY, &[&creator.creator_name])
+                    .await?;
+                decode(&row, 0, "AuthorID")?
+            }
+        };
+        transaction
+            .execute(
+                INSERT_MANGA_CREATOR_QUERY,
+                &[&manga_id, &author_id, &creator.role.as_str(), &credit_order],
+            )
+            .await?;
+    }
+    Ok(())
+}
+
+async fn find_chapter_index(
+    transaction: &Transaction<'_>,
+    manga_id: i32,
+    chapter_number: &str,
+) -> Result<Option<i32>> {
+    transaction
+        .query_opt(SELECT_CHAPTER_INDEX_QUERY, &[&manga_id, &chapter_number])
+        .await?
+        .map(|row| decode(&row, 0, "ChapterIndex"))
+        .transpose()
+}
+
+/// A write that collided with `MangaChapter`'s unique chapter number is a conflict,
+/// any other error is passed on.
+fn unique_violation(err: tokio_postgres::Error, chapter_number: &ChapterNumber) -> Error {
+    if err.code() == Some(&SqlState::UNIQUE_VIOLATION) {
+        chapter_exists(chapter_number)
+    } else {
+        err.into()
+    }
+}
+
+/// Adds `pages`, each URL's priority follows its position in `urls`.
+async fn insert_pages(
+    transaction: &Transaction<'_>,
+    manga_id: i32,
+    chapter_index: i32,
+    pages: &[NewPage],
+) -> Result<()> {
+    for page in pages {
+        let row = transaction
+            .query_one(
+                INSERT_PAGE_QUERY,
+                &[&manga_id, &chapter_index, &page.page_number],
+            )
+            .await?;
+        let page_id: i32 = decode(&row, 0, "PageID")?;
+        for (priority, url) in (1..).zip(&page.urls) {
+            transaction
+                .execute(INSERT_PAGE_URL_QUERY, &[&page_id, url, &priority])
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+async fn delete_pages(
+    transaction: &Transaction<'_>,
+    manga_id: i32,
+    chapter_index: i32,
+) -> Result<()> {
+    transaction
+        .execute(DELETE_CHAPTER_PAGE_URLS_QUERY, &[&manga_id, &chapter_index])
+        .await?;
+    transaction
+        .execute(DELETE_CHAPTER_PAGES_QUERY, &[&manga_id, &chapter_index])
+        .await?;
+    Ok(())
+}
+
+#[async_trait]
+impl MangaService<i32> for PostgresWaifusims {
+    async fn get_all_manga_titles(&mut self) -> Result<Vec<Manga>> {
+        let rows = self.client.query(SELECT_ALL_MANGA_QUERY, &[]).await?;
+        let mut mangas = rows
+            .iter()
+            .enumerate()
+            .map(|(index, row)| decode_manga(row, index))
+            .collect::<Result<Vec<Manga>>>()?;
+        let rows = self
+            .client
+            .query(SELECT_ALL_MANGA_CREATORS_QUERY, &[])
+            .await?;
+        let creators = rows
+            .iter()
+            .enumerate()
+            .map(|(index, row)| Ok((decode(row, index, "MangaID")?, decode_creator(row, index)?)))
+            .collect::<Result<Vec<(i32, Creator)>>>()?;
+        assign_creators(&mut mangas, creators);
+        Ok(mangas)
+    }
+
+    async fn list_manga(&mut self, query: &MangaListQuery) -> Result<MangaListing> {
+        let status = query.status.map(|status| status.as_str());
+        let row = self
+            .client
+            .query_one(count_manga_list_query().as_str(), &[&status, &query.author])
+            .await?;
+        let total: i64 = decode(&row, 0, "Total")?;
+        let rows = self
+            .client
+            .query(
+                select_manga_list_query(query.sort).as_str(),
+                &[
+                    &status,
+                    &query.author,
+                    &i64::from(query.offset),
+                    &list_limit(query),
+                ],
+            )
+            .await?;
+        let mut mangas = rows
+            .iter()
+            .enumerate()
+            .map(|(index, row)| decode_manga(row, index))
+            .collect::<Result<Vec<Manga>>>()?;
+        assign_listed_creators(&self.client, &mut mangas).await?;
+        Ok(MangaListing {
+            mangas,
+            total: total as u64,
+        })
+    }
+
+    async fn search(&mut self, query: &str, limit: u32) -> Result<Vec<SearchResult>> {
+        let pattern = match like_pattern(&search_terms(query)) {
+            Some(pattern) => pattern,
+            None => return Ok(vec![]),
+        };
+        let rows = self
+            .client
+            .query(SEARCH_QUERY, &[&pattern, &MAX_CANDIDATES])
+            .await?;
+        let hits = rows
+            .iter()
+            .enumerate()
+            .map(|(index, row)| decode_search_hit(row, index))
+            .collect::<Result<Vec<SearchHit>>>()?;
+        let ranked = rank(query, hits, limit as usize);
+        let manga_ids = ranked
+            .iter()
+            .map(|(hit, _)| hit.manga_id)
+            .collect::<Vec<i32>>();
+        let rows = self
+            .client
+            .query(SELECT_MANGA_BY_IDS_QUERY, &[&manga_ids])
+            .await?;
+        let mut mangas = rows
+            .iter()
+            .enumerate()
+            .map(|(index, row)| decode_manga(row, index))
+            .collect::<Result<Vec<Manga>>>()?;
+        assign_listed_creators(&self.client, &mut mangas).await?;
+        Ok(search_results(ranked, mangas))
+    }
+
+    async fn get_manga(&mut self, manga_id: i32) -> Result<Option<Manga>> {
+        let row = self
+            .client
+            .query_opt(SELECT_MANGA_QUERY, &[&manga_id])
+            .await?;
+        let mut mangas = row
+            .map(|row| decode_manga(&row, 0))
+            .transpose()?
+            .into_iter()
+            .collect::<Vec<Manga>>();
+        assign_listed_creators(&self.client, &mut mangas).await?;
+        Ok(mangas.pop())
+    }
+
+    async fn get_manga_creators(&mut self, manga_id: i32) -> Result<Vec<Creator>> {
+        let rows = self
+            .client
+            .query(SELECT_MANGA_CREATORS_QUERY, &[&manga_id])
+            .await?;
+        rows.iter()
+            .enumerate()
+            .map(|(index, row)| decode_creator(row, index))
+            .collect()
+    }
+
+    async fn get_manga_chapters(&mut self, manga_id: i32) -> Result<Vec<Chapter>> {
+        let rows = self
+            .client
+            .query(SELECT_MANGA_CHAPTERS_QUERY, &[&manga_id])
+            .await?;
+        let mut chapters = rows
+            .iter()
+            .enumerate()
+            .map(|(index, row)| decode_chapter(row, index))
+            .collect::<Result<Vec<Chapter>>>()?;
+        sort_chapters(&mut chapters);
+        Ok(chapters)
+    }
+
+    async fn get_mangas_chapters(&mut self, manga_ids: &[i32]) -> Result<Vec<Vec<Chapter>>> {
+        let rows = self
+            .client
+            .query(SELECT_MANGAS_CHAPTERS_QUERY, &[&manga_ids])
+            .await?;
+        let chapters = rows
+            .iter()
+            .enumerate()
+            .map(|(index, row)| decode_chapter(row, index))
+            .collect::<Result<Vec<Chapter>>>()?;
+        Ok(group_manga_chapters(manga_ids, chapters))
+    }
+
+    async fn get_chapter(
+        &mut self,
+        manga_id: i32,
+        chapter_number: &str,
+    ) -> Result<Option<Chapter>> {
+        let row = self
+            .client
+            .query_opt(SELECT_CHAPTER_QUERY, &[&manga_id, &chapter_number])
+            .await?;
+        row.map(|row| decode_chapter(&row, 0)).transpose()
+    }
+
+    async fn get_recent_chapters(
+        &mut self,
+        limit: u32,
+        since: Option<NaiveDateTime>,
+    ) -> Result<Vec<RecentChapter>> {
+        let rows = self
+            .client
+            .query(SELECT_RECENT_CHAPTERS_QUERY, &[&since, &i64::from(limit)])
+            .await?;
+        let chapters = rows
+            .iter()
+            .enumerate()
+            .map(|(index, row)| decode_chapter(row, index))
+            .collect::<Result<Vec<Chapter>>>()?;
+        let rows = self
+            .client
+            .query(SELECT_MANGA_BY_IDS_QUERY, &[&chapter_manga_ids(&chapters)])
+            .await?;
+        let mut mangas = rows
+            .iter()
+            .enumerate()
+            .map(|(index, row)| decode_manga(row, index))
+            .collect::<Result<Vec<Manga>>>()?;
+        assign_listed_creators(&self.client, &mut mangas).await?;
+        Ok(recent_chapters(chapters, mangas))
+    }
+
+    async fn get_pages(&mut self, manga_id: i32, chapter_number: &str) -> Result<Vec<Page>> {
+        let rows = self
+            .client
+            .query(SELECT_CHAPTER_PAGES_QUERY, &[&manga_id, &chapter_number])
+            .await?;
+        let urls = rows
+            .iter()
+            .enumerate()
+            .map(|(index, row)| {
+                Ok((
+                    decode(row, index, "PageID")?,
+                    decode(row, index, "PageNumber")?,
+                    decode(row, index, "URL")?,
+                ))
+            })
+            .collect::<Result<Vec<(i32, i32, String)>>>()?;
+        Ok(group_page_urls(urls))
+    }
+
+    async fn get_chapters_pages(&mut self, chapters: &[(i32, String)]) -> Result<Vec<Vec<Page>>> {
+        let (manga_ids, chapter_numbers): (Vec<i32>, Vec<&str>) = chapters
+            .iter()
+            .map(|(manga_id, chapter_number)| (*manga_id, chapter_number.as_str()))
+            .unzip();
+        let rows = self
+            .client
+            .query(SELECT_CHAPTERS_PAGES_QUERY, &[&manga_ids, &chapter_numbers])
+            .await?;
+        let urls = rows
+            .iter()
+            .enumerate()
+            .map(|(index, row)| {
+                Ok((
+                    decode(row, index, "MangaID")?,
+                    decode(row, index, "ChapterNumber")?,
+                    decode(row, index, "PageID")?,
+                    decode(row, index, "PageNumber")?,
+                    decode(row, index, "URL")?,
+                ))
+            })
+            .collect::<Result<Vec<(i32, String, i32, i32, String)>>>()?;
+        Ok(group_chapter_page_urls(chapters, urls))
+    }
+
+    async fn create_manga(&mut self, manga: NewManga) -> Result<Manga> {
+        validate_manga(&manga)?;
+        let creation_date = creation_date();
+        // Returning early without committing drops and so rolls back the transaction
+        let transaction = self.client.transaction().await?;
+        let row = transaction
+            .query_one(
+                INSERT_MANGA_QUERY,
+                &[
+                    &manga.manga_name,
+                    &manga.cover_image_url,
+                    &manga.purchase_url,
+                    &manga.status.as_str(),
+                    &creation_date,
+                ],
+            )
+            .await?;
+        let manga_id: i32 = decode(&row, 0, "MangaID")?;
+        insert_creators(&transaction, manga_id, &manga.creators).await?;
+        insert_alternate_titles(&transaction, manga_id, &manga.alternate_titles).await?;
+        transaction.commit().await?;
+        Ok(stored_manga(
+            manga_id,
+            manga,
+            Some(creation_date),
+            creation_date,
+        ))
+    }
+
+    async fn update_manga(&mut self, manga_id: i32, manga: NewManga) -> Result<Option<Manga>> {
+        validate_manga(&manga)?;
+        let updated_date = creation_date();
+        let transaction = self.client.transaction().await?;
+        let row = transaction
+            .query_opt(
+                UPDATE_MANGA_QUERY,
+                &[
+                    &manga_id,
+                    &manga.manga_name,
+                    &manga.cover_image_url,
+                    &manga.purchase_url,
+                    &manga.status.as_str(),
+                    &updated_date,
+                ],
+            )
+            .await?;
+        let creation_date = match row {
+            Some(row) => decode_nullable(&row, 0, "DateCreated")?,
+            None => return Ok(None),
+        };
+        transaction
+            .execute(DELETE_MANGA_CREATORS_QUERY, &[&manga_id])
+            .await?;
+        transaction
+            .execute(DELETE_MANGA_ALTERNATE_TITLES_QUERY, &[&manga_id])
+            .await?;
+        insert_creators(&transaction, manga_id, &manga.creators).await?;
+        insert_alternate_titles(&transaction, manga_id, &manga.alternate_titles).await?;
+        transaction.commit().await?;
+        Ok(Some(stored_manga(
+            manga_id,
+            manga,
+            creation_date,
+            updated_date,
+        )))
+    }
+
+    async fn delete_manga(&mut self, manga_id: i32) -> Result<bool> {
+        let transaction = self.client.transaction().await?;
+        for query in [
+            DELETE_MANGA_PAGE_URLS_QUERY,
+            DELETE_MANGA_PAGES_QUERY,
+            DELETE_MANGA_CHAPTERS_QUERY,
+            DELETE_MANGA_CREATORS_QUERY,
+            DELETE_MANGA_ALTERNATE_TITLES_QUERY,
+        ] {
+            transaction.execute(query, &[&manga_id]).await?;
+        }
+        let deleted = transaction
+            .execute(DELETE_MANGA_QUERY, &[&manga_id])
+            .await?;
+        transaction.commit().await?;
+        Ok(deleted > 0)
+    }
+
+    async fn create_chapter(
+        &mut self,
+        manga_id: i32,
+        chapter: NewChapter,
+    ) -> Result<Option<Chapter>> {
+        validate_chapter(&chapter)?;
+        let transaction = self.client.transaction().await?;
+        if transaction
+            .query_opt(LOCK_MANGA_QUERY, &[&manga_id])
+            .await?
+            .is_none()
+        {
+            return Ok(None);
+        }
+        let chapter_number = chapter.chapter_number.as_str();
+        if find_chapter_index(&transaction, manga_id, chapter_number)
+            .await?
+            .is_some()
+        {
+            return Err(chapter_exists(&chapter.chapter_number));
+        }
+        let creation_date = creation_date();
+        let row = transaction
+            .query_one(
+                INSERT_CHAPTER_QUERY,
+                &[
+                    &manga_id,
+                    &chapter_number,
+                    &chapter.chapter_name,
+                    &creation_date,
+                    &chapter.release_date,
+                ],
+            )
+            .await
+            .map_err(|err| unique_violation(err, &chapter.chapter_number))?;
+        let chapter_index: i32 = decode(&row, 0, "ChapterIndex")?;
+        insert_pages(&transaction, manga_id, chapter_index, &chapter.pages).await?;
+        transaction
+            .execute(
+                UPDATE_MANGA_UPDATED_DATE_QUERY,
+                &[&manga_id, &creation_date],
+            )
+            .await?;
+        transaction.commit().await?;
+        Ok(Some(stored_chapter(
+            manga_id,
+            chapter,
+            creation_date,
+            creation_date,
+        )))
+    }
+
+    async fn update_chapter(
+        &mut self,
+        manga_id: i32,
+        chapter_number: &str,
+        chapter: NewChapter,
+    ) -> Result<Option<Chapter>> {
+        validate_chapter(&chapter)?;
+        let transaction = self.client.transaction().await?;
+        if transaction
+            .query_opt(LOCK_MANGA_QUERY, &[&manga_id])
+            .await?
+            .is_none()
+        {
+            return Ok(None);
+        }
+        let chapter_index = match find_chapter_index(&transaction, manga_id, chapter_number).await?
+        {
+            Some(chapter_index) => chapter_index,
+            None => return Ok(None),
+        };
+        let new_chapter_number = chapter.chapter_number.as_str();
+        match find_chapter_index(&transaction, manga_id, new_chapter_number).await? {
+            Some(other_index) if other_index != chapter_index => {
+                return Err(chapter_exists(&chapter.chapter_number))
+            }
+            _ => {}
+        }
+        let updated_date = creation_date();
+        let row = transaction
+            .query_one(
+                UPDATE_CHAPTER_QUERY,
+                &[
+                    &manga_id,
+                    &chapter_index,
+                    &new_chapter_number,
+                    &chapter.chapter_name,
+                    &chapter.release_date,
+                    &updated_date,
+                ],
+            )
+            .await
+            .map_err(|err| unique_violation(err, &chapter.chapter_number))?;
+        let creation_date = decode(&row, 0, "DateCreated")?;
+        delete_pages(&transaction, manga_id, chapter_index).await?;
+        insert_pages(&transaction, manga_id, chapter_index, &chapter.pages).await?;
+        transaction
+            .execute(UPDATE_MANGA_UPDATED_DATE_QUERY, &[&manga_id, &updated_date])
+            .await?;
+        transaction.commit().await?;
+        Ok(Some(stored_chapter(
+            manga_id,
+            chapter,
+            creation_date,
+            updated_date,
+        )))
+    }
+
+    async fn delete_chapter(&mut self, manga_id: i32, chapter_number: &str) -> Result<bool> {
+        let transaction = self.client.transaction().await?;
+        if transaction
+            .query_opt(LOCK_MANGA_QUERY, &[&manga_id])
+            .await?
+            .is_none()
+        {
+            return Ok(false);
+        }
+        let chapter_index = match find_chapter_index(&transaction, manga_id, chapter_number).await?
+        {
+            Some(chapter_index) => chapter_index,
+            None => return Ok(false),
+        };
+        delete_pages(&transaction, manga_id, chapter_index).await?;
+        transaction
+            .execute(DELETE_CHAPTER_QUERY, &[&manga_id, &chapter_index])
+            .await?;
+        transaction
+            .execute(
+                UPDATE_MANGA_UPDATED_DATE_QUERY,
+                &[&manga_id, &creation_date()],
+            )
+            .await?;
+        transaction.commit().await?;
+        Ok(true)
+    }
+}
+
+async fn record_migration(transaction: &Transaction<'_>, migration: &Migration) -> Result<()> {
+    transaction
+        .execute(
+            INSERT_SCHEMA_MIGRATION_QUERY,
+            &[&migration.version, &migration.name, &creation_date()],
+        )
+        .await?;
+    Ok(())
+}
+
+#[async_trait]
+impl Migrate for PostgresWaifusims {
+    fn dialect(&self) -> Dialect {
+        Dialect::Postgres
+    }
+
+    async fn applied_migrations(&mut self) -> Result<Vec<AppliedMigration>> {
+        self.client
+            .batch_execute(CREATE_SCHEMA_MIGRATION_QUERY)
+            .await?;
+        let rows = self
+            .client
+            .query(SELECT_SCHEMA_MIGRATIONS_QUERY, &[])
+            .await?;
+        rows.iter()
+            .enumerate()
+            .map(|(index, row)| {
+                Ok(AppliedMigration {
+                    version: decode(row, index, "Version")?,
+                    name: decode(row, index, "Name")?,
+                    applied_at: decode(row, index, "AppliedAt")?,
+                })
+            })
+            .collect()
+    }
+
+    async fn has_waifusims_tables(&mut self) -> Result<bool> {
+        let row = self
+            .client
+            .query_one(SELECT_HAS_WAIFUSIMS_TABLES_QUERY, &[])
+            .await?;
+        decode(&row, 0, "HasTables")
+    }
+
+    async fn run_migration(
+        &mut self,
+        migration: &'static Migration,
+        direction: Direction,
+    ) -> Result<()> {
+        let transaction = self.client.transaction().await?;
+        transaction
+            .batch_execute(migration.script(direction))
+            .await?;
+        match direction {
+            Direction::Up => record_migration(&transaction, migration).await?,
+            Direction::Down => {
+                transaction
+                    .execute(DELETE_SCHEMA_MIGRATION_QUERY, &[&migration.version])
+                    .await?;
+            }
+        }
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    async fn record_migrations(&mut self, migrations: &'static [Migration]) -> Result<()> {
+        let transaction = self.client.transaction().await?;
+        for migration in migrations {
+            record_migration(&transaction, migration).await?;
+        }
+        transaction.commit().await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CreatorRole, MangaStatus, PoolConfig, PostgresPool, SearchField};
+    use futures::FutureExt;
+    use std::future::Future;
+
+    /// Connects to `LLRS_TEST_POSTGRES`, a libpq connection string such as
+    /// `host=localhost user=postgres dbname=llrs_test`, and works in an emptied `schema`
+    /// of it so tests don't share tables. Tests pass without the variable.
+    async fn connect(schema: &str) -> Option<PostgresWaifusims> {
+        let connection_string = std::env::var("LLRS_TEST_POSTGRES").ok()?;
+        let waifusims = PostgresWaifusims::new(&connection_string).await.unwrap();
+        waifusims
+            .client
+            .batch_execute(&format!(
+                "DROP SCHEMA IF EXISTS {0} CASCADE; CREATE SCHEMA {0}; SET search_path TO {0};",
+                schema
+            ))
+            .await
+            .unwrap();
+        Some(waifusims)
+    }
+
+    /// Another connection to the `schema` a test is working in.
+    async fn connect_to(schema: &str) -> PostgresWaifusims {
+        let connection_string = std::env::var("LLRS_TEST_POSTGRES").unwrap();
+        let waifusims = PostgresWaifusims::new(&connection_string).await.unwrap();
+        waifusims
+            .client
+            .batch_execute(&format!("SET search_path TO {};", schema))
+            .await
+            .unwrap();
+        waifusims
+    }
+
+    const SEED_QUERY: &str = "
+INSERT INTO Author (AuthorID, AuthorName) VALUES (1, 'Author'), (2, 'Artist'), (3, 'Writer');
+INSERT INTO Manga (MangaID, MangaName, CoverImageURL, PurchaseURL)
+    VALUES (1, 'Manga', 'cover.png', NULL);
+INSERT INTO MangaCreator VALUES (1, 1, 'Author', 0);
+INSERT INTO MangaCreator VALUES (1, 2, 'Artist', 1);
+INSERT INTO MangaCreator VALUES (1, 3, 'Author', 2);
+INSERT INTO MangaCreator VALUES (1, 1, 'Artist', 3);
+INSERT INTO MangaChapter VALUES (1, 1, '10', 'Ten', '2021-01-10 00:00:00', '2021-01-10 00:00:00', '2021-01-10 00:00:00');
+INSERT INTO MangaChapter VALUES (1, 2, '2', 'Two', '2021-01-02 00:00:00', '2021-01-02 00:00:00', '2021-01-02 00:00:00');
+INSERT INTO MangaChapter VALUES (1, 3, '2.5', 'Two and a half', '2021-01-03 00:00:00', NULL, '2021-01-03 00:00:00');
+INSERT INTO Page (PageID, MangaID, ChapterIndex, PageNumber) VALUES (1, 1, 2, 2);
+INSERT INTO Page (PageID, MangaID, ChapterIndex, PageNumber) VALUES (2, 1, 2, 1);
+INSERT INTO PageURL VALUES (1, 'two-2.png', 1);
+INSERT INTO PageURL VALUES (1, 'two-2-mirror.png', 2);
+INSERT INTO PageURL VALUES (2, 'two-1.png', 1);
+SELECT setval(pg_get_serial_sequence('Author', 'authorid'), 3);
+SELECT setval(pg_get_serial_sequence('Manga', 'mangaid'), 1);
+SELECT setval(pg_get_serial_sequence('Page', 'pageid'), 2);
+";
+
+    /// Runs `test` on a migrated and seeded database, in a runtime of its own since the
+    /// client's connection is a task of the runtime it connected in.
+    fn with_seeded<F: Future<Output = ()>>(
+        schema: &str,
+        test: impl FnOnce(PostgresWaifusims) -> F,
+    ) {
+        tokio_test::block_on(async {
+            if let Some(mut waifusims) = connect(schema).await {
+                waifusims.migrate_up(None).await.unwrap();
+                waifusims.client.batch_execute(SEED_QUERY).await.unwrap();
+                test(waifusims).await;
+            }
+        })
+    }
+
+    fn with_empty<F: Future<Output = ()>>(schema: &str, test: impl FnOnce(PostgresWaifusims) -> F) {
+        tokio_test::block_on(async {
+            if let Some(waifusims) = connect(schema).await {
+                test(waifusims).await;
+            }
+        })
+    }
+
+    fn new_chapter(chapter_number: &str, page_count: i32) -> NewChapter {
+        NewChapter {
+            chapter_number: chapter_number.into(),
+            chapter_name: format!("Chapter {}", chapter_number),
+            release_date: None,
+            pages: (1..=page_count)
+                .map(|page_number| NewPage {
+                    page_number,
+                    urls: vec![
+                        format!("{}-{}.png", chapter_number, page_number),
+                        format!("{}-{}-mirror.png", chapter_number, page_number),
+                    ],
+                })
+                .collect(),
+        }
+    }
+
+    async fn page_urls(waifusims: &mut PostgresWaifusims, chapter_number: &str) -> Vec<String> {
+        waifusims
+            .get_pages(1, chapter_number)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|page| page.url_string)
+            .collect()
+    }
+
+    #[test]
+    fn gets_all_manga_titles() {
+        with_seeded("llrs_gets_all_manga_titles", |mut waifusims| async move {
+            let mangas = waifusims.get_all_manga_titles().await.unwrap();
+            assert_eq!(mangas.len(), 1);
+            assert_eq!(mangas[0].manga_name, "Manga");
+            assert_eq!(mangas[0].author_names, vec!["Author", "Writer"]);
+            assert_eq!(mangas[0].artist_names, vec!["Artist", "Author"]);
+            assert_eq!(mangas[0].cover_image_url.as_deref(), Some("cover.png"));
+            assert_eq!(mangas[0].purchase_url, None);
+        });
+    }
+
+    async fn listed_ids(
+        waifusims: &mut PostgresWaifusims,
+        query: MangaListQuery,
+    ) -> (Vec<i32>, u64) {
+        let listing = waifusims.list_manga(&query).await.unwrap();
+        let manga_ids = listing.mangas.iter().map(|manga| manga.manga_id).collect();
+        (manga_ids, listing.total)
+    }
+
+    #[test]
+    fn lists_manga_sorted_filtered_and_paged() {
+        with_seeded("llrs_lists_manga", |mut waifusims| async move {
+            waifusims
+                .client
+                .batch_execute(
+                    "
+INSERT INTO Manga (MangaID, MangaName, Status, DateCreated)
+    VALUES (2, 'another', 'Completed', '2021-02-01 00:00:00');
+INSERT INTO Manga (MangaID, MangaName, Status, DateCreated)
+    VALUES (3, 'Zeta', 'Ongoing', '2021-03-01 00:00:00');
+INSERT INTO MangaCreator VALUES (2, 3, 'Author', 0);
+INSERT INTO MangaChapter VALUES (2, 1, '1', 'One', '2021-02-05 00:00:00', NULL, '2021-02-05 00:00:00');
+",
+                )
+                .await
+                .unwrap();
+            let sorted = |sort| MangaListQuery {
+                sort,
+                ..MangaListQuery::default()
+            };
+            assert_eq!(
+                listed_ids(&mut waifusims, sorted(MangaSort::Name)).await,
+                (vec![2, 1, 3], 3)
+            );
+            assert_eq!(
+                listed_ids(&mut waifusims, sorted(MangaSort::NewestChapter)).await,
+                (vec![2, 1, 3], 3)
+            );
+            assert_eq!(
+                listed_ids(&mut waifusims, sorted(MangaSort::CreationDate)).await,
+                (vec![3, 2, 1], 3)
+            );
+            let page = MangaListQuery {
+                offset: 1,
+                limit: Some(1),
+                ..MangaListQuery::default()
+            };
+            assert_eq!(listed_ids(&mut waifusims, page).await, (vec![1], 3));
+            let completed = MangaListQuery {
+                status: Some(MangaStatus::Completed),
+                ..MangaListQuery::default()
+            };
+            assert_eq!(listed_ids(&mut waifusims, completed).await, (vec![2], 1));
+            let by_writer = MangaListQuery {
+                author: Some("writer".to_owned()),
+                ..MangaListQuery::default()
+            };
+            assert_eq!(listed_ids(&mut waifusims, by_writer).await, (vec![2, 1], 2));
+            // Credited only as an artist
+            let by_artist = MangaListQuery {
+                author: Some("Artist".to_owned()),
+                offset: 5,
+                ..MangaListQuery::default()
+            };
+            assert_eq!(listed_ids(&mut waifusims, by_artist).await, (vec![], 0));
+
+            let listing = waifusims
+                .list_manga(&MangaListQuery::default())
+                .await
+                .unwrap();
+            assert_eq!(listing.mangas[1].author_names, vec!["Author", "Writer"]);
+            assert_eq!(listing.mangas[0].author_names, vec!["Writer"]);
+            assert_eq!(listing.mangas[0].status, MangaStatus::Completed);
+            assert!(listing.mangas[0].creation_date.is_some());
+        });
+    }
+
+    #[test]
+    fn reports_undecodable_columns() {
+        with_seeded("llrs_reports_undecodable", |mut waifusims| async move {
+            waifusims
+                .client
+                .batch_execute("ALTER TABLE Manga ALTER COLUMN PurchaseURL TYPE INTEGER USING 0;")
+                .await
+                .unwrap();
+            match waifusims.get_all_manga_titles().await {
+                Err(Error::Decode { column, row, .. }) => {
+                    assert_eq!(column, "PurchaseURL");
+                    assert_eq!(row, 0);
+                }
+                result => panic!("expected a decode error, got {:?}", result),
+            }
+        });
+    }
+
+    #[test]
+    fn gets_manga_creators_in_credit_order() {
+        with_seeded("llrs_gets_creators", |mut waifusims| async move {
+            let creators = waifusims.get_manga_creators(1).await.unwrap();
+            let credits = creators
+                .iter()
+                .map(|creator| (creator.creator_name.as_str(), creator.role))
+                .collect::<Vec<(&str, CreatorRole)>>();
+            assert_eq!(
+                credits,
+                vec![
+                    ("Author", CreatorRole::Author),
+                    ("Artist", CreatorRole::Artist),
+                    ("Writer", CreatorRole::Author),
+                    ("Author", CreatorRole::Artist),
+                ]
+            );
+            assert!(waifusims.get_manga_creators(2).await.unwrap().is_empty());
+        });
+    }
+
+    async fn searched(waifusims: &mut PostgresWaifusims, query: &str) -> Vec<(i32, SearchField)> {
+        waifusims
+            .search(query, 10)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|result| (result.manga.manga_id, result.field))
+            .collect()
+    }
+
+    #[test]
+    fn searches_titles_creators_and_chapters() {
+        with_seeded("llrs_searches", |mut waifusims| async move {
+            waifusims
+                .client
+                .batch_execute(
+                    "
+INSERT INTO Manga (MangaID, MangaName) VALUES (2, 'Half Moon');
+INSERT INTO MangaAlternateTitle VALUES (2, 'Hangetsu 100%');
+INSERT INTO MangaAlternateTitle VALUES (2, 'ÉCLIPSE');
+INSERT INTO MangaCreator VALUES (2, 3, 'Author', 0);
+",
+                )
+                .await
+                .unwrap();
+            assert_eq!(
+                searched(&mut waifusims, "HALF").await,
+                vec![(2, SearchField::Title), (1, SearchField::Chapter)]
+            );
+            assert_eq!(
+                searched(&mut waifusims, "writer").await,
+                vec![(1, SearchField::Creator), (2, SearchField::Creator)]
+            );
+            assert_eq!(
+                searched(&mut waifusims, "100%").await,
+                vec![(2, SearchField::AlternateTitle)]
+            );
+            // ILIKE only ignores ASCII case in a C locale database
+            assert_eq!(
+                searched(&mut waifusims, "éclipse").await,
+                vec![(2, SearchField::AlternateTitle)]
+            );
+            // `_` is a literal, not a single character wildcard
+            assert!(searched(&mut waifusims, "T_n").await.is_empty());
+            assert!(searched(&mut waifusims, "   ").await.is_empty());
+            assert!(searched(&mut waifusims, "t").await.is_empty());
+
+            let results = waifusims.search("and a half", 10).await.unwrap();
+            assert_eq!(results.len(), 1);
+            assert_eq!(results[0].matched_text, "Two and a half");
+            assert_eq!(
+                results[0]
+                    .chapter_number
+                    .as_ref()
+                    .map(ChapterNumber::as_str),
+                Some("2.5")
+            );
+            assert_eq!(results[0].manga.author_names, vec!["Author", "Writer"]);
+        });
+    }
+
+    #[test]
+    fn fetches_titles_before_capping_candidates() {
+        with_seeded("llrs_caps_candidates", |mut waifusims| async move {
+            waifusims
+                .client
+                .batch_execute(
+                    "
+INSERT INTO Manga (MangaID, MangaName) VALUES (2, 'Filler');
+INSERT INTO MangaChapter
+SELECT 1, i, 'f' || i, 'Filler ' || i, '2021-01-01 00:00:00', NULL, '2021-01-01 00:00:00'
+FROM generate_series(4, 604) i;
+",
+                )
+                .await
+                .unwrap();
+            assert_eq!(
+                searched(&mut waifusims, "filler").await,
+                vec![(2, SearchField::Title), (1, SearchField::Chapter)]
+            );
+        });
+    }
+
+    #[test]
+    fn gets_single_manga_and_chapters() {
+        with_seeded("llrs_gets_single", |mut waifusims| async move {
+            let manga = waifusims.get_manga(1).await.unwrap().unwrap();
+            assert_eq!(manga.manga_name, "Manga");
+            assert_eq!(manga.author_names, vec!["Author", "Writer"]);
+            assert_eq!(waifusims.get_manga(2).await.unwrap(), None);
+
+            let chapter = waifusims.get_chapter(1, "2.5").await.unwrap().unwrap();
+            assert_eq!(chapter.chapter_name, "Two and a half");
+            assert_eq!(chapter.release_date, None);
+            assert_eq!(waifusims.get_chapter(1, "3").await.unwrap(), None);
+            assert_eq!(waifusims.get_chapter(2, "2").await.unwrap(), None);
+        });
+    }
+
+    #[test]
+    fn gets_chapters_and_pages_in_batches() {
+        with_seeded("llrs_gets_batches", |mut waifusims| async move {
+            let chapters = waifusims.get_mangas_chapters(&[3, 1]).await.unwrap();
+            assert!(chapters[0].is_empty());
+            let chapter_numbers = chapters[1]
+                .iter()
+                .map(|chapter| chapter.chapter_number.to_string())
+                .collect::<Vec<String>>();
+            assert_eq!(chapter_numbers, vec!["2", "2.5", "10"]);
+
+            let keys = [
+                (1, "10".to_owned()),
+                (1, "2".to_owned()),
+                (2, "2".to_owned()),
+            ];
+            let pages = waifusims.get_chapters_pages(&keys).await.unwrap();
+            assert_eq!(pages.len(), 3);
+            assert!(pages[0].is_empty());
+            assert_eq!(pages[1], waifusims.get_pages(1, "2").await.unwrap());
+            assert_eq!(pages[1][1].mirror_urls, vec!["two-2-mirror.png"]);
+            assert!(pages[2].is_empty());
+        });
+    }
+
+    #[test]
+    fn lists_recent_chapters_by_release() {
+        with_seeded("llrs_lists_recent", |mut waifusims| async move {
+            waifusims
+                .client
+                .batch_execute(
+                    "
+INSERT INTO Manga (MangaID, MangaName) VALUES (2, 'Other');
+INSERT INTO MangaChapter VALUES (2, 1, '1', 'One', '2021-02-01 00:00:00', '2021-01-05 00:00:00', '2021-02-01 00:00:00');
+",
+                )
+                .await
+                .unwrap();
+            let recent = |chapters: Vec<RecentChapter>| {
+                chapters
+                    .into_iter()
+                    .map(|recent| {
+                        (
+                            recent.manga.manga_id,
+                            recent.chapter.chapter_number.to_string(),
+                        )
+                    })
+                    .collect::<Vec<(i32, String)>>()
+            };
+            // 2.5 has no release date, so it counts as released when it was added
+            assert_eq!(
+                recent(waifusims.get_recent_chapters(3, None).await.unwrap()),
+                vec![
+                    (1, "10".to_owned()),
+                    (2, "1".to_owned()),
+                    (1, "2.5".to_owned())
+                ]
+            );
+            let since =
+                NaiveDateTime::parse_from_str("2021-01-03 00:00:00", "%Y-%m-%d %H:%M:%S").ok();
+            assert_eq!(
+                recent(waifusims.get_recent_chapters(10, since).await.unwrap()),
+                vec![(1, "10".to_owned()), (2, "1".to_owned())]
+            );
+            let chapters = waifusims.get_recent_chapters(1, None).await.unwrap();
+            assert_eq!(chapters[0].manga.author_names, vec!["Author", "Writer"]);
+        });
+    }
+
+    #[test]
+    fn sorts_chapters_numerically() {
+        with_seeded("llrs_sorts_chapters", |mut waifusims| async move {
+            let chapters = waifusims.get_manga_chapters(1).await.unwrap();
+            let chapter_numbers = chapters
+                .iter()
+                .map(|chapter| chapter.chapter_number.as_str())
+                .collect::<Vec<&str>>();
+            assert_eq!(chapter_numbers, vec!["2", "2.5", "10"]);
+            assert!(chapters[0].release_date.is_some());
+            assert_eq!(chapters[1].release_date, None);
+        });
+    }
+
+    #[test]
+    fn gets_pages_in_order_with_mirrors() {
+        with_seeded("llrs_gets_pages", |mut waifusims| async move {
+            let pages = waifusims.get_pages(1, "2").await.unwrap();
+            let urls = pages
+                .iter()
+                .map(|page| page.url_string.as_str())
+                .collect::<Vec<&str>>();
+            assert_eq!(urls, vec!["two-1.png", "two-2.png"]);
+            assert!(pages[0].mirror_urls.is_empty());
+            assert_eq!(pages[1].mirror_urls, vec!["two-2-mirror.png"]);
+        });
+    }
+
+    #[test]
+    fn creates_manga_with_existing_and_new_creators() {
+        with_seeded("llrs_creates_manga", |mut waifusims| async move {
+            let manga = waifusims
+                .create_manga(NewManga {
+                    manga_name: "Collab".to_owned(),
+                    creators: vec![
+                        Creator {
+                            creator_name: "Artist".to_owned(),
+                            role: CreatorRole::Artist,
+                        },
+                        Creator {
+                            creator_name: "Newcomer".to_owned(),
+                            role: CreatorRole::Author,
+                        },
+                    ],
+                    cover_image_url: None,
+                    purchase_url: None,
+                    status: MangaStatus::Hiatus,
+                    alternate_titles: vec!["Kyousaku".to_owned()],
+                })
+                .await
+                .unwrap();
+            assert_eq!(manga.manga_id, 2);
+            let mangas = waifusims.get_all_manga_titles().await.unwrap();
+            assert_eq!(mangas[1].author_names, vec!["Newcomer"]);
+            assert_eq!(mangas[1].artist_names, vec!["Artist"]);
+            assert_eq!(mangas[1].status, MangaStatus::Hiatus);
+            assert_eq!(mangas[1].creation_date, manga.creation_date);
+            assert_eq!(
+                searched(&mut waifusims, "kyousaku").await,
+                vec![(2, SearchField::AlternateTitle)]
+            );
+            waifusims.delete_manga(2).await.unwrap();
+            assert!(searched(&mut waifusims, "kyousaku").await.is_empty());
+        });
+    }
+
+    #[test]
+    fn creates_chapters_with_pages() {
+        with_seeded("llrs_creates_chapters", |mut waifusims| async move {
+            let chapter = waifusims
+                .create_chapter(1, new_chapter("11", 2))
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(chapter.chapter_number.as_str(), "11");
+            assert_eq!(
+                page_urls(&mut waifusims, "11").await,
+                vec!["11-1.png", "11-2.png"]
+            );
+            let chapters = waifusims.get_manga_chapters(1).await.unwrap();
+            assert_eq!(
+                chapters.last().unwrap().creation_date,
+                chapter.creation_date
+            );
+
+            assert!(waifusims
+                .create_chapter(2, new_chapter("1", 1))
+                .await
+                .unwrap()
+                .is_none());
+            match waifusims.create_chapter(1, new_chapter("10", 1)).await {
+                Err(Error::Conflict(_)) => {}
+                result => panic!("expected a conflict, got {:?}", result),
+            }
+        });
+    }
+
+    /// Creates `chapter_number` while another connection is partway through `other_write`,
+    /// which commits once `create_chapter` is waiting on it. The other connection takes
+    /// the manga's lock first like `create_chapter` when `locking`.
+    async fn create_during_other_write(
+        waifusims: &mut PostgresWaifusims,
+        schema: &str,
+        locking: bool,
+        other_write: &str,
+        chapter_number: &str,
+    ) -> Result<Option<Chapter>> {
+        let pid: i32 = waifusims
+            .client
+            .query_one("SELECT pg_backend_pid()", &[])
+            .await
+            .unwrap()
+            .get(0);
+        let mut other = connect_to(schema).await;
+        let transaction = other.client.transaction().await.unwrap();
+        if locking {
+            transaction
+                .query_one(LOCK_MANGA_QUERY, &[&1])
+                .await
+                .unwrap();
+        }
+        transaction.batch_execute(other_write).await.unwrap();
+        let commit_once_waiting = async {
+            let waiting = "SELECT COUNT(*) FROM pg_locks WHERE pid = $1 AND NOT granted";
+            while transaction
+                .query_one(waiting, &[&pid])
+                .await
+                .unwrap()
+                .get::<_, i64>(0)
+                == 0
+            {}
+            transaction.commit().await.unwrap();
+        };
+        let (created, ()) = futures::join!(
+            waifusims.create_chapter(1, new_chapter(chapter_number, 1)),
+            commit_once_waiting
+        );
+        created
+    }
+
+    #[test]
+    fn serializes_concurrent_chapter_writes() {
+        let schema = "llrs_concurrent_chapters";
+        with_seeded(schema, |mut waifusims| async move {
+            let insert = |chapter_index: i32, chapter_number: &str| {
+                format!(
+                    "INSERT INTO MangaChapter VALUES (1, {}, '{}', 'Other', NOW(), NULL, NOW());",
+                    chapter_index, chapter_number
+                )
+            };
+            let created =
+                create_during_other_write(&mut waifusims, schema, true, &insert(4, "11"), "12")
+                    .await;
+            assert!(created.unwrap().is_some());
+            match create_during_other_write(&mut waifusims, schema, true, &insert(6, "13"), "13")
+                .await
+            {
+                Err(Error::Conflict(_)) => {}
+                result => panic!("expected a conflict, got {:?}", result),
+            }
+            // Renames don't lock the manga, only the unique chapter number stops them
+            let rename =
+                "UPDATE MangaChapter SET ChapterNumber = '14' WHERE MangaID = 1 AND ChapterNumber = '2';";
+            match create_during_other_write(&mut waifusims, schema, false, rename, "14").await {
+                Err(Error::Conflict(_)) => {}
+                result => panic!("expected a conflict, got {:?}", result),
+            }
+            let chapters = waifusims.get_manga_chapters(1).await.unwrap();
+            assert_eq!(chapters.len(), 6);
+            assert_eq!(page_urls(&mut waifusims, "12").await, vec!["12-1.png"]);
+        });
+    }
+
+    #[test]
+    fn reuses_pooled_connections_after_dropped_writes() {
+        with_seeded("llrs_pooled", |_| async {
+            let connection_string = std::env::var("LLRS_TEST_POSTGRES").unwrap();
+            let pool_config = PoolConfig {
+                max_size: 1,
+                health_check: false,
+                ..PoolConfig::default()
+            };
+            let mut pool = PostgresPool::new(
+                &format!("{} options='-c search_path=llrs_pooled'", connection_string),
+                pool_config,
+            );
+            assert!(pool.get_manga(1).await.unwrap().is_some());
+
+            // A client disconnecting drops the write after BEGIN was sent
+            assert!(pool
+                .create_chapter(1, new_chapter("11", 1))
+                .now_or_never()
+                .is_none());
+            // Savepoints are only allowed inside a transaction, so this fails unless one was left open
+            assert!(pool
+                .get()
+                .await
+                .unwrap()
+                .client
+                .batch_execute("SAVEPOINT left_open")
+                .await
+                .is_err());
+            assert!(pool
+                .create_chapter(1, new_chapter("11", 1))
+                .await
+                .unwrap()
+                .is_some());
+        });
+    }
+
+    #[test]
+    fn replaces_chapters_and_their_pages() {
+        with_seeded("llrs_replaces_chapters", |mut waifusims| async move {
+            let chapter = waifusims
+                .update_chapter(1, "2", new_chapter("3", 1))
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(chapter.chapter_name, "Chapter 3");
+            assert!(page_urls(&mut waifusims, "2").await.is_empty());
+            assert_eq!(page_urls(&mut waifusims, "3").await, vec!["3-1.png"]);
+            match waifusims.update_chapter(1, "3", new_chapter("10", 1)).await {
+                Err(Error::Conflict(_)) => {}
+                result => panic!("expected a conflict, got {:?}", result),
+            }
+            assert!(waifusims
+                .update_chapter(1, "2", new_chapter("2", 1))
+                .await
+                .unwrap()
+                .is_none());
+        });
+    }
+
+    #[test]
+    fn deletes_chapters_and_manga() {
+        with_seeded("llrs_deletes", |mut waifusims| async move {
+            assert!(waifusims.delete_chapter(1, "2").await.unwrap());
+            assert!(!waifusims.delete_chapter(1, "2").await.unwrap());
+            assert!(page_urls(&mut waifusims, "2").await.is_empty());
+
+            assert!(waifusims.delete_manga(1).await.unwrap());
+            assert!(!waifusims.delete_manga(1).await.unwrap());
+            assert!(waifusims.get_all_manga_titles().await.unwrap().is_empty());
+            assert!(waifusims.get_manga_chapters(1).await.unwrap().is_empty());
+        });
+    }
+
+    #[test]
+    fn rolls_back_failed_chapters() {
+        with_seeded("llrs_rolls_back", |mut waifusims| async move {
+            waifusims
+                .client
+                .batch_execute("DROP TABLE PageURL;")
+                .await
+                .unwrap();
+            assert!(waifusims
+                .create_chapter(1, new_chapter("11", 1))
+                .await
+                .is_err());
+            let chapters = waifusims.get_manga_chapters(1).await.unwrap();
+            assert_eq!(chapters.len(), 3);
+        });
+    }
+
+    #[test]
+    fn rejects_invalid_chapters_before_writing() {
+        with_seeded("llrs_rejects_invalid", |mut waifusims| async move {
+            let mut chapter = new_chapter("11", 1);
+            chapter.pages.push(chapter.pages[0].clone());
+            match waifusims.create_chapter(1, chapter).await {
+                Err(Error::InvalidInput(_)) => {}
+                result => panic!("expected invalid input, got {:?}", result),
+            }
+        });
+    }
+
+    async fn applied_versions(waifusims: &mut PostgresWaifusims) -> Vec<(i64, bool)> {
+        waifusims
+            .migration_status()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|status| (status.version, status.applied_at.is_some()))
+            .collect()
+    }
+
+    fn versions(migrations: Vec<&Migration>) -> Vec<i64> {
+        migrations.iter().map(|m| m.version).collect()
+    }
+
+    #[test]
+    fn migrates_up_and_down() {
+        with_empty("llrs_migrates", |mut waifusims| async move {
+            assert_eq!(
+                applied_versions(&mut waifusims).await,
+                vec![(1, false), (2, false), (3, false), (4, false), (5, false)]
+            );
+            let applied = waifusims.migrate_up(Some(2)).await.unwrap();
+            assert_eq!(versions(applied), vec![1, 2]);
+            let applied = waifusims.migrate_up(None).await.unwrap();
+            assert_eq!(versions(applied), vec![3, 4, 5]);
+            assert!(waifusims.migrate_up(None).await.unwrap().is_empty());
+
+            let reverted = waifusims.migrate_down(1).await.unwrap();
+            assert_eq!(versions(reverted), vec![5, 4, 3, 2]);
+            assert_eq!(
+                applied_versions(&mut waifusims).await,
+                vec![(1, true), (2, false), (3, false), (4, false), (5, false)]
+            );
+            waifusims.migrate_down(0).await.unwrap();
+            assert!(!waifusims.has_waifusims_tables().await.unwrap());
+            waifusims.migrate_up(None).await.unwrap();
+            assert_eq!(waifusims.get_all_manga_titles().await.unwrap(), vec![]);
+        });
+    }
+
+    #[test]
+    fn moves_single_authors_into_creators() {
+        with_empty("llrs_moves_authors", |mut waifusims| async move {
+            waifusims.migrate_up(Some(1)).await.unwrap();
+            waifusims
+                .client
+                .batch_execute(
+                    "
+INSERT INTO Author (AuthorID, AuthorName) VALUES (1, 'Author');
+INSERT INTO Manga (MangaID, MangaName, AuthorID) VALUES (1, 'Manga', 1);
+INSERT INTO MangaChapter VALUES (1, 1, '1', 'One', '2021-01-01 00:00:00', NULL);
+",
+                )
+                .await
+                .unwrap();
+            waifusims.migrate_up(None).await.unwrap();
+            let mangas = waifusims.get_all_manga_titles().await.unwrap();
+            assert_eq!(mangas[0].author_names, vec!["Author".to_owned()]);
+            assert_eq!(mangas[0].status, MangaStatus::Ongoing);
+            assert_eq!(waifusims.get_manga_chapters(1).await.unwrap().len(), 1);
+
+            waifusims.migrate_down(1).await.unwrap();
+            let row = waifusims
+                .client
+                .query_one("SELECT AuthorID FROM Manga", &[])
+                .await
+                .unwrap();
+            assert_eq!(row.get::<_, i32>("AuthorID"), 1);
+        });
+    }
+
+    #[test]
+    fn baselines_databases_that_predate_migrations() {
+        with_empty("llrs_baselines", |mut waifusims| async move {
+            waifusims
+                .client
+                .batch_execute(Dialect::Postgres.migrations()[0].up)
+                .await
+                .unwrap();
+            assert!(matches!(
+                waifusims.migrate_up(None).await,
+                Err(Error::Migration(_))
+            ));
+            assert!(matches!(
+                waifusims.baseline(7).await,
+                Err(Error::Migration(_))
+            ));
+            waifusims.baseline(1).await.unwrap();
+            assert!(matches!(
+                waifusims.baseline(1).await,
+                Err(Error::Migration(_))
+            ));
+            waifusims.migrate_up(None).await.unwrap();
+            assert_eq!(
+                applied_versions(&mut waifusims).await,
+                vec![(1, true), (2, true), (3, true), (4, true), (5, true)]
+            );
+        });
+    }
+}
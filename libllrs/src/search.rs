@@ -0,0 +1,313 @@
+//! Ranking shared by every `MangaService::search`, so backends agree on result order.
+//!
+//! Backends only narrow candidates with `like_pattern`, fetching the first `MAX_CANDIDATES`
+//! of them in `candidates` order, and every hit is then scored here.
+//! A hit matches when its text contains every search term, ignoring case as Rust's
+//! `to_lowercase` does, whatever the database's collation.
+//! Titles outrank alternate titles, then creators and then chapter names,
+//! and within a field a whole or leading match outranks a match mid text.
+
+use std::collections::HashMap;
+
+use crate::{ChapterNumber, Manga, SearchField, SearchResult};
+
+/// Text of one manga that a search may match.
+#[derive(Debug, Clone)]
+pub(crate) struct SearchHit {
+    pub(crate) manga_id: i32,
+    pub(crate) field: SearchField,
+    pub(crate) text: String,
+    pub(crate) chapter_number: Option<ChapterNumber>,
+}
+
+/// A search needs a term at least this long, shorter ones would match most of the catalog.
+pub(crate) const MIN_TERM_CHARS: usize = 2;
+
+/// Most hits a backend fetches for ranking, in `candidates` order. Titles are fetched
+/// first and chapter names last, so a broad search drops chapter matches before anything else,
+/// and shorter texts come first within a field so whole matches make the cut.
+pub(crate) const MAX_CANDIDATES: i64 = 500;
+
+/// Lowercase whitespace separated terms of a search, none when even the longest
+/// is shorter than `MIN_TERM_CHARS`.
+pub(crate) fn search_terms(query: &str) -> Vec<String> {
+    let terms = query
+        .split_whitespace()
+        .map(str::to_lowercase)
+        .collect::<Vec<String>>();
+    if terms
+        .iter()
+        .all(|term| term.chars().count() < MIN_TERM_CHARS)
+    {
+        return vec![];
+    }
+    terms
+}
+
+fn longest_term(terms: &[String]) -> Option<&String> {
+    terms.iter().max_by_key(|term| term.chars().count())
+}
+
+/// Whether a letter is left to a `_` wildcard, see `like_pattern`.
+fn is_wildcarded(c: char) -> bool {
+    !c.is_ascii() && (c.is_lowercase() || c.is_uppercase())
+}
+
+/// `LIKE` pattern for the longest term so the database returns the fewest candidates,
+/// `\` escapes wildcards. `None` when there's nothing to search for.
+///
+/// Outside ASCII, databases fold case by their locale or not at all, so those letters
+/// are left to a `_` wildcard and `score` tells them apart.
+pub(crate) fn like_pattern(terms: &[String]) -> Option<String> {
+    let term = longest_term(terms)?;
+    let mut pattern = String::with_capacity(term.len() + 2);
+    pattern.push('%');
+    for c in term.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            pattern.push('\\');
+        } else if is_wildcarded(c) {
+            pattern.push('_');
+            continue;
+        }
+        pattern.push(c);
+    }
+    pattern.push('%');
+    Some(pattern)
+}
+
+/// Whether `text` matches `like_pattern(terms)` as the databases compare them.
+fn is_like(terms: &[String], text: &str) -> bool {
+    let term = match longest_term(terms) {
+        Some(term) => term.chars().collect::<Vec<char>>(),
+        None => return false,
+    };
+    let text = text.chars().collect::<Vec<char>>();
+    text.windows(term.len()).any(|window| {
+        window
+            .iter()
+            .zip(&term)
+            .all(|(c, term_c)| is_wildcarded(*term_c) || c.eq_ignore_ascii_case(term_c))
+    })
+}
+
+fn field_order(field: SearchField) -> u8 {
+    match field {
+        SearchField::Title => 1,
+        SearchField::AlternateTitle => 2,
+        SearchField::Creator => 3,
+        SearchField::Chapter => 4,
+    }
+}
+
+/// The hits a backend's search query returns for `terms`, for backends without one.
+///
+/// Ordered as the queries order them before cutting at `MAX_CANDIDATES`: by field,
+/// then by text length, `manga_id`, and text and chapter number compared bytewise.
+pub(crate) fn candidates(terms: &[String], hits: Vec<SearchHit>) -> Vec<SearchHit> {
+    let mut candidates = hits
+        .into_iter()
+        .filter(|hit| is_like(terms, &hit.text))
+        .collect::<Vec<SearchHit>>();
+    candidates.sort_by(|a, b| {
+        field_order(a.field)
+            .cmp(&field_order(b.field))
+            .then_with(|| a.text.chars().count().cmp(&b.text.chars().count()))
+            .then(a.manga_id.cmp(&b.manga_id))
+            .then_with(|| a.text.cmp(&b.text))
+            .then_with(|| {
+                let a_number = a.chapter_number.as_ref().map(ChapterNumber::as_str);
+                a_number.cmp(&b.chapter_number.as_ref().map(ChapterNumber::as_str))
+            })
+    });
+    candidates.truncate(MAX_CANDIDATES as usize);
+    candidates
+}
+
+fn field_weight(field: SearchField) -> u32 {
+    match field {
+        SearchField::Title => 400,
+        SearchField::AlternateTitle => 300,
+        SearchField::Creator => 200,
+        SearchField::Chapter => 100,
+    }
+}
+
+fn score(terms: &[String], hit: &SearchHit) -> Option<u32> {
+    let text = hit.text.to_lowercase();
+    if !terms.iter().all(|term| text.contains(term.as_str())) {
+        return None;
+    }
+    let phrase = terms.join(" ");
+    let mut score = field_weight(hit.field);
+    if text == phrase {
+        score += 60;
+    } else if text.starts_with(&phrase) {
+        score += 40;
+    } else if text.contains(&phrase) {
+        score += 20;
+    }
+    let words = text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<&str>>();
+    if terms
+        .iter()
+        .all(|term| words.iter().any(|word| word.starts_with(term.as_str())))
+    {
+        score += 10;
+    }
+    Some(score)
+}
+
+/// Best scoring hit of each manga, best first, at most `limit` of them.
+/// Ties are broken by the matched text and then by `manga_id`.
+pub(crate) fn rank(query: &str, hits: Vec<SearchHit>, limit: usize) -> Vec<(SearchHit, u32)> {
+    let terms = search_terms(query);
+    if terms.is_empty() {
+        return vec![];
+    }
+    let mut best: HashMap<i32, (SearchHit, u32)> = HashMap::new();
+    for hit in hits {
+        let score = match score(&terms, &hit) {
+            Some(score) => score,
+            None => continue,
+        };
+        match best.get(&hit.manga_id) {
+            Some((_, best_score)) if *best_score >= score => {}
+            _ => {
+                best.insert(hit.manga_id, (hit, score));
+            }
+        }
+    }
+    let mut ranked = best.into_values().collect::<Vec<(SearchHit, u32)>>();
+    ranked.sort_by(|(a, a_score), (b, b_score)| {
+        b_score
+            .cmp(a_score)
+            .then_with(|| a.text.to_lowercase().cmp(&b.text.to_lowercase()))
+            .then_with(|| a.manga_id.cmp(&b.manga_id))
+    });
+    ranked.truncate(limit);
+    ranked
+}
+
+/// Pairs ranked hits with their manga, dropping any manga that has since been deleted.
+pub(crate) fn search_results(
+    ranked: Vec<(SearchHit, u32)>,
+    mangas: Vec<Manga>,
+) -> Vec<SearchResult> {
+    let mut mangas = mangas
+        .into_iter()
+        .map(|manga| (manga.manga_id, manga))
+        .collect::<HashMap<i32, Manga>>();
+    ranked
+        .into_iter()
+        .filter_map(|(hit, score)| {
+            Some(SearchResult {
+                manga: mangas.remove(&hit.manga_id)?,
+                field: hit.field,
+                matched_text: hit.text,
+                chapter_number: hit.chapter_number,
+                score,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hit(manga_id: i32, field: SearchField, text: &str) -> SearchHit {
+        SearchHit {
+            manga_id,
+            field,
+            text: text.to_owned(),
+            chapter_number: None,
+        }
+    }
+
+    fn ranked_ids(query: &str, hits: Vec<SearchHit>) -> Vec<i32> {
+        rank(query, hits, 10)
+            .into_iter()
+            .map(|(hit, _)| hit.manga_id)
+            .collect()
+    }
+
+    #[test]
+    fn ranks_titles_over_other_fields() {
+        let hits = vec![
+            hit(1, SearchField::Chapter, "The Dragon Returns"),
+            hit(2, SearchField::Creator, "Dragon Author"),
+            hit(3, SearchField::AlternateTitle, "Dragon Quest"),
+            hit(4, SearchField::Title, "Tale of a Dragon"),
+        ];
+        assert_eq!(ranked_ids("dragon", hits), vec![4, 3, 2, 1]);
+    }
+
+    #[test]
+    fn ranks_whole_and_leading_matches_first() {
+        let hits = vec![
+            hit(1, SearchField::Title, "Blue Sky Hero"),
+            hit(2, SearchField::Title, "Skyhero"),
+            hit(3, SearchField::Title, "Sky Hero"),
+            hit(4, SearchField::Title, "Sky Hero Returns"),
+        ];
+        assert_eq!(ranked_ids("SKY hero", hits), vec![3, 4, 1, 2]);
+    }
+
+    #[test]
+    fn keeps_the_best_hit_per_manga() {
+        let hits = vec![
+            hit(1, SearchField::Chapter, "Moon"),
+            hit(1, SearchField::Title, "Moonlight"),
+            hit(2, SearchField::Creator, "Moon"),
+        ];
+        let ranked = rank("moon", hits, 10);
+        assert_eq!(ranked.len(), 2);
+        assert_eq!(ranked[0].0.field, SearchField::Title);
+        assert_eq!(rank("moon", vec![], 10).len(), 0);
+        assert!(rank("   ", vec![hit(1, SearchField::Title, "Moon")], 10).is_empty());
+    }
+
+    #[test]
+    fn escapes_like_wildcards() {
+        let terms = search_terms("a 100%_\\");
+        assert_eq!(like_pattern(&terms).as_deref(), Some("%100\\%\\_\\\\%"));
+        assert_eq!(like_pattern(&[]), None);
+    }
+
+    #[test]
+    fn leaves_non_ascii_case_to_ranking() {
+        let terms = search_terms("ÉMILE");
+        assert_eq!(like_pattern(&terms).as_deref(), Some("%_mile%"));
+        assert_eq!(
+            ranked_ids("ÉMILE", vec![hit(1, SearchField::Title, "émile")]),
+            vec![1]
+        );
+        // Scripts without case are matched as they are
+        let terms = search_terms("進撃");
+        assert_eq!(like_pattern(&terms).as_deref(), Some("%進撃%"));
+    }
+
+    #[test]
+    fn orders_candidates_like_the_queries() {
+        let hits = vec![
+            hit(1, SearchField::Chapter, "Smile"),
+            hit(3, SearchField::Title, "Émile and friends"),
+            hit(2, SearchField::Title, "EMILE"),
+            hit(4, SearchField::Title, "Emil"),
+        ];
+        let candidates = candidates(&search_terms("émile"), hits)
+            .into_iter()
+            .map(|hit| hit.manga_id)
+            .collect::<Vec<i32>>();
+        assert_eq!(candidates, vec![2, 3, 1]);
+    }
+
+    #[test]
+    fn needs_a_term_of_min_length() {
+        assert!(search_terms("a b").is_empty());
+        assert_eq!(search_terms("a bc"), vec!["a", "bc"]);
+        assert!(rank("a", vec![hit(1, SearchField::Title, "a")], 10).is_empty());
+    }
+}
@@ -0,0 +1,1857 @@
+use std::{
+    any::type_name,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use async_trait::async_trait;
+use chrono::NaiveDateTime;
+use rusqlite::{params, types::FromSql, Connection, OptionalExtension, Row};
+
+use crate::{
+    assign_creators, chapter_exists, chapter_manga_ids, creation_date, decode_parsed,
+    group_chapter_page_urls, group_manga_chapters, group_page_urls, like_pattern, list_limit, rank,
+    recent_chapters, search_results, search_terms, sort_chapters, stored_chapter, stored_manga,
+    validate_chapter, validate_manga, Chapter, ChapterNumber, Creator, Error, Manga,
+    MangaListQuery, MangaListing, MangaService, MangaSort, NewChapter, NewManga, NewPage, Page,
+    RecentChapter, Result, SearchHit, SearchResult, MAX_CANDIDATES,
+};
+use crate::{AppliedMigration, Dialect, Direction, Migrate, Migration};
+
+const SELECT_ALL_MANGA_QUERY: &str = "
+SELECT
+    m.MangaID,
+    m.MangaName,
+    m.CoverImageURL,
+    m.PurchaseURL,
+    m.Status,
+    m.DateCreated,
+    m.UpdatedDate
+FROM Manga m
+ORDER BY m.MangaID
+";
+
+/// Shared by a listing and its count, `?1` is the status and `?2` the author
+const MANGA_LIST_FILTER: &str = "
+WHERE (?1 IS NULL OR m.Status = ?1)
+    AND (?2 IS NULL OR EXISTS (
+        SELECT 1
+        FROM MangaCreator mc
+        JOIN Author a
+            ON mc.AuthorID = a.AuthorID
+        WHERE mc.MangaID = m.MangaID
+            AND mc.Role = 'Author'
+            AND a.AuthorName = ?2 COLLATE NOCASE
+    ))
+";
+
+/// Lists a slice of manga, `?3` is the offset and `?4` the limit.
+fn select_manga_list_query(sort: MangaSort) -> String {
+    // NULLs sort last when descending
+    let order = match sort {
+        MangaSort::Name => "m.MangaName COLLATE NOCASE, m.MangaID",
+        MangaSort::NewestChapter => {
+            "(SELECT MAX(c.DateCreated) FROM MangaChapter c WHERE c.MangaID = m.MangaID) DESC,
+    m.MangaID"
+        }
+        MangaSort::CreationDate => "m.DateCreated DESC, m.MangaID DESC",
+    };
+    format!(
+        "
+SELECT
+    m.MangaID,
+    m.MangaName,
+    m.CoverImageURL,
+    m.PurchaseURL,
+    m.Status,
+    m.DateCreated,
+    m.UpdatedDate
+FROM Manga m{}ORDER BY {}
+LIMIT ?4 OFFSET ?3
+",
+        MANGA_LIST_FILTER, order
+    )
+}
+
+fn count_manga_list_query() -> String {
+    format!(
+        "
+SELECT COUNT(*) AS Total
+FROM Manga m{}",
+        MANGA_LIST_FILTER
+    )
+}
+
+/// `?1` is a JSON array of manga IDs
+const SELECT_LISTED_MANGA_CREATORS_QUERY: &str = "
+SELECT
+    mc.MangaID,
+    a.AuthorName,
+    mc.Role
+FROM MangaCreator mc
+JOIN Author a
+    ON mc.AuthorID = a.AuthorID
+WHERE mc.MangaID IN (SELECT value FROM json_each(?1))
+ORDER BY mc.MangaID, mc.CreditOrder, a.AuthorName
+";
+
+/// `?1` is a JSON array of manga IDs
+const SELECT_MANGA_BY_IDS_QUERY: &str = "
+SELECT
+    m.MangaID,
+    m.MangaName,
+    m.CoverImageURL,
+    m.PurchaseURL,
+    m.Status,
+    m.DateCreated,
+    m.UpdatedDate
+FROM Manga m
+WHERE m.MangaID IN (SELECT value FROM json_each(?1))
+";
+
+/// Search candidates, `?1` is a `LIKE` pattern escaped with `\`.
+/// SQLite's `LIKE` already ignores case for ASCII.
+/// The first `?2` in `search::candidates` order are returned.
+const SEARCH_QUERY: &str = "
+SELECT MangaID, Field, Text, ChapterNumber
+FROM (
+    SELECT m.MangaID, 'Title' AS Field, m.MangaName AS Text, NULL AS ChapterNumber, 1 AS FieldOrder
+    FROM Manga m
+    WHERE m.MangaName LIKE ?1 ESCAPE '\\'
+    UNION ALL
+    SELECT t.MangaID, 'AlternateTitle', t.Title, NULL, 2
+    FROM MangaAlternateTitle t
+    WHERE t.Title LIKE ?1 ESCAPE '\\'
+    UNION ALL
+    SELECT mc.MangaID, 'Creator', a.AuthorName, NULL, 3
+    FROM MangaCreator mc
+    JOIN Author a
+        ON mc.AuthorID = a.AuthorID
+    WHERE a.AuthorName LIKE ?1 ESCAPE '\\'
+    UNION ALL
+    SELECT c.MangaID, 'Chapter', c.ChapterName, c.ChapterNumber, 4
+    FROM MangaChapter c
+    WHERE c.ChapterName LIKE ?1 ESCAPE '\\'
+)
+ORDER BY FieldOrder, LENGTH(Text), MangaID, Text COLLATE BINARY, ChapterNumber COLLATE BINARY
+LIMIT ?2
+";
+
+const SELECT_MANGA_QUERY: &str = "
+SELECT
+    m.MangaID,
+    m.MangaName,
+    m.CoverImageURL,
+    m.PurchaseURL,
+    m.Status,
+    m.DateCreated,
+    m.UpdatedDate
+FROM Manga m
+WHERE m.MangaID = ?1
+";
+
+const SELECT_ALL_MANGA_CREATORS_QUERY: &str = "
+SELECT
+    mc.MangaID,
+    a.AuthorName,
+    mc.Role
+FROM MangaCreator mc
+JOIN Author a
+    ON mc.AuthorID = a.AuthorID
+ORDER BY mc.MangaID, mc.CreditOrder, a.AuthorName
+";
+
+const SELECT_MANGA_CREATORS_QUERY: &str = "
+SELECT
+    mc.MangaID,
+    a.AuthorName,
+    mc.Role
+FROM MangaCreator mc
+JOIN Author a
+    ON mc.AuthorID = a.AuthorID
+WHERE mc.MangaID = ?1
+ORDER BY mc.CreditOrder, a.AuthorName
+";
+
+const SELECT_MANGA_CHAPTERS_QUERY: &str = "
+SELECT
+    ChapterNumber,
+    ChapterName,
+    DateCreated,
+    DateReleased,
+    UpdatedDate,
+    MangaID
+FROM MangaChapter
+WHERE MangaID = ?1
+";
+
+/// `?1` is a JSON array of manga IDs
+const SELECT_MANGAS_CHAPTERS_QUERY: &str = "
+SELECT
+    ChapterNumber,
+    ChapterName,
+    DateCreated,
+    DateReleased,
+    UpdatedDate,
+    MangaID
+FROM MangaChapter
+WHERE MangaID IN (SELECT value FROM json_each(?1))
+";
+
+const SELECT_CHAPTER_QUERY: &str = "
+SELECT
+    ChapterNumber,
+    ChapterName,
+    DateCreated,
+    DateReleased,
+    UpdatedDate,
+    MangaID
+FROM MangaChapter
+WHERE MangaID = ?1
+    AND ChapterNumber = ?2
+";
+
+/// Newest releases first, `?1` is the release to list chapters after and `?2` the limit.
+/// Chapters without a release date count as released when they were added.
+const SELECT_RECENT_CHAPTERS_QUERY: &str = "
+SELECT
+    ChapterNumber,
+    ChapterName,
+    DateCreated,
+    DateReleased,
+    UpdatedDate,
+    MangaID
+FROM MangaChapter
+WHERE ?1 IS NULL OR COALESCE(DateReleased, DateCreated) > ?1
+ORDER BY COALESCE(DateReleased, DateCreated) DESC, MangaID, ChapterNumber
+LIMIT ?2
+";
+
+const SELECT_CHAPTER_PAGES_QUERY: &str = "
+SELECT
+    u.URL,
+    p.PageID,
+    p.PageNumber
+FROM Page p
+JOIN PageURL u
+    ON p.PageID = u.PageID
+JOIN MangaChapter mc
+    ON mc.ChapterIndex = p.ChapterIndex
+        AND mc.MangaID = p.MangaID
+        AND mc.ChapterNumber = ?2
+WHERE p.MangaID = ?1
+ORDER BY p.PageNumber, p.PageID, u.Priority
+";
+
+/// `?1` is a JSON array of `[manga_id, chapter_number]` pairs,
+/// each row carries the pair it matched as it was requested
+const SELECT_CHAPTERS_PAGES_QUERY: &str = "
+SELECT
+    json_extract(k.value, '$[0]') AS MangaID,
+    json_extract(k.value, '$[1]') AS ChapterNumber,
+    u.URL,
+    p.PageID,
+    p.PageNumber
+FROM json_each(?1) k
+JOIN MangaChapter mc
+    ON mc.MangaID = json_extract(k.value, '$[0]')
+        AND mc.ChapterNumber = json_extract(k.value, '$[1]')
+JOIN Page p
+    ON p.ChapterIndex = mc.ChapterIndex
+        AND p.MangaID = mc.MangaID
+JOIN PageURL u
+    ON p.PageID = u.PageID
+ORDER BY p.PageNumber, p.PageID, u.Priority
+";
+
+const INSERT_MANGA_QUERY: &str = "
+INSERT INTO Manga (MangaName, CoverImageURL, PurchaseURL, Status, DateCreated, UpdatedDate)
+VALUES (?1, ?2, ?3, ?4, ?5, ?5)
+RETURNING MangaID
+";
+
+const UPDATE_MANGA_QUERY: &str = "
+UPDATE Manga
+SET MangaName = ?2,
+    CoverImageURL = ?3,
+    PurchaseURL = ?4,
+    Status = ?5,
+    UpdatedDate = ?6
+WHERE MangaID = ?1
+RETURNING DateCreated
+";
+
+/// Marks the manga changed by a write to one of its chapters, `?2` is when
+const UPDATE_MANGA_UPDATED_DATE_QUERY: &str = "
+UPDATE Manga
+SET UpdatedDate = ?2
+WHERE MangaID = ?1
+";
+
+const SELECT_MANGA_ID_QUERY: &str = "
+SELECT MangaID
+FROM Manga
+WHERE MangaID = ?1
+";
+
+const SELECT_AUTHOR_ID_QUERY: &str = "
+SELECT AuthorID
+FROM Author
+WHERE AuthorName = ?1
+";
+
+const INSERT_AUTHOR_QUERY: &str = "
+INSERT INTO Author (AuthorName)
+VALUES (?1)
+RETURNING AuthorID
+";
+
+const INSERT_MANGA_CREATOR_QUERY: &str = "
+INSERT INTO MangaCreator (MangaID, AuthorID, Role, CreditOrder)
+VALUES (?1, ?2, ?3, ?4)
+";
+
+const DELETE_MANGA_CREATORS_QUERY: &str = "
+DELETE FROM MangaCreator
+WHERE MangaID = ?1
+";
+
+const INSERT_ALTERNATE_TITLE_QUERY: &str = "
+INSERT INTO MangaAlternateTitle (MangaID, Title)
+VALUES (?1, ?2)
+";
+
+const DELETE_MANGA_ALTERNATE_TITLES_QUERY: &str = "
+DELETE FROM MangaAlternateTitle
+WHERE MangaID = ?1
+";
+
+const DELETE_MANGA_PAGE_URLS_QUERY: &str = "
+DELETE FROM PageURL
+WHERE PageID IN (SELECT PageID FROM Page WHERE MangaID = ?1)
+";
+
+const DELETE_MANGA_PAGES_QUERY: &str = "
+DELETE FROM Page
+WHERE MangaID = ?1
+";
+
+const DELETE_MANGA_CHAPTERS_QUERY: &str = "
+DELETE FROM MangaChapter
+WHERE MangaID = ?1
+";
+
+const DELETE_MANGA_QUERY: &str = "
+DELETE FROM Manga
+WHERE MangaID = ?1
+";
+
+const SELECT_CHAPTER_INDEX_QUERY: &str = "
+SELECT ChapterIndex
+FROM MangaChapter
+WHERE MangaID = ?1
+    AND ChapterNumber = ?2
+";
+
+const INSERT_CHAPTER_QUERY: &str = "
+INSERT INTO MangaChapter (MangaID, ChapterIndex, ChapterNumber, ChapterName, DateCreated, DateReleased, UpdatedDate)
+SELECT ?1, COALESCE(MAX(ChapterIndex), 0) + 1, ?2, ?3, ?4, ?5, ?4
+FROM MangaChapter
+WHERE MangaID = ?1
+RETURNING ChapterIndex
+";
+
+const UPDATE_CHAPTER_QUERY: &str = "
+UPDATE MangaChapter
+SET ChapterNumber = ?3,
+    ChapterName = ?4,
+    DateReleased = ?5,
+    UpdatedDate = ?6
+WHERE MangaID = ?1
+    AND ChapterIndex = ?2
+RETURNING DateCreated
+";
+
+const DELETE_CHAPTER_PAGE_URLS_QUERY: &str = "
+DELETE FROM PageURL
+WHERE PageID IN (SELECT PageID FROM Page WHERE MangaID = ?1 AND ChapterIndex = ?2)
+";
+
+const DELETE_CHAPTER_PAGES_QUERY: &str = "
+DELETE FROM Page
+WHERE MangaID = ?1
+    AND ChapterIndex = ?2
+";
+
+const DELETE_CHAPTER_QUERY: &str = "
+DELETE FROM MangaChapter
+WHERE MangaID = ?1
+    AND ChapterIndex = ?2
+";
+
+const INSERT_PAGE_QUERY: &str = "
+INSERT INTO Page (MangaID, ChapterIndex, PageNumber)
+VALUES (?1, ?2, ?3)
+RETURNING PageID
+";
+
+const INSERT_PAGE_URL_QUERY: &str = "
+INSERT INTO PageURL (PageID, URL, Priority)
+VALUES (?1, ?2, ?3)
+";
+
+const CREATE_SCHEMA_MIGRATION_QUERY: &str = "
+CREATE TABLE IF NOT EXISTS SchemaMigration (
+    Version INTEGER PRIMARY KEY,
+    Name TEXT NOT NULL,
+    AppliedAt TEXT NOT NULL
+)
+";
+
+const SELECT_SCHEMA_MIGRATIONS_QUERY: &str = "
+SELECT Version, Name, AppliedAt
+FROM SchemaMigration
+ORDER BY Version
+";
+
+const SELECT_HAS_WAIFUSIMS_TABLES_QUERY: &str = "
+SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'Manga')
+";
+
+const INSERT_SCHEMA_MIGRATION_QUERY: &str = "
+INSERT INTO SchemaMigration (Version, Name, AppliedAt)
+VALUES (?1, ?2, ?3)
+";
+
+const DELETE_SCHEMA_MIGRATION_QUERY: &str = "
+DELETE FROM SchemaMigration
+WHERE Version = ?1
+";
+
+/// Waifusims database stored in a single SQLite file.
+///
+/// rusqlite is synchronous, so every query runs on tokio's blocking pool.
+/// Cloning shares the same underlying connection.
+#[derive(Clone)]
+pub struct SqliteWaifusims {
+    connection: Arc<Mutex<Connection>>,
+}
+
+impl SqliteWaifusims {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<SqliteWaifusims> {
+        Ok(SqliteWaifusims::from(Connection::open(path)?))
+    }
+
+    pub fn open_in_memory() -> Result<SqliteWaifusims> {
+        Ok(SqliteWaifusims::from(Connection::open_in_memory()?))
+    }
+
+    async fn with_connection<F, R>(&self, query: F) -> Result<R>
+    where
+        F: FnOnce(&Connection) -> Result<R> + Send + 'static,
+        R: Send + 'static,
+    {
+        let connection = Arc::clone(&self.connection);
+        let result = tokio::task::spawn_blocking(move || {
+            // A panic in another query can't leave the connection half-written,
+            // so a poisoned lock is still safe to use.
+            let connection = connection
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            query(&connection)
+        })
+        .await??;
+        Ok(result)
+    }
+
+    /// Runs `query` in a transaction that is rolled back if it returns an error.
+    async fn with_transaction<F, R>(&self, query: F) -> Result<R>
+    where
+        F: FnOnce(&Connection) -> Result<R> + Send + 'static,
+        R: Send + 'static,
+    {
+        self.with_connection(|connection| {
+            let transaction = connection.unchecked_transaction()?;
+            let result = query(&transaction)?;
+            transaction.commit()?;
+            Ok(result)
+        })
+        .await
+    }
+}
+
+impl From<Connection> for SqliteWaifusims {
+    fn from(connection: Connection) -> Self {
+        SqliteWaifusims {
+            connection: Arc::new(Mutex::new(connection)),
+        }
+    }
+}
+
+/// Reads a NOT NULL column, `row_index` is only used to describe errors.
+fn decode<R: FromSql>(row: &Row, row_index: usize, column: &'static str) -> Result<R> {
+    decode_nullable(row, row_index, column)?.ok_or(Error::UnexpectedNull {
+        column,
+        row: row_index,
+        expected: type_name::<R>(),
+    })
+}
+
+fn decode_nullable<R: FromSql>(
+    row: &Row,
+    row_index: usize,
+    column: &'static str,
+) -> Result<Option<R>> {
+    row.get(column).map_err(|err| Error::Decode {
+        column,
+        row: row_index,
+        expected: type_name::<R>(),
+        reason: err.to_string(),
+    })
+}
+
+fn decode_manga(row: &Row, row_index: usize) -> Result<Manga> {
+    let status: String = decode(row, row_index, "Status")?;
+    Ok(Manga {
+        manga_id: decode(row, row_index, "MangaID")?,
+        manga_name: decode(row, row_index, "MangaName")?,
+        author_names: vec![],
+        artist_names: vec![],
+        cover_image_url: decode_nullable(row, row_index, "CoverImageURL")?,
+        purchase_url: decode_nullable(row, row_index, "PurchaseURL")?,
+        status: decode_parsed(&status, "Status", row_index)?,
+        creation_date: decode_nullable(row, row_index, "DateCreated")?,
+        updated_date: decode_nullable(row, row_index, "UpdatedDate")?,
+    })
+}
+
+fn decode_search_hit(row: &Row, row_index: usize) -> Result<SearchHit> {
+    let field: String = decode(row, row_index, "Field")?;
+    Ok(SearchHit {
+        manga_id: decode(row, row_index, "MangaID")?,
+        field: decode_parsed(&field, "Field", row_index)?,
+        text: decode(row, row_index, "Text")?,
+        chapter_number: decode_nullable::<String>(row, row_index, "ChapterNumber")?
+            .map(ChapterNumber::from),
+    })
+}
+
+fn decode_chapter(row: &Row, row_index: usize) -> Result<Chapter> {
+    Ok(Chapter {
+        manga_id: decode(row, row_index, "MangaID")?,
+        chapter_number: decode::<String>(row, row_index, "ChapterNumber")?.into(),
+        chapter_name: decode(row, row_index, "ChapterName")?,
+        creation_date: decode(row, row_index, "DateCreated")?,
+        release_date: decode_nullable(row, row_index, "DateReleased")?,
+        updated_date: decode_nullable(row, row_index, "UpdatedDate")?,
+    })
+}
+
+fn decode_creator(row: &Row, row_index: usize) -> Result<Creator> {
+    let role: String = decode(row, row_index, "Role")?;
+    Ok(Creator {
+        creator_name: decode(row, row_index, "AuthorName")?,
+        role: decode_parsed(&role, "Role", row_index)?,
+    })
+}
+
+/// Credits `creators` in order, adding any authors that don't exist yet.
+fn insert_creators(connection: &Connection, manga_id: i32, creators: &[Creator]) -> Result<()> {
+    for (credit_order, creator) in creators.iter().enumerate() {
+        let author_id = connection
+            .query_row(
+                SELECT_AUTHOR_ID_QUERY,
+                params![creator.creator_name],
+                |row| row.get::<_, i32>(0),
+            )
+            .optional()?;
+        let author_id = match author_id {
+            Some(author_id) => author_id,
+            None => {
+                connection.query_row(INSERT_AUTHOR_QUERY, params![creator.creator_name], |row| {
+                    row.get(0)
+                })?
+            }
+        };
+        connection.execute(
+            INSERT_MANGA_CREATOR_QUERY,
+            params![manga_id, author_id, creator.role.as_str(), credit_order],
+        )?;
+    }
+    Ok(())
+}
+
+fn insert_alternate_titles(
+    connection: &Connection,
+    manga_id: i32,
+    titles: &[String],
+) -> Result<()> {
+    for title in titles {
+        connection.execute(INSERT_ALTERNATE_TITLE_QUERY, params![manga_id, title])?;
+    }
+    Ok(())
+}
+
+/// Fills in creators for manga fetched without them.
+fn assign_listed_creators(connection: &Connection, mangas: &mut [Manga]) -> Result<()> {
+    let manga_ids = mangas
+        .iter()
+        .map(|manga| manga.manga_id)
+        .collect::<Vec<i32>>();
+    let mut statement = connection.prepare(SELECT_LISTED_MANGA_CREATORS_QUERY)?;
+    let mut rows = statement.query(params![serde_json::to_string(&manga_ids)?])?;
+    let mut creators = vec![];
+    while let Some(row) = rows.next()? {
+        let index = creators.len();
+        creators.push((decode(row, index, "MangaID")?, decode_creator(row, index)?));
+    }
+    assign_creators(mangas, creators);
+    Ok(())
+}
+
+fn find_chapter_index(
+    connection: &Connection,
+    manga_id: i32,
+    chapter_number: &str,
+) -> Result<Option<i32>> {
+    Ok(connection
+        .query_row(
+            SELECT_CHAPTER_INDEX_QUERY,
+            params![manga_id, chapter_number],
+            |row| row.get(0),
+        )
+        .optional()?)
+}
+
+/// Adds `pages`, each URL's priority follows its position in `urls`.
+fn insert_pages(
+    connection: &Connection,
+    manga_id: i32,
+    chapter_index: i32,
+    pages: &[NewPage],
+) -> Result<()> {
+    for page in pages {
+        let page_id: i32 = connection.query_row(
+            INSERT_PAGE_QUERY,
+            params![manga_id, chapter_index, page.page_number],
+            |row| row.get(0),
+        )?;
+        for (priority, url) in (1..).zip(&page.urls) {
+            connection.execute(INSERT_PAGE_URL_QUERY, params![page_id, url, priority])?;
+        }
+    }
+    Ok(())
+}
+
+fn delete_pages(connection: &Connection, manga_id: i32, chapter_index: i32) -> Result<()> {
+    connection.execute(
+        DELETE_CHAPTER_PAGE_URLS_QUERY,
+        params![manga_id, chapter_index],
+    )?;
+    connection.execute(DELETE_CHAPTER_PAGES_QUERY, params![manga_id, chapter_index])?;
+    Ok(())
+}
+
+#[async_trait]
+impl MangaService<i32> for SqliteWaifusims {
+    async fn get_all_manga_titles(&mut self) -> Result<Vec<Manga>> {
+        self.with_connection(|connection| {
+            let mut statement = connection.prepare(SELECT_ALL_MANGA_QUERY)?;
+            let mut rows = statement.query([])?;
+            let mut mangas = vec![];
+            while let Some(row) = rows.next()? {
+                mangas.push(decode_manga(row, mangas.len())?);
+            }
+            let mut statement = connection.prepare(SELECT_ALL_MANGA_CREATORS_QUERY)?;
+            let mut rows = statement.query([])?;
+            let mut creators = vec![];
+            while let Some(row) = rows.next()? {
+                let index = creators.len();
+                creators.push((decode(row, index, "MangaID")?, decode_creator(row, index)?));
+            }
+            assign_creators(&mut mangas, creators);
+            Ok(mangas)
+        })
+        .await
+    }
+
+    async fn list_manga(&mut self, query: &MangaListQuery) -> Result<MangaListing> {
+        let query = query.clone();
+        self.with_connection(move |connection| {
+            let status = query.status.map(|status| status.as_str());
+            let total: i64 = connection.query_row(
+                &count_manga_list_query(),
+                params![status, query.author],
+                |row| row.get("Total"),
+            )?;
+            let mut statement = connection.prepare(&select_manga_list_query(query.sort))?;
+            let mut rows = statement.query(params![
+                status,
+                query.author,
+                query.offset,
+                list_limit(&query)
+            ])?;
+            let mut mangas = vec![];
+            while let Some(row) = rows.next()? {
+                mangas.push(decode_manga(row, mangas.len())?);
+            }
+            assign_listed_creators(connection, &mut mangas)?;
+            Ok(MangaListing {
+                mangas,
+                total: total as u64,
+            })
+        })
+        .await
+    }
+
+    async fn search(&mut self, query: &str, limit: u32) -> Result<Vec<SearchResult>> {
+        let pattern = match like_pattern(&search_terms(query)) {
+            Some(pattern) => pattern,
+            None => return Ok(vec![]),
+        };
+        let query = query.to_owned();
+        self.with_connection(move |connection| {
+            let mut statement = connection.prepare(SEARCH_QUERY)?;
+            let mut rows = statement.query(params![pattern, MAX_CANDIDATES])?;
+            let mut hits = vec![];
+            while let Some(row) = rows.next()? {
+                hits.push(decode_search_hit(row, hits.len())?);
+            }
+            let ranked = rank(&query, hits, limit as usize);
+            let manga_ids = ranked
+                .iter()
+                .map(|(hit, _)| hit.manga_id)
+                .collect::<Vec<i32>>();
+            let mut statement = connection.prepare(SELECT_MANGA_BY_IDS_QUERY)?;
+            let mut rows = statement.query(params![serde_json::to_string(&manga_ids)?])?;
+            let mut mangas = vec![];
+            while let Some(row) = rows.next()? {
+                mangas.push(decode_manga(row, mangas.len())?);
+            }
+            assign_listed_creators(connection, &mut mangas)?;
+            Ok(search_results(ranked, mangas))
+        })
+        .await
+    }
+
+    async fn get_manga(&mut self, manga_id: i32) -> Result<Option<Manga>> {
+        self.with_connection(move |connection| {
+            let mut mangas = connection
+                .query_row(SELECT_MANGA_QUERY, params![manga_id], |row| {
+                    Ok(decode_manga(row, 0))
+                })
+                .optional()?
+                .transpose()?
+                .into_iter()
+                .collect::<Vec<Manga>>();
+            assign_listed_creators(connection, &mut mangas)?;
+            Ok(mangas.pop())
+        })
+        .await
+    }
+
+    async fn get_manga_creators(&mut self, manga_id: i32) -> Result<Vec<Creator>> {
+        self.with_connection(move |connection| {
+            let mut statement = connection.prepare(SELECT_MANGA_CREATORS_QUERY)?;
+            let mut rows = statement.query(params![manga_id])?;
+            let mut creators = vec![];
+            while let Some(row) = rows.next()? {
+                creators.push(decode_creator(row, creators.len())?);
+            }
+            Ok(creators)
+        })
+        .await
+    }
+
+    async fn get_manga_chapters(&mut self, manga_id: i32) -> Result<Vec<Chapter>> {
+        let mut chapters = self
+            .with_connection(move |connection| {
+                let mut statement = connection.prepare(SELECT_MANGA_CHAPTERS_QUERY)?;
+                let mut rows = statement.query(params![manga_id])?;
+                let mut chapters = vec![];
+                while let Some(row) = rows.next()? {
+                    chapters.push(decode_chapter(row, chapters.len())?);
+                }
+                Ok(chapters)
+            })
+            .await?;
+        sort_chapters(&mut chapters);
+        Ok(chapters)
+    }
+
+    async fn get_mangas_chapters(&mut self, manga_ids: &[i32]) -> Result<Vec<Vec<Chapter>>> {
+        let manga_ids = manga_ids.to_vec();
+        self.with_connection(move |connection| {
+            let mut statement = connection.prepare(SELECT_MANGAS_CHAPTERS_QUERY)?;
+            let mut rows = statement.query(params![serde_json::to_string(&manga_ids)?])?;
+            let mut chapters = vec![];
+            while let Some(row) = rows.next()? {
+                chapters.push(decode_chapter(row, chapters.len())?);
+            }
+            Ok(group_manga_chapters(&manga_ids, chapters))
+        })
+        .await
+    }
+
+    async fn get_chapter(
+        &mut self,
+        manga_id: i32,
+        chapter_number: &str,
+    ) -> Result<Option<Chapter>> {
+        let chapter_number = chapter_number.to_owned();
+        self.with_connection(move |connection| {
+            connection
+                .query_row(
+                    SELECT_CHAPTER_QUERY,
+                    params![manga_id, chapter_number],
+                    |row| Ok(decode_chapter(row, 0)),
+                )
+                .optional()?
+                .transpose()
+        })
+        .await
+    }
+
+    async fn get_recent_chapters(
+        &mut self,
+        limit: u32,
+        since: Option<NaiveDateTime>,
+    ) -> Result<Vec<RecentChapter>> {
+        self.with_connection(move |connection| {
+            let mut statement = connection.prepare(SELECT_RECENT_CHAPTERS_QUERY)?;
+            let mut rows = statement.query(params![since, limit])?;
+            let mut chapters = vec![];
+            while let Some(row) = rows.next()? {
+                chapters.push(decode_chapter(row, chapters.len())?);
+            }
+            let manga_ids = chapter_manga_ids(&chapters);
+            let mut statement = connection.prepare(SELECT_MANGA_BY_IDS_QUERY)?;
+            let mut rows = statement.query(params![serde_json::to_string(&manga_ids)?])?;
+            let mut mangas = vec![];
+            while let Some(row) = rows.next()? {
+                mangas.push(decode_manga(row, mangas.len())?);
+            }
+            assign_listed_creators(connection, &mut mangas)?;
+            Ok(recent_chapters(chapters, mangas))
+        })
+        .await
+    }
+
+    async fn get_pages(&mut self, manga_id: i32, chapter_number: &str) -> Result<Vec<Page>> {
+        let chapter_number = chapter_number.to_owned();
+        self.with_connection(move |connection| {
+            let mut statement = connection.prepare(SELECT_CHAPTER_PAGES_QUERY)?;
+            let mut rows = statement.query(params![manga_id, chapter_number])?;
+            let mut urls = vec![];
+            while let Some(row) = rows.next()? {
+                let index = urls.len();
+                urls.push((
+                    decode(row, index, "PageID")?,
+                    decode(row, index, "PageNumber")?,
+                    decode(row, index, "URL")?,
+                ));
+            }
+            Ok(group_page_urls(urls))
+        })
+        .await
+    }
+
+    async fn get_chapters_pages(&mut self, chapters: &[(i32, String)]) -> Result<Vec<Vec<Page>>> {
+        let chapters = chapters.to_vec();
+        self.with_connection(move |connection| {
+            let mut statement = connection.prepare(SELECT_CHAPTERS_PAGES_QUERY)?;
+            let mut rows = statement.query(params![serde_json::to_string(&chapters)?])?;
+            let mut urls = vec![];
+            while let Some(row) = rows.next()? {
+                let index = urls.len();
+                urls.push((
+                    decode(row, index, "MangaID")?,
+                    decode(row, index, "ChapterNumber")?,
+                    decode(row, index, "PageID")?,
+                    decode(row, index, "PageNumber")?,
+                    decode(row, index, "URL")?,
+                ));
+            }
+            Ok(group_chapter_page_urls(&chapters, urls))
+        })
+        .await
+    }
+
+    async fn create_manga(&mut self, manga: NewManga) -> Result<Manga> {
+        validate_manga(&manga)?;
+        self.with_transaction(move |transaction| {
+            let creation_date = creation_date();
+            let manga_id: i32 = transaction.query_row(
+                INSERT_MANGA_QUERY,
+                params![
+                    manga.manga_name,
+                    manga.cover_image_url,
+                    manga.purchase_url,
+                    manga.status.as_str(),
+                    creation_date
+                ],
+                |row| row.get(0),
+            )?;
+            insert_creators(transaction, manga_id, &manga.creators)?;
+            insert_alternate_titles(transaction, manga_id, &manga.alternate_titles)?;
+            Ok(stored_manga(
+                manga_id,
+                manga,
+                Some(creation_date),
+                creation_date,
+            ))
+        })
+        .await
+    }
+
+    async fn update_manga(&mut self, manga_id: i32, manga: NewManga) -> Result<Option<Manga>> {
+        validate_manga(&manga)?;
+        self.with_transaction(move |transaction| {
+            let updated_date = creation_date();
+            let creation_date = transaction
+                .query_row(
+                    UPDATE_MANGA_QUERY,
+                    params![
+                        manga_id,
+                        manga.manga_name,
+                        manga.cover_image_url,
+                        manga.purchase_url,
+                        manga.status.as_str(),
+                        updated_date
+                    ],
+                    |row| row.get(0),
+                )
+                .optional()?;
+            let creation_date = match creation_date {
+                Some(creation_date) => creation_date,
+                None => return Ok(None),
+            };
+            transaction.execute(DELETE_MANGA_CREATORS_QUERY, params![manga_id])?;
+            transaction.execute(DELETE_MANGA_ALTERNATE_TITLES_QUERY, params![manga_id])?;
+            insert_creators(transaction, manga_id, &manga.creators)?;
+            insert_alternate_titles(transaction, manga_id, &manga.alternate_titles)?;
+            Ok(Some(stored_manga(
+                manga_id,
+                manga,
+                creation_date,
+                updated_date,
+            )))
+        })
+        .await
+    }
+
+    async fn delete_manga(&mut self, manga_id: i32) -> Result<bool> {
+        self.with_transaction(move |transaction| {
+            for query in [
+                DELETE_MANGA_PAGE_URLS_QUERY,
+                DELETE_MANGA_PAGES_QUERY,
+                DELETE_MANGA_CHAPTERS_QUERY,
+                DELETE_MANGA_CREATORS_QUERY,
+                DELETE_MANGA_ALTERNATE_TITLES_QUERY,
+            ] {
+                transaction.execute(query, params![manga_id])?;
+            }
+            Ok(transaction.execute(DELETE_MANGA_QUERY, params![manga_id])? > 0)
+        })
+        .await
+    }
+
+    async fn create_chapter(
+        &mut self,
+        manga_id: i32,
+        chapter: NewChapter,
+    ) -> Result<Option<Chapter>> {
+        validate_chapter(&chapter)?;
+        self.with_transaction(move |transaction| {
+            let manga_exists = transaction
+                .query_row(SELECT_MANGA_ID_QUERY, params![manga_id], |_| Ok(()))
+                .optional()?
+                .is_some();
+            if !manga_exists {
+                return Ok(None);
+            }
+            let chapter_number = chapter.chapter_number.as_str();
+            if find_chapter_index(transaction, manga_id, chapter_number)?.is_some() {
+                return Err(chapter_exists(&chapter.chapter_number));
+            }
+            let creation_date = creation_date();
+            let chapter_index: i32 = transaction.query_row(
+                INSERT_CHAPTER_QUERY,
+                params![
+                    manga_id,
+                    chapter_number,
+                    chapter.chapter_name,
+                    creation_date,
+                    chapter.release_date
+                ],
+                |row| row.get(0),
+            )?;
+            insert_pages(transaction, manga_id, chapter_index, &chapter.pages)?;
+            transaction.execute(
+                UPDATE_MANGA_UPDATED_DATE_QUERY,
+                params![manga_id, creation_date],
+            )?;
+            Ok(Some(stored_chapter(
+                manga_id,
+                chapter,
+                creation_date,
+                creation_date,
+            )))
+        })
+        .await
+    }
+
+    async fn update_chapter(
+        &mut self,
+        manga_id: i32,
+        chapter_number: &str,
+        chapter: NewChapter,
+    ) -> Result<Option<Chapter>> {
+        validate_chapter(&chapter)?;
+        let chapter_number = chapter_number.to_owned();
+        self.with_transaction(move |transaction| {
+            let chapter_index = match find_chapter_index(transaction, manga_id, &chapter_number)? {
+                Some(chapter_index) => chapter_index,
+                None => return Ok(None),
+            };
+            let new_chapter_number = chapter.chapter_number.as_str();
+            match find_chapter_index(transaction, manga_id, new_chapter_number)? {
+                Some(other_index) if other_index != chapter_index => {
+                    return Err(chapter_exists(&chapter.chapter_number))
+                }
+                _ => {}
+            }
+            let updated_date = creation_date();
+            let creation_date = transaction.query_row(
+                UPDATE_CHAPTER_QUERY,
+                params![
+                    manga_id,
+                    chapter_index,
+                    new_chapter_number,
+                    chapter.chapter_name,
+                    chapter.release_date,
+                    updated_date
+                ],
+                |row| row.get(0),
+            )?;
+            delete_pages(transaction, manga_id, chapter_index)?;
+            insert_pages(transaction, manga_id, chapter_index, &chapter.pages)?;
+            transaction.execute(
+                UPDATE_MANGA_UPDATED_DATE_QUERY,
+                params![manga_id, updated_date],
+            )?;
+            Ok(Some(stored_chapter(
+                manga_id,
+                chapter,
+                creation_date,
+                updated_date,
+            )))
+        })
+        .await
+    }
+
+    async fn delete_chapter(&mut self, manga_id: i32, chapter_number: &str) -> Result<bool> {
+        let chapter_number = chapter_number.to_owned();
+        self.with_transaction(move |transaction| {
+            let chapter_index = match find_chapter_index(transaction, manga_id, &chapter_number)? {
+                Some(chapter_index) => chapter_index,
+                None => return Ok(false),
+            };
+            delete_pages(transaction, manga_id, chapter_index)?;
+            transaction.execute(DELETE_CHAPTER_QUERY, params![manga_id, chapter_index])?;
+            transaction.execute(
+                UPDATE_MANGA_UPDATED_DATE_QUERY,
+                params![manga_id, creation_date()],
+            )?;
+            Ok(true)
+        })
+        .await
+    }
+}
+
+#[async_trait]
+impl Migrate for SqliteWaifusims {
+    fn dialect(&self) -> Dialect {
+        Dialect::Sqlite
+    }
+
+    async fn applied_migrations(&mut self) -> Result<Vec<AppliedMigration>> {
+        self.with_connection(|connection| {
+            connection.execute_batch(CREATE_SCHEMA_MIGRATION_QUERY)?;
+            let mut statement = connection.prepare(SELECT_SCHEMA_MIGRATIONS_QUERY)?;
+            let mut rows = statement.query([])?;
+            let mut applied = vec![];
+            while let Some(row) = rows.next()? {
+                let row_index = applied.len();
+                applied.push(AppliedMigration {
+                    version: decode(row, row_index, "Version")?,
+                    name: decode(row, row_index, "Name")?,
+                    applied_at: decode(row, row_index, "AppliedAt")?,
+                });
+            }
+            Ok(applied)
+        })
+        .await
+    }
+
+    async fn has_waifusims_tables(&mut self) -> Result<bool> {
+        self.with_connection(|connection| {
+            Ok(connection.query_row(SELECT_HAS_WAIFUSIMS_TABLES_QUERY, [], |row| row.get(0))?)
+        })
+        .await
+    }
+
+    async fn run_migration(
+        &mut self,
+        migration: &'static Migration,
+        direction: Direction,
+    ) -> Result<()> {
+        self.with_connection(move |connection| {
+            // SQLite rebuilds tables to drop columns, which enforced foreign keys would refuse.
+            // The pragma has no effect inside a transaction, so it's toggled around it.
+            let foreign_keys: bool =
+                connection.query_row("PRAGMA foreign_keys", [], |row| row.get(0))?;
+            connection.execute_batch("PRAGMA foreign_keys = OFF")?;
+            let result = apply_migration(connection, migration, direction);
+            if foreign_keys {
+                connection.execute_batch("PRAGMA foreign_keys = ON")?;
+            }
+            result
+        })
+        .await
+    }
+
+    async fn record_migrations(&mut self, migrations: &'static [Migration]) -> Result<()> {
+        self.with_transaction(move |connection| {
+            for migration in migrations {
+                record_migration(connection, migration)?;
+            }
+            Ok(())
+        })
+        .await
+    }
+}
+
+fn record_migration(connection: &Connection, migration: &Migration) -> Result<()> {
+    connection.execute(
+        INSERT_SCHEMA_MIGRATION_QUERY,
+        params![migration.version, migration.name, creation_date()],
+    )?;
+    Ok(())
+}
+
+/// Runs and records one script of `migration`, rolled back if it breaks a foreign key.
+fn apply_migration(
+    connection: &Connection,
+    migration: &Migration,
+    direction: Direction,
+) -> Result<()> {
+    let transaction = connection.unchecked_transaction()?;
+    transaction.execute_batch(migration.script(direction))?;
+    match direction {
+        Direction::Up => record_migration(&transaction, migration)?,
+        Direction::Down => {
+            transaction.execute(DELETE_SCHEMA_MIGRATION_QUERY, params![migration.version])?;
+        }
+    }
+    let violation: Option<String> = transaction
+        .query_row("PRAGMA foreign_key_check", [], |row| row.get(0))
+        .optional()?;
+    if let Some(table) = violation {
+        return Err(Error::Migration(format!(
+            "migration {} left rows in {} without the rows they reference",
+            migration.name, table
+        )));
+    }
+    transaction.commit()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CreatorRole, InMemoryMangaService, MangaStatus, SearchField};
+
+    fn new_chapter(chapter_number: &str, page_count: i32) -> NewChapter {
+        NewChapter {
+            chapter_number: chapter_number.into(),
+            chapter_name: format!("Chapter {}", chapter_number),
+            release_date: None,
+            pages: (1..=page_count)
+                .map(|page_number| NewPage {
+                    page_number,
+                    urls: vec![
+                        format!("{}-{}.png", chapter_number, page_number),
+                        format!("{}-{}-mirror.png", chapter_number, page_number),
+                    ],
+                })
+                .collect(),
+        }
+    }
+
+    fn page_urls(waifusims: &mut SqliteWaifusims, chapter_number: &str) -> Vec<String> {
+        tokio_test::block_on(waifusims.get_pages(1, chapter_number))
+            .unwrap()
+            .into_iter()
+            .map(|page| page.url_string)
+            .collect()
+    }
+
+    const SEED_QUERY: &str = "
+INSERT INTO Author (AuthorID, AuthorName) VALUES (1, 'Author'), (2, 'Artist'), (3, 'Writer');
+INSERT INTO Manga (MangaID, MangaName, CoverImageURL, PurchaseURL)
+    VALUES (1, 'Manga', 'cover.png', NULL);
+INSERT INTO MangaCreator VALUES (1, 1, 'Author', 0);
+INSERT INTO MangaCreator VALUES (1, 2, 'Artist', 1);
+INSERT INTO MangaCreator VALUES (1, 3, 'Author', 2);
+INSERT INTO MangaCreator VALUES (1, 1, 'Artist', 3);
+INSERT INTO MangaChapter VALUES (1, 1, '10', 'Ten', '2021-01-10 00:00:00', '2021-01-10 00:00:00', '2021-01-10 00:00:00');
+INSERT INTO MangaChapter VALUES (1, 2, '2', 'Two', '2021-01-02 00:00:00', '2021-01-02 00:00:00', '2021-01-02 00:00:00');
+INSERT INTO MangaChapter VALUES (1, 3, '2.5', 'Two and a half', '2021-01-03 00:00:00', NULL, '2021-01-03 00:00:00');
+INSERT INTO Page (PageID, MangaID, ChapterIndex, PageNumber) VALUES (1, 1, 2, 2);
+INSERT INTO Page (PageID, MangaID, ChapterIndex, PageNumber) VALUES (2, 1, 2, 1);
+INSERT INTO PageURL VALUES (1, 'two-2.png', 1);
+INSERT INTO PageURL VALUES (1, 'two-2-mirror.png', 2);
+INSERT INTO PageURL VALUES (2, 'two-1.png', 1);
+";
+
+    fn seeded_waifusims() -> SqliteWaifusims {
+        tokio_test::block_on(async {
+            let mut waifusims = SqliteWaifusims::open_in_memory().unwrap();
+            waifusims.migrate_up(None).await.unwrap();
+            waifusims
+                .with_connection(|connection| Ok(connection.execute_batch(SEED_QUERY)?))
+                .await
+                .unwrap();
+            waifusims
+        })
+    }
+
+    #[test]
+    fn gets_all_manga_titles() {
+        let mut waifusims = seeded_waifusims();
+        let mangas = tokio_test::block_on(waifusims.get_all_manga_titles()).unwrap();
+        assert_eq!(mangas.len(), 1);
+        assert_eq!(mangas[0].manga_name, "Manga");
+        assert_eq!(mangas[0].author_names, vec!["Author", "Writer"]);
+        assert_eq!(mangas[0].artist_names, vec!["Artist", "Author"]);
+        assert_eq!(mangas[0].cover_image_url.as_deref(), Some("cover.png"));
+        assert_eq!(mangas[0].purchase_url, None);
+    }
+
+    fn listed_ids(waifusims: &mut SqliteWaifusims, query: MangaListQuery) -> (Vec<i32>, u64) {
+        let listing = tokio_test::block_on(waifusims.list_manga(&query)).unwrap();
+        let manga_ids = listing.mangas.iter().map(|manga| manga.manga_id).collect();
+        (manga_ids, listing.total)
+    }
+
+    #[test]
+    fn lists_manga_sorted_filtered_and_paged() {
+        let mut waifusims = seeded_waifusims();
+        tokio_test::block_on(waifusims.with_connection(|connection| {
+            Ok(connection.execute_batch(
+                "
+INSERT INTO Manga (MangaID, MangaName, Status, DateCreated)
+    VALUES (2, 'another', 'Completed', '2021-02-01 00:00:00');
+INSERT INTO Manga (MangaID, MangaName, Status, DateCreated)
+    VALUES (3, 'Zeta', 'Ongoing', '2021-03-01 00:00:00');
+INSERT INTO MangaCreator VALUES (2, 3, 'Author', 0);
+INSERT INTO MangaChapter VALUES (2, 1, '1', 'One', '2021-02-05 00:00:00', NULL, '2021-02-05 00:00:00');
+",
+            )?)
+        }))
+        .unwrap();
+        let sorted = |sort| MangaListQuery {
+            sort,
+            ..MangaListQuery::default()
+        };
+        assert_eq!(
+            listed_ids(&mut waifusims, sorted(MangaSort::Name)),
+            (vec![2, 1, 3], 3)
+        );
+        assert_eq!(
+            listed_ids(&mut waifusims, sorted(MangaSort::NewestChapter)),
+            (vec![2, 1, 3], 3)
+        );
+        assert_eq!(
+            listed_ids(&mut waifusims, sorted(MangaSort::CreationDate)),
+            (vec![3, 2, 1], 3)
+        );
+        let page = MangaListQuery {
+            offset: 1,
+            limit: Some(1),
+            ..MangaListQuery::default()
+        };
+        assert_eq!(listed_ids(&mut waifusims, page), (vec![1], 3));
+        let completed = MangaListQuery {
+            status: Some(MangaStatus::Completed),
+            ..MangaListQuery::default()
+        };
+        assert_eq!(listed_ids(&mut waifusims, completed), (vec![2], 1));
+        let by_writer = MangaListQuery {
+            author: Some("writer".to_owned()),
+            ..MangaListQuery::default()
+        };
+        assert_eq!(listed_ids(&mut waifusims, by_writer), (vec![2, 1], 2));
+        // Credited only as an artist
+        let by_artist = MangaListQuery {
+            author: Some("Artist".to_owned()),
+            offset: 5,
+            ..MangaListQuery::default()
+        };
+        assert_eq!(listed_ids(&mut waifusims, by_artist), (vec![], 0));
+
+        let listing =
+            tokio_test::block_on(waifusims.list_manga(&MangaListQuery::default())).unwrap();
+        assert_eq!(listing.mangas[1].author_names, vec!["Author", "Writer"]);
+        assert_eq!(listing.mangas[0].author_names, vec!["Writer"]);
+        assert_eq!(listing.mangas[0].status, MangaStatus::Completed);
+        assert!(listing.mangas[0].creation_date.is_some());
+    }
+
+    #[test]
+    fn reports_undecodable_columns() {
+        let mut waifusims = seeded_waifusims();
+        tokio_test::block_on(waifusims.with_connection(|connection| {
+            Ok(connection
+                .execute_batch("INSERT INTO Manga (MangaID, MangaName) VALUES (2, X'00');")?)
+        }))
+        .unwrap();
+        match tokio_test::block_on(waifusims.get_all_manga_titles()) {
+            Err(Error::Decode { column, row, .. }) => {
+                assert_eq!(column, "MangaName");
+                assert_eq!(row, 1);
+            }
+            result => panic!("expected a decode error, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn gets_manga_creators_in_credit_order() {
+        let mut waifusims = seeded_waifusims();
+        let creators = tokio_test::block_on(waifusims.get_manga_creators(1)).unwrap();
+        let credits = creators
+            .iter()
+            .map(|creator| (creator.creator_name.as_str(), creator.role))
+            .collect::<Vec<(&str, CreatorRole)>>();
+        assert_eq!(
+            credits,
+            vec![
+                ("Author", CreatorRole::Author),
+                ("Artist", CreatorRole::Artist),
+                ("Writer", CreatorRole::Author),
+                ("Author", CreatorRole::Artist),
+            ]
+        );
+        assert!(tokio_test::block_on(waifusims.get_manga_creators(2))
+            .unwrap()
+            .is_empty());
+    }
+
+    fn searched(waifusims: &mut SqliteWaifusims, query: &str) -> Vec<(i32, SearchField)> {
+        tokio_test::block_on(waifusims.search(query, 10))
+            .unwrap()
+            .into_iter()
+            .map(|result| (result.manga.manga_id, result.field))
+            .collect()
+    }
+
+    #[test]
+    fn searches_titles_creators_and_chapters() {
+        let mut waifusims = seeded_waifusims();
+        tokio_test::block_on(waifusims.with_connection(|connection| {
+            Ok(connection.execute_batch(
+                "
+INSERT INTO Manga (MangaID, MangaName) VALUES (2, 'Half Moon');
+INSERT INTO MangaAlternateTitle VALUES (2, 'Hangetsu 100%');
+INSERT INTO MangaAlternateTitle VALUES (2, 'ÉCLIPSE');
+INSERT INTO MangaCreator VALUES (2, 3, 'Author', 0);
+",
+            )?)
+        }))
+        .unwrap();
+        assert_eq!(
+            searched(&mut waifusims, "HALF"),
+            vec![(2, SearchField::Title), (1, SearchField::Chapter)]
+        );
+        assert_eq!(
+            searched(&mut waifusims, "writer"),
+            vec![(1, SearchField::Creator), (2, SearchField::Creator)]
+        );
+        assert_eq!(
+            searched(&mut waifusims, "100%"),
+            vec![(2, SearchField::AlternateTitle)]
+        );
+        // SQLite's LIKE only ignores ASCII case
+        assert_eq!(
+            searched(&mut waifusims, "éclipse"),
+            vec![(2, SearchField::AlternateTitle)]
+        );
+        // `_` is a literal, not a single character wildcard
+        assert!(searched(&mut waifusims, "T_n").is_empty());
+        assert!(searched(&mut waifusims, "   ").is_empty());
+        assert!(searched(&mut waifusims, "t").is_empty());
+
+        let results = tokio_test::block_on(waifusims.search("and a half", 10)).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].matched_text, "Two and a half");
+        assert_eq!(
+            results[0]
+                .chapter_number
+                .as_ref()
+                .map(ChapterNumber::as_str),
+            Some("2.5")
+        );
+        assert_eq!(results[0].manga.author_names, vec!["Author", "Writer"]);
+    }
+
+    #[test]
+    fn fetches_titles_before_capping_candidates() {
+        let mut waifusims = seeded_waifusims();
+        tokio_test::block_on(waifusims.with_connection(|connection| {
+            Ok(connection.execute_batch(
+                "
+INSERT INTO Manga (MangaID, MangaName) VALUES (2, 'Filler');
+WITH RECURSIVE n(i) AS (SELECT 4 UNION ALL SELECT i + 1 FROM n WHERE i < 604)
+INSERT INTO MangaChapter
+SELECT 1, i, 'f' || i, 'Filler ' || i, '2021-01-01 00:00:00', NULL, '2021-01-01 00:00:00' FROM n;
+",
+            )?)
+        }))
+        .unwrap();
+        assert_eq!(
+            searched(&mut waifusims, "filler"),
+            vec![(2, SearchField::Title), (1, SearchField::Chapter)]
+        );
+    }
+
+    #[test]
+    fn caps_candidates_like_the_in_memory_backend() {
+        async fn seed(waifusims: &mut impl MangaService<i32>) {
+            let new_manga = |manga_name: String| NewManga {
+                manga_name,
+                creators: vec![],
+                cover_image_url: None,
+                purchase_url: None,
+                status: MangaStatus::default(),
+                alternate_titles: vec![],
+            };
+            for i in 1..=600 {
+                let manga_name = format!("Filler {}", i);
+                waifusims.create_manga(new_manga(manga_name)).await.unwrap();
+            }
+            waifusims
+                .create_manga(new_manga("Filler".to_owned()))
+                .await
+                .unwrap();
+            let other = waifusims
+                .create_manga(new_manga("Other".to_owned()))
+                .await
+                .unwrap();
+            let chapter = NewChapter {
+                chapter_name: "Filler".to_owned(),
+                ..new_chapter("1", 0)
+            };
+            waifusims
+                .create_chapter(other.manga_id, chapter)
+                .await
+                .unwrap();
+        }
+        async fn searched(waifusims: &mut impl MangaService<i32>) -> Vec<(i32, String)> {
+            waifusims
+                .search("filler", 1000)
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|result| (result.manga.manga_id, result.matched_text))
+                .collect()
+        }
+
+        tokio_test::block_on(async {
+            let mut waifusims = SqliteWaifusims::open_in_memory().unwrap();
+            waifusims.migrate_up(None).await.unwrap();
+            seed(&mut waifusims).await;
+            let mut in_memory = InMemoryMangaService::default();
+            seed(&mut in_memory).await;
+
+            let results = searched(&mut waifusims).await;
+            assert_eq!(results.len(), MAX_CANDIDATES as usize);
+            // The whole match is the shortest, it makes the cut despite being created last
+            assert_eq!(results[0], (601, "Filler".to_owned()));
+            assert_eq!(results, searched(&mut in_memory).await);
+        });
+    }
+
+    #[test]
+    fn gets_single_manga_and_chapters() {
+        let mut waifusims = seeded_waifusims();
+        let manga = tokio_test::block_on(waifusims.get_manga(1))
+            .unwrap()
+            .unwrap();
+        assert_eq!(manga.manga_name, "Manga");
+        assert_eq!(manga.author_names, vec!["Author", "Writer"]);
+        assert_eq!(tokio_test::block_on(waifusims.get_manga(2)).unwrap(), None);
+
+        let chapter = tokio_test::block_on(waifusims.get_chapter(1, "2.5"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(chapter.chapter_name, "Two and a half");
+        assert_eq!(chapter.release_date, None);
+        assert_eq!(
+            tokio_test::block_on(waifusims.get_chapter(1, "3")).unwrap(),
+            None
+        );
+        assert_eq!(
+            tokio_test::block_on(waifusims.get_chapter(2, "2")).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn gets_chapters_and_pages_in_batches() {
+        let mut waifusims = seeded_waifusims();
+        let chapters = tokio_test::block_on(waifusims.get_mangas_chapters(&[3, 1])).unwrap();
+        assert!(chapters[0].is_empty());
+        let chapter_numbers = chapters[1]
+            .iter()
+            .map(|chapter| chapter.chapter_number.to_string())
+            .collect::<Vec<String>>();
+        assert_eq!(chapter_numbers, vec!["2", "2.5", "10"]);
+
+        let keys = [
+            (1, "10".to_owned()),
+            (1, "2".to_owned()),
+            (2, "2".to_owned()),
+        ];
+        let pages = tokio_test::block_on(waifusims.get_chapters_pages(&keys)).unwrap();
+        assert_eq!(pages.len(), 3);
+        assert!(pages[0].is_empty());
+        assert_eq!(
+            pages[1],
+            tokio_test::block_on(waifusims.get_pages(1, "2")).unwrap()
+        );
+        assert_eq!(pages[1][1].mirror_urls, vec!["two-2-mirror.png"]);
+        assert!(pages[2].is_empty());
+    }
+
+    #[test]
+    fn lists_recent_chapters_by_release() {
+        let mut waifusims = seeded_waifusims();
+        tokio_test::block_on(waifusims.with_connection(|connection| {
+            Ok(connection.execute_batch(
+                "
+INSERT INTO Manga (MangaID, MangaName) VALUES (2, 'Other');
+INSERT INTO MangaChapter VALUES (2, 1, '1', 'One', '2021-02-01 00:00:00', '2021-01-05 00:00:00', '2021-02-01 00:00:00');
+",
+            )?)
+        }))
+        .unwrap();
+        let recent = |waifusims: &mut SqliteWaifusims, limit, since| {
+            tokio_test::block_on(waifusims.get_recent_chapters(limit, since))
+                .unwrap()
+                .into_iter()
+                .map(|recent| {
+                    (
+                        recent.manga.manga_id,
+                        recent.chapter.chapter_number.to_string(),
+                    )
+                })
+                .collect::<Vec<(i32, String)>>()
+        };
+        // 2.5 has no release date, so it counts as released when it was added
+        assert_eq!(
+            recent(&mut waifusims, 3, None),
+            vec![
+                (1, "10".to_owned()),
+                (2, "1".to_owned()),
+                (1, "2.5".to_owned())
+            ]
+        );
+        let since = NaiveDateTime::parse_from_str("2021-01-03 00:00:00", "%Y-%m-%d %H:%M:%S").ok();
+        assert_eq!(
+            recent(&mut waifusims, 10, since),
+            vec![(1, "10".to_owned()), (2, "1".to_owned())]
+        );
+        let chapters = tokio_test::block_on(waifusims.get_recent_chapters(1, None)).unwrap();
+        assert_eq!(chapters[0].manga.author_names, vec!["Author", "Writer"]);
+    }
+
+    #[test]
+    fn sorts_chapters_numerically() {
+        let mut waifusims = seeded_waifusims();
+        let chapters = tokio_test::block_on(waifusims.get_manga_chapters(1)).unwrap();
+        let chapter_numbers = chapters
+            .iter()
+            .map(|chapter| chapter.chapter_number.as_str())
+            .collect::<Vec<&str>>();
+        assert_eq!(chapter_numbers, vec!["2", "2.5", "10"]);
+        assert!(chapters[0].release_date.is_some());
+        assert_eq!(chapters[1].release_date, None);
+    }
+
+    #[test]
+    fn gets_pages_in_order_with_mirrors() {
+        let mut waifusims = seeded_waifusims();
+        let pages = tokio_test::block_on(waifusims.get_pages(1, "2")).unwrap();
+        let urls = pages
+            .iter()
+            .map(|page| page.url_string.as_str())
+            .collect::<Vec<&str>>();
+        assert_eq!(urls, vec!["two-1.png", "two-2.png"]);
+        assert!(pages[0].mirror_urls.is_empty());
+        assert_eq!(pages[1].mirror_urls, vec!["two-2-mirror.png"]);
+    }
+
+    #[test]
+    fn creates_manga_with_existing_and_new_creators() {
+        let mut waifusims = seeded_waifusims();
+        let manga = tokio_test::block_on(waifusims.create_manga(NewManga {
+            manga_name: "Collab".to_owned(),
+            creators: vec![
+                Creator {
+                    creator_name: "Artist".to_owned(),
+                    role: CreatorRole::Artist,
+                },
+                Creator {
+                    creator_name: "Newcomer".to_owned(),
+                    role: CreatorRole::Author,
+                },
+            ],
+            cover_image_url: None,
+            purchase_url: None,
+            status: MangaStatus::Hiatus,
+            alternate_titles: vec!["Kyousaku".to_owned()],
+        }))
+        .unwrap();
+        assert_eq!(manga.manga_id, 2);
+        let mangas = tokio_test::block_on(waifusims.get_all_manga_titles()).unwrap();
+        assert_eq!(mangas[1].author_names, vec!["Newcomer"]);
+        assert_eq!(mangas[1].artist_names, vec!["Artist"]);
+        assert_eq!(mangas[1].status, MangaStatus::Hiatus);
+        assert_eq!(mangas[1].creation_date, manga.creation_date);
+        assert_eq!(
+            searched(&mut waifusims, "kyousaku"),
+            vec![(2, SearchField::AlternateTitle)]
+        );
+        tokio_test::block_on(waifusims.delete_manga(2)).unwrap();
+        assert!(searched(&mut waifusims, "kyousaku").is_empty());
+    }
+
+    #[test]
+    fn creates_chapters_with_pages() {
+        let mut waifusims = seeded_waifusims();
+        let chapter = tokio_test::block_on(waifusims.create_chapter(1, new_chapter("11", 2)))
+            .unwrap()
+            .unwrap();
+        assert_eq!(chapter.chapter_number.as_str(), "11");
+        assert_eq!(
+            page_urls(&mut waifusims, "11"),
+            vec!["11-1.png", "11-2.png"]
+        );
+        let chapters = tokio_test::block_on(waifusims.get_manga_chapters(1)).unwrap();
+        assert_eq!(
+            chapters.last().unwrap().creation_date,
+            chapter.creation_date
+        );
+        assert_eq!(chapter.updated_date, Some(chapter.creation_date));
+        let manga = tokio_test::block_on(waifusims.get_manga(1))
+            .unwrap()
+            .unwrap();
+        assert_eq!(manga.updated_date, chapter.updated_date);
+
+        assert!(
+            tokio_test::block_on(waifusims.create_chapter(2, new_chapter("1", 1)))
+                .unwrap()
+                .is_none()
+        );
+        match tokio_test::block_on(waifusims.create_chapter(1, new_chapter("10", 1))) {
+            Err(Error::Conflict(_)) => {}
+            result => panic!("expected a conflict, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn replaces_chapters_and_their_pages() {
+        let mut waifusims = seeded_waifusims();
+        let chapter = tokio_test::block_on(waifusims.update_chapter(1, "2", new_chapter("3", 1)))
+            .unwrap()
+            .unwrap();
+        assert_eq!(chapter.chapter_name, "Chapter 3");
+        assert!(chapter.updated_date > Some(chapter.creation_date));
+        let manga = tokio_test::block_on(waifusims.get_manga(1))
+            .unwrap()
+            .unwrap();
+        assert_eq!(manga.updated_date, chapter.updated_date);
+        assert!(page_urls(&mut waifusims, "2").is_empty());
+        assert_eq!(page_urls(&mut waifusims, "3"), vec!["3-1.png"]);
+        match tokio_test::block_on(waifusims.update_chapter(1, "3", new_chapter("10", 1))) {
+            Err(Error::Conflict(_)) => {}
+            result => panic!("expected a conflict, got {:?}", result),
+        }
+        assert!(
+            tokio_test::block_on(waifusims.update_chapter(1, "2", new_chapter("2", 1)))
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn deletes_chapters_and_manga() {
+        let mut waifusims = seeded_waifusims();
+        assert!(tokio_test::block_on(waifusims.delete_chapter(1, "2")).unwrap());
+        assert!(!tokio_test::block_on(waifusims.delete_chapter(1, "2")).unwrap());
+        assert!(page_urls(&mut waifusims, "2").is_empty());
+        let manga = tokio_test::block_on(waifusims.get_manga(1))
+            .unwrap()
+            .unwrap();
+        assert!(manga.updated_date.is_some());
+
+        assert!(tokio_test::block_on(waifusims.delete_manga(1)).unwrap());
+        assert!(!tokio_test::block_on(waifusims.delete_manga(1)).unwrap());
+        assert!(tokio_test::block_on(waifusims.get_all_manga_titles())
+            .unwrap()
+            .is_empty());
+        assert!(tokio_test::block_on(waifusims.get_manga_chapters(1))
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn rolls_back_failed_chapters() {
+        let mut waifusims = seeded_waifusims();
+        tokio_test::block_on(
+            waifusims
+                .with_connection(|connection| Ok(connection.execute_batch("DROP TABLE PageURL;")?)),
+        )
+        .unwrap();
+        assert!(tokio_test::block_on(waifusims.create_chapter(1, new_chapter("11", 1))).is_err());
+        let chapters = tokio_test::block_on(waifusims.get_manga_chapters(1)).unwrap();
+        assert_eq!(chapters.len(), 3);
+    }
+
+    #[test]
+    fn rejects_invalid_chapters_before_writing() {
+        let mut waifusims = seeded_waifusims();
+        let mut chapter = new_chapter("11", 1);
+        chapter.pages.push(chapter.pages[0].clone());
+        match tokio_test::block_on(waifusims.create_chapter(1, chapter)) {
+            Err(Error::InvalidInput(_)) => {}
+            result => panic!("expected invalid input, got {:?}", result),
+        }
+    }
+
+    fn applied_versions(waifusims: &mut SqliteWaifusims) -> Vec<(i64, bool)> {
+        tokio_test::block_on(waifusims.migration_status())
+            .unwrap()
+            .into_iter()
+            .map(|status| (status.version, status.applied_at.is_some()))
+            .collect()
+    }
+
+    #[test]
+    fn migrates_up_and_down() {
+        let mut waifusims = SqliteWaifusims::open_in_memory().unwrap();
+        assert_eq!(
+            applied_versions(&mut waifusims),
+            vec![(1, false), (2, false), (3, false), (4, false), (5, false)]
+        );
+        let applied = tokio_test::block_on(waifusims.migrate_up(Some(2))).unwrap();
+        assert_eq!(
+            applied.iter().map(|m| m.version).collect::<Vec<i64>>(),
+            vec![1, 2]
+        );
+        let applied = tokio_test::block_on(waifusims.migrate_up(None)).unwrap();
+        assert_eq!(
+            applied.iter().map(|m| m.version).collect::<Vec<i64>>(),
+            vec![3, 4, 5]
+        );
+        assert!(tokio_test::block_on(waifusims.migrate_up(None))
+            .unwrap()
+            .is_empty());
+
+        let reverted = tokio_test::block_on(waifusims.migrate_down(1)).unwrap();
+        assert_eq!(
+            reverted.iter().map(|m| m.version).collect::<Vec<i64>>(),
+            vec![5, 4, 3, 2]
+        );
+        assert_eq!(
+            applied_versions(&mut waifusims),
+            vec![(1, true), (2, false), (3, false), (4, false), (5, false)]
+        );
+        tokio_test::block_on(waifusims.migrate_down(0)).unwrap();
+        assert!(!tokio_test::block_on(waifusims.has_waifusims_tables()).unwrap());
+        tokio_test::block_on(waifusims.migrate_up(None)).unwrap();
+        assert_eq!(
+            tokio_test::block_on(waifusims.get_all_manga_titles()).unwrap(),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn moves_single_authors_into_creators() {
+        let mut waifusims = SqliteWaifusims::open_in_memory().unwrap();
+        tokio_test::block_on(async {
+            waifusims.migrate_up(Some(1)).await.unwrap();
+            waifusims
+                .with_connection(|connection| {
+                    Ok(connection.execute_batch(
+                        "
+INSERT INTO Author (AuthorID, AuthorName) VALUES (1, 'Author');
+INSERT INTO Manga (MangaID, MangaName, AuthorID) VALUES (1, 'Manga', 1);
+INSERT INTO MangaChapter VALUES (1, 1, '1', 'One', '2021-01-01 00:00:00', NULL);
+",
+                    )?)
+                })
+                .await
+                .unwrap();
+            waifusims.migrate_up(None).await.unwrap();
+        });
+        let mangas = tokio_test::block_on(waifusims.get_all_manga_titles()).unwrap();
+        assert_eq!(mangas[0].author_names, vec!["Author".to_owned()]);
+        assert_eq!(mangas[0].status, MangaStatus::Ongoing);
+        assert_eq!(
+            tokio_test::block_on(waifusims.get_manga_chapters(1))
+                .unwrap()
+                .len(),
+            1
+        );
+
+        tokio_test::block_on(waifusims.migrate_down(1)).unwrap();
+        let author_id: i32 = tokio_test::block_on(waifusims.with_connection(|connection| {
+            Ok(connection.query_row("SELECT AuthorID FROM Manga", [], |row| row.get(0))?)
+        }))
+        .unwrap();
+        assert_eq!(author_id, 1);
+    }
+
+    #[test]
+    fn baselines_databases_that_predate_migrations() {
+        let mut waifusims = SqliteWaifusims::open_in_memory().unwrap();
+        tokio_test::block_on(async {
+            waifusims
+                .with_connection(|connection| {
+                    Ok(connection.execute_batch(Dialect::Sqlite.migrations()[0].up)?)
+                })
+                .await
+                .unwrap();
+            assert!(matches!(
+                waifusims.migrate_up(None).await,
+                Err(Error::Migration(_))
+            ));
+            assert!(matches!(
+                waifusims.baseline(7).await,
+                Err(Error::Migration(_))
+            ));
+            waifusims.baseline(1).await.unwrap();
+            assert!(matches!(
+                waifusims.baseline(1).await,
+                Err(Error::Migration(_))
+            ));
+            waifusims.migrate_up(None).await.unwrap();
+        });
+        assert_eq!(
+            applied_versions(&mut waifusims),
+            vec![(1, true), (2, true), (3, true), (4, true), (5, true)]
+        );
+    }
+}
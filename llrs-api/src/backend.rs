@@ -0,0 +1,37 @@
+use libllrs::{
+    CachedMangaService, InMemoryMangaService, MangaCache, MangaService, PostgresPool, Result,
+    SqliteWaifusims, WaifusimsPool,
+};
+
+pub(crate) type BoxedMangaService = Box<dyn MangaService<i32> + Send>;
+
+/// The database the API serves manga from, chosen at startup.
+#[derive(Clone)]
+pub(crate) enum Backend {
+    SqlServer(WaifusimsPool),
+    Postgres(PostgresPool),
+    Sqlite(SqliteWaifusims),
+    Memory(InMemoryMangaService),
+    /// Another backend behind a cache shared by every request
+    Cached {
+        backend: Box<Backend>,
+        cache: MangaCache,
+    },
+}
+
+impl Backend {
+    pub(crate) async fn connect(&self) -> Result<BoxedMangaService> {
+        match self {
+            // Each call checks out its own connection, the pool is shared between requests
+            Backend::SqlServer(pool) => Ok(Box::new(pool.clone())),
+            Backend::Postgres(pool) => Ok(Box::new(pool.clone())),
+            // SQLite is a local file, the one connection is shared between requests
+            Backend::Sqlite(waifusims) => Ok(Box::new(waifusims.clone())),
+            Backend::Memory(service) => Ok(Box::new(service.clone())),
+            Backend::Cached { backend, cache } => {
+                let service = Box::pin(backend.connect()).await?;
+                Ok(Box::new(CachedMangaService::new(service, cache.clone())))
+            }
+        }
+    }
+}
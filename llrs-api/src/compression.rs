@@ -0,0 +1,236 @@
+//! `br`, `gzip` and `deflate` compression of responses, negotiated from `Accept-Encoding`.
+//!
+//! Manga and chapter lists repeat the same keys in every entry, so they shrink a lot.
+//! Responses are compressed before `http_cache::conditional` hashes them, so every
+//! encoding of a response gets its own strong `ETag` as RFC 9110 asks.
+
+use brotli::enc::BrotliEncoderParams;
+use flate2::{
+    write::{GzEncoder, ZlibEncoder},
+    Compression,
+};
+use log::error;
+use std::io::{self, Write};
+use warp::{
+    http::{header, HeaderValue, StatusCode},
+    hyper::{self, Body},
+    reply::Response,
+    Reply,
+};
+
+/// How responses are compressed.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct CompressionConfig {
+    /// Smallest body in bytes worth compressing, smaller ones are sent as they are
+    pub min_size: usize,
+    /// 0 to 9
+    pub gzip_level: u32,
+    /// 0 to 9
+    pub deflate_level: u32,
+    /// 0 to 11, higher levels are too slow to compress every response with
+    pub brotli_level: u32,
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        CompressionConfig {
+            min_size: 1024,
+            gzip_level: 6,
+            deflate_level: 6,
+            brotli_level: 5,
+        }
+    }
+}
+
+/// A content coding of a response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Encoding {
+    Brotli,
+    Gzip,
+    Deflate,
+    Identity,
+}
+
+impl Encoding {
+    /// Codings the client weighs equally are picked in this order, smallest output first.
+    const PREFERENCE: [Encoding; 4] = [
+        Encoding::Brotli,
+        Encoding::Gzip,
+        Encoding::Deflate,
+        Encoding::Identity,
+    ];
+
+    fn token(self) -> &'static str {
+        match self {
+            Encoding::Brotli => "br",
+            Encoding::Gzip => "gzip",
+            Encoding::Deflate => "deflate",
+            Encoding::Identity => "identity",
+        }
+    }
+
+    fn encode(self, body: &[u8], config: &CompressionConfig) -> io::Result<Option<Vec<u8>>> {
+        let encoded = match self {
+            Encoding::Brotli => {
+                let params = BrotliEncoderParams {
+                    quality: config.brotli_level as i32,
+                    ..BrotliEncoderParams::default()
+                };
+                let mut encoded = Vec::new();
+                brotli::BrotliCompress(&mut &body[..], &mut encoded, &params)?;
+                encoded
+            }
+            Encoding::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), Compression::new(config.gzip_level));
+                encoder.write_all(body)?;
+                encoder.finish()?
+            }
+            // HTTP's `deflate` is the zlib format, not a raw deflate stream
+            Encoding::Deflate => {
+                let mut encoder =
+                    ZlibEncoder::new(Vec::new(), Compression::new(config.deflate_level));
+                encoder.write_all(body)?;
+                encoder.finish()?
+            }
+            Encoding::Identity => return Ok(None),
+        };
+        Ok(Some(encoded))
+    }
+}
+
+/// Weight of a coding in thousandths, `None` for an unparsable `q`.
+fn weight(params: &str) -> Option<u16> {
+    params
+        .split(';')
+        .map(str::trim)
+        .filter_map(|param| param.split_once('='))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("q"))
+        .map_or(Some(1000), |(_, q)| {
+            q.trim()
+                .parse::<f32>()
+                .ok()
+                .filter(|q| (0.0..=1.0).contains(q))
+                .map(|q| (q * 1000.0).round() as u16)
+        })
+}
+
+/// The coding to answer an `Accept-Encoding` with. Codings the client didn't list are
+/// only sent when it lists `*`, and `identity` is the fallback when nothing listed fits,
+/// even one it refused, since an uncompressed response beats a `406`.
+pub(crate) fn negotiate(accept_encoding: Option<&str>) -> Encoding {
+    let accept_encoding = match accept_encoding {
+        Some(accept_encoding) => accept_encoding,
+        None => return Encoding::Identity,
+    };
+    let weights: Vec<(&str, u16)> = accept_encoding
+        .split(',')
+        .filter_map(|entry| {
+            let (coding, params) = entry.split_once(';').unwrap_or((entry, ""));
+            match coding.trim() {
+                "" => None,
+                coding if coding.eq_ignore_ascii_case("x-gzip") => Some("gzip"),
+                coding => Some(coding),
+            }
+            .zip(weight(params))
+        })
+        .collect();
+    let listed = |token: &str| {
+        weights
+            .iter()
+            .find(|(coding, _)| coding.eq_ignore_ascii_case(token))
+            .map(|(_, weight)| *weight)
+    };
+    let wildcard = listed("*");
+    Encoding::PREFERENCE
+        .iter()
+        .map(|&encoding| (encoding, listed(encoding.token()).or(wildcard).unwrap_or(0)))
+        .filter(|(_, weight)| *weight > 0)
+        .fold(None, |best, candidate| match best {
+            Some((_, weight)) if weight >= candidate.1 => best,
+            _ => Some(candidate),
+        })
+        .map_or(Encoding::Identity, |(encoding, _)| encoding)
+}
+
+/// Compresses `response` with the coding `accept_encoding` prefers, when its body is
+/// at least `config.min_size` bytes and it isn't already encoded.
+pub(crate) async fn compress(
+    response: Response,
+    accept_encoding: Option<String>,
+    config: CompressionConfig,
+) -> Response {
+    let status = response.status();
+    if status.is_informational()
+        || status == StatusCode::NO_CONTENT
+        || status == StatusCode::NOT_MODIFIED
+        || response.headers().contains_key(header::CONTENT_ENCODING)
+    {
+        return response;
+    }
+    let (mut parts, body) = response.into_parts();
+    let body = match hyper::body::to_bytes(body).await {
+        Ok(body) => body,
+        Err(err) => {
+            error!("buffering a response to compress it: {}", err);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    if body.len() < config.min_size {
+        return Response::from_parts(parts, Body::from(body));
+    }
+    // Whether or not this one was compressed, caches must keep a copy per Accept-Encoding
+    parts
+        .headers
+        .append(header::VARY, HeaderValue::from_static("accept-encoding"));
+    let encoding = negotiate(accept_encoding.as_deref());
+    match encoding.encode(&body, &config) {
+        Ok(Some(encoded)) if encoded.len() < body.len() => {
+            parts.headers.insert(
+                header::CONTENT_ENCODING,
+                HeaderValue::from_static(encoding.token()),
+            );
+            parts.headers.remove(header::CONTENT_LENGTH);
+            Response::from_parts(parts, Body::from(encoded))
+        }
+        Ok(_) => Response::from_parts(parts, Body::from(body)),
+        Err(err) => {
+            error!("compressing a response with {}: {}", encoding.token(), err);
+            Response::from_parts(parts, Body::from(body))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn negotiates_accept_encoding() {
+        for (accept_encoding, expected) in [
+            (None, Encoding::Identity),
+            (Some(""), Encoding::Identity),
+            (Some("gzip"), Encoding::Gzip),
+            (Some("x-gzip"), Encoding::Gzip),
+            (Some("deflate"), Encoding::Deflate),
+            (Some("gzip, deflate, br"), Encoding::Brotli),
+            (Some("GZIP, Deflate"), Encoding::Gzip),
+            (Some("br;q=0.5, gzip;q=0.8, deflate"), Encoding::Deflate),
+            (Some("br;q=0, gzip"), Encoding::Gzip),
+            (Some("gzip;q=0"), Encoding::Identity),
+            (Some("*"), Encoding::Brotli),
+            (Some("*, br;q=0"), Encoding::Gzip),
+            (Some("identity"), Encoding::Identity),
+            (Some("identity;q=1, gzip;q=0.5"), Encoding::Identity),
+            (Some("identity;q=0, *;q=0"), Encoding::Identity),
+            (Some("gzip;q=2, deflate"), Encoding::Deflate),
+            (Some("compress, zstd"), Encoding::Identity),
+        ] {
+            assert_eq!(
+                negotiate(accept_encoding),
+                expected,
+                "{:?}",
+                accept_encoding
+            );
+        }
+    }
+}
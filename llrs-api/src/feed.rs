@@ -0,0 +1,256 @@
+//! RSS 2.0 and Atom renderings of chapter releases, linking to llrs-site's reader.
+//!
+//! Waifusims dates have no time zone, they're written out as UTC.
+
+use chrono::NaiveDateTime;
+use libllrs::{Chapter, Manga};
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
+use serde::Deserialize;
+use utoipa::{IntoParams, ToSchema};
+
+/// Characters escaped in a chapter number so it stays one path segment of a link
+const PATH_SEGMENT: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'/')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum FeedFormat {
+    #[default]
+    Rss,
+    Atom,
+}
+
+impl FeedFormat {
+    pub(crate) fn content_type(self) -> &'static str {
+        match self {
+            FeedFormat::Rss => "application/rss+xml; charset=utf-8",
+            FeedFormat::Atom => "application/atom+xml; charset=utf-8",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct FeedQuery {
+    /// RSS unless `atom` is asked for
+    #[serde(default)]
+    pub(crate) format: FeedFormat,
+}
+
+/// A feed of chapter releases, newest first.
+#[derive(Debug)]
+pub(crate) struct Feed {
+    pub(crate) title: String,
+    /// Site page the feed follows
+    pub(crate) link: String,
+    pub(crate) entries: Vec<FeedEntry>,
+}
+
+#[derive(Debug)]
+pub(crate) struct FeedEntry {
+    pub(crate) title: String,
+    /// Reader page of the chapter, also the entry's ID
+    pub(crate) link: String,
+    pub(crate) released: NaiveDateTime,
+}
+
+/// `AppRoute::ChapterList` of a manga on the site.
+pub(crate) fn manga_url(site_url: &str, manga_id: i32) -> String {
+    format!("{}/manga/{}", site_url.trim_end_matches('/'), manga_id)
+}
+
+/// `AppRoute::MangaChapter` of a chapter on the site.
+pub(crate) fn chapter_url(site_url: &str, chapter: &Chapter) -> String {
+    format!(
+        "{}/{}",
+        manga_url(site_url, chapter.manga_id),
+        encode_segment(&chapter.chapter_number.to_string())
+    )
+}
+
+/// Percent encodes `segment` to be one segment of a link path.
+pub(crate) fn encode_segment(segment: &str) -> String {
+    utf8_percent_encode(segment, PATH_SEGMENT).to_string()
+}
+
+/// Chapters without a release date count as released when they were added.
+pub(crate) fn released(chapter: &Chapter) -> NaiveDateTime {
+    chapter.release_date.unwrap_or(chapter.creation_date)
+}
+
+/// An entry titled with the manga, for feeds spanning every manga.
+pub(crate) fn manga_chapter_entry(site_url: &str, manga: &Manga, chapter: &Chapter) -> FeedEntry {
+    FeedEntry {
+        title: format!("{} {}", manga.manga_name, chapter_title(chapter)),
+        link: chapter_url(site_url, chapter),
+        released: released(chapter),
+    }
+}
+
+/// An entry titled with only the chapter, for the feed of its manga.
+pub(crate) fn chapter_entry(site_url: &str, chapter: &Chapter) -> FeedEntry {
+    FeedEntry {
+        title: chapter_title(chapter),
+        link: chapter_url(site_url, chapter),
+        released: released(chapter),
+    }
+}
+
+fn chapter_title(chapter: &Chapter) -> String {
+    format!(
+        "Chapter {}: {}",
+        chapter.chapter_number, chapter.chapter_name
+    )
+}
+
+impl Feed {
+    pub(crate) fn render(&self, format: FeedFormat) -> String {
+        match format {
+            FeedFormat::Rss => self.rss(),
+            FeedFormat::Atom => self.atom(),
+        }
+    }
+
+    /// Newest release, the epoch for a feed without entries
+    fn updated(&self) -> NaiveDateTime {
+        self.entries
+            .iter()
+            .map(|entry| entry.released)
+            .max()
+            .unwrap_or_default()
+    }
+
+    fn rss(&self) -> String {
+        let mut xml = String::from(r#"<?xml version="1.0" encoding="utf-8"?>"#);
+        xml.push_str(r#"<rss version="2.0"><channel>"#);
+        xml.push_str(&format!(
+            "<title>{}</title><link>{}</link><description>{}</description>",
+            escape(&self.title),
+            escape(&self.link),
+            escape(&format!("New chapters of {}", self.title)),
+        ));
+        if !self.entries.is_empty() {
+            xml.push_str(&format!(
+                "<lastBuildDate>{}</lastBuildDate>",
+                rfc_2822(self.updated())
+            ));
+        }
+        for entry in &self.entries {
+            xml.push_str(&format!(
+                "<item><title>{}</title><link>{link}</link>\
+                 <guid isPermaLink=\"true\">{link}</guid><pubDate>{}</pubDate></item>",
+                escape(&entry.title),
+                rfc_2822(entry.released),
+                link = escape(&entry.link),
+            ));
+        }
+        xml.push_str("</channel></rss>");
+        xml
+    }
+
+    fn atom(&self) -> String {
+        let mut xml = String::from(r#"<?xml version="1.0" encoding="utf-8"?>"#);
+        xml.push_str(r#"<feed xmlns="http://www.w3.org/2005/Atom">"#);
+        xml.push_str(&format!(
+            "<id>{link}</id><title>{}</title><link href=\"{link}\"/>\
+             <updated>{}</updated><author><name>llrs</name></author>",
+            escape(&self.title),
+            rfc_3339(self.updated()),
+            link = escape(&self.link),
+        ));
+        for entry in &self.entries {
+            xml.push_str(&format!(
+                "<entry><id>{link}</id><title>{}</title><link href=\"{link}\"/>\
+                 <updated>{}</updated></entry>",
+                escape(&entry.title),
+                rfc_3339(entry.released),
+                link = escape(&entry.link),
+            ));
+        }
+        xml.push_str("</feed>");
+        xml
+    }
+}
+
+fn rfc_2822(date: NaiveDateTime) -> String {
+    date.format("%a, %d %b %Y %H:%M:%S +0000").to_string()
+}
+
+pub(crate) fn rfc_3339(date: NaiveDateTime) -> String {
+    date.format("%Y-%m-%dT%H:%M:%SZ").to_string()
+}
+
+/// Escapes text for XML content and double quoted attributes.
+pub(crate) fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn feed() -> Feed {
+        let released = NaiveDate::from_ymd_opt(2021, 3, 4)
+            .unwrap()
+            .and_hms_opt(5, 6, 7)
+            .unwrap();
+        Feed {
+            title: "Tom & Jerry".to_owned(),
+            link: "http://site/manga/1".to_owned(),
+            entries: vec![FeedEntry {
+                title: "Chapter 1: <Begin>".to_owned(),
+                link: "http://site/manga/1/Vol.1%20Ch.1".to_owned(),
+                released,
+            }],
+        }
+    }
+
+    #[test]
+    fn renders_escaped_rss_and_atom() {
+        let rss = feed().render(FeedFormat::Rss);
+        assert!(rss.contains("<title>Tom &amp; Jerry</title>"));
+        assert!(rss.contains("<title>Chapter 1: &lt;Begin&gt;</title>"));
+        assert!(rss.contains("<pubDate>Thu, 04 Mar 2021 05:06:07 +0000</pubDate>"));
+        let atom = feed().render(FeedFormat::Atom);
+        assert!(atom.contains("<link href=\"http://site/manga/1/Vol.1%20Ch.1\"/>"));
+        assert!(atom.contains("<updated>2021-03-04T05:06:07Z</updated>"));
+    }
+
+    #[test]
+    fn encodes_chapter_numbers_in_site_links() {
+        let chapter = Chapter {
+            chapter_number: "Vol.2 Ch.3/4".into(),
+            chapter_name: String::new(),
+            creation_date: NaiveDateTime::default(),
+            release_date: None,
+            updated_date: None,
+            manga_id: 7,
+        };
+        assert_eq!(
+            chapter_url("http://site/", &chapter),
+            "http://site/manga/7/Vol.2%20Ch.3%2F4"
+        );
+    }
+}
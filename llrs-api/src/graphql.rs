@@ -0,0 +1,360 @@
+//! GraphQL over the manga catalog at `POST /graphql`, so a client can fetch a manga,
+//! its chapters and their pages in one round trip.
+//!
+//! Chapter and page lists are loaded through `DataLoader`s, so every manga or chapter
+//! at the same depth of a query shares one batched `MangaService` call.
+
+use crate::backend::Backend;
+use async_graphql::{
+    dataloader::{DataLoader, Loader},
+    Context, EmptyMutation, EmptySubscription, Enum, ErrorExtensions, Object, Request, Response,
+    Schema,
+};
+use chrono::NaiveDateTime;
+use libllrs::{Chapter, Error as WaifusimsError, Manga, MangaListQuery, Page};
+use log::error;
+use std::collections::HashMap;
+
+/// Deep enough for a listing of manga, their chapters and pages with a little to spare
+const MAX_DEPTH: usize = 8;
+
+const MAX_COMPLEXITY: usize = 500;
+
+/// Manga returned by `mangas` without a `limit`
+const DEFAULT_MANGA_LIMIT: u32 = 20;
+
+/// Most manga `mangas` returns, nested chapters and pages multiply from there
+const MAX_MANGA_LIMIT: u32 = 100;
+
+pub(crate) type LlrsSchema = Schema<Query, EmptyMutation, EmptySubscription>;
+
+pub(crate) fn schema() -> LlrsSchema {
+    Schema::build(Query, EmptyMutation, EmptySubscription)
+        .limit_depth(MAX_DEPTH)
+        .limit_complexity(MAX_COMPLEXITY)
+        .finish()
+}
+
+/// Runs `request` with loaders of its own, batches never mix requests.
+pub(crate) async fn execute(schema: &LlrsSchema, request: Request, backend: Backend) -> Response {
+    let request = request
+        .data(DataLoader::new(
+            ChapterListLoader {
+                backend: backend.clone(),
+            },
+            tokio::spawn,
+        ))
+        .data(DataLoader::new(
+            PageListLoader {
+                backend: backend.clone(),
+            },
+            tokio::spawn,
+        ))
+        .data(backend);
+    schema.execute(request).await
+}
+
+/// A field error with the same `code` extension as the API's `ErrorBody`,
+/// server side failures are logged and don't leak their details.
+fn field_error(error: WaifusimsError) -> async_graphql::Error {
+    let (code, message) = match error {
+        WaifusimsError::InvalidInput(message) => ("invalid_input", message),
+        WaifusimsError::Conflict(message) => ("conflict", message),
+        error if error.is_unavailable() => {
+            error!("graphql {}", error);
+            (
+                "database_unavailable",
+                "the database is unavailable, try again later".to_owned(),
+            )
+        }
+        error => {
+            error!("graphql {}", error);
+            ("internal", "internal server error".to_owned())
+        }
+    };
+    async_graphql::Error::new(message).extend_with(|_, extensions| extensions.set("code", code))
+}
+
+fn check_manga_id(manga_id: i32) -> async_graphql::Result<()> {
+    if manga_id < 0 {
+        Err(field_error(WaifusimsError::InvalidInput(format!(
+            "manga ID {} is negative",
+            manga_id
+        ))))
+    } else {
+        Ok(())
+    }
+}
+
+/// Chapters of each manga, keyed by manga ID.
+struct ChapterListLoader {
+    backend: Backend,
+}
+
+impl Loader<i32> for ChapterListLoader {
+    type Value = Vec<Chapter>;
+    type Error = async_graphql::Error;
+
+    async fn load(&self, manga_ids: &[i32]) -> Result<HashMap<i32, Vec<Chapter>>, Self::Error> {
+        let mut llrs = self.backend.connect().await.map_err(field_error)?;
+        let chapter_lists = llrs
+            .get_mangas_chapters(manga_ids)
+            .await
+            .map_err(field_error)?;
+        Ok(manga_ids.iter().copied().zip(chapter_lists).collect())
+    }
+}
+
+/// Pages of each chapter, keyed by manga ID and chapter number.
+struct PageListLoader {
+    backend: Backend,
+}
+
+impl Loader<(i32, String)> for PageListLoader {
+    type Value = Vec<Page>;
+    type Error = async_graphql::Error;
+
+    async fn load(
+        &self,
+        chapters: &[(i32, String)],
+    ) -> Result<HashMap<(i32, String), Vec<Page>>, Self::Error> {
+        let mut llrs = self.backend.connect().await.map_err(field_error)?;
+        let page_lists = llrs
+            .get_chapters_pages(chapters)
+            .await
+            .map_err(field_error)?;
+        Ok(chapters.iter().cloned().zip(page_lists).collect())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+#[graphql(remote = "libllrs::MangaStatus", name = "MangaStatus")]
+enum MangaStatusValue {
+    Ongoing,
+    Completed,
+    Hiatus,
+    Cancelled,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+#[graphql(remote = "libllrs::MangaSort", name = "MangaSort")]
+enum MangaSortValue {
+    Name,
+    NewestChapter,
+    CreationDate,
+}
+
+pub(crate) struct Query;
+
+#[Object]
+impl Query {
+    /// One slice of the catalog, like `GET /`
+    async fn mangas(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default_with = "MangaSortValue::Name")] sort: MangaSortValue,
+        author: Option<String>,
+        status: Option<MangaStatusValue>,
+        #[graphql(default)] offset: u32,
+        limit: Option<u32>,
+    ) -> async_graphql::Result<MangaListObject> {
+        let query = MangaListQuery {
+            sort: sort.into(),
+            author,
+            status: status.map(Into::into),
+            offset,
+            limit: Some(limit.unwrap_or(DEFAULT_MANGA_LIMIT).min(MAX_MANGA_LIMIT)),
+        };
+        let mut llrs = ctx
+            .data_unchecked::<Backend>()
+            .connect()
+            .await
+            .map_err(field_error)?;
+        let listing = llrs.list_manga(&query).await.map_err(field_error)?;
+        Ok(MangaListObject {
+            total: listing.total,
+            mangas: listing.mangas.into_iter().map(MangaObject).collect(),
+        })
+    }
+
+    /// The manga with its creators, `null` when there's no such manga
+    async fn manga(
+        &self,
+        ctx: &Context<'_>,
+        manga_id: i32,
+    ) -> async_graphql::Result<Option<MangaObject>> {
+        check_manga_id(manga_id)?;
+        let mut llrs = ctx
+            .data_unchecked::<Backend>()
+            .connect()
+            .await
+            .map_err(field_error)?;
+        let manga = llrs.get_manga(manga_id).await.map_err(field_error)?;
+        Ok(manga.map(MangaObject))
+    }
+
+    /// One chapter, `null` when there's no such chapter
+    async fn chapter(
+        &self,
+        ctx: &Context<'_>,
+        manga_id: i32,
+        chapter_number: String,
+    ) -> async_graphql::Result<Option<ChapterObject>> {
+        check_manga_id(manga_id)?;
+        let chapters = ctx
+            .data_unchecked::<DataLoader<ChapterListLoader>>()
+            .load_one(manga_id)
+            .await?
+            .unwrap_or_default();
+        Ok(find_chapter(chapters, &chapter_number))
+    }
+}
+
+fn find_chapter(chapters: Vec<Chapter>, chapter_number: &str) -> Option<ChapterObject> {
+    chapters
+        .into_iter()
+        .find(|chapter| chapter.chapter_number.to_string() == chapter_number)
+        .map(ChapterObject)
+}
+
+struct MangaListObject {
+    total: u64,
+    mangas: Vec<MangaObject>,
+}
+
+#[Object(name = "MangaList")]
+impl MangaListObject {
+    /// Manga matching the filters, the list only holds the requested slice
+    async fn total(&self) -> u64 {
+        self.total
+    }
+
+    async fn mangas(&self) -> &[MangaObject] {
+        &self.mangas
+    }
+}
+
+struct MangaObject(Manga);
+
+#[Object(name = "Manga")]
+impl MangaObject {
+    async fn manga_id(&self) -> i32 {
+        self.0.manga_id
+    }
+
+    async fn manga_name(&self) -> &str {
+        &self.0.manga_name
+    }
+
+    async fn author_names(&self) -> &[String] {
+        &self.0.author_names
+    }
+
+    async fn artist_names(&self) -> &[String] {
+        &self.0.artist_names
+    }
+
+    async fn cover_image_url(&self) -> Option<&str> {
+        self.0.cover_image_url.as_deref()
+    }
+
+    async fn purchase_url(&self) -> Option<&str> {
+        self.0.purchase_url.as_deref()
+    }
+
+    async fn status(&self) -> MangaStatusValue {
+        self.0.status.into()
+    }
+
+    async fn creation_date(&self) -> Option<NaiveDateTime> {
+        self.0.creation_date
+    }
+
+    /// Chapters in reading order, sliced with `offset` and `limit`
+    async fn chapters(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default)] offset: usize,
+        limit: Option<usize>,
+    ) -> async_graphql::Result<Vec<ChapterObject>> {
+        let chapters = ctx
+            .data_unchecked::<DataLoader<ChapterListLoader>>()
+            .load_one(self.0.manga_id)
+            .await?
+            .unwrap_or_default();
+        Ok(chapters
+            .into_iter()
+            .skip(offset)
+            .take(limit.unwrap_or(usize::MAX))
+            .map(ChapterObject)
+            .collect())
+    }
+
+    async fn chapter(
+        &self,
+        ctx: &Context<'_>,
+        chapter_number: String,
+    ) -> async_graphql::Result<Option<ChapterObject>> {
+        let chapters = ctx
+            .data_unchecked::<DataLoader<ChapterListLoader>>()
+            .load_one(self.0.manga_id)
+            .await?
+            .unwrap_or_default();
+        Ok(find_chapter(chapters, &chapter_number))
+    }
+}
+
+struct ChapterObject(Chapter);
+
+#[Object(name = "Chapter")]
+impl ChapterObject {
+    async fn manga_id(&self) -> i32 {
+        self.0.manga_id
+    }
+
+    async fn chapter_number(&self) -> String {
+        self.0.chapter_number.to_string()
+    }
+
+    async fn chapter_name(&self) -> &str {
+        &self.0.chapter_name
+    }
+
+    async fn creation_date(&self) -> NaiveDateTime {
+        self.0.creation_date
+    }
+
+    async fn release_date(&self) -> Option<NaiveDateTime> {
+        self.0.release_date
+    }
+
+    /// Pages in reading order
+    async fn pages(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<PageObject>> {
+        let key = (self.0.manga_id, self.0.chapter_number.to_string());
+        let pages = ctx
+            .data_unchecked::<DataLoader<PageListLoader>>()
+            .load_one(key)
+            .await?
+            .unwrap_or_default();
+        Ok(pages.into_iter().map(PageObject).collect())
+    }
+}
+
+struct PageObject(Page);
+
+#[Object(name = "Page")]
+impl PageObject {
+    /// The preferred mirror
+    async fn url_string(&self) -> &str {
+        &self.0.url_string
+    }
+
+    async fn page_number(&self) -> i32 {
+        self.0.page_number
+    }
+
+    /// Other mirrors of the same image to try in order when `urlString` fails
+    async fn mirror_urls(&self) -> &[String] {
+        &self.0.mirror_urls
+    }
+}
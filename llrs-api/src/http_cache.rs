@@ -0,0 +1,200 @@
+//! HTTP caching of successful `GET`s, so browsers and CDNs revalidate instead of refetching.
+//!
+//! Every `200` gets a strong `ETag` hashed from its body, handlers add `Last-Modified`
+//! from the `updated_date` of the manga or chapter a response shows,
+//! and routes add their `Cache-Control` policy.
+//! Conditional requests matching the response are answered `304 Not Modified`.
+//!
+//! Lists spanning every manga have no `Last-Modified`, deleting a manga or chapter
+//! leaves no date behind to tell them apart.
+
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+use log::error;
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use warp::{
+    http::{header, header::HeaderName, HeaderMap, HeaderValue, Method, StatusCode},
+    hyper::{self, Body},
+    reply::Response,
+    Reply,
+};
+
+/// `Cache-Control` of each kind of route.
+#[derive(Debug, Clone)]
+pub(crate) struct CachePolicies {
+    /// Manga listings, searches, single manga and the latest releases
+    pub manga: Arc<str>,
+    /// Chapter lists and single chapters
+    pub chapters: Arc<str>,
+    /// Page lists, which rarely change once a chapter is added
+    pub pages: Arc<str>,
+    /// RSS, Atom and OPDS
+    pub feeds: Arc<str>,
+}
+
+impl Default for CachePolicies {
+    fn default() -> Self {
+        CachePolicies {
+            manga: Arc::from("public, max-age=60"),
+            chapters: Arc::from("public, max-age=60"),
+            pages: Arc::from("public, max-age=3600"),
+            feeds: Arc::from("public, max-age=300"),
+        }
+    }
+}
+
+/// Maps a reply to a response with `policy` as its `Cache-Control`, for a route's `.map()`.
+pub(crate) fn cache_control<R: Reply>(
+    policy: &Arc<str>,
+) -> impl Fn(R) -> Response + Clone + Send + Sync + 'static {
+    let policy = policy.clone();
+    move |reply| {
+        let mut response = reply.into_response();
+        if let Ok(policy) = HeaderValue::from_str(&policy) {
+            response.headers_mut().insert(header::CACHE_CONTROL, policy);
+        }
+        response
+    }
+}
+
+/// Adds `Last-Modified` to `reply` once the date is a whole second past,
+/// dates are whole seconds so another change within the same second would share it.
+pub(crate) fn with_last_modified(
+    reply: impl Reply,
+    last_modified: Option<NaiveDateTime>,
+) -> Response {
+    let mut response = reply.into_response();
+    let second_ago = Utc::now().naive_utc() - Duration::seconds(1);
+    if let Some(last_modified) = last_modified.filter(|date| *date <= second_ago) {
+        if let Ok(value) = HeaderValue::from_str(&http_date(last_modified)) {
+            response.headers_mut().insert(header::LAST_MODIFIED, value);
+        }
+    }
+    response
+}
+
+/// An IMF-fixdate, eg: `Sun, 06 Nov 1994 08:49:37 GMT`
+pub(crate) fn http_date(date: NaiveDateTime) -> String {
+    date.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
+
+fn parse_http_date(date: &str) -> Option<NaiveDateTime> {
+    DateTime::parse_from_rfc2822(date)
+        .ok()
+        .map(|date| date.naive_utc())
+}
+
+/// Strong validator of a body, the hex SHA-256 of its bytes.
+pub(crate) fn etag(body: &[u8]) -> String {
+    format!("\"{:x}\"", Sha256::digest(body))
+}
+
+/// Whether `If-None-Match` lists `etag`, compared weakly as RFC 9110 asks of `GET`s.
+fn none_match(if_none_match: &str, etag: &str) -> bool {
+    if_none_match.split(',').map(str::trim).any(|candidate| {
+        candidate == "*" || candidate.strip_prefix("W/").unwrap_or(candidate) == etag
+    })
+}
+
+fn header_str(headers: &HeaderMap, name: HeaderName) -> Option<&str> {
+    headers.get(name).and_then(|value| value.to_str().ok())
+}
+
+/// Whether the request's validators match the response, `If-Modified-Since` only counts
+/// without an `If-None-Match` as RFC 9110 asks.
+fn not_modified(request_headers: &HeaderMap, response_headers: &HeaderMap, etag: &str) -> bool {
+    if let Some(if_none_match) = header_str(request_headers, header::IF_NONE_MATCH) {
+        return none_match(if_none_match, etag);
+    }
+    let if_modified_since =
+        header_str(request_headers, header::IF_MODIFIED_SINCE).and_then(parse_http_date);
+    let last_modified =
+        header_str(response_headers, header::LAST_MODIFIED).and_then(parse_http_date);
+    match (if_modified_since, last_modified) {
+        (Some(if_modified_since), Some(last_modified)) => last_modified <= if_modified_since,
+        _ => false,
+    }
+}
+
+/// Tags a successful `GET` with its `ETag` and answers `304` when the request already has it.
+pub(crate) async fn conditional(
+    response: Response,
+    method: Method,
+    request_headers: HeaderMap,
+) -> Response {
+    if method != Method::GET || response.status() != StatusCode::OK {
+        return response;
+    }
+    let (mut parts, body) = response.into_parts();
+    let body = match hyper::body::to_bytes(body).await {
+        Ok(body) => body,
+        Err(err) => {
+            error!("buffering a response for its ETag: {}", err);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    let etag = etag(&body);
+    let not_modified = not_modified(&request_headers, &parts.headers, &etag);
+    parts.headers.insert(
+        header::ETAG,
+        HeaderValue::from_str(&etag).expect("hex is a valid header"),
+    );
+    if not_modified {
+        parts.status = StatusCode::NOT_MODIFIED;
+        parts.headers.remove(header::CONTENT_TYPE);
+        parts.headers.remove(header::CONTENT_LENGTH);
+        return Response::from_parts(parts, Body::empty());
+    }
+    Response::from_parts(parts, Body::from(body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(pairs: &[(header::HeaderName, &str)]) -> HeaderMap {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.clone(), HeaderValue::from_str(value).unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn matches_validators() {
+        let etag = etag(b"[]");
+        let last_modified = headers(&[(header::LAST_MODIFIED, "Fri, 01 Jan 2021 00:00:00 GMT")]);
+        for (request, expected) in [
+            (headers(&[(header::IF_NONE_MATCH, &etag)]), true),
+            (
+                headers(&[(header::IF_NONE_MATCH, &format!("\"other\", W/{}", etag))]),
+                true,
+            ),
+            (headers(&[(header::IF_NONE_MATCH, "*")]), true),
+            (headers(&[(header::IF_NONE_MATCH, "\"other\"")]), false),
+            (
+                headers(&[(header::IF_MODIFIED_SINCE, "Fri, 01 Jan 2021 00:00:00 GMT")]),
+                true,
+            ),
+            (
+                headers(&[(header::IF_MODIFIED_SINCE, "Thu, 31 Dec 2020 23:59:59 GMT")]),
+                false,
+            ),
+            (headers(&[(header::IF_MODIFIED_SINCE, "yesterday")]), false),
+            // A changed ETag wins over an unchanged date
+            (
+                headers(&[
+                    (header::IF_NONE_MATCH, "\"other\""),
+                    (header::IF_MODIFIED_SINCE, "Fri, 01 Jan 2021 00:00:00 GMT"),
+                ]),
+                false,
+            ),
+        ] {
+            assert_eq!(
+                not_modified(&request, &last_modified, &etag),
+                expected,
+                "{:?}",
+                request
+            );
+        }
+    }
+}
@@ -1,14 +1,50 @@
+mod backend;
+mod compression;
+mod feed;
+mod graphql;
+mod http_cache;
+mod migrate;
+#[cfg(test)]
+mod model_compat;
+mod opds;
+mod openapi;
+mod routes;
+mod version;
+
+use backend::Backend;
 use clap::{App, Arg, ArgMatches};
-use libllrs::{Auth, Config, Error as WaifusimsError, MangaService, Waifusims};
+use compression::CompressionConfig;
+use http_cache::CachePolicies;
+use libllrs::{
+    Auth, CacheConfig, Config, InMemoryMangaService, MangaCache, Migrate, Migration, PoolConfig,
+    PostgresPool, PostgresWaifusims, SqliteWaifusims, Waifusims, WaifusimsPool,
+};
 use log::*;
+use migrate::MigrateCommand;
 use nameof::name_of;
-use std::net::SocketAddr;
-use warp::Filter;
+use routes::RouteConfig;
+use std::{net::SocketAddr, sync::Arc, time::Duration};
 
 #[derive(Debug)]
 struct ServerConfig {
     pub addr: SocketAddr,
-    pub sql_config: SqlConfig,
+    /// Present when serving from SQLite instead of SQL Server
+    pub sqlite_path: Option<String>,
+    /// Present when serving from Postgres instead of SQL Server
+    pub postgres_connection: Option<String>,
+    /// Present when serving a JSON fixture from memory instead of SQL Server
+    pub fixture_path: Option<String>,
+    pub sql_config: Option<SqlConfig>,
+    /// Used for SQL Server and Postgres
+    pub pool_config: PoolConfig,
+    /// Bearer token required by writes, writes are disabled without one
+    pub api_token: Option<String>,
+    /// Absent when caching is turned off
+    pub cache_config: Option<CacheConfig>,
+    /// Where llrs-site is served, feeds link to its chapters
+    pub site_url: String,
+    pub cache_control: CachePolicies,
+    pub compression: CompressionConfig,
 }
 
 #[derive(Debug)]
@@ -30,6 +66,94 @@ impl<'a> From<ArgMatches<'a>> for ServerConfig {
             .to_owned()
             .parse()
             .expect("must be a valid socket addr. eg: 127.0.0.1:8080");
+        let sqlite_path = arg_matches
+            .value_of(name_of!(sqlite_path in ServerConfig))
+            .map(str::to_owned);
+        let fixture_path = arg_matches
+            .value_of(name_of!(fixture_path in ServerConfig))
+            .map(str::to_owned);
+        let postgres_connection = arg_matches
+            .value_of(name_of!(postgres_connection in ServerConfig))
+            .map(str::to_owned);
+        let api_token = arg_matches
+            .value_of(name_of!(api_token in ServerConfig))
+            .map(str::to_owned);
+        let cache_config = if !arg_matches.is_present("cache") {
+            None
+        } else {
+            let seconds = |name: &str| {
+                let seconds = arg_matches
+                    .value_of(name)
+                    .expect("should have defaulted if not provided")
+                    .parse::<u64>()
+                    .expect("invalid cache ttl");
+                Duration::from_secs(seconds)
+            };
+            Some(CacheConfig {
+                manga_list_ttl: seconds("cache_list_ttl_secs"),
+                chapter_list_ttl: seconds("cache_list_ttl_secs"),
+                page_list_ttl: seconds("cache_page_ttl_secs"),
+                ..CacheConfig::default()
+            })
+        };
+        let site_url = arg_matches
+            .value_of(name_of!(site_url in ServerConfig))
+            .expect("should have defaulted if not provided")
+            .to_owned();
+        let defaults = CachePolicies::default();
+        let policy =
+            |name: &str, default: Arc<str>| arg_matches.value_of(name).map_or(default, Arc::from);
+        let cache_control = CachePolicies {
+            manga: policy("cache_control_manga", defaults.manga),
+            chapters: policy("cache_control_chapters", defaults.chapters),
+            pages: policy("cache_control_pages", defaults.pages),
+            feeds: policy("cache_control_feeds", defaults.feeds),
+        };
+        let level = |name: &str, max: u32| {
+            arg_matches
+                .value_of(name)
+                .expect("should have defaulted if not provided")
+                .parse::<u32>()
+                .ok()
+                .filter(|level| *level <= max)
+                .unwrap_or_else(|| panic!("{} must be from 0 to {}", name, max))
+        };
+        let compression = CompressionConfig {
+            min_size: arg_matches
+                .value_of("compression_min_size")
+                .expect("should have defaulted if not provided")
+                .parse::<usize>()
+                .expect("invalid compression min size"),
+            gzip_level: level("gzip_level", 9),
+            deflate_level: level("deflate_level", 9),
+            brotli_level: level("brotli_level", 11),
+        };
+        let sql_config =
+            if sqlite_path.is_some() || fixture_path.is_some() || postgres_connection.is_some() {
+                None
+            } else {
+                Some(SqlConfig::from(&arg_matches))
+            };
+        let pool_config = pool_config(&arg_matches);
+
+        ServerConfig {
+            addr,
+            sqlite_path,
+            postgres_connection,
+            fixture_path,
+            sql_config,
+            pool_config,
+            api_token,
+            cache_config,
+            site_url,
+            cache_control,
+            compression,
+        }
+    }
+}
+
+impl<'a> From<&ArgMatches<'a>> for SqlConfig {
+    fn from(arg_matches: &ArgMatches<'a>) -> Self {
         let sql_user = arg_matches
             .value_of(name_of!(sql_user in SqlConfig))
             .expect("required")
@@ -50,19 +174,98 @@ impl<'a> From<ArgMatches<'a>> for ServerConfig {
             .value_of(name_of!(sql_port in SqlConfig))
             .map(|port_string| port_string.parse::<u16>().expect("invalid port number"));
 
-        ServerConfig {
-            addr,
-            sql_config: SqlConfig {
-                sql_user,
-                sql_pass,
-                sql_port,
-                sql_domain,
-                sql_database,
-            },
+        SqlConfig {
+            sql_user,
+            sql_pass,
+            sql_port,
+            sql_domain,
+            sql_database,
         }
     }
 }
 
+fn pool_config(arg_matches: &ArgMatches) -> PoolConfig {
+    let max_size = arg_matches
+        .value_of(name_of!(max_size in PoolConfig))
+        .expect("should have defaulted if not provided")
+        .parse::<u32>()
+        .expect("invalid pool max size");
+    let min_idle = arg_matches
+        .value_of(name_of!(min_idle in PoolConfig))
+        .map(|min_idle| min_idle.parse::<u32>().expect("invalid pool min idle"));
+    let idle_timeout_secs = arg_matches
+        .value_of(name_of!(idle_timeout in PoolConfig))
+        .expect("should have defaulted if not provided")
+        .parse::<u64>()
+        .expect("invalid pool idle timeout");
+    let connection_timeout_secs = arg_matches
+        .value_of(name_of!(connection_timeout in PoolConfig))
+        .expect("should have defaulted if not provided")
+        .parse::<u64>()
+        .expect("invalid pool connection timeout");
+    PoolConfig {
+        max_size,
+        min_idle,
+        idle_timeout: Some(Duration::from_secs(idle_timeout_secs)),
+        connection_timeout: Duration::from_secs(connection_timeout_secs),
+        health_check: !arg_matches.is_present("pool_no_health_check"),
+    }
+}
+
+fn sql_server_config(sql_config: SqlConfig) -> Config {
+    Config {
+        auth: Auth::Sql {
+            user: sql_config.sql_user,
+            pass: sql_config.sql_pass,
+        },
+        database: Some(sql_config.sql_database),
+        host: sql_config.sql_domain,
+        port: sql_config.sql_port,
+        trust_cert: true,
+    }
+}
+
+fn cache_control_arg<'a, 'b>(name: &'a str, long: &'a str, help: &'a str) -> Arg<'a, 'b> {
+    Arg::with_name(name)
+        .long(long)
+        .value_name("POLICY")
+        .help(help)
+        .takes_value(true)
+}
+
+fn compression_level_arg<'a, 'b>(
+    name: &'a str,
+    long: &'a str,
+    help: &'a str,
+    default: &'a str,
+) -> Arg<'a, 'b> {
+    Arg::with_name(name)
+        .long(long)
+        .value_name("LEVEL")
+        .help(help)
+        .takes_value(true)
+        .default_value(default)
+}
+
+/// Startup can't go on without its database, so failures exit instead of panicking.
+fn or_exit<T>(result: libllrs::Result<T>, message: &str) -> T {
+    result.unwrap_or_else(|err| {
+        error!("{}: {}", message, err);
+        std::process::exit(1);
+    })
+}
+
+/// SQLite and Postgres databases are brought up to date when serving starts.
+fn log_applied(applied: libllrs::Result<Vec<&Migration>>) {
+    let applied = or_exit(
+        applied,
+        "could not migrate the database, see `llrs-api migrate`",
+    );
+    for migration in applied {
+        info!("Applied migration {}", migration.name);
+    }
+}
+
 #[tokio::main]
 async fn main() {
     env_logger::init();
@@ -86,7 +289,11 @@ async fn main() {
                 .value_name("SQL_USERNAME")
                 .help("username for sql password auth")
                 .takes_value(true)
-                .required(true),
+                .required_unless_one(&[
+                    name_of!(sqlite_path in ServerConfig),
+                    name_of!(postgres_connection in ServerConfig),
+                    name_of!(fixture_path in ServerConfig),
+                ]),
         )
         .arg(
             Arg::with_name(name_of!(sql_pass in SqlConfig))
@@ -95,7 +302,11 @@ async fn main() {
                 .value_name("SQL_USER_PASSWORD")
                 .help("password for sql password auth")
                 .takes_value(true)
-                .required(true),
+                .required_unless_one(&[
+                    name_of!(sqlite_path in ServerConfig),
+                    name_of!(postgres_connection in ServerConfig),
+                    name_of!(fixture_path in ServerConfig),
+                ]),
         )
         .arg(
             Arg::with_name(name_of!(sql_domain in SqlConfig))
@@ -104,7 +315,11 @@ async fn main() {
                 .value_name("SQL_SRV_ADDR")
                 .help("address of sql server")
                 .takes_value(true)
-                .required(true),
+                .required_unless_one(&[
+                    name_of!(sqlite_path in ServerConfig),
+                    name_of!(postgres_connection in ServerConfig),
+                    name_of!(fixture_path in ServerConfig),
+                ]),
         )
         .arg(
             Arg::with_name(name_of!(sql_database in SqlConfig))
@@ -113,7 +328,11 @@ async fn main() {
                 .value_name("SQL_SRV_DATABASE")
                 .help("DATABASE DATABASE")
                 .takes_value(true)
-                .required(true),
+                .required_unless_one(&[
+                    name_of!(sqlite_path in ServerConfig),
+                    name_of!(postgres_connection in ServerConfig),
+                    name_of!(fixture_path in ServerConfig),
+                ]),
         )
         .arg(
             Arg::with_name(name_of!(sql_port in SqlConfig))
@@ -123,79 +342,270 @@ async fn main() {
                 .help("db port")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name(name_of!(max_size in PoolConfig))
+                .long("pool-max-size")
+                .value_name("CONNECTIONS")
+                .help("most sql server or postgres connections to keep open")
+                .takes_value(true)
+                .default_value("10"),
+        )
+        .arg(
+            Arg::with_name(name_of!(min_idle in PoolConfig))
+                .long("pool-min-idle")
+                .value_name("CONNECTIONS")
+                .help("sql server or postgres connections to keep open while idle")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name(name_of!(idle_timeout in PoolConfig))
+                .long("pool-idle-timeout")
+                .value_name("SECONDS")
+                .help("close idle connections above the minimum after this long")
+                .takes_value(true)
+                .default_value("600"),
+        )
+        .arg(
+            Arg::with_name(name_of!(connection_timeout in PoolConfig))
+                .long("pool-connection-timeout")
+                .value_name("SECONDS")
+                .help("fail a request after waiting this long for a free connection")
+                .takes_value(true)
+                .default_value("30"),
+        )
+        .arg(
+            Arg::with_name("pool_no_health_check")
+                .long("pool-no-health-check")
+                .help("skip checking sql server or postgres connections before each use"),
+        )
+        .arg(
+            Arg::with_name(name_of!(sqlite_path in ServerConfig))
+                .long("sqlite")
+                .value_name("SQLITE_DB_PATH")
+                .help("serve from a sqlite database file instead of sql server")
+                .takes_value(true)
+                .conflicts_with_all(&[
+                    name_of!(sql_user in SqlConfig),
+                    name_of!(sql_pass in SqlConfig),
+                    name_of!(sql_domain in SqlConfig),
+                    name_of!(sql_database in SqlConfig),
+                    name_of!(sql_port in SqlConfig),
+                ]),
+        )
+        .arg(
+            Arg::with_name(name_of!(postgres_connection in ServerConfig))
+                .long("postgres")
+                .value_name("POSTGRES_CONNECTION_STRING")
+                .help("serve from postgres instead of sql server. eg: \"host=localhost user=llrs\"")
+                .takes_value(true)
+                .conflicts_with_all(&[
+                    name_of!(sqlite_path in ServerConfig),
+                    name_of!(sql_user in SqlConfig),
+                    name_of!(sql_pass in SqlConfig),
+                    name_of!(sql_domain in SqlConfig),
+                    name_of!(sql_database in SqlConfig),
+                    name_of!(sql_port in SqlConfig),
+                ]),
+        )
+        .arg(
+            Arg::with_name(name_of!(fixture_path in ServerConfig))
+                .long("fixture")
+                .value_name("FIXTURE_JSON_PATH")
+                .help("serve a json fixture from memory instead of sql server")
+                .takes_value(true)
+                .conflicts_with_all(&[
+                    name_of!(sqlite_path in ServerConfig),
+                    name_of!(postgres_connection in ServerConfig),
+                    name_of!(sql_user in SqlConfig),
+                    name_of!(sql_pass in SqlConfig),
+                    name_of!(sql_domain in SqlConfig),
+                    name_of!(sql_database in SqlConfig),
+                    name_of!(sql_port in SqlConfig),
+                ]),
+        )
+        .arg(
+            Arg::with_name(name_of!(api_token in ServerConfig))
+                .long("api-token")
+                .value_name("API_TOKEN")
+                .env("LLRS_API_TOKEN")
+                .hide_env_values(true)
+                .help("bearer token allowing writes, writes are disabled without one")
+                .takes_value(true),
+        )
+        .arg(Arg::with_name("cache").long("cache").help(
+            "cache manga, chapter and page lists in memory, writes made through \
+                     another llrs-api or straight to the database go unseen until they expire",
+        ))
+        .arg(
+            Arg::with_name("cache_list_ttl_secs")
+                .long("cache-list-ttl")
+                .value_name("SECONDS")
+                .help("how long manga and chapter lists are cached with --cache")
+                .takes_value(true)
+                .default_value("60"),
+        )
+        .arg(
+            Arg::with_name("cache_page_ttl_secs")
+                .long("cache-page-ttl")
+                .value_name("SECONDS")
+                .help("how long page lists are cached with --cache")
+                .takes_value(true)
+                .default_value("3600"),
+        )
+        .arg(cache_control_arg(
+            "cache_control_manga",
+            "cache-control-manga",
+            "cache policy of manga listings, searches and the latest releases, \
+             default: public, max-age=60",
+        ))
+        .arg(cache_control_arg(
+            "cache_control_chapters",
+            "cache-control-chapters",
+            "cache policy of chapter lists and chapters, default: public, max-age=60",
+        ))
+        .arg(cache_control_arg(
+            "cache_control_pages",
+            "cache-control-pages",
+            "cache policy of page lists, default: public, max-age=3600",
+        ))
+        .arg(cache_control_arg(
+            "cache_control_feeds",
+            "cache-control-feeds",
+            "cache policy of RSS, Atom and OPDS, default: public, max-age=300",
+        ))
+        .arg(
+            Arg::with_name("compression_min_size")
+                .long("compression-min-size")
+                .value_name("BYTES")
+                .help("smallest response compressed when the client accepts it")
+                .takes_value(true)
+                .default_value("1024"),
+        )
+        .arg(compression_level_arg(
+            "gzip_level",
+            "gzip-level",
+            "gzip compression level, from 0 to 9",
+            "6",
+        ))
+        .arg(compression_level_arg(
+            "deflate_level",
+            "deflate-level",
+            "deflate compression level, from 0 to 9",
+            "6",
+        ))
+        .arg(compression_level_arg(
+            "brotli_level",
+            "brotli-level",
+            "brotli compression level, from 0 to 11",
+            "5",
+        ))
+        .arg(
+            Arg::with_name(name_of!(site_url in ServerConfig))
+                .long("site-url")
+                .value_name("URL")
+                .env("LLRS_SITE_URL")
+                .help("where llrs-site is served, feeds link to its chapters")
+                .takes_value(true)
+                .default_value("http://localhost:8000"),
+        )
+        .subcommand(migrate::subcommand())
         .get_matches();
+    let migrate_command = arg_matches
+        .subcommand_matches("migrate")
+        .map(MigrateCommand::from);
     let config = ServerConfig::from(arg_matches);
 
-    let db_config = Config {
-        auth: Auth::Sql {
-            user: config.sql_config.sql_user,
-            pass: config.sql_config.sql_pass,
-        },
-        database: Some(config.sql_config.sql_database),
-        host: config.sql_config.sql_domain,
-        port: config.sql_config.sql_port,
-        trust_cert: true,
-    };
-
-    // TODO: Connection pooling with deadpool? or just Arc<Waifuims>
-    let config_copy = db_config.clone();
-    let list_manga = warp::path::end().and_then(move || {
-        let db_config = config_copy.clone();
-        async move {
-            let mut llrs = Waifusims::new(db_config.clone()).await.expect("ok");
-            match llrs.get_all_manga_titles().await {
-                Ok(mangas) => Ok::<warp::reply::Json, warp::Rejection>(warp::reply::json(&mangas)),
-                Err(err) => Err(Error::from(err).into()),
+    if let Some(command) = migrate_command {
+        let mut database: Box<dyn Migrate> = match (
+            config.sqlite_path,
+            config.postgres_connection,
+            config.fixture_path,
+            config.sql_config,
+        ) {
+            (Some(sqlite_path), _, _, _) => Box::new(or_exit(
+                SqliteWaifusims::open(&sqlite_path),
+                "could not open sqlite db",
+            )),
+            (None, Some(postgres_connection), _, _) => Box::new(or_exit(
+                PostgresWaifusims::new(&postgres_connection).await,
+                "could not connect to postgres",
+            )),
+            (None, None, Some(_), _) => {
+                error!("fixtures are loaded from json, there is no schema to migrate");
+                std::process::exit(1);
             }
-        }
-    });
-
-    // TODO: return message for id? < 0
-    let config_copy = db_config.clone();
-    let list_chapters = warp::path!("manga" / i32).and_then(move |manga_id| {
-        let db_config = config_copy.clone();
-        async move {
-            let mut llrs = Waifusims::new(db_config.clone()).await.expect("ok");
-            match llrs.get_manga_chapters(manga_id).await {
-                Ok(mangas) => Ok::<warp::reply::Json, warp::Rejection>(warp::reply::json(&mangas)),
-                Err(err) => Err(Error::from(err).into()),
+            (None, None, None, Some(sql_config)) => {
+                let db_config = sql_server_config(sql_config);
+                Box::new(or_exit(
+                    Waifusims::new(db_config).await,
+                    "could not connect to sql server",
+                ))
             }
-        }
-    });
-
-    // TODO: return message for id? < 0
-    let config_copy = db_config.clone();
-    let list_pages =
-        warp::path!("manga" / i32 / String).and_then(move |manga_id, chapter_number: String| {
-            let db_config = config_copy.clone();
-            async move {
-                let mut llrs = Waifusims::new(db_config.clone()).await.expect("ok");
-                match llrs.get_pages(manga_id, &chapter_number).await {
-                    Ok(mangas) => {
-                        Ok::<warp::reply::Json, warp::Rejection>(warp::reply::json(&mangas))
-                    }
-                    Err(err) => Err(Error::from(err).into()),
-                }
+            (None, None, None, None) => {
+                unreachable!("clap requires sql server args without another db")
             }
-        });
+        };
+        if let Err(err) = migrate::run(command, database.as_mut()).await {
+            error!("{}", err);
+            std::process::exit(1);
+        }
+        return;
+    }
 
-    let routes = list_manga
-        .or(list_chapters)
-        .or(list_pages)
-        .with(warp::cors().allow_any_origin());
+    let backend = match (
+        config.sqlite_path,
+        config.postgres_connection,
+        config.fixture_path,
+        config.sql_config,
+    ) {
+        (Some(sqlite_path), _, _, _) => {
+            let mut waifusims = or_exit(
+                SqliteWaifusims::open(&sqlite_path),
+                "could not open sqlite db",
+            );
+            log_applied(waifusims.migrate_up(None).await);
+            Backend::Sqlite(waifusims)
+        }
+        (None, Some(postgres_connection), _, _) => {
+            let mut waifusims = or_exit(
+                PostgresWaifusims::new(&postgres_connection).await,
+                "could not connect to postgres",
+            );
+            log_applied(waifusims.migrate_up(None).await);
+            Backend::Postgres(PostgresPool::new(&postgres_connection, config.pool_config))
+        }
+        (None, None, Some(fixture_path), _) => Backend::Memory(or_exit(
+            InMemoryMangaService::from_json_file(&fixture_path),
+            "could not load fixture json",
+        )),
+        // SQL Server is shared, so its schema only changes through `migrate`
+        (None, None, None, Some(sql_config)) => {
+            let db_config = sql_server_config(sql_config);
+            Backend::SqlServer(WaifusimsPool::new(db_config, config.pool_config))
+        }
+        (None, None, None, None) => {
+            unreachable!("clap requires sql server args without another db")
+        }
+    };
 
-    warp::serve(routes).run(config.addr).await;
-}
+    // Writes through the API invalidate the cache, direct database edits wait out the TTLs
+    let backend = match config.cache_config {
+        Some(cache_config) => Backend::Cached {
+            backend: Box::new(backend),
+            cache: MangaCache::new(cache_config),
+        },
+        None => backend,
+    };
 
-#[derive(Debug)]
-struct Error {
-    inner: WaifusimsError,
-}
+    let routes = routes::routes(
+        backend,
+        RouteConfig {
+            api_token: config.api_token,
+            site_url: config.site_url,
+            cache_control: config.cache_control,
+            compression: config.compression,
+        },
+    );
 
-impl From<WaifusimsError> for Error {
-    fn from(error: WaifusimsError) -> Error {
-        Error { inner: error }
-    }
+    warp::serve(routes).run(config.addr).await;
 }
-
-impl warp::reject::Reject for Error {}
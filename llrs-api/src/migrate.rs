@@ -0,0 +1,170 @@
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use libllrs::{Migrate, Migration, Result};
+
+/// `migrate` subcommand, run against whichever database the other arguments select.
+#[derive(Debug, PartialEq)]
+pub(crate) enum MigrateCommand {
+    /// Apply pending migrations up to `to`, after recording `baseline` as already applied
+    Up {
+        to: Option<i64>,
+        baseline: Option<i64>,
+    },
+    /// Revert migrations newer than `to`, only the latest when `None`
+    Down {
+        to: Option<i64>,
+    },
+    Status,
+}
+
+fn version_arg<'a, 'b>(name: &'a str, help: &'a str) -> Arg<'a, 'b> {
+    Arg::with_name(name)
+        .long(name)
+        .value_name("VERSION")
+        .help(help)
+        .takes_value(true)
+}
+
+pub(crate) fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("migrate")
+        .about("manage the database schema, eg: llrs-api --sqlite waifusims.db migrate up")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(
+            SubCommand::with_name("up")
+                .about("apply pending migrations")
+                .arg(version_arg("to", "stop after this version"))
+                .arg(version_arg(
+                    "baseline",
+                    "first record migrations up to this version as applied, \
+                     for databases created before migrations whose tables already match it",
+                )),
+        )
+        .subcommand(
+            SubCommand::with_name("down")
+                .about("revert the latest migration")
+                .arg(version_arg(
+                    "to",
+                    "revert every migration after this version, 0 for all",
+                )),
+        )
+        .subcommand(SubCommand::with_name("status").about("list applied and pending migrations"))
+}
+
+fn version(arg_matches: &ArgMatches, name: &str) -> Option<i64> {
+    arg_matches
+        .value_of(name)
+        .map(|version| version.parse::<i64>().expect("invalid migration version"))
+}
+
+impl<'a> From<&ArgMatches<'a>> for MigrateCommand {
+    fn from(arg_matches: &ArgMatches<'a>) -> Self {
+        match arg_matches.subcommand() {
+            ("up", Some(up)) => MigrateCommand::Up {
+                to: version(up, "to"),
+                baseline: version(up, "baseline"),
+            },
+            ("down", Some(down)) => MigrateCommand::Down {
+                to: version(down, "to"),
+            },
+            _ => MigrateCommand::Status,
+        }
+    }
+}
+
+fn print_migrations(action: &str, migrations: &[&Migration]) {
+    if migrations.is_empty() {
+        println!("Nothing to {}", action);
+    }
+    for migration in migrations {
+        println!("{} {}", action, migration.name);
+    }
+}
+
+pub(crate) async fn run(command: MigrateCommand, database: &mut dyn Migrate) -> Result<()> {
+    match command {
+        MigrateCommand::Up { to, baseline } => {
+            if let Some(baseline) = baseline {
+                print_migrations("Recorded", &database.baseline(baseline).await?);
+            }
+            print_migrations("Applied", &database.migrate_up(to).await?);
+        }
+        MigrateCommand::Down { to } => {
+            let to = match to {
+                Some(to) => to,
+                None => {
+                    let applied = database
+                        .migration_status()
+                        .await?
+                        .into_iter()
+                        .filter(|status| status.applied_at.is_some())
+                        .map(|status| status.version)
+                        .collect::<Vec<i64>>();
+                    applied
+                        .len()
+                        .checked_sub(2)
+                        .map_or(0, |previous| applied[previous])
+                }
+            };
+            print_migrations("Reverted", &database.migrate_down(to).await?);
+        }
+        MigrateCommand::Status => {
+            for status in database.migration_status().await? {
+                let state = match status.applied_at {
+                    Some(applied_at) => format!("applied {}", applied_at),
+                    None => "pending".to_owned(),
+                };
+                let unknown = if status.embedded {
+                    ""
+                } else {
+                    " (unknown to this build)"
+                };
+                println!(
+                    "{:>4} {:<32} {}{}",
+                    status.version, status.name, state, unknown
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> MigrateCommand {
+        let arg_matches = App::new("test")
+            .subcommand(subcommand())
+            .get_matches_from(args);
+        MigrateCommand::from(arg_matches.subcommand_matches("migrate").unwrap())
+    }
+
+    #[test]
+    fn parses_migrate_subcommands() {
+        assert_eq!(
+            parse(&["test", "migrate", "status"]),
+            MigrateCommand::Status
+        );
+        assert_eq!(
+            parse(&["test", "migrate", "up", "--to", "3"]),
+            MigrateCommand::Up {
+                to: Some(3),
+                baseline: None,
+            }
+        );
+        assert_eq!(
+            parse(&["test", "migrate", "up", "--baseline", "4"]),
+            MigrateCommand::Up {
+                to: None,
+                baseline: Some(4),
+            }
+        );
+        assert_eq!(
+            parse(&["test", "migrate", "down"]),
+            MigrateCommand::Down { to: None }
+        );
+        assert_eq!(
+            parse(&["test", "migrate", "down", "--to", "0"]),
+            MigrateCommand::Down { to: Some(0) }
+        );
+    }
+}
@@ -0,0 +1,126 @@
+//! Checks the JSON served by the API against the `llrs_model` types llrs-site
+//! deserializes it into, so the API and site can't drift apart.
+
+use crate::{
+    backend::Backend,
+    routes::{routes, RouteConfig},
+};
+use chrono::NaiveDate;
+use libllrs::{FixturePage, InMemoryMangaService, MangaFixture};
+use llrs_model::{Chapter, Manga, MangaStatus, Page};
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+
+fn test_backend() -> Backend {
+    let date = NaiveDate::from_ymd_opt(2021, 1, 2)
+        .unwrap()
+        .and_hms_opt(3, 4, 5)
+        .unwrap();
+    Backend::Memory(InMemoryMangaService::new(MangaFixture {
+        mangas: vec![Manga {
+            manga_id: 1,
+            manga_name: "Manga".to_owned(),
+            author_names: vec!["Writer".to_owned(), "Co-writer".to_owned()],
+            artist_names: vec!["Artist".to_owned()],
+            cover_image_url: None,
+            purchase_url: Some("https://example.com".to_owned()),
+            status: MangaStatus::Completed,
+            creation_date: Some(date),
+            updated_date: Some(date),
+        }],
+        chapters: vec![Chapter {
+            chapter_number: "Vol.1 Ch.10a".into(),
+            chapter_name: "Chapter".to_owned(),
+            creation_date: date,
+            release_date: None,
+            updated_date: Some(date),
+            manga_id: 1,
+        }],
+        pages: vec![FixturePage {
+            manga_id: 1,
+            chapter_number: "Vol.1 Ch.10a".to_owned(),
+            page: Page {
+                url_string: "page.png".to_owned(),
+                page_number: 1,
+                mirror_urls: vec!["mirror.png".to_owned()],
+            },
+        }],
+        alternate_titles: vec![],
+        ..MangaFixture::default()
+    }))
+}
+
+/// Fetches `path` as llrs-site would and checks the site's types read every field,
+/// by serializing them back and comparing against the original JSON.
+async fn get_as_site<T: DeserializeOwned + serde::Serialize>(path: &str) -> (Value, T) {
+    let response = warp::test::request()
+        .path(path)
+        .reply(&routes(test_backend(), RouteConfig::default()))
+        .await;
+    assert_eq!(response.status(), 200, "GET {}", path);
+    let body: Value = serde_json::from_slice(response.body()).unwrap();
+    let site_value: T = serde_json::from_value(body.clone())
+        .unwrap_or_else(|err| panic!("GET {} doesn't match llrs_model: {}", path, err));
+    assert_eq!(
+        serde_json::to_value(&site_value).unwrap(),
+        body,
+        "GET {}",
+        path
+    );
+    (body, site_value)
+}
+
+#[tokio::test]
+async fn manga_list_matches_site_model() {
+    let (body, mangas) = get_as_site::<Vec<Manga>>("/").await;
+    assert_eq!(
+        body,
+        json!([{
+            "manga_id": 1,
+            "manga_name": "Manga",
+            "author_names": ["Writer", "Co-writer"],
+            "artist_names": ["Artist"],
+            "cover_image_url": null,
+            "purchase_url": "https://example.com",
+            "status": "Completed",
+            "creation_date": "2021-01-02T03:04:05",
+            "updated_date": "2021-01-02T03:04:05"
+        }])
+    );
+    assert_eq!(mangas[0].author_names.len(), 2);
+}
+
+#[tokio::test]
+async fn chapter_list_matches_site_model() {
+    let (body, chapters) = get_as_site::<Vec<Chapter>>("/manga/1").await;
+    assert_eq!(
+        body,
+        json!([{
+            "chapter_number": "Vol.1 Ch.10a",
+            "chapter_name": "Chapter",
+            "creation_date": "2021-01-02T03:04:05",
+            "release_date": null,
+            "updated_date": "2021-01-02T03:04:05",
+            "manga_id": 1
+        }])
+    );
+    assert_eq!(chapters[0].chapter_number.volume(), Some(1));
+}
+
+#[tokio::test]
+async fn page_list_matches_site_model() {
+    let (body, pages) = get_as_site::<Vec<Page>>("/manga/1/Vol.1%20Ch.10a").await;
+    assert_eq!(
+        body,
+        json!([{
+            "url_string": "page.png",
+            "page_number": 1,
+            "mirror_urls": ["mirror.png"]
+        }])
+    );
+    assert_eq!(pages[0].page_number, 1);
+    assert_eq!(
+        pages[0].urls().collect::<Vec<&str>>(),
+        vec!["page.png", "mirror.png"]
+    );
+}
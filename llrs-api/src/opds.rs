@@ -0,0 +1,468 @@
+//! OPDS catalogs for e-reader apps, built from the same `MangaService` calls as the JSON API.
+//!
+//! OPDS 1.2 under `/opds` is Atom, manga are navigation entries and chapters acquisition
+//! entries streamed page by page through the page streaming extension (PSE).
+//! OPDS 2.0 under `/opds/v2` is JSON, chapters are Divina publications listing their images.
+//! Links are absolute paths, so clients resolve them against wherever the API is served.
+
+use crate::feed::{self, encode_segment, escape, rfc_3339};
+use chrono::NaiveDateTime;
+use libllrs::{Chapter, Manga, MangaListing, Page};
+use serde::{Deserialize, Serialize};
+use utoipa::IntoParams;
+
+/// Manga per page of the catalog
+pub(crate) const CATALOG_PAGE_SIZE: u32 = 50;
+
+pub(crate) const NAVIGATION_TYPE: &str =
+    "application/atom+xml;profile=opds-catalog;kind=navigation";
+pub(crate) const ACQUISITION_TYPE: &str =
+    "application/atom+xml;profile=opds-catalog;kind=acquisition";
+pub(crate) const OPDS_JSON_TYPE: &str = "application/opds+json";
+pub(crate) const DIVINA_TYPE: &str = "application/divina+json";
+
+const PSE_STREAM_REL: &str = "http://vaemendis.net/opds-pse/stream";
+const OPEN_ACCESS_REL: &str = "http://opds-spec.org/acquisition/open-access";
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct CatalogQuery {
+    /// Manga to skip from the start of the catalog
+    #[serde(default)]
+    pub(crate) offset: u32,
+}
+
+/// A chapter with its number of pages, PSE clients need the count before streaming.
+#[derive(Debug)]
+pub(crate) struct CountedChapter {
+    pub(crate) chapter: Chapter,
+    pub(crate) page_count: usize,
+}
+
+fn catalog_path(root: &str, offset: u32) -> String {
+    if offset == 0 {
+        root.to_owned()
+    } else {
+        format!("{}?offset={}", root, offset)
+    }
+}
+
+fn manga_path(root: &str, manga_id: i32) -> String {
+    format!("{}/manga/{}", root, manga_id)
+}
+
+fn chapter_path(root: &str, chapter: &Chapter) -> String {
+    format!(
+        "{}/{}",
+        manga_path(root, chapter.manga_id),
+        encode_segment(&chapter.chapter_number.to_string())
+    )
+}
+
+/// Offsets of the previous and next catalog pages, when there are any.
+fn neighbour_offsets(listing: &MangaListing, offset: u32) -> (Option<u32>, Option<u32>) {
+    let previous = if offset > 0 {
+        Some(offset.saturating_sub(CATALOG_PAGE_SIZE))
+    } else {
+        None
+    };
+    let next = offset + CATALOG_PAGE_SIZE;
+    let next = if u64::from(next) < listing.total {
+        Some(next)
+    } else {
+        None
+    };
+    (previous, next)
+}
+
+fn chapter_title(chapter: &Chapter) -> String {
+    format!(
+        "Chapter {}: {}",
+        chapter.chapter_number, chapter.chapter_name
+    )
+}
+
+/// Images are served from wherever they were scraped, so their type is guessed from the URL.
+fn image_type(url: &str) -> &'static str {
+    let path = url.split(['?', '#']).next().unwrap_or(url).to_lowercase();
+    if path.ends_with(".png") {
+        "image/png"
+    } else if path.ends_with(".gif") {
+        "image/gif"
+    } else if path.ends_with(".webp") {
+        "image/webp"
+    } else {
+        "image/jpeg"
+    }
+}
+
+fn atom_link(rel: &str, href: &str, link_type: &str) -> String {
+    format!(
+        "<link rel=\"{}\" href=\"{}\" type=\"{}\"/>",
+        escape(rel),
+        escape(href),
+        escape(link_type)
+    )
+}
+
+fn atom_feed(id: &str, title: &str, updated: NaiveDateTime, links: &str, entries: &str) -> String {
+    format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\
+         <feed xmlns=\"http://www.w3.org/2005/Atom\" \
+         xmlns:opds=\"http://opds-spec.org/2010/catalog\" \
+         xmlns:pse=\"http://vaemendis.net/opds-pse/ns\">\
+         <id>{}</id><title>{}</title><updated>{}</updated>\
+         <author><name>llrs</name></author>{}{}</feed>",
+        escape(id),
+        escape(title),
+        rfc_3339(updated),
+        links,
+        entries
+    )
+}
+
+/// OPDS 1.2 navigation feed of one page of manga, alphabetically.
+pub(crate) fn catalog_feed(listing: &MangaListing, offset: u32) -> String {
+    let root = "/opds";
+    let mut links = atom_link("self", &catalog_path(root, offset), NAVIGATION_TYPE);
+    links.push_str(&atom_link("start", root, NAVIGATION_TYPE));
+    links.push_str(&atom_link("alternate", "/opds/v2", OPDS_JSON_TYPE));
+    let (previous, next) = neighbour_offsets(listing, offset);
+    if let Some(previous) = previous {
+        links.push_str(&atom_link(
+            "previous",
+            &catalog_path(root, previous),
+            NAVIGATION_TYPE,
+        ));
+    }
+    if let Some(next) = next {
+        links.push_str(&atom_link(
+            "next",
+            &catalog_path(root, next),
+            NAVIGATION_TYPE,
+        ));
+    }
+    let mut entries = String::new();
+    for manga in &listing.mangas {
+        let path = manga_path(root, manga.manga_id);
+        entries.push_str(&format!(
+            "<entry><id>urn:llrs:manga:{}</id><title>{}</title><updated>{}</updated>",
+            manga.manga_id,
+            escape(&manga.manga_name),
+            rfc_3339(manga.creation_date.unwrap_or_default()),
+        ));
+        for author_name in &manga.author_names {
+            entries.push_str(&format!(
+                "<author><name>{}</name></author>",
+                escape(author_name)
+            ));
+        }
+        entries.push_str(&atom_link("subsection", &path, ACQUISITION_TYPE));
+        if let Some(cover_image_url) = &manga.cover_image_url {
+            let cover_type = image_type(cover_image_url);
+            entries.push_str(&atom_link(
+                "http://opds-spec.org/image",
+                cover_image_url,
+                cover_type,
+            ));
+            entries.push_str(&atom_link(
+                "http://opds-spec.org/image/thumbnail",
+                cover_image_url,
+                cover_type,
+            ));
+        }
+        entries.push_str("</entry>");
+    }
+    let updated = listing
+        .mangas
+        .iter()
+        .filter_map(|manga| manga.creation_date)
+        .max()
+        .unwrap_or_default();
+    atom_feed("urn:llrs:catalog", "llrs", updated, &links, &entries)
+}
+
+/// OPDS 1.2 acquisition feed of a manga's chapters. Each chapter opens on the site
+/// and streams its pages through `/opds/manga/{id}/{chapter}/{pageNumber}`.
+pub(crate) fn manga_feed(site_url: &str, manga: &Manga, chapters: &[CountedChapter]) -> String {
+    let root = "/opds";
+    let mut links = atom_link("self", &manga_path(root, manga.manga_id), ACQUISITION_TYPE);
+    links.push_str(&atom_link("start", root, NAVIGATION_TYPE));
+    links.push_str(&atom_link("up", root, NAVIGATION_TYPE));
+    let mut entries = String::new();
+    for CountedChapter {
+        chapter,
+        page_count,
+    } in chapters
+    {
+        entries.push_str(&format!(
+            "<entry><id>urn:llrs:manga:{}:chapter:{}</id><title>{}</title><updated>{}</updated>",
+            chapter.manga_id,
+            escape(&encode_segment(&chapter.chapter_number.to_string())),
+            escape(&chapter_title(chapter)),
+            rfc_3339(feed::released(chapter)),
+        ));
+        entries.push_str(&atom_link(
+            OPEN_ACCESS_REL,
+            &feed::chapter_url(site_url, chapter),
+            "text/html",
+        ));
+        // PSE clients substitute `{pageNumber}`, counting from 0
+        entries.push_str(&format!(
+            "<link rel=\"{}\" href=\"{}/{{pageNumber}}\" type=\"image/jpeg\" pse:count=\"{}\"/>",
+            PSE_STREAM_REL,
+            escape(&chapter_path(root, chapter)),
+            page_count
+        ));
+        entries.push_str("</entry>");
+    }
+    let updated = chapters
+        .iter()
+        .map(|counted| feed::released(&counted.chapter))
+        .max()
+        .unwrap_or_default();
+    atom_feed(
+        &format!("urn:llrs:manga:{}", manga.manga_id),
+        &manga.manga_name,
+        updated,
+        &links,
+        &entries,
+    )
+}
+
+/// A link of an OPDS 2.0 feed or a Readium Web Publication Manifest.
+#[derive(Debug, Serialize)]
+pub(crate) struct Link {
+    href: String,
+    #[serde(rename = "type")]
+    link_type: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rel: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+    /// Other copies of the same resource, a page's mirrors
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    alternate: Vec<Link>,
+}
+
+impl Link {
+    fn new(rel: &'static str, href: String, link_type: &'static str) -> Link {
+        Link {
+            href,
+            link_type,
+            rel: Some(rel),
+            title: None,
+            alternate: vec![],
+        }
+    }
+
+    /// An image, typed from its URL
+    fn image(href: &str) -> Link {
+        Link {
+            href: href.to_owned(),
+            link_type: image_type(href),
+            rel: None,
+            title: None,
+            alternate: vec![],
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct FeedMetadata {
+    title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    number_of_items: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    items_per_page: Option<u32>,
+}
+
+/// OPDS 2.0 feed, holding either navigation links or publications.
+#[derive(Debug, Serialize)]
+pub(crate) struct CatalogV2 {
+    metadata: FeedMetadata,
+    links: Vec<Link>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    navigation: Vec<Link>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    publications: Vec<Publication>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PublicationMetadata {
+    #[serde(rename = "@type")]
+    schema_type: &'static str,
+    identifier: String,
+    title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    published: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    author: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    artist: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    conforms_to: Option<&'static str>,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct Publication {
+    metadata: PublicationMetadata,
+    links: Vec<Link>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    images: Vec<Link>,
+}
+
+/// Readium Web Publication Manifest of a chapter, following the Divina profile for comics.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Manifest {
+    #[serde(rename = "@context")]
+    context: &'static str,
+    metadata: PublicationMetadata,
+    links: Vec<Link>,
+    reading_order: Vec<Link>,
+}
+
+/// OPDS 2.0 navigation feed of one page of manga, alphabetically.
+pub(crate) fn catalog_v2(listing: &MangaListing, offset: u32) -> CatalogV2 {
+    let root = "/opds/v2";
+    let mut links = vec![
+        Link::new("self", catalog_path(root, offset), OPDS_JSON_TYPE),
+        Link::new("start", root.to_owned(), OPDS_JSON_TYPE),
+    ];
+    let (previous, next) = neighbour_offsets(listing, offset);
+    if let Some(previous) = previous {
+        links.push(Link::new(
+            "previous",
+            catalog_path(root, previous),
+            OPDS_JSON_TYPE,
+        ));
+    }
+    if let Some(next) = next {
+        links.push(Link::new("next", catalog_path(root, next), OPDS_JSON_TYPE));
+    }
+    CatalogV2 {
+        metadata: FeedMetadata {
+            title: "llrs".to_owned(),
+            number_of_items: Some(listing.total),
+            items_per_page: Some(CATALOG_PAGE_SIZE),
+        },
+        links,
+        navigation: listing
+            .mangas
+            .iter()
+            .map(|manga| Link {
+                title: Some(manga.manga_name.clone()),
+                ..Link::new(
+                    "subsection",
+                    manga_path(root, manga.manga_id),
+                    OPDS_JSON_TYPE,
+                )
+            })
+            .collect(),
+        publications: vec![],
+    }
+}
+
+fn chapter_metadata(manga: &Manga, chapter: &Chapter) -> PublicationMetadata {
+    PublicationMetadata {
+        schema_type: "http://schema.org/ComicIssue",
+        identifier: format!(
+            "urn:llrs:manga:{}:chapter:{}",
+            manga.manga_id,
+            encode_segment(&chapter.chapter_number.to_string())
+        ),
+        title: format!("{} {}", manga.manga_name, chapter_title(chapter)),
+        published: Some(rfc_3339(feed::released(chapter))),
+        author: manga.author_names.clone(),
+        artist: manga.artist_names.clone(),
+        conforms_to: None,
+    }
+}
+
+/// OPDS 2.0 feed of a manga's chapters, each a publication opening its Divina manifest.
+pub(crate) fn manga_v2(manga: &Manga, chapters: &[Chapter]) -> CatalogV2 {
+    let root = "/opds/v2";
+    CatalogV2 {
+        metadata: FeedMetadata {
+            title: manga.manga_name.clone(),
+            number_of_items: Some(chapters.len() as u64),
+            items_per_page: None,
+        },
+        links: vec![
+            Link::new("self", manga_path(root, manga.manga_id), OPDS_JSON_TYPE),
+            Link::new("start", root.to_owned(), OPDS_JSON_TYPE),
+            Link::new("up", root.to_owned(), OPDS_JSON_TYPE),
+        ],
+        navigation: vec![],
+        publications: chapters
+            .iter()
+            .map(|chapter| Publication {
+                metadata: chapter_metadata(manga, chapter),
+                links: vec![Link::new(
+                    OPEN_ACCESS_REL,
+                    chapter_path(root, chapter),
+                    DIVINA_TYPE,
+                )],
+                images: manga
+                    .cover_image_url
+                    .iter()
+                    .map(|cover_image_url| Link::image(cover_image_url))
+                    .collect(),
+            })
+            .collect(),
+    }
+}
+
+/// Divina manifest reading a chapter's pages in order, mirrors as alternates.
+pub(crate) fn chapter_manifest(manga: &Manga, chapter: &Chapter, pages: &[Page]) -> Manifest {
+    Manifest {
+        context: "https://readium.org/webpub-manifest/context.jsonld",
+        metadata: PublicationMetadata {
+            conforms_to: Some("https://readium.org/webpub-manifest/profiles/divina"),
+            ..chapter_metadata(manga, chapter)
+        },
+        links: vec![Link::new(
+            "self",
+            chapter_path("/opds/v2", chapter),
+            DIVINA_TYPE,
+        )],
+        reading_order: pages
+            .iter()
+            .map(|page| Link {
+                alternate: page
+                    .mirror_urls
+                    .iter()
+                    .map(|mirror_url| Link::image(mirror_url))
+                    .collect(),
+                ..Link::image(&page.url_string)
+            })
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn guesses_image_types_from_urls() {
+        assert_eq!(image_type("https://cdn/1.PNG"), "image/png");
+        assert_eq!(image_type("https://cdn/1.webp?w=800"), "image/webp");
+        assert_eq!(image_type("https://cdn/1"), "image/jpeg");
+    }
+
+    #[test]
+    fn pages_through_the_catalog() {
+        let listing = |total| MangaListing {
+            mangas: vec![],
+            total,
+        };
+        assert_eq!(neighbour_offsets(&listing(10), 0), (None, None));
+        assert_eq!(neighbour_offsets(&listing(120), 0), (None, Some(50)));
+        assert_eq!(neighbour_offsets(&listing(120), 60), (Some(10), Some(110)));
+        assert_eq!(neighbour_offsets(&listing(120), 100), (Some(50), None));
+    }
+}
@@ -0,0 +1,113 @@
+//! OpenAPI 3 description of the routes, generated from the `#[utoipa::path]` annotations
+//! on the handlers in `routes` and the schemas of the model types.
+//!
+//! `openapi.json` at the crate root is the committed copy, a test fails when it's stale.
+
+use crate::routes;
+use libllrs::{
+    Chapter, Creator, CreatorRole, Manga, MangaSort, MangaStatus, NewChapter, NewManga, NewPage,
+    Page, RecentChapter, SearchField, SearchResult,
+};
+use utoipa::{
+    openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme},
+    Modify, OpenApi,
+};
+
+/// Offline documentation of `/openapi.json`, nothing is fetched from elsewhere.
+pub(crate) const DOCS_PAGE: &str = include_str!("../static/docs.html");
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "llrs-api",
+        description = "Manga, chapters and pages of the Waifusims database"
+    ),
+    servers(
+        (url = "/v1", description = "Current version"),
+        (url = "/", description = "Unversioned aliases of v1"),
+    ),
+    paths(
+        routes::list_manga,
+        routes::search,
+        routes::recent_chapters,
+        routes::recent_feed,
+        routes::opds_catalog,
+        routes::opds_manga,
+        routes::opds_page,
+        routes::opds_catalog_v2,
+        routes::opds_manga_v2,
+        routes::opds_manifest,
+        routes::list_chapters,
+        routes::manga_info,
+        routes::manga_feed,
+        routes::chapter_info,
+        routes::list_pages,
+        routes::graphql,
+        routes::create_manga,
+        routes::update_manga,
+        routes::delete_manga,
+        routes::create_chapter,
+        routes::update_chapter,
+        routes::delete_chapter,
+    ),
+    components(schemas(
+        Manga,
+        MangaStatus,
+        MangaSort,
+        Chapter,
+        Page,
+        SearchResult,
+        SearchField,
+        RecentChapter,
+        Creator,
+        CreatorRole,
+        NewManga,
+        NewChapter,
+        NewPage,
+        routes::ErrorBody,
+    )),
+    modifiers(&Amendments),
+    tags(
+        (name = "manga", description = "Listing, searching and editing manga"),
+        (name = "chapters", description = "Chapters of a manga and their pages"),
+        (name = "feeds", description = "RSS and Atom feeds of new chapters"),
+        (name = "opds", description = "OPDS 1.2 and 2.0 catalogs for e-reader apps"),
+        (name = "graphql", description = "Manga, chapters and pages in one query"),
+    )
+)]
+pub(crate) struct ApiDoc;
+
+/// Declares the `bearer` scheme the write routes require, and drops the empty license
+/// utoipa reads from a Cargo.toml without one.
+struct Amendments;
+
+impl Modify for Amendments {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        openapi.info.license = None;
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearer",
+            SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Regenerate `openapi.json` with `UPDATE_OPENAPI=1 cargo test -p llrs-api`
+    #[test]
+    fn committed_spec_is_up_to_date() {
+        let generated = ApiDoc::openapi().to_pretty_json().unwrap() + "\n";
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/openapi.json");
+        if std::env::var_os("UPDATE_OPENAPI").is_some() {
+            std::fs::write(path, &generated).unwrap();
+        }
+        let committed = std::fs::read_to_string(path).unwrap_or_default();
+        assert!(
+            committed == generated,
+            "openapi.json is stale, regenerate it with UPDATE_OPENAPI=1 cargo test -p llrs-api"
+        );
+    }
+}
@@ -0,0 +1,2086 @@
+use crate::{
+    backend::{Backend, BoxedMangaService},
+    compression::{self, CompressionConfig},
+    feed::{self, Feed, FeedQuery},
+    graphql::{self, LlrsSchema},
+    http_cache::{self, cache_control, with_last_modified, CachePolicies},
+    opds::{self, CatalogQuery, CountedChapter},
+    openapi::{self, ApiDoc},
+    version::{versioned_with_aliases, DEPRECATION_HEADER, SUNSET_HEADER, V1},
+};
+use libllrs::{
+    Chapter, Error as WaifusimsError, Manga, MangaListQuery, NewChapter, NewManga, Page,
+    RecentChapter, RecentChaptersQuery, SearchResult,
+};
+use log::error;
+use percent_encoding::percent_decode_str;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{convert::Infallible, sync::Arc};
+use utoipa::{IntoParams, OpenApi, ToSchema};
+use uuid::Uuid;
+use warp::{
+    http::{header, HeaderMap, StatusCode},
+    Filter, Rejection, Reply,
+};
+
+/// Total number of manga matching a listing, the body only holds the requested slice
+const TOTAL_COUNT_HEADER: &str = "x-total-count";
+
+/// Identifies a request in responses and logs, kept from the request when a proxy set one
+const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Longest request ID taken from a request, longer ones are replaced
+const MAX_REQUEST_ID_LENGTH: usize = 64;
+
+/// Results returned by `/search` without a `limit`
+const DEFAULT_SEARCH_LIMIT: u32 = 20;
+
+/// Most results `/search` returns, larger limits are clamped
+const MAX_SEARCH_LIMIT: u32 = 50;
+
+/// Chapters returned by `/recent` without a `limit`
+const DEFAULT_RECENT_LIMIT: u32 = 20;
+
+/// Most chapters `/recent` returns, larger limits are clamped
+const MAX_RECENT_LIMIT: u32 = 100;
+
+/// Most chapters in a feed, readers poll often enough that older ones were already seen
+const FEED_ENTRY_LIMIT: u32 = 50;
+
+/// Largest JSON body accepted by writes, a chapter with a few hundred mirrored pages fits
+const JSON_BODY_LIMIT: u64 = 4 * 1024 * 1024;
+
+/// Settings of the routes besides the backend.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct RouteConfig {
+    /// Bearer token allowing writes, writes are rejected without one
+    pub api_token: Option<String>,
+    /// Where llrs-site is served, feeds link to its pages, eg: `https://llrs.example`
+    pub site_url: String,
+    pub cache_control: CachePolicies,
+    pub compression: CompressionConfig,
+}
+
+/// Builds every route, writes require `Authorization: Bearer <api_token>`
+/// and are rejected when there's no `api_token`.
+/// Every response carries a request ID, failures answer with an `ErrorBody`.
+pub(crate) fn routes(
+    backend: Backend,
+    config: RouteConfig,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    let site_url: Arc<str> = Arc::from(config.site_url);
+    let cache_policies = config.cache_control;
+    let compression = config.compression;
+    let spec: Arc<str> = Arc::from(
+        ApiDoc::openapi()
+            .to_json()
+            .expect("the OpenAPI document serializes"),
+    );
+    let openapi_json = warp::path!("openapi.json")
+        .and(warp::get())
+        .map(move || with_content_type(spec.to_string(), "application/json"));
+    let docs = warp::path!("docs")
+        .and(warp::get())
+        .map(|| warp::reply::html(openapi::DOCS_PAGE));
+    let list_manga = warp::path::end()
+        .and(warp::get())
+        .and(warp::query::<MangaListQuery>())
+        .and(with_backend(backend.clone()))
+        .and_then(list_manga)
+        .map(cache_control(&cache_policies.manga));
+    let search = warp::path!("search")
+        .and(warp::get())
+        .and(warp::query::<SearchQuery>())
+        .and(with_backend(backend.clone()))
+        .and_then(search)
+        .map(cache_control(&cache_policies.manga));
+    let recent_chapters = warp::path!("recent")
+        .and(warp::get())
+        .and(warp::query::<RecentChaptersQuery>())
+        .and(with_backend(backend.clone()))
+        .and_then(recent_chapters)
+        .map(cache_control(&cache_policies.manga));
+    let recent_feed = warp::path!("feed.xml")
+        .and(warp::get())
+        .and(warp::query::<FeedQuery>())
+        .and(with_backend(backend.clone()))
+        .and(with_site_url(site_url.clone()))
+        .and_then(recent_feed)
+        .map(cache_control(&cache_policies.feeds));
+    let manga_feed = warp::path!("manga" / i32 / "feed.xml")
+        .and(warp::get())
+        .and(warp::query::<FeedQuery>())
+        .and(with_backend(backend.clone()))
+        .and(with_site_url(site_url.clone()))
+        .and_then(manga_feed)
+        .map(cache_control(&cache_policies.feeds));
+    let opds_catalog = warp::path!("opds")
+        .and(warp::get())
+        .and(warp::query::<CatalogQuery>())
+        .and(with_backend(backend.clone()))
+        .and_then(opds_catalog)
+        .map(cache_control(&cache_policies.feeds));
+    let opds_manga = warp::path!("opds" / "manga" / i32)
+        .and(warp::get())
+        .and(with_backend(backend.clone()))
+        .and(with_site_url(site_url))
+        .and_then(opds_manga)
+        .map(cache_control(&cache_policies.feeds));
+    let opds_page = warp::path!("opds" / "manga" / i32 / String / usize)
+        .and(warp::get())
+        .and(with_backend(backend.clone()))
+        .and_then(opds_page)
+        .map(cache_control(&cache_policies.pages));
+    let opds_catalog_v2 = warp::path!("opds" / "v2")
+        .and(warp::get())
+        .and(warp::query::<CatalogQuery>())
+        .and(with_backend(backend.clone()))
+        .and_then(opds_catalog_v2)
+        .map(cache_control(&cache_policies.feeds));
+    let opds_manga_v2 = warp::path!("opds" / "v2" / "manga" / i32)
+        .and(warp::get())
+        .and(with_backend(backend.clone()))
+        .and_then(opds_manga_v2)
+        .map(cache_control(&cache_policies.feeds));
+    let opds_manifest = warp::path!("opds" / "v2" / "manga" / i32 / String)
+        .and(warp::get())
+        .and(with_backend(backend.clone()))
+        .and_then(opds_manifest)
+        .map(cache_control(&cache_policies.pages));
+    let opds = opds_catalog
+        .or(opds_manga)
+        .or(opds_page)
+        .or(opds_catalog_v2)
+        .or(opds_manga_v2)
+        .or(opds_manifest);
+    let list_chapters = warp::path!("manga" / i32)
+        .and(warp::get())
+        .and(with_backend(backend.clone()))
+        .and_then(list_chapters)
+        .map(cache_control(&cache_policies.chapters));
+    let manga_info = warp::path!("manga" / i32 / "info")
+        .and(warp::get())
+        .and(with_backend(backend.clone()))
+        .and_then(manga_info)
+        .map(cache_control(&cache_policies.manga));
+    let chapter_info = warp::path!("manga" / i32 / String / "info")
+        .and(warp::get())
+        .and(with_backend(backend.clone()))
+        .and_then(chapter_info)
+        .map(cache_control(&cache_policies.chapters));
+    let list_pages = warp::path!("manga" / i32 / String)
+        .and(warp::get())
+        .and(with_backend(backend.clone()))
+        .and_then(list_pages)
+        .map(cache_control(&cache_policies.pages));
+    let graphql = warp::path!("graphql")
+        .and(warp::post())
+        .and(json_body::<async_graphql::Request>())
+        .and(with_schema(graphql::schema()))
+        .and(with_backend(backend.clone()))
+        .and_then(graphql);
+
+    let authorized = authorized(config.api_token);
+    let create_manga = warp::path!("manga")
+        .and(warp::post())
+        .and(authorized.clone())
+        .and(json_body())
+        .and(with_backend(backend.clone()))
+        .and_then(create_manga);
+    let update_manga = warp::path!("manga" / i32)
+        .and(warp::put())
+        .and(authorized.clone())
+        .and(json_body())
+        .and(with_backend(backend.clone()))
+        .and_then(update_manga);
+    let delete_manga = warp::path!("manga" / i32)
+        .and(warp::delete())
+        .and(authorized.clone())
+        .and(with_backend(backend.clone()))
+        .and_then(delete_manga);
+    let create_chapter = warp::path!("manga" / i32)
+        .and(warp::post())
+        .and(authorized.clone())
+        .and(json_body())
+        .and(with_backend(backend.clone()))
+        .and_then(create_chapter);
+    let update_chapter = warp::path!("manga" / i32 / String)
+        .and(warp::put())
+        .and(authorized.clone())
+        .and(json_body())
+        .and(with_backend(backend.clone()))
+        .and_then(update_chapter);
+    let delete_chapter = warp::path!("manga" / i32 / String)
+        .and(warp::delete())
+        .and(authorized)
+        .and(with_backend(backend))
+        .and_then(delete_chapter);
+
+    let v1 = list_manga
+        .or(openapi_json)
+        .or(docs)
+        .or(search)
+        .or(recent_chapters)
+        .or(recent_feed)
+        .or(opds)
+        .or(list_chapters)
+        // Before `list_pages`, which would take "info" or "feed.xml" as a chapter number
+        .or(manga_info)
+        .or(manga_feed)
+        .or(chapter_info)
+        .or(list_pages)
+        .or(graphql)
+        .or(create_manga)
+        .or(update_manga)
+        .or(delete_manga)
+        .or(create_chapter)
+        .or(update_chapter)
+        .or(delete_chapter)
+        .map(Reply::into_response);
+
+    // The unversioned paths predate versions, deployed sites still call them
+    let api = versioned_with_aliases(V1, v1)
+        .and(warp::header::optional::<String>(
+            header::ACCEPT_ENCODING.as_str(),
+        ))
+        .then(move |response, accept_encoding| {
+            compression::compress(response, accept_encoding, compression)
+        })
+        .and(warp::method())
+        .and(warp::header::headers_cloned())
+        .then(http_cache::conditional)
+        .map(Ok)
+        // Rejections are answered once the request ID is known
+        .or_else(|rejection| async { Ok::<_, Infallible>((Err(rejection),)) });
+
+    request_id()
+        .and(api)
+        .map(
+            |request_id: String, result: Result<warp::reply::Response, Rejection>| {
+                let reply =
+                    result.unwrap_or_else(|rejection| rejection_reply(rejection, &request_id));
+                warp::reply::with_header(reply, REQUEST_ID_HEADER, request_id)
+            },
+        )
+        .with(warp::cors().allow_any_origin().expose_headers(vec![
+            TOTAL_COUNT_HEADER,
+            REQUEST_ID_HEADER,
+            DEPRECATION_HEADER,
+            SUNSET_HEADER,
+            header::ETAG.as_str(),
+        ]))
+}
+
+/// The request's own ID when it has a reasonable one, otherwise a new one.
+fn request_id() -> impl Filter<Extract = (String,), Error = Infallible> + Clone {
+    warp::header::headers_cloned().map(|headers: HeaderMap| {
+        headers
+            .get(REQUEST_ID_HEADER)
+            .and_then(|request_id| request_id.to_str().ok())
+            .filter(|request_id| {
+                !request_id.is_empty()
+                    && request_id.len() <= MAX_REQUEST_ID_LENGTH
+                    && request_id.bytes().all(|b| b.is_ascii_graphic())
+            })
+            .map_or_else(|| Uuid::new_v4().to_string(), str::to_owned)
+    })
+}
+
+fn with_backend(backend: Backend) -> impl Filter<Extract = (Backend,), Error = Infallible> + Clone {
+    warp::any().map(move || backend.clone())
+}
+
+fn with_site_url(
+    site_url: Arc<str>,
+) -> impl Filter<Extract = (Arc<str>,), Error = Infallible> + Clone {
+    warp::any().map(move || site_url.clone())
+}
+
+fn with_schema(
+    schema: LlrsSchema,
+) -> impl Filter<Extract = (LlrsSchema,), Error = Infallible> + Clone {
+    warp::any().map(move || schema.clone())
+}
+
+fn json_body<T: DeserializeOwned + Send>() -> impl Filter<Extract = (T,), Error = Rejection> + Clone
+{
+    warp::body::content_length_limit(JSON_BODY_LIMIT).and(warp::body::json())
+}
+
+/// Passes requests bearing the API token, rejects everything when there's no token.
+fn authorized(api_token: Option<String>) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    let api_token: Option<Arc<str>> = api_token.map(Arc::from);
+    warp::header::optional::<String>(header::AUTHORIZATION.as_str())
+        .and_then(move |authorization: Option<String>| {
+            let api_token = api_token.clone();
+            async move {
+                match (api_token, authorization) {
+                    (Some(api_token), Some(authorization))
+                        if bearer_matches(&api_token, &authorization) =>
+                    {
+                        Ok(())
+                    }
+                    _ => Err(warp::reject::custom(Unauthorized)),
+                }
+            }
+        })
+        .untuple_one()
+}
+
+/// Compares in constant time so response timing doesn't leak the token.
+fn bearer_matches(api_token: &str, authorization: &str) -> bool {
+    match authorization.strip_prefix("Bearer ") {
+        Some(token) => {
+            token.len() == api_token.len()
+                && token
+                    .bytes()
+                    .zip(api_token.bytes())
+                    .fold(0, |difference, (a, b)| difference | (a ^ b))
+                    == 0
+        }
+        None => false,
+    }
+}
+
+/// warp leaves path segments encoded, eg: `Vol.2%20Ch.3`
+fn decode_segment(segment: &str) -> String {
+    percent_decode_str(segment).decode_utf8_lossy().into_owned()
+}
+
+fn reject(error: WaifusimsError) -> Rejection {
+    warp::reject::custom(Error::from(error))
+}
+
+async fn connect(backend: &Backend) -> Result<BoxedMangaService, Rejection> {
+    backend.connect().await.map_err(reject)
+}
+
+/// IDs are never negative, so a negative one is a mistake rather than a missing manga.
+fn check_manga_id(manga_id: i32) -> Result<(), Rejection> {
+    if manga_id < 0 {
+        Err(reject(WaifusimsError::InvalidInput(format!(
+            "manga ID {} is negative",
+            manga_id
+        ))))
+    } else {
+        Ok(())
+    }
+}
+
+/// Lists manga as a JSON array, eg: `/?sort=newest_chapter&status=Ongoing&offset=20&limit=20`
+#[utoipa::path(
+    get,
+    path = "/",
+    tag = "manga",
+    params(
+        MangaListQuery,
+    ),
+    responses(
+        (status = 200, description = "One slice of the manga, in order", body = Vec<Manga>, headers(("x-total-count" = u64, description = "Manga matching the filters"))),
+        (status = 400, description = "Invalid input", body = ErrorBody),
+        (status = 503, description = "The database is unavailable", body = ErrorBody),
+    )
+)]
+pub(crate) async fn list_manga(
+    query: MangaListQuery,
+    backend: Backend,
+) -> Result<impl Reply, Rejection> {
+    let mut llrs = connect(&backend).await?;
+    let listing = llrs.list_manga(&query).await.map_err(reject)?;
+    Ok(warp::reply::with_header(
+        warp::reply::json(&listing.mangas),
+        TOTAL_COUNT_HEADER,
+        listing.total.to_string(),
+    ))
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct SearchQuery {
+    /// Words to look for
+    q: String,
+    /// Most results to return, at most 50
+    limit: Option<u32>,
+}
+
+/// Ranked matches across titles, alternate titles, creators and chapter names,
+/// eg: `/search?q=one%20piece&limit=10`
+#[utoipa::path(
+    get,
+    path = "/search",
+    tag = "manga",
+    params(
+        SearchQuery,
+    ),
+    responses(
+        (status = 200, description = "Best matches first", body = Vec<SearchResult>),
+        (status = 400, description = "Invalid input", body = ErrorBody),
+        (status = 503, description = "The database is unavailable", body = ErrorBody),
+    )
+)]
+pub(crate) async fn search(query: SearchQuery, backend: Backend) -> Result<impl Reply, Rejection> {
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_SEARCH_LIMIT)
+        .min(MAX_SEARCH_LIMIT);
+    let mut llrs = connect(&backend).await?;
+    let results = llrs.search(&query.q, limit).await.map_err(reject)?;
+    Ok(warp::reply::json(&results))
+}
+
+/// Latest releases across every manga with their manga, newest first,
+/// eg: `/recent?since=2021-01-01T00:00:00&limit=10`
+#[utoipa::path(
+    get,
+    path = "/recent",
+    tag = "chapters",
+    params(
+        RecentChaptersQuery,
+    ),
+    responses(
+        (status = 200, description = "Newest releases first", body = Vec<RecentChapter>),
+        (status = 400, description = "Invalid input", body = ErrorBody),
+        (status = 503, description = "The database is unavailable", body = ErrorBody),
+    )
+)]
+pub(crate) async fn recent_chapters(
+    query: RecentChaptersQuery,
+    backend: Backend,
+) -> Result<impl Reply, Rejection> {
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_RECENT_LIMIT)
+        .min(MAX_RECENT_LIMIT);
+    let mut llrs = connect(&backend).await?;
+    let chapters = llrs
+        .get_recent_chapters(limit, query.since)
+        .await
+        .map_err(reject)?;
+    Ok(warp::reply::json(&chapters))
+}
+
+fn with_content_type(reply: impl Reply, content_type: &'static str) -> impl Reply {
+    warp::reply::with_header(reply, header::CONTENT_TYPE, content_type)
+}
+
+fn feed_reply(feed: Feed, query: FeedQuery) -> impl Reply {
+    with_content_type(feed.render(query.format), query.format.content_type())
+}
+
+/// Latest releases across every manga as RSS, or Atom with `?format=atom`
+#[utoipa::path(
+    get,
+    path = "/feed.xml",
+    tag = "feeds",
+    params(
+        FeedQuery,
+    ),
+    responses(
+        (status = 200, description = "The feed", content((String = "application/rss+xml"), (String = "application/atom+xml"))),
+        (status = 503, description = "The database is unavailable", body = ErrorBody),
+    )
+)]
+pub(crate) async fn recent_feed(
+    query: FeedQuery,
+    backend: Backend,
+    site_url: Arc<str>,
+) -> Result<impl Reply, Rejection> {
+    let mut llrs = connect(&backend).await?;
+    let chapters = llrs
+        .get_recent_chapters(FEED_ENTRY_LIMIT, None)
+        .await
+        .map_err(reject)?;
+    let feed = Feed {
+        title: "Latest releases".to_owned(),
+        link: format!("{}/", site_url.trim_end_matches('/')),
+        entries: chapters
+            .iter()
+            .map(|recent| feed::manga_chapter_entry(&site_url, &recent.manga, &recent.chapter))
+            .collect(),
+    };
+    Ok(feed_reply(feed, query))
+}
+
+/// Latest releases of one manga, eg: `/manga/1/feed.xml?format=atom`
+#[utoipa::path(
+    get,
+    path = "/manga/{manga_id}/feed.xml",
+    tag = "feeds",
+    params(
+        ("manga_id" = i32, Path, description = "Manga ID, never negative"),
+        FeedQuery,
+    ),
+    responses(
+        (status = 200, description = "The feed", content((String = "application/rss+xml"), (String = "application/atom+xml"))),
+        (status = 400, description = "Invalid input", body = ErrorBody),
+        (status = 404, description = "No such manga", body = ErrorBody),
+        (status = 503, description = "The database is unavailable", body = ErrorBody),
+    )
+)]
+pub(crate) async fn manga_feed(
+    manga_id: i32,
+    query: FeedQuery,
+    backend: Backend,
+    site_url: Arc<str>,
+) -> Result<impl Reply, Rejection> {
+    check_manga_id(manga_id)?;
+    let mut llrs = connect(&backend).await?;
+    let manga = match llrs.get_manga(manga_id).await.map_err(reject)? {
+        Some(manga) => manga,
+        None => return Err(warp::reject::custom(NotFound("manga"))),
+    };
+    let mut chapters = llrs.get_manga_chapters(manga_id).await.map_err(reject)?;
+    chapters.sort_by_key(|chapter| std::cmp::Reverse(feed::released(chapter)));
+    chapters.truncate(FEED_ENTRY_LIMIT as usize);
+    let last_modified = manga.updated_date;
+    let feed = Feed {
+        title: manga.manga_name,
+        link: feed::manga_url(&site_url, manga_id),
+        entries: chapters
+            .iter()
+            .map(|chapter| feed::chapter_entry(&site_url, chapter))
+            .collect(),
+    };
+    Ok(with_last_modified(feed_reply(feed, query), last_modified))
+}
+
+async fn get_manga(llrs: &mut BoxedMangaService, manga_id: i32) -> Result<Manga, Rejection> {
+    check_manga_id(manga_id)?;
+    match llrs.get_manga(manga_id).await.map_err(reject)? {
+        Some(manga) => Ok(manga),
+        None => Err(warp::reject::custom(NotFound("manga"))),
+    }
+}
+
+/// One page of the manga catalog for OPDS 1.2 clients, eg: `/opds?offset=50`
+#[utoipa::path(
+    get,
+    path = "/opds",
+    tag = "opds",
+    params(
+        CatalogQuery,
+    ),
+    responses(
+        (status = 200, description = "OPDS 1.2 navigation feed", body = String, content_type = "application/atom+xml;profile=opds-catalog;kind=navigation"),
+        (status = 503, description = "The database is unavailable", body = ErrorBody),
+    )
+)]
+pub(crate) async fn opds_catalog(
+    query: CatalogQuery,
+    backend: Backend,
+) -> Result<impl Reply, Rejection> {
+    let mut llrs = connect(&backend).await?;
+    let listing = llrs
+        .list_manga(&MangaListQuery {
+            offset: query.offset,
+            limit: Some(opds::CATALOG_PAGE_SIZE),
+            ..MangaListQuery::default()
+        })
+        .await
+        .map_err(reject)?;
+    Ok(with_content_type(
+        opds::catalog_feed(&listing, query.offset),
+        opds::NAVIGATION_TYPE,
+    ))
+}
+
+/// Chapters of a manga for OPDS 1.2 clients, each streamable page by page
+#[utoipa::path(
+    get,
+    path = "/opds/manga/{manga_id}",
+    tag = "opds",
+    params(
+        ("manga_id" = i32, Path, description = "Manga ID, never negative"),
+    ),
+    responses(
+        (status = 200, description = "OPDS 1.2 acquisition feed", body = String, content_type = "application/atom+xml;profile=opds-catalog;kind=acquisition"),
+        (status = 400, description = "Invalid input", body = ErrorBody),
+        (status = 404, description = "No such manga", body = ErrorBody),
+        (status = 503, description = "The database is unavailable", body = ErrorBody),
+    )
+)]
+pub(crate) async fn opds_manga(
+    manga_id: i32,
+    backend: Backend,
+    site_url: Arc<str>,
+) -> Result<impl Reply, Rejection> {
+    let mut llrs = connect(&backend).await?;
+    let manga = get_manga(&mut llrs, manga_id).await?;
+    let chapters = llrs.get_manga_chapters(manga_id).await.map_err(reject)?;
+    // PSE needs every page count before a chapter is opened
+    let keys = chapters
+        .iter()
+        .map(|chapter| (manga_id, chapter.chapter_number.to_string()))
+        .collect::<Vec<(i32, String)>>();
+    let page_lists = llrs.get_chapters_pages(&keys).await.map_err(reject)?;
+    let counted_chapters = chapters
+        .into_iter()
+        .zip(page_lists)
+        .map(|(chapter, pages)| CountedChapter {
+            chapter,
+            page_count: pages.len(),
+        })
+        .collect::<Vec<CountedChapter>>();
+    Ok(with_content_type(
+        opds::manga_feed(&site_url, &manga, &counted_chapters),
+        opds::ACQUISITION_TYPE,
+    ))
+}
+
+/// Redirects a PSE client to a page image, counting pages from 0,
+/// eg: `/opds/manga/1/Vol.2%20Ch.3/0`
+#[utoipa::path(
+    get,
+    path = "/opds/manga/{manga_id}/{chapter_number}/{page_index}",
+    tag = "opds",
+    params(
+        ("manga_id" = i32, Path, description = "Manga ID, never negative"),
+        ("chapter_number" = String, Path, description = "Chapter number as published, percent encoded, eg: `Vol.2%20Ch.3`"),
+        ("page_index" = usize, Path, description = "Page counted from 0"),
+    ),
+    responses(
+        (status = 302, description = "Redirect to the page image", headers(("location" = String, description = "Preferred mirror of the page"))),
+        (status = 400, description = "Invalid input", body = ErrorBody),
+        (status = 404, description = "No such page", body = ErrorBody),
+        (status = 503, description = "The database is unavailable", body = ErrorBody),
+    )
+)]
+pub(crate) async fn opds_page(
+    manga_id: i32,
+    chapter_number: String,
+    page_index: usize,
+    backend: Backend,
+) -> Result<impl Reply, Rejection> {
+    check_manga_id(manga_id)?;
+    let chapter_number = decode_segment(&chapter_number);
+    let mut llrs = connect(&backend).await?;
+    let pages = llrs
+        .get_pages(manga_id, &chapter_number)
+        .await
+        .map_err(reject)?;
+    match pages.into_iter().nth(page_index) {
+        Some(page) => Ok(warp::reply::with_header(
+            StatusCode::FOUND,
+            header::LOCATION,
+            page.url_string,
+        )),
+        None => Err(warp::reject::custom(NotFound("page"))),
+    }
+}
+
+/// One page of the manga catalog for OPDS 2.0 clients, eg: `/opds/v2?offset=50`
+#[utoipa::path(
+    get,
+    path = "/opds/v2",
+    tag = "opds",
+    params(
+        CatalogQuery,
+    ),
+    responses(
+        (status = 200, description = "OPDS 2.0 catalog", body = Object, content_type = "application/opds+json"),
+        (status = 503, description = "The database is unavailable", body = ErrorBody),
+    )
+)]
+pub(crate) async fn opds_catalog_v2(
+    query: CatalogQuery,
+    backend: Backend,
+) -> Result<impl Reply, Rejection> {
+    let mut llrs = connect(&backend).await?;
+    let listing = llrs
+        .list_manga(&MangaListQuery {
+            offset: query.offset,
+            limit: Some(opds::CATALOG_PAGE_SIZE),
+            ..MangaListQuery::default()
+        })
+        .await
+        .map_err(reject)?;
+    Ok(with_content_type(
+        warp::reply::json(&opds::catalog_v2(&listing, query.offset)),
+        opds::OPDS_JSON_TYPE,
+    ))
+}
+
+/// Chapters of a manga as publications for OPDS 2.0 clients
+#[utoipa::path(
+    get,
+    path = "/opds/v2/manga/{manga_id}",
+    tag = "opds",
+    params(
+        ("manga_id" = i32, Path, description = "Manga ID, never negative"),
+    ),
+    responses(
+        (status = 200, description = "OPDS 2.0 feed of the chapters", body = Object, content_type = "application/opds+json"),
+        (status = 400, description = "Invalid input", body = ErrorBody),
+        (status = 404, description = "No such manga", body = ErrorBody),
+        (status = 503, description = "The database is unavailable", body = ErrorBody),
+    )
+)]
+pub(crate) async fn opds_manga_v2(
+    manga_id: i32,
+    backend: Backend,
+) -> Result<impl Reply, Rejection> {
+    let mut llrs = connect(&backend).await?;
+    let manga = get_manga(&mut llrs, manga_id).await?;
+    let chapters = llrs.get_manga_chapters(manga_id).await.map_err(reject)?;
+    Ok(with_content_type(
+        warp::reply::json(&opds::manga_v2(&manga, &chapters)),
+        opds::OPDS_JSON_TYPE,
+    ))
+}
+
+/// Divina manifest of a chapter's pages, eg: `/opds/v2/manga/1/Vol.2%20Ch.3`
+#[utoipa::path(
+    get,
+    path = "/opds/v2/manga/{manga_id}/{chapter_number}",
+    tag = "opds",
+    params(
+        ("manga_id" = i32, Path, description = "Manga ID, never negative"),
+        ("chapter_number" = String, Path, description = "Chapter number as published, percent encoded, eg: `Vol.2%20Ch.3`"),
+    ),
+    responses(
+        (status = 200, description = "Divina manifest of the pages", body = Object, content_type = "application/divina+json"),
+        (status = 400, description = "Invalid input", body = ErrorBody),
+        (status = 404, description = "No such manga or chapter", body = ErrorBody),
+        (status = 503, description = "The database is unavailable", body = ErrorBody),
+    )
+)]
+pub(crate) async fn opds_manifest(
+    manga_id: i32,
+    chapter_number: String,
+    backend: Backend,
+) -> Result<impl Reply, Rejection> {
+    let chapter_number = decode_segment(&chapter_number);
+    let mut llrs = connect(&backend).await?;
+    let manga = get_manga(&mut llrs, manga_id).await?;
+    let chapter = match llrs
+        .get_chapter(manga_id, &chapter_number)
+        .await
+        .map_err(reject)?
+    {
+        Some(chapter) => chapter,
+        None => return Err(warp::reject::custom(NotFound("chapter"))),
+    };
+    let pages = llrs
+        .get_pages(manga_id, &chapter_number)
+        .await
+        .map_err(reject)?;
+    Ok(with_content_type(
+        warp::reply::json(&opds::chapter_manifest(&manga, &chapter, &pages)),
+        opds::DIVINA_TYPE,
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/manga/{manga_id}",
+    tag = "chapters",
+    params(
+        ("manga_id" = i32, Path, description = "Manga ID, never negative"),
+    ),
+    responses(
+        (status = 200, description = "Chapters in reading order", body = Vec<Chapter>),
+        (status = 400, description = "Invalid input", body = ErrorBody),
+        (status = 404, description = "No such manga", body = ErrorBody),
+        (status = 503, description = "The database is unavailable", body = ErrorBody),
+    )
+)]
+pub(crate) async fn list_chapters(
+    manga_id: i32,
+    backend: Backend,
+) -> Result<impl Reply, Rejection> {
+    check_manga_id(manga_id)?;
+    let mut llrs = connect(&backend).await?;
+    // The manga's updated date covers deleted chapters, which leave none of their own
+    let manga = get_manga(&mut llrs, manga_id).await?;
+    let chapters = llrs.get_manga_chapters(manga_id).await.map_err(reject)?;
+    Ok(with_last_modified(
+        warp::reply::json(&chapters),
+        manga.updated_date,
+    ))
+}
+
+/// The manga with its creators, eg: `/manga/1/info`
+#[utoipa::path(
+    get,
+    path = "/manga/{manga_id}/info",
+    tag = "manga",
+    params(
+        ("manga_id" = i32, Path, description = "Manga ID, never negative"),
+    ),
+    responses(
+        (status = 200, description = "The manga", body = Manga),
+        (status = 400, description = "Invalid input", body = ErrorBody),
+        (status = 404, description = "No such manga", body = ErrorBody),
+        (status = 503, description = "The database is unavailable", body = ErrorBody),
+    )
+)]
+pub(crate) async fn manga_info(manga_id: i32, backend: Backend) -> Result<impl Reply, Rejection> {
+    check_manga_id(manga_id)?;
+    let mut llrs = connect(&backend).await?;
+    match llrs.get_manga(manga_id).await.map_err(reject)? {
+        Some(manga) => Ok(with_last_modified(
+            warp::reply::json(&manga),
+            manga.updated_date,
+        )),
+        None => Err(warp::reject::custom(NotFound("manga"))),
+    }
+}
+
+/// One chapter without its pages, eg: `/manga/1/Vol.2%20Ch.3/info`
+#[utoipa::path(
+    get,
+    path = "/manga/{manga_id}/{chapter_number}/info",
+    tag = "chapters",
+    params(
+        ("manga_id" = i32, Path, description = "Manga ID, never negative"),
+        ("chapter_number" = String, Path, description = "Chapter number as published, percent encoded, eg: `Vol.2%20Ch.3`"),
+    ),
+    responses(
+        (status = 200, description = "The chapter", body = Chapter),
+        (status = 400, description = "Invalid input", body = ErrorBody),
+        (status = 404, description = "No such chapter", body = ErrorBody),
+        (status = 503, description = "The database is unavailable", body = ErrorBody),
+    )
+)]
+pub(crate) async fn chapter_info(
+    manga_id: i32,
+    chapter_number: String,
+    backend: Backend,
+) -> Result<impl Reply, Rejection> {
+    check_manga_id(manga_id)?;
+    let chapter_number = decode_segment(&chapter_number);
+    let mut llrs = connect(&backend).await?;
+    match llrs
+        .get_chapter(manga_id, &chapter_number)
+        .await
+        .map_err(reject)?
+    {
+        Some(chapter) => Ok(with_last_modified(
+            warp::reply::json(&chapter),
+            chapter.updated_date,
+        )),
+        None => Err(warp::reject::custom(NotFound("chapter"))),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/manga/{manga_id}/{chapter_number}",
+    tag = "chapters",
+    params(
+        ("manga_id" = i32, Path, description = "Manga ID, never negative"),
+        ("chapter_number" = String, Path, description = "Chapter number as published, percent encoded, eg: `Vol.2%20Ch.3`"),
+    ),
+    responses(
+        (status = 200, description = "Pages by page number", body = Vec<Page>),
+        (status = 400, description = "Invalid input", body = ErrorBody),
+        (status = 404, description = "No such chapter", body = ErrorBody),
+        (status = 503, description = "The database is unavailable", body = ErrorBody),
+    )
+)]
+pub(crate) async fn list_pages(
+    manga_id: i32,
+    chapter_number: String,
+    backend: Backend,
+) -> Result<impl Reply, Rejection> {
+    // `manga_info` and `manga_feed` answer for these paths, even when they reject an unknown manga
+    if chapter_number == "info" || chapter_number == "feed.xml" {
+        return Err(warp::reject::not_found());
+    }
+    check_manga_id(manga_id)?;
+    let chapter_number = decode_segment(&chapter_number);
+    let mut llrs = connect(&backend).await?;
+    // The chapter dates the pages for `Last-Modified`, and tells a missing chapter from an empty one
+    let chapter = match llrs
+        .get_chapter(manga_id, &chapter_number)
+        .await
+        .map_err(reject)?
+    {
+        Some(chapter) => chapter,
+        None => return Err(warp::reject::custom(NotFound("chapter"))),
+    };
+    let pages = llrs
+        .get_pages(manga_id, &chapter_number)
+        .await
+        .map_err(reject)?;
+    Ok(with_last_modified(
+        warp::reply::json(&pages),
+        chapter.updated_date,
+    ))
+}
+
+/// GraphQL errors are part of the response body, the request itself succeeds.
+#[utoipa::path(
+    post,
+    path = "/graphql",
+    tag = "graphql",
+    request_body(content = Object, description = "A GraphQL request with `query`, `variables` and `operationName`"),
+    responses(
+        (status = 200, description = "`data` and `errors` of the query", body = Object),
+        (status = 503, description = "The database is unavailable", body = ErrorBody),
+    )
+)]
+pub(crate) async fn graphql(
+    request: async_graphql::Request,
+    schema: LlrsSchema,
+    backend: Backend,
+) -> Result<impl Reply, Infallible> {
+    let response = graphql::execute(&schema, request, backend).await;
+    Ok(warp::reply::json(&response))
+}
+
+#[utoipa::path(
+    post,
+    path = "/manga",
+    tag = "manga",
+    request_body = NewManga,
+    security(("bearer" = [])),
+    responses(
+        (status = 201, description = "The created manga", body = Manga),
+        (status = 400, description = "Invalid input", body = ErrorBody),
+        (status = 401, description = "Missing or wrong bearer token", body = ErrorBody),
+        (status = 409, description = "Conflicts with an existing manga", body = ErrorBody),
+        (status = 503, description = "The database is unavailable", body = ErrorBody),
+    )
+)]
+pub(crate) async fn create_manga(
+    manga: NewManga,
+    backend: Backend,
+) -> Result<impl Reply, Rejection> {
+    let mut llrs = connect(&backend).await?;
+    let manga = llrs.create_manga(manga).await.map_err(reject)?;
+    Ok(warp::reply::with_status(
+        warp::reply::json(&manga),
+        StatusCode::CREATED,
+    ))
+}
+
+#[utoipa::path(
+    put,
+    path = "/manga/{manga_id}",
+    tag = "manga",
+    params(
+        ("manga_id" = i32, Path, description = "Manga ID, never negative"),
+    ),
+    request_body = NewManga,
+    security(("bearer" = [])),
+    responses(
+        (status = 200, description = "The updated manga", body = Manga),
+        (status = 400, description = "Invalid input", body = ErrorBody),
+        (status = 401, description = "Missing or wrong bearer token", body = ErrorBody),
+        (status = 404, description = "No such manga", body = ErrorBody),
+        (status = 503, description = "The database is unavailable", body = ErrorBody),
+    )
+)]
+pub(crate) async fn update_manga(
+    manga_id: i32,
+    manga: NewManga,
+    backend: Backend,
+) -> Result<impl Reply, Rejection> {
+    check_manga_id(manga_id)?;
+    let mut llrs = connect(&backend).await?;
+    match llrs.update_manga(manga_id, manga).await.map_err(reject)? {
+        Some(manga) => Ok(warp::reply::json(&manga)),
+        None => Err(warp::reject::custom(NotFound("manga"))),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/manga/{manga_id}",
+    tag = "manga",
+    params(
+        ("manga_id" = i32, Path, description = "Manga ID, never negative"),
+    ),
+    security(("bearer" = [])),
+    responses(
+        (status = 204, description = "Deleted with its chapters"),
+        (status = 400, description = "Invalid input", body = ErrorBody),
+        (status = 401, description = "Missing or wrong bearer token", body = ErrorBody),
+        (status = 404, description = "No such manga", body = ErrorBody),
+        (status = 503, description = "The database is unavailable", body = ErrorBody),
+    )
+)]
+pub(crate) async fn delete_manga(manga_id: i32, backend: Backend) -> Result<impl Reply, Rejection> {
+    check_manga_id(manga_id)?;
+    let mut llrs = connect(&backend).await?;
+    if llrs.delete_manga(manga_id).await.map_err(reject)? {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(warp::reject::custom(NotFound("manga")))
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/manga/{manga_id}",
+    tag = "chapters",
+    params(
+        ("manga_id" = i32, Path, description = "Manga ID, never negative"),
+    ),
+    request_body = NewChapter,
+    security(("bearer" = [])),
+    responses(
+        (status = 201, description = "The created chapter", body = Chapter),
+        (status = 400, description = "Invalid input", body = ErrorBody),
+        (status = 401, description = "Missing or wrong bearer token", body = ErrorBody),
+        (status = 404, description = "No such manga", body = ErrorBody),
+        (status = 409, description = "The chapter already exists", body = ErrorBody),
+        (status = 503, description = "The database is unavailable", body = ErrorBody),
+    )
+)]
+pub(crate) async fn create_chapter(
+    manga_id: i32,
+    chapter: NewChapter,
+    backend: Backend,
+) -> Result<impl Reply, Rejection> {
+    check_manga_id(manga_id)?;
+    let mut llrs = connect(&backend).await?;
+    match llrs
+        .create_chapter(manga_id, chapter)
+        .await
+        .map_err(reject)?
+    {
+        Some(chapter) => Ok(warp::reply::with_status(
+            warp::reply::json(&chapter),
+            StatusCode::CREATED,
+        )),
+        None => Err(warp::reject::custom(NotFound("manga"))),
+    }
+}
+
+#[utoipa::path(
+    put,
+    path = "/manga/{manga_id}/{chapter_number}",
+    tag = "chapters",
+    params(
+        ("manga_id" = i32, Path, description = "Manga ID, never negative"),
+        ("chapter_number" = String, Path, description = "Chapter number as published, percent encoded, eg: `Vol.2%20Ch.3`"),
+    ),
+    request_body = NewChapter,
+    security(("bearer" = [])),
+    responses(
+        (status = 200, description = "The replaced chapter", body = Chapter),
+        (status = 400, description = "Invalid input", body = ErrorBody),
+        (status = 401, description = "Missing or wrong bearer token", body = ErrorBody),
+        (status = 404, description = "No such chapter", body = ErrorBody),
+        (status = 503, description = "The database is unavailable", body = ErrorBody),
+    )
+)]
+pub(crate) async fn update_chapter(
+    manga_id: i32,
+    chapter_number: String,
+    chapter: NewChapter,
+    backend: Backend,
+) -> Result<impl Reply, Rejection> {
+    check_manga_id(manga_id)?;
+    let chapter_number = decode_segment(&chapter_number);
+    let mut llrs = connect(&backend).await?;
+    match llrs
+        .update_chapter(manga_id, &chapter_number, chapter)
+        .await
+        .map_err(reject)?
+    {
+        Some(chapter) => Ok(warp::reply::json(&chapter)),
+        None => Err(warp::reject::custom(NotFound("chapter"))),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/manga/{manga_id}/{chapter_number}",
+    tag = "chapters",
+    params(
+        ("manga_id" = i32, Path, description = "Manga ID, never negative"),
+        ("chapter_number" = String, Path, description = "Chapter number as published, percent encoded, eg: `Vol.2%20Ch.3`"),
+    ),
+    security(("bearer" = [])),
+    responses(
+        (status = 204, description = "Deleted with its pages"),
+        (status = 400, description = "Invalid input", body = ErrorBody),
+        (status = 401, description = "Missing or wrong bearer token", body = ErrorBody),
+        (status = 404, description = "No such chapter", body = ErrorBody),
+        (status = 503, description = "The database is unavailable", body = ErrorBody),
+    )
+)]
+pub(crate) async fn delete_chapter(
+    manga_id: i32,
+    chapter_number: String,
+    backend: Backend,
+) -> Result<impl Reply, Rejection> {
+    check_manga_id(manga_id)?;
+    let chapter_number = decode_segment(&chapter_number);
+    let mut llrs = connect(&backend).await?;
+    if llrs
+        .delete_chapter(manga_id, &chapter_number)
+        .await
+        .map_err(reject)?
+    {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(warp::reject::custom(NotFound("chapter")))
+    }
+}
+
+/// Body of every failed response.
+#[derive(Debug, Serialize, ToSchema)]
+pub(crate) struct ErrorBody<'a> {
+    /// Stable snake_case name of the failure, eg: `not_found`
+    code: &'static str,
+    message: String,
+    request_id: &'a str,
+}
+
+fn error_reply(
+    status: StatusCode,
+    code: &'static str,
+    message: String,
+    request_id: &str,
+) -> warp::reply::Response {
+    let body = ErrorBody {
+        code,
+        message,
+        request_id,
+    };
+    warp::reply::with_status(warp::reply::json(&body), status).into_response()
+}
+
+/// Answers every rejection with an `ErrorBody`, ours and warp's alike.
+/// Server side failures are logged with the request ID and don't leak their details.
+fn rejection_reply(rejection: Rejection, request_id: &str) -> warp::reply::Response {
+    let (status, code, message) = if let Some(Error { inner }) = rejection.find::<Error>() {
+        match inner {
+            WaifusimsError::InvalidInput(message) => {
+                (StatusCode::BAD_REQUEST, "invalid_input", message.to_owned())
+            }
+            WaifusimsError::Conflict(message) => {
+                (StatusCode::CONFLICT, "conflict", message.to_owned())
+            }
+            inner if inner.is_unavailable() => {
+                error!("{} {}", request_id, inner);
+                (
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    "database_unavailable",
+                    "the database is unavailable, try again later".to_owned(),
+                )
+            }
+            inner => {
+                error!("{} {}", request_id, inner);
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "internal",
+                    "internal server error".to_owned(),
+                )
+            }
+        }
+    } else if let Some(NotFound(kind)) = rejection.find::<NotFound>() {
+        (
+            StatusCode::NOT_FOUND,
+            "not_found",
+            format!("{} not found", kind),
+        )
+    } else if rejection.find::<Unauthorized>().is_some() {
+        let reply = error_reply(
+            StatusCode::UNAUTHORIZED,
+            "unauthorized",
+            "a valid bearer token is required".to_owned(),
+            request_id,
+        );
+        return warp::reply::with_header(reply, header::WWW_AUTHENTICATE, "Bearer").into_response();
+    } else if let Some(err) = rejection.find::<warp::body::BodyDeserializeError>() {
+        (StatusCode::BAD_REQUEST, "invalid_body", err.to_string())
+    } else if let Some(err) = rejection.find::<warp::reject::InvalidQuery>() {
+        (StatusCode::BAD_REQUEST, "invalid_query", err.to_string())
+    } else if let Some(err) = rejection.find::<warp::reject::PayloadTooLarge>() {
+        (
+            StatusCode::PAYLOAD_TOO_LARGE,
+            "payload_too_large",
+            err.to_string(),
+        )
+    } else if let Some(err) = rejection.find::<warp::reject::UnsupportedMediaType>() {
+        (
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            "unsupported_media_type",
+            err.to_string(),
+        )
+    } else if let Some(err) = rejection.find::<warp::reject::LengthRequired>() {
+        (
+            StatusCode::LENGTH_REQUIRED,
+            "length_required",
+            err.to_string(),
+        )
+    } else if let Some(err) = rejection.find::<warp::reject::MethodNotAllowed>() {
+        (
+            StatusCode::METHOD_NOT_ALLOWED,
+            "method_not_allowed",
+            err.to_string(),
+        )
+    } else if rejection.is_not_found() {
+        (
+            StatusCode::NOT_FOUND,
+            "not_found",
+            "no such route".to_owned(),
+        )
+    } else {
+        error!("{} unhandled rejection {:?}", request_id, rejection);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "internal",
+            "internal server error".to_owned(),
+        )
+    };
+    error_reply(status, code, message, request_id)
+}
+
+#[derive(Debug)]
+struct Error {
+    inner: WaifusimsError,
+}
+
+impl From<WaifusimsError> for Error {
+    fn from(error: WaifusimsError) -> Error {
+        Error { inner: error }
+    }
+}
+
+impl warp::reject::Reject for Error {}
+
+/// A lookup or write targeted a manga or chapter that doesn't exist.
+#[derive(Debug)]
+struct NotFound(&'static str);
+
+impl warp::reject::Reject for NotFound {}
+
+#[derive(Debug)]
+struct Unauthorized;
+
+impl warp::reject::Reject for Unauthorized {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use libllrs::{
+        Chapter, FixtureAlternateTitle, FixturePage, InMemoryMangaService, Manga, MangaFixture,
+        MangaStatus, Page, PoolConfig, PostgresPool,
+    };
+    use serde_json::Value;
+    use std::{io::Read, time::Duration};
+
+    fn manga(manga_id: i32, manga_name: &str) -> Manga {
+        Manga {
+            manga_id,
+            manga_name: manga_name.to_owned(),
+            author_names: vec!["Author".to_owned()],
+            artist_names: vec!["Artist".to_owned()],
+            cover_image_url: Some(format!("{}.png", manga_name)),
+            purchase_url: None,
+            status: MangaStatus::Ongoing,
+            creation_date: None,
+            updated_date: NaiveDate::from_ymd_opt(2021, 1, 2)
+                .unwrap()
+                .and_hms_opt(0, 0, 0),
+        }
+    }
+
+    fn chapter(manga_id: i32, chapter_number: &str) -> Chapter {
+        let date = NaiveDate::from_ymd_opt(2021, 1, 1)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap();
+        Chapter {
+            chapter_number: chapter_number.into(),
+            chapter_name: format!("Chapter {}", chapter_number),
+            creation_date: date,
+            release_date: Some(date),
+            updated_date: Some(date),
+            manga_id,
+        }
+    }
+
+    fn page(manga_id: i32, chapter_number: &str, page_number: i32) -> FixturePage {
+        FixturePage {
+            manga_id,
+            chapter_number: chapter_number.to_owned(),
+            page: Page {
+                url_string: format!("{}-{}-{}.png", manga_id, chapter_number, page_number),
+                page_number,
+                mirror_urls: vec![],
+            },
+        }
+    }
+
+    fn test_backend() -> Backend {
+        Backend::Memory(InMemoryMangaService::new(MangaFixture {
+            mangas: vec![manga(2, "Second"), manga(1, "First")],
+            chapters: vec![
+                chapter(1, "10"),
+                chapter(1, "2"),
+                chapter(1, "Extra"),
+                chapter(1, "2.5"),
+                chapter(2, "1"),
+            ],
+            pages: vec![
+                page(1, "2", 3),
+                page(1, "2", 1),
+                page(1, "2", 2),
+                page(1, "10", 1),
+            ],
+            alternate_titles: vec![FixtureAlternateTitle {
+                manga_id: 2,
+                title: "Deuxieme".to_owned(),
+            }],
+            ..MangaFixture::default()
+        }))
+    }
+
+    async fn get_json(path: &str) -> Value {
+        let response = warp::test::request()
+            .path(path)
+            .reply(&routes(test_backend(), RouteConfig::default()))
+            .await;
+        assert_eq!(response.status(), 200, "GET {}", path);
+        serde_json::from_slice(response.body()).unwrap()
+    }
+
+    fn field<'a>(values: &'a Value, field: &str) -> Vec<&'a Value> {
+        values
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|value| &value[field])
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn lists_manga_by_id() {
+        let mangas = get_json("/").await;
+        assert_eq!(field(&mangas, "manga_id"), vec![1, 2]);
+    }
+
+    #[tokio::test]
+    async fn lists_manga_slices_with_total_count() {
+        let response = warp::test::request()
+            .path("/?sort=newest_chapter&offset=1&limit=1")
+            .reply(&routes(test_backend(), RouteConfig::default()))
+            .await;
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.headers()[TOTAL_COUNT_HEADER], "2");
+        // Every chapter shares a date, so ties fall back to the ID
+        let mangas: Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(field(&mangas, "manga_id"), vec![2]);
+
+        let mangas = get_json("/?status=Completed").await;
+        assert_eq!(mangas, Value::Array(vec![]));
+        let mangas = get_json("/?author=author&sort=name").await;
+        assert_eq!(field(&mangas, "manga_name"), vec!["First", "Second"]);
+    }
+
+    #[tokio::test]
+    async fn rejects_invalid_list_queries() {
+        for path in ["/?sort=popularity", "/?limit=-1", "/?status=ongoing"] {
+            let response = warp::test::request()
+                .path(path)
+                .reply(&routes(test_backend(), RouteConfig::default()))
+                .await;
+            assert_eq!(response.status(), 400, "GET {}", path);
+        }
+    }
+
+    #[tokio::test]
+    async fn searches_titles_and_chapters() {
+        let results = get_json("/search?q=SECOND").await;
+        assert_eq!(results[0]["manga"]["manga_id"], 2);
+        assert_eq!(results[0]["field"], "Title");
+        let results = get_json("/search?q=deux").await;
+        assert_eq!(field(&results, "field"), vec!["AlternateTitle"]);
+        let results = get_json("/search?q=chapter%202.5").await;
+        assert_eq!(field(&results, "chapter_number"), vec!["2.5"]);
+        let results = get_json("/search?q=author&limit=1").await;
+        assert_eq!(results.as_array().unwrap().len(), 1);
+        assert_eq!(get_json("/search?q=").await, Value::Array(vec![]));
+
+        let response = warp::test::request()
+            .path("/search")
+            .reply(&routes(test_backend(), RouteConfig::default()))
+            .await;
+        assert_eq!(response.status(), 400);
+    }
+
+    #[tokio::test]
+    async fn lists_recent_chapters_with_their_manga() {
+        let recent = get_json("/recent?limit=2").await;
+        assert_eq!(recent.as_array().unwrap().len(), 2);
+        // Every chapter shares a release date, so ties fall back to the manga and number
+        assert_eq!(recent[0]["manga"]["manga_name"], "First");
+        assert_eq!(recent[0]["chapter"]["chapter_number"], "10");
+        let recent = get_json("/recent?since=2021-01-01T00:00:00").await;
+        assert_eq!(recent, Value::Array(vec![]));
+        let recent = get_json("/recent?since=2020-12-31T00:00:00").await;
+        assert_eq!(recent.as_array().unwrap().len(), 5);
+
+        let (status, body) = get_error("/recent?since=yesterday").await;
+        assert_eq!(status, 400);
+        assert_eq!(body["code"], "invalid_query");
+    }
+
+    async fn get_feed(path: &str) -> (String, String) {
+        let config = RouteConfig {
+            site_url: "https://site/".to_owned(),
+            ..RouteConfig::default()
+        };
+        let response = warp::test::request()
+            .path(path)
+            .reply(&routes(test_backend(), config))
+            .await;
+        assert_eq!(response.status(), 200, "GET {}", path);
+        let content_type = response.headers()[header::CONTENT_TYPE].to_str().unwrap();
+        (
+            content_type.to_owned(),
+            String::from_utf8(response.body().to_vec()).unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn serves_rss_and_atom_feeds() {
+        let (content_type, rss) = get_feed("/feed.xml").await;
+        assert!(content_type.starts_with("application/rss+xml"));
+        assert_eq!(rss.matches("<item>").count(), 5);
+        assert!(rss.contains("<title>First Chapter 10: Chapter 10</title>"));
+        assert!(rss.contains("<link>https://site/manga/1/10</link>"));
+
+        let (content_type, atom) = get_feed("/manga/1/feed.xml?format=atom").await;
+        assert!(content_type.starts_with("application/atom+xml"));
+        assert_eq!(atom.matches("<entry>").count(), 4);
+        assert!(atom.contains("<link href=\"https://site/manga/1\"/>"));
+        assert!(atom.contains("<id>https://site/manga/1/2.5</id>"));
+
+        let (status, body) = get_error("/manga/3/feed.xml").await;
+        assert_eq!(status, 404);
+        assert_eq!(body["message"], "manga not found");
+        let (status, _) = get_error("/feed.xml?format=json").await;
+        assert_eq!(status, 400);
+    }
+
+    #[tokio::test]
+    async fn serves_opds_catalogs() {
+        let (content_type, catalog) = get_feed("/opds").await;
+        assert!(content_type.contains("kind=navigation"));
+        assert_eq!(catalog.matches("<entry>").count(), 2);
+        assert!(catalog.contains("href=\"/opds/manga/1\""));
+
+        let (_, chapters) = get_feed("/opds/manga/1").await;
+        assert_eq!(chapters.matches("<entry>").count(), 4);
+        assert!(chapters
+            .contains("href=\"/opds/manga/1/2/{pageNumber}\" type=\"image/jpeg\" pse:count=\"3\""));
+        assert!(chapters.contains("href=\"https://site/manga/1/2.5\""));
+
+        let response = warp::test::request()
+            .path("/opds/manga/1/2/1")
+            .reply(&routes(test_backend(), RouteConfig::default()))
+            .await;
+        assert_eq!(response.status(), 302);
+        assert_eq!(response.headers()[header::LOCATION], "1-2-2.png");
+        let (status, body) = get_error("/opds/manga/1/2/3").await;
+        assert_eq!(status, 404);
+        assert_eq!(body["message"], "page not found");
+
+        let catalog = get_json("/opds/v2").await;
+        assert_eq!(catalog["metadata"]["numberOfItems"], 2);
+        assert_eq!(field(&catalog["navigation"], "href")[0], "/opds/v2/manga/1");
+        let chapters = get_json("/opds/v2/manga/2").await;
+        assert_eq!(
+            chapters["publications"][0]["links"][0]["href"],
+            "/opds/v2/manga/2/1"
+        );
+        assert_eq!(
+            chapters["publications"][0]["images"][0]["href"],
+            "Second.png"
+        );
+        let manifest = get_json("/opds/v2/manga/1/2").await;
+        assert_eq!(field(&manifest["readingOrder"], "href")[0], "1-2-1.png");
+        assert_eq!(manifest["readingOrder"][0]["type"], "image/png");
+
+        let (status, _) = get_error("/opds/v2/manga/3").await;
+        assert_eq!(status, 404);
+        let (status, body) = get_error("/opds/v2/manga/1/3").await;
+        assert_eq!(status, 404);
+        assert_eq!(body["message"], "chapter not found");
+    }
+
+    #[tokio::test]
+    async fn lists_chapters_numerically_with_non_numeric_first() {
+        let chapters = get_json("/manga/1").await;
+        assert_eq!(
+            field(&chapters, "chapter_number"),
+            vec!["Extra", "2", "2.5", "10"]
+        );
+    }
+
+    async fn get_error(path: &str) -> (StatusCode, Value) {
+        let response = warp::test::request()
+            .path(path)
+            .reply(&routes(test_backend(), RouteConfig::default()))
+            .await;
+        let body: Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(
+            response.headers()[REQUEST_ID_HEADER].to_str().unwrap(),
+            body["request_id"],
+            "GET {}",
+            path
+        );
+        (response.status(), body)
+    }
+
+    #[tokio::test]
+    async fn rejects_unknown_manga_and_chapters() {
+        for (path, message) in [
+            ("/manga/3", "manga not found"),
+            ("/manga/1/3", "chapter not found"),
+            ("/manga/3/1", "chapter not found"),
+        ] {
+            let (status, body) = get_error(path).await;
+            assert_eq!(status, 404, "GET {}", path);
+            assert_eq!(body["code"], "not_found");
+            assert_eq!(body["message"], message);
+        }
+        // A chapter without pages yet still exists
+        assert_eq!(get_json("/manga/1/Extra").await, Value::Array(vec![]));
+    }
+
+    #[tokio::test]
+    async fn rejects_negative_manga_ids() {
+        for path in [
+            "/manga/-1",
+            "/manga/-1/info",
+            "/manga/-1/2",
+            "/manga/-1/2/info",
+        ] {
+            let (status, body) = get_error(path).await;
+            assert_eq!(status, 400, "GET {}", path);
+            assert_eq!(body["code"], "invalid_input");
+            assert_eq!(body["message"], "manga ID -1 is negative");
+        }
+    }
+
+    #[tokio::test]
+    async fn reports_an_unavailable_database() {
+        // Nothing listens on port 1, so connecting is refused until the pool gives up
+        let pool_config = PoolConfig {
+            connection_timeout: Duration::from_secs(1),
+            ..PoolConfig::default()
+        };
+        let backend = Backend::Postgres(PostgresPool::new(
+            "host=127.0.0.1 port=1 connect_timeout=1",
+            pool_config,
+        ));
+        let response = warp::test::request()
+            .path("/manga/1")
+            .reply(&routes(backend, RouteConfig::default()))
+            .await;
+        assert_eq!(response.status(), 503);
+        let body: Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(body["code"], "database_unavailable");
+        assert!(!body["message"].as_str().unwrap().contains("127.0.0.1"));
+    }
+
+    #[tokio::test]
+    async fn keeps_or_assigns_request_ids() {
+        let routes = routes(test_backend(), RouteConfig::default());
+        let response = warp::test::request()
+            .path("/")
+            .header(REQUEST_ID_HEADER, "proxy-42")
+            .reply(&routes)
+            .await;
+        assert_eq!(response.headers()[REQUEST_ID_HEADER], "proxy-42");
+
+        let too_long = "x".repeat(MAX_REQUEST_ID_LENGTH + 1);
+        let response = warp::test::request()
+            .path("/")
+            .header(REQUEST_ID_HEADER, too_long.as_str())
+            .reply(&routes)
+            .await;
+        let request_id = response.headers()[REQUEST_ID_HEADER].to_str().unwrap();
+        assert!(Uuid::parse_str(request_id).is_ok());
+    }
+
+    #[tokio::test]
+    async fn lists_pages_by_page_number() {
+        let pages = get_json("/manga/1/2").await;
+        assert_eq!(field(&pages, "page_number"), vec![1, 2, 3]);
+        assert_eq!(field(&pages, "url_string")[0], "1-2-1.png");
+    }
+
+    #[tokio::test]
+    async fn gets_single_manga_and_chapter() {
+        let manga = get_json("/manga/2/info").await;
+        assert_eq!(manga["manga_name"], "Second");
+        assert_eq!(manga["author_names"], serde_json::json!(["Author"]));
+        let chapter = get_json("/manga/1/2.5/info").await;
+        assert_eq!(chapter["chapter_name"], "Chapter 2.5");
+
+        for (path, message) in [
+            ("/manga/3/info", "manga not found"),
+            ("/manga/1/3/info", "chapter not found"),
+            ("/manga/3/1/info", "chapter not found"),
+        ] {
+            let response = warp::test::request()
+                .path(path)
+                .reply(&routes(test_backend(), RouteConfig::default()))
+                .await;
+            assert_eq!(response.status(), 404, "GET {}", path);
+            let body: Value = serde_json::from_slice(response.body()).unwrap();
+            assert_eq!(body["message"], message);
+        }
+    }
+
+    async fn post_graphql(query: &str) -> Value {
+        let response = warp::test::request()
+            .method("POST")
+            .path("/graphql")
+            .json(&serde_json::json!({ "query": query }))
+            .reply(&routes(test_backend(), RouteConfig::default()))
+            .await;
+        assert_eq!(response.status(), 200);
+        serde_json::from_slice(response.body()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn resolves_nested_graphql_queries() {
+        let body = post_graphql(
+            "{ mangas(limit: 10) { total mangas { mangaName \
+               chapters(limit: 2) { chapterNumber pages { pageNumber urlString } } } } }",
+        )
+        .await;
+        assert!(body.get("errors").is_none(), "{}", body);
+        let listing = &body["data"]["mangas"];
+        assert_eq!(listing["total"], 2);
+        let first = &listing["mangas"][0];
+        assert_eq!(first["mangaName"], "First");
+        assert_eq!(first["chapters"][0]["chapterNumber"], "Extra");
+        assert_eq!(first["chapters"][1]["chapterNumber"], "2");
+        let pages = &first["chapters"][1]["pages"];
+        assert_eq!(field(pages, "pageNumber"), vec![1, 2, 3]);
+        assert_eq!(pages[0]["urlString"], "1-2-1.png");
+        assert_eq!(
+            listing["mangas"][1]["chapters"][0]["pages"],
+            serde_json::json!([])
+        );
+
+        let body = post_graphql(
+            "{ manga(mangaId: 2) { status chapter(chapterNumber: \"1\") { chapterName } } \
+               chapter(mangaId: 1, chapterNumber: \"3\") { chapterName } }",
+        )
+        .await;
+        assert_eq!(body["data"]["manga"]["status"], "ONGOING");
+        assert_eq!(body["data"]["manga"]["chapter"]["chapterName"], "Chapter 1");
+        assert_eq!(body["data"]["chapter"], Value::Null);
+    }
+
+    #[tokio::test]
+    async fn reports_graphql_errors_with_api_codes() {
+        let body = post_graphql("{ manga(mangaId: -1) { mangaName } }").await;
+        assert_eq!(body["data"]["manga"], Value::Null);
+        let error = &body["errors"][0];
+        assert_eq!(error["message"], "manga ID -1 is negative");
+        assert_eq!(error["extensions"]["code"], "invalid_input");
+
+        let body = post_graphql("{ manga(mangaId: 1) { unknownField } }").await;
+        assert!(body["errors"][0]["message"]
+            .as_str()
+            .unwrap()
+            .contains("unknownField"));
+    }
+
+    #[tokio::test]
+    async fn serves_demo_fixture() {
+        let fixture_path = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/demo.json");
+        let backend = Backend::Memory(InMemoryMangaService::from_json_file(fixture_path).unwrap());
+        let response = warp::test::request()
+            .path("/manga/1/1")
+            .reply(&routes(backend, RouteConfig::default()))
+            .await;
+        let pages: Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(field(&pages, "page_number"), vec![1, 2]);
+    }
+
+    #[tokio::test]
+    async fn rejects_unknown_routes() {
+        let (status, body) = get_error("/manga/1/2/3").await;
+        assert_eq!(status, 404);
+        assert_eq!(body["code"], "not_found");
+        assert_eq!(body["message"], "no such route");
+        let (status, body) = get_error("/search").await;
+        assert_eq!(status, 400);
+        assert_eq!(body["code"], "invalid_query");
+    }
+
+    #[tokio::test]
+    async fn serves_the_openapi_document_and_docs() {
+        let spec = get_json("/openapi.json").await;
+        assert_eq!(spec["openapi"], "3.1.0");
+        assert!(spec["paths"]["/manga/{manga_id}"]["get"].is_object());
+        let response = warp::test::request()
+            .path("/docs")
+            .reply(&routes(test_backend(), RouteConfig::default()))
+            .await;
+        assert_eq!(response.status(), 200);
+        assert!(String::from_utf8_lossy(response.body()).contains("openapi.json"));
+    }
+
+    #[tokio::test]
+    async fn answers_conditional_requests_with_304() {
+        let config = RouteConfig {
+            cache_control: CachePolicies {
+                pages: Arc::from("public, max-age=86400, immutable"),
+                ..CachePolicies::default()
+            },
+            ..RouteConfig::default()
+        };
+        let routes = routes(test_backend(), config);
+        let response = warp::test::request()
+            .path("/manga/1/2")
+            .reply(&routes)
+            .await;
+        assert_eq!(response.status(), 200);
+        let etag = response.headers()[header::ETAG]
+            .to_str()
+            .unwrap()
+            .to_owned();
+        assert_eq!(etag, http_cache::etag(response.body()));
+        assert_eq!(
+            response.headers()[header::LAST_MODIFIED],
+            "Fri, 01 Jan 2021 00:00:00 GMT"
+        );
+        assert_eq!(
+            response.headers()[header::CACHE_CONTROL],
+            "public, max-age=86400, immutable"
+        );
+
+        let response = warp::test::request()
+            .path("/v1/manga/1/2")
+            .header(header::IF_NONE_MATCH, etag.as_str())
+            .reply(&routes)
+            .await;
+        assert_eq!(response.status(), 304);
+        assert!(response.body().is_empty());
+        assert_eq!(response.headers()[header::ETAG], etag.as_str());
+
+        let response = warp::test::request()
+            .path("/manga/1/2")
+            .header(header::IF_NONE_MATCH, "\"stale\"")
+            .reply(&routes)
+            .await;
+        assert_eq!(response.status(), 200);
+        let response = warp::test::request()
+            .path("/manga/1/2")
+            .header(header::IF_MODIFIED_SINCE, "Sat, 02 Jan 2021 00:00:00 GMT")
+            .reply(&routes)
+            .await;
+        assert_eq!(response.status(), 304);
+        assert_eq!(response.headers()[header::ETAG], etag.as_str());
+        let response = warp::test::request()
+            .path("/manga/1/2")
+            .header(header::IF_MODIFIED_SINCE, "Thu, 31 Dec 2020 00:00:00 GMT")
+            .reply(&routes)
+            .await;
+        assert_eq!(response.status(), 200);
+        // If-None-Match takes precedence over If-Modified-Since
+        let response = warp::test::request()
+            .path("/manga/1/2")
+            .header(header::IF_NONE_MATCH, "\"stale\"")
+            .header(header::IF_MODIFIED_SINCE, "Sat, 02 Jan 2021 00:00:00 GMT")
+            .reply(&routes)
+            .await;
+        assert_eq!(response.status(), 200);
+        let response = warp::test::request().path("/manga/1").reply(&routes).await;
+        assert_eq!(
+            response.headers()[header::CACHE_CONTROL],
+            "public, max-age=60"
+        );
+
+        let response = warp::test::request().path("/manga/3").reply(&routes).await;
+        assert_eq!(response.status(), 404);
+        assert!(response.headers().get(header::ETAG).is_none());
+        assert!(response.headers().get(header::CACHE_CONTROL).is_none());
+    }
+
+    #[tokio::test]
+    async fn compresses_by_accept_encoding() {
+        let with_min_size = |min_size| {
+            let config = RouteConfig {
+                compression: CompressionConfig {
+                    min_size,
+                    ..CompressionConfig::default()
+                },
+                ..RouteConfig::default()
+            };
+            routes(test_backend(), config)
+        };
+        let routes = with_min_size(64);
+        let identity = warp::test::request().path("/manga/1").reply(&routes).await;
+        assert_eq!(identity.status(), 200);
+        assert!(identity.headers().get(header::CONTENT_ENCODING).is_none());
+        assert_eq!(identity.headers()[header::VARY], "accept-encoding");
+        let decode = |encoding: &str, body: &[u8]| {
+            let mut decoded = Vec::new();
+            match encoding {
+                "br" => brotli::Decompressor::new(body, 4096).read_to_end(&mut decoded),
+                "gzip" => flate2::read::GzDecoder::new(body).read_to_end(&mut decoded),
+                "deflate" => flate2::read::ZlibDecoder::new(body).read_to_end(&mut decoded),
+                encoding => panic!("unexpected encoding {}", encoding),
+            }
+            .unwrap();
+            decoded
+        };
+
+        for (accept_encoding, expected) in [
+            ("gzip", Some("gzip")),
+            ("deflate", Some("deflate")),
+            ("br", Some("br")),
+            ("gzip, deflate, br", Some("br")),
+            ("br;q=0.5, gzip", Some("gzip")),
+            ("identity", None),
+            ("gzip;q=0", None),
+        ] {
+            let response = warp::test::request()
+                .path("/manga/1")
+                .header(header::ACCEPT_ENCODING, accept_encoding)
+                .reply(&routes)
+                .await;
+            assert_eq!(response.status(), 200, "{}", accept_encoding);
+            let encoding = response
+                .headers()
+                .get(header::CONTENT_ENCODING)
+                .map(|encoding| encoding.to_str().unwrap());
+            assert_eq!(encoding, expected, "{}", accept_encoding);
+            let body = encoding.map_or_else(
+                || response.body().to_vec(),
+                |encoding| decode(encoding, response.body()),
+            );
+            assert_eq!(body, identity.body().as_ref(), "{}", accept_encoding);
+
+            // Each encoding has its own ETag and is revalidated with it
+            let etag = &response.headers()[header::ETAG];
+            assert_eq!(
+                etag != identity.headers()[header::ETAG],
+                expected.is_some(),
+                "{}",
+                accept_encoding
+            );
+            let response = warp::test::request()
+                .path("/manga/1")
+                .header(header::ACCEPT_ENCODING, accept_encoding)
+                .header(header::IF_NONE_MATCH, etag.to_str().unwrap())
+                .reply(&routes)
+                .await;
+            assert_eq!(response.status(), 304, "{}", accept_encoding);
+        }
+
+        let routes = with_min_size(identity.body().len() + 1);
+        let response = warp::test::request()
+            .path("/manga/1")
+            .header(header::ACCEPT_ENCODING, "gzip, deflate, br")
+            .reply(&routes)
+            .await;
+        assert_eq!(response.status(), 200);
+        assert!(response.headers().get(header::CONTENT_ENCODING).is_none());
+        assert!(response.headers().get(header::VARY).is_none());
+        assert_eq!(response.body(), identity.body());
+    }
+
+    #[tokio::test]
+    async fn serves_v1_and_its_unversioned_aliases() {
+        for path in ["/manga/1/2", "/v1/manga/1/2"] {
+            let pages = get_json(path).await;
+            assert_eq!(field(&pages, "page_number"), vec![1, 2, 3], "GET {}", path);
+        }
+        let manga = get_json("/v1/").await;
+        assert_eq!(field(&manga, "manga_id"), vec![1, 2]);
+        let (status, body) = get_error("/v1/manga/-1").await;
+        assert_eq!(status, 400);
+        assert_eq!(body["code"], "invalid_input");
+        let (status, body) = get_error("/v2/manga/1").await;
+        assert_eq!(status, 404);
+        assert_eq!(body["message"], "no such route");
+    }
+
+    /// Fails when a route moves or changes method without its `#[utoipa::path]`
+    #[tokio::test]
+    async fn routes_every_documented_operation() {
+        let spec = serde_json::to_value(ApiDoc::openapi()).unwrap();
+        let routes = routes(test_backend(), RouteConfig::default());
+        let paths = spec["paths"].as_object().unwrap();
+        assert!(!paths.is_empty());
+        for (path, item) in paths {
+            let concrete = format!("/v1{}", path)
+                .replace("{manga_id}", "1")
+                .replace("{chapter_number}", "2")
+                .replace("{page_index}", "0");
+            for method in item.as_object().unwrap().keys() {
+                let response = warp::test::request()
+                    .method(&method.to_uppercase())
+                    .path(&concrete)
+                    .reply(&routes)
+                    .await;
+                let body: Value = serde_json::from_slice(response.body()).unwrap_or_default();
+                assert_ne!(
+                    body["message"], "no such route",
+                    "{} {} is documented but not routed",
+                    method, path
+                );
+                assert_ne!(body["code"], "method_not_allowed", "{} {}", method, path);
+            }
+        }
+    }
+
+    const TOKEN: &str = "secret";
+
+    fn with_token(api_token: Option<&str>) -> RouteConfig {
+        RouteConfig {
+            api_token: api_token.map(str::to_owned),
+            ..RouteConfig::default()
+        }
+    }
+
+    async fn write(
+        filter: &(impl Filter<Extract = impl Reply, Error = Rejection> + Clone + 'static),
+        method: &str,
+        path: &str,
+        body: Option<Value>,
+    ) -> (StatusCode, Value) {
+        let mut request = warp::test::request()
+            .method(method)
+            .path(path)
+            .header("authorization", format!("Bearer {}", TOKEN));
+        if let Some(body) = body {
+            request = request.json(&body);
+        }
+        let response = request.reply(filter).await;
+        let body = serde_json::from_slice(response.body()).unwrap_or(Value::Null);
+        (response.status(), body)
+    }
+
+    #[tokio::test]
+    async fn rejects_writes_without_the_token() {
+        let body = serde_json::json!({ "manga_name": "Third" });
+        for (api_token, authorization) in [
+            (Some(TOKEN), None),
+            (Some(TOKEN), Some("Bearer wrong")),
+            (Some(TOKEN), Some(TOKEN)),
+            (None, Some("Bearer secret")),
+        ] {
+            let mut request = warp::test::request()
+                .method("POST")
+                .path("/manga")
+                .json(&body);
+            if let Some(authorization) = authorization {
+                request = request.header("authorization", authorization);
+            }
+            let response = request
+                .reply(&routes(test_backend(), with_token(api_token)))
+                .await;
+            assert_eq!(response.status(), 401, "{:?}", authorization);
+            assert_eq!(response.headers()["www-authenticate"], "Bearer");
+        }
+    }
+
+    #[tokio::test]
+    async fn creates_updates_and_deletes_manga() {
+        let routes = routes(test_backend(), with_token(Some(TOKEN)));
+        let (status, created) = write(
+            &routes,
+            "POST",
+            "/manga",
+            Some(serde_json::json!({
+                "manga_name": "Third",
+                "creators": [{ "creator_name": "Writer", "role": "Author" }]
+            })),
+        )
+        .await;
+        assert_eq!(status, 201);
+        assert_eq!(created["manga_id"], 3);
+        assert_eq!(created["author_names"], serde_json::json!(["Writer"]));
+
+        let (status, updated) = write(
+            &routes,
+            "PUT",
+            "/manga/3",
+            Some(serde_json::json!({ "manga_name": "Renamed" })),
+        )
+        .await;
+        assert_eq!(status, 200);
+        assert_eq!(updated["manga_name"], "Renamed");
+
+        let (status, _) = write(&routes, "DELETE", "/manga/3", None).await;
+        assert_eq!(status, 204);
+        let (status, body) = write(&routes, "DELETE", "/manga/3", None).await;
+        assert_eq!(status, 404);
+        assert_eq!(body["message"], "manga not found");
+    }
+
+    #[tokio::test]
+    async fn creates_and_replaces_chapters_with_pages() {
+        let routes = routes(test_backend(), with_token(Some(TOKEN)));
+        let chapter = |page_count: i32| {
+            serde_json::json!({
+                "chapter_number": "Vol.1 Ch.3",
+                "chapter_name": "Third",
+                "release_date": null,
+                "pages": (1..=page_count)
+                    .map(|page_number| serde_json::json!({
+                        "page_number": page_number,
+                        "urls": [format!("{}.png", page_number)]
+                    }))
+                    .collect::<Vec<Value>>()
+            })
+        };
+        let (status, created) = write(&routes, "POST", "/manga/2", Some(chapter(2))).await;
+        assert_eq!(status, 201);
+        assert_eq!(created["chapter_number"], "Vol.1 Ch.3");
+
+        let (status, _) = write(&routes, "POST", "/manga/2", Some(chapter(2))).await;
+        assert_eq!(status, 409);
+
+        let (status, _) = write(&routes, "PUT", "/manga/2/Vol.1%20Ch.3", Some(chapter(3))).await;
+        assert_eq!(status, 200);
+        let response = warp::test::request()
+            .path("/manga/2/Vol.1%20Ch.3")
+            .reply(&routes)
+            .await;
+        let pages: Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(field(&pages, "page_number"), vec![1, 2, 3]);
+
+        let (status, _) = write(&routes, "DELETE", "/manga/2/Vol.1%20Ch.3", None).await;
+        assert_eq!(status, 204);
+        let (status, _) = write(&routes, "PUT", "/manga/2/Vol.1%20Ch.3", Some(chapter(1))).await;
+        assert_eq!(status, 404);
+    }
+
+    #[tokio::test]
+    async fn rejects_invalid_writes() {
+        let routes = routes(test_backend(), with_token(Some(TOKEN)));
+        let (status, body) = write(
+            &routes,
+            "POST",
+            "/manga",
+            Some(serde_json::json!({ "manga_name": "" })),
+        )
+        .await;
+        assert_eq!(status, 400);
+        assert!(body["message"].is_string());
+
+        let (status, _) = write(
+            &routes,
+            "POST",
+            "/manga/9",
+            Some(serde_json::json!({
+                "chapter_number": "1",
+                "chapter_name": "First",
+                "release_date": null
+            })),
+        )
+        .await;
+        assert_eq!(status, 404);
+    }
+}
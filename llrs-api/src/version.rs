@@ -0,0 +1,167 @@
+//! Versions of the API mounted side by side under `/{name}/`.
+//!
+//! A response shape only changes in a new version, so a deployed llrs-site keeps the
+//! version its endpoint names. Serving a `v2` next to `v1` looks like
+//!
+//! ```ignore
+//! versioned(V2, v2_routes).or(versioned_with_aliases(V1_DEPRECATED, v1_routes))
+//! ```
+//!
+//! where `V1_DEPRECATED` is `V1` with a `Deprecation`, so `v1` responses, and those of the
+//! unversioned paths that alias it, announce when they stop being served.
+
+use crate::http_cache::http_date;
+use chrono::NaiveDateTime;
+use warp::{
+    http::{HeaderValue, Response},
+    hyper::Body,
+    Filter, Rejection,
+};
+
+/// Deprecation date of a version as a structured field date, eg: `@1688169599` (RFC 9745)
+pub(crate) const DEPRECATION_HEADER: &str = "deprecation";
+
+/// When a version stops being served as an HTTP date (RFC 8594)
+pub(crate) const SUNSET_HEADER: &str = "sunset";
+
+/// A version of the API and whether it's on its way out.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ApiVersion {
+    /// First path segment of the version's routes, eg: `v1`
+    pub(crate) name: &'static str,
+    pub(crate) deprecation: Option<Deprecation>,
+}
+
+/// The current version, the unversioned paths are aliases of it.
+pub(crate) const V1: ApiVersion = ApiVersion {
+    name: "v1",
+    deprecation: None,
+};
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Deprecation {
+    /// Since when the version is deprecated, in UTC
+    pub(crate) deprecated_at: NaiveDateTime,
+    /// When the version stops being served, in UTC
+    pub(crate) sunset: Option<NaiveDateTime>,
+}
+
+impl ApiVersion {
+    /// Adds the deprecation headers of this version to `response`, if it's deprecated.
+    pub(crate) fn annotate(&self, mut response: Response<Body>) -> Response<Body> {
+        if let Some(deprecation) = self.deprecation {
+            let headers = response.headers_mut();
+            let deprecated_at = format!("@{}", deprecation.deprecated_at.and_utc().timestamp());
+            headers.insert(
+                DEPRECATION_HEADER,
+                HeaderValue::from_str(&deprecated_at).expect("a timestamp is a valid header"),
+            );
+            if let Some(sunset) = deprecation.sunset {
+                let sunset = http_date(sunset);
+                headers.insert(
+                    SUNSET_HEADER,
+                    HeaderValue::from_str(&sunset).expect("a date is a valid header"),
+                );
+            }
+        }
+        response
+    }
+}
+
+/// Mounts `routes` under `/{version.name}/`, marking their replies when the version is
+/// deprecated. Rejections are answered outside of any version, without the headers.
+pub(crate) fn versioned<F>(
+    version: ApiVersion,
+    routes: F,
+) -> impl Filter<Extract = (Response<Body>,), Error = Rejection> + Clone
+where
+    F: Filter<Extract = (Response<Body>,), Error = Rejection> + Clone + Send + Sync + 'static,
+{
+    warp::path(version.name)
+        .and(routes)
+        .map(move |response| version.annotate(response))
+}
+
+/// Mounts `routes` under `/{version.name}/` and again without the prefix. The aliases are
+/// marked like the versioned paths, so they announce `version`'s deprecation too.
+pub(crate) fn versioned_with_aliases<F>(
+    version: ApiVersion,
+    routes: F,
+) -> impl Filter<Extract = (Response<Body>,), Error = Rejection> + Clone
+where
+    F: Filter<Extract = (Response<Body>,), Error = Rejection> + Clone + Send + Sync + 'static,
+{
+    versioned(version, routes.clone())
+        .or(routes.map(move |response| version.annotate(response)))
+        .unify()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use warp::Reply;
+
+    fn routes() -> impl Filter<Extract = (Response<Body>,), Error = Rejection> + Clone {
+        warp::path!("manga").map(|| "manga".into_response())
+    }
+
+    #[tokio::test]
+    async fn serves_versions_side_by_side() {
+        let date = |day| {
+            NaiveDate::from_ymd_opt(2023, 6, day)
+                .unwrap()
+                .and_hms_opt(0, 0, 0)
+                .unwrap()
+        };
+        let deprecated = ApiVersion {
+            name: "v1",
+            deprecation: Some(Deprecation {
+                deprecated_at: date(1),
+                sunset: Some(date(30)),
+            }),
+        };
+        let current = ApiVersion {
+            name: "v2",
+            deprecation: None,
+        };
+        let routes = versioned(current, routes()).or(versioned(deprecated, routes()));
+
+        let response = warp::test::request().path("/v1/manga").reply(&routes).await;
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.headers()[DEPRECATION_HEADER], "@1685577600");
+        assert_eq!(
+            response.headers()[SUNSET_HEADER],
+            "Fri, 30 Jun 2023 00:00:00 GMT"
+        );
+
+        let response = warp::test::request().path("/v2/manga").reply(&routes).await;
+        assert_eq!(response.status(), 200);
+        assert!(response.headers().get(DEPRECATION_HEADER).is_none());
+        assert!(response.headers().get(SUNSET_HEADER).is_none());
+
+        let response = warp::test::request().path("/v3/manga").reply(&routes).await;
+        assert_eq!(response.status(), 404);
+    }
+
+    #[tokio::test]
+    async fn marks_aliases_of_deprecated_versions() {
+        let deprecated = ApiVersion {
+            deprecation: Some(Deprecation {
+                deprecated_at: NaiveDate::from_ymd_opt(2023, 6, 1)
+                    .unwrap()
+                    .and_hms_opt(0, 0, 0)
+                    .unwrap(),
+                sunset: None,
+            }),
+            ..V1
+        };
+        let routes = versioned_with_aliases(deprecated, routes());
+        for path in ["/v1/manga", "/manga"] {
+            let response = warp::test::request().path(path).reply(&routes).await;
+            assert_eq!(response.status(), 200, "GET {}", path);
+            assert_eq!(response.headers()[DEPRECATION_HEADER], "@1685577600");
+            assert!(response.headers().get(SUNSET_HEADER).is_none());
+        }
+    }
+}
@@ -0,0 +1,530 @@
+//! Typed client for llrs-api, works natively and from wasm.
+//!
+//! ```no_run
+//! # async fn example() -> llrs_client::Result<()> {
+//! let client = llrs_client::Client::new("http://localhost:42069/v1")?;
+//! for manga in client.get_manga_list().await? {
+//!     println!("{}", manga.manga_name);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use llrs_model::{
+    Chapter, Manga, MangaListQuery, MangaListing, NewChapter, NewManga, Page, RecentChapter,
+    RecentChaptersQuery, SearchResult,
+};
+use reqwest::{RequestBuilder, Response, Url};
+use serde::{de::DeserializeOwned, Deserialize};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Invalid API endpoint {0}")]
+    InvalidEndpoint(String),
+    #[error("HTTP error {0}")]
+    Http(reqwest::Error),
+    #[error("API responded {status}: {message}")]
+    Api {
+        status: u16,
+        message: String,
+        /// Machine readable reason, eg: `not_found`, `None` for responses not from llrs-api
+        code: Option<String>,
+        /// Quote this when reporting a failure, the API logs errors with it
+        request_id: Option<String>,
+    },
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(e: reqwest::Error) -> Self {
+        Error::Http(e)
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Header llrs-api reports the size of a whole manga listing in.
+const TOTAL_COUNT_HEADER: &str = "x-total-count";
+
+/// Error body llrs-api responds with for failed requests.
+#[derive(Debug, Deserialize)]
+struct ErrorBody {
+    code: String,
+    message: String,
+    request_id: String,
+}
+
+/// Client for one llrs-api endpoint, cloning shares the underlying connections.
+#[derive(Debug, Clone)]
+pub struct Client {
+    http: reqwest::Client,
+    endpoint: Url,
+    /// Bearer token sent with writes
+    api_token: Option<String>,
+}
+
+impl Client {
+    /// Creates a client for a version of the API at `endpoint`, eg: `https://api.example.com/llrs/v1`
+    pub fn new(endpoint: &str) -> Result<Client> {
+        Client::with_http_client(endpoint, reqwest::Client::new())
+    }
+
+    /// Creates a client reusing a configured `reqwest::Client`, eg: for timeouts.
+    pub fn with_http_client(endpoint: &str, http: reqwest::Client) -> Result<Client> {
+        let endpoint =
+            Url::parse(endpoint).map_err(|err| Error::InvalidEndpoint(err.to_string()))?;
+        if endpoint.cannot_be_a_base() {
+            return Err(Error::InvalidEndpoint(endpoint.to_string()));
+        }
+        Ok(Client {
+            http,
+            endpoint,
+            api_token: None,
+        })
+    }
+
+    /// Authenticates writes with the API's `--api-token`.
+    pub fn with_api_token(mut self, api_token: impl Into<String>) -> Client {
+        self.api_token = Some(api_token.into());
+        self
+    }
+
+    pub async fn get_manga_list(&self) -> Result<Vec<Manga>> {
+        self.get(&[]).await
+    }
+
+    /// Lists one slice of the catalog, `total` counts every manga matching the query.
+    pub async fn list_manga(&self, query: &MangaListQuery) -> Result<MangaListing> {
+        let response = send(self.http.get(self.url(&[])).query(query)).await?;
+        let total = response
+            .headers()
+            .get(TOTAL_COUNT_HEADER)
+            .and_then(|total| total.to_str().ok())
+            .and_then(|total| total.parse().ok());
+        let mangas: Vec<Manga> = response.json().await?;
+        Ok(MangaListing {
+            total: total.unwrap_or(mangas.len() as u64),
+            mangas,
+        })
+    }
+
+    /// Best matches for `query` first, the API picks the limit when it's `None`.
+    pub async fn search(&self, query: &str, limit: Option<u32>) -> Result<Vec<SearchResult>> {
+        let mut request = self.http.get(self.url(&["search"])).query(&[("q", query)]);
+        if let Some(limit) = limit {
+            request = request.query(&[("limit", limit)]);
+        }
+        Ok(send(request).await?.json().await?)
+    }
+
+    /// Latest releases across every manga, newest first.
+    pub async fn get_recent_chapters(
+        &self,
+        query: &RecentChaptersQuery,
+    ) -> Result<Vec<RecentChapter>> {
+        let request = self.http.get(self.url(&["recent"])).query(query);
+        Ok(send(request).await?.json().await?)
+    }
+
+    /// Fails with a 404 `Error::Api` when there's no such manga.
+    pub async fn get_manga(&self, manga_id: i32) -> Result<Manga> {
+        self.get(&["manga", &manga_id.to_string(), "info"]).await
+    }
+
+    pub async fn get_chapter_list(&self, manga_id: i32) -> Result<Vec<Chapter>> {
+        self.get(&["manga", &manga_id.to_string()]).await
+    }
+
+    /// One chapter without its pages, fails with a 404 `Error::Api` when there's no such chapter.
+    pub async fn get_chapter(&self, manga_id: i32, chapter_number: &str) -> Result<Chapter> {
+        self.get(&["manga", &manga_id.to_string(), chapter_number, "info"])
+            .await
+    }
+
+    pub async fn get_page_list(&self, manga_id: i32, chapter_number: &str) -> Result<Vec<Page>> {
+        self.get(&["manga", &manga_id.to_string(), chapter_number])
+            .await
+    }
+
+    pub async fn create_manga(&self, manga: &NewManga) -> Result<Manga> {
+        self.write(self.http.post(self.url(&["manga"])).json(manga))
+            .await?
+            .json()
+            .await
+            .map_err(Error::from)
+    }
+
+    pub async fn update_manga(&self, manga_id: i32, manga: &NewManga) -> Result<Manga> {
+        let url = self.url(&["manga", &manga_id.to_string()]);
+        self.write(self.http.put(url).json(manga))
+            .await?
+            .json()
+            .await
+            .map_err(Error::from)
+    }
+
+    pub async fn delete_manga(&self, manga_id: i32) -> Result<()> {
+        let url = self.url(&["manga", &manga_id.to_string()]);
+        self.write(self.http.delete(url)).await?;
+        Ok(())
+    }
+
+    pub async fn create_chapter(&self, manga_id: i32, chapter: &NewChapter) -> Result<Chapter> {
+        let url = self.url(&["manga", &manga_id.to_string()]);
+        self.write(self.http.post(url).json(chapter))
+            .await?
+            .json()
+            .await
+            .map_err(Error::from)
+    }
+
+    /// Replaces the chapter's metadata and all of its pages.
+    pub async fn update_chapter(
+        &self,
+        manga_id: i32,
+        chapter_number: &str,
+        chapter: &NewChapter,
+    ) -> Result<Chapter> {
+        let url = self.url(&["manga", &manga_id.to_string(), chapter_number]);
+        self.write(self.http.put(url).json(chapter))
+            .await?
+            .json()
+            .await
+            .map_err(Error::from)
+    }
+
+    pub async fn delete_chapter(&self, manga_id: i32, chapter_number: &str) -> Result<()> {
+        let url = self.url(&["manga", &manga_id.to_string(), chapter_number]);
+        self.write(self.http.delete(url)).await?;
+        Ok(())
+    }
+
+    /// Appends `segments` to the endpoint path, percent encoding each one.
+    fn url(&self, segments: &[&str]) -> Url {
+        let mut url = self.endpoint.clone();
+        url.path_segments_mut()
+            .expect("checked by Client::with_http_client")
+            .pop_if_empty()
+            .extend(segments);
+        url
+    }
+
+    async fn get<T: DeserializeOwned>(&self, segments: &[&str]) -> Result<T> {
+        let response = send(self.http.get(self.url(segments))).await?;
+        Ok(response.json().await?)
+    }
+
+    async fn write(&self, request: RequestBuilder) -> Result<Response> {
+        let request = match &self.api_token {
+            Some(api_token) => request.bearer_auth(api_token),
+            None => request,
+        };
+        send(request).await
+    }
+}
+
+/// Sends `request`, turning unsuccessful responses into `Error::Api`.
+async fn send(request: RequestBuilder) -> Result<Response> {
+    let response = request.send().await?;
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    // Proxies in front of the API may answer in plain text
+    let body = response.text().await?;
+    Err(match serde_json::from_str::<ErrorBody>(&body) {
+        Ok(error) => Error::Api {
+            status: status.as_u16(),
+            message: error.message,
+            code: Some(error.code),
+            request_id: Some(error.request_id),
+        },
+        Err(_) => Error::Api {
+            status: status.as_u16(),
+            message: body,
+            code: None,
+            request_id: None,
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::SocketAddr;
+    use warp::{http::StatusCode, Filter};
+
+    /// Serves canned llrs-api responses, echoing the decoded chapter number as a page URL.
+    fn serve() -> SocketAddr {
+        // Names the manga after the query string, listings report 7 manga in total
+        let mangas = warp::path::end()
+            .and(warp::query::raw().or(warp::any().map(String::new)).unify())
+            .map(|query: String| {
+                let mangas = warp::reply::json(&serde_json::json!([{
+                    "manga_id": 1,
+                    "manga_name": if query.is_empty() { "Manga".to_owned() } else { query },
+                    "author_names": ["Author"],
+                    "artist_names": ["Artist"],
+                    "cover_image_url": null,
+                    "purchase_url": null
+                }]));
+                warp::reply::with_header(mangas, TOTAL_COUNT_HEADER, "7")
+            });
+        // Echoes the query string as the matched text
+        let search = warp::path!("search")
+            .and(warp::query::raw())
+            .map(|query: String| {
+                warp::reply::json(&serde_json::json!([{
+                    "manga": {
+                        "manga_id": 1,
+                        "manga_name": "Manga",
+                        "author_names": [],
+                        "artist_names": [],
+                        "cover_image_url": null,
+                        "purchase_url": null
+                    },
+                    "field": "Chapter",
+                    "matched_text": query,
+                    "chapter_number": "Vol.1 Ch.2",
+                    "score": 100
+                }]))
+            });
+        // Echoes the query string as the chapter name
+        let recent = warp::path!("recent")
+            .and(warp::query::raw().or(warp::any().map(String::new)).unify())
+            .map(|query: String| {
+                warp::reply::json(&serde_json::json!([{
+                    "manga": {
+                        "manga_id": 1,
+                        "manga_name": "Manga",
+                        "author_names": [],
+                        "artist_names": [],
+                        "cover_image_url": null,
+                        "purchase_url": null
+                    },
+                    "chapter": {
+                        "chapter_number": "2",
+                        "chapter_name": query,
+                        "creation_date": "2021-01-01T00:00:00",
+                        "release_date": null,
+                        "manga_id": 1
+                    }
+                }]))
+            });
+        let chapters = warp::path!("manga" / i32).map(|manga_id: i32| {
+            let status = if manga_id < 0 {
+                StatusCode::INTERNAL_SERVER_ERROR
+            } else {
+                StatusCode::OK
+            };
+            warp::reply::with_status(
+                warp::reply::json(&serde_json::json!({
+                    "code": "database_unavailable",
+                    "message": "Database is down",
+                    "request_id": "request-1"
+                })),
+                status,
+            )
+        });
+        let manga_info = warp::path!("manga" / i32 / "info").map(|manga_id: i32| {
+            warp::reply::json(&serde_json::json!({
+                "manga_id": manga_id,
+                "manga_name": "Manga",
+                "author_names": ["Author"],
+                "artist_names": [],
+                "cover_image_url": null,
+                "purchase_url": null
+            }))
+        });
+        // Echoes the decoded chapter number as the chapter name
+        let chapter_info = warp::path!("manga" / i32 / String / "info").map(
+            |manga_id: i32, chapter_number: String| {
+                warp::reply::json(&serde_json::json!({
+                    "chapter_number": "1",
+                    "chapter_name": chapter_number,
+                    "creation_date": "2021-01-01T00:00:00",
+                    "release_date": null,
+                    "manga_id": manga_id
+                }))
+            },
+        );
+        let pages = warp::path!("manga" / i32 / String).map(|_, chapter_number: String| {
+            warp::reply::json(&serde_json::json!([{
+                "url_string": chapter_number,
+                "page_number": 1
+            }]))
+        });
+        let create_manga = warp::post()
+            .and(warp::path!("manga"))
+            .and(warp::header::optional::<String>("authorization"))
+            .and(warp::body::json())
+            .map(|authorization: Option<String>, manga: NewManga| {
+                if authorization.as_deref() != Some("Bearer secret") {
+                    return warp::reply::with_status(
+                        warp::reply::json(&serde_json::json!({ "message": "Unauthorized" })),
+                        StatusCode::UNAUTHORIZED,
+                    );
+                }
+                warp::reply::with_status(
+                    warp::reply::json(&serde_json::json!({
+                        "manga_id": 2,
+                        "manga_name": manga.manga_name,
+                        "author_names": [],
+                        "artist_names": [],
+                        "cover_image_url": manga.cover_image_url,
+                        "purchase_url": manga.purchase_url
+                    })),
+                    StatusCode::CREATED,
+                )
+            });
+        let delete_chapter = warp::delete()
+            .and(warp::path!("manga" / i32 / String))
+            .map(|_, _| StatusCode::NO_CONTENT);
+        let (addr, server) = warp::serve(
+            create_manga
+                .or(delete_chapter)
+                .or(mangas)
+                .or(search)
+                .or(recent)
+                .or(chapters)
+                .or(manga_info)
+                .or(chapter_info)
+                .or(pages),
+        )
+        .bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+        addr
+    }
+
+    #[tokio::test]
+    async fn gets_manga_list() {
+        let client = Client::new(&format!("http://{}", serve())).unwrap();
+        let mangas = client.get_manga_list().await.unwrap();
+        assert_eq!(mangas[0].manga_name, "Manga");
+    }
+
+    #[tokio::test]
+    async fn lists_manga_with_total_count() {
+        let client = Client::new(&format!("http://{}", serve())).unwrap();
+        let query = MangaListQuery {
+            sort: llrs_model::MangaSort::NewestChapter,
+            limit: Some(1),
+            ..MangaListQuery::default()
+        };
+        let listing = client.list_manga(&query).await.unwrap();
+        assert_eq!(listing.total, 7);
+        assert_eq!(
+            listing.mangas[0].manga_name,
+            "sort=newest_chapter&offset=0&limit=1"
+        );
+    }
+
+    #[tokio::test]
+    async fn searches_with_an_optional_limit() {
+        let client = Client::new(&format!("http://{}", serve())).unwrap();
+        let results = client.search("one piece", Some(5)).await.unwrap();
+        assert_eq!(results[0].matched_text, "q=one+piece&limit=5");
+        assert_eq!(results[0].field, llrs_model::SearchField::Chapter);
+        let results = client.search("a&b", None).await.unwrap();
+        assert_eq!(results[0].matched_text, "q=a%26b");
+    }
+
+    #[tokio::test]
+    async fn gets_recent_chapters() {
+        let client = Client::new(&format!("http://{}", serve())).unwrap();
+        let query = RecentChaptersQuery {
+            limit: Some(5),
+            since: "2021-01-01T12:00:00".parse().ok(),
+        };
+        let recent = client.get_recent_chapters(&query).await.unwrap();
+        assert_eq!(recent[0].manga.manga_name, "Manga");
+        assert_eq!(
+            recent[0].chapter.chapter_name,
+            "limit=5&since=2021-01-01T12%3A00%3A00"
+        );
+        let recent = client
+            .get_recent_chapters(&RecentChaptersQuery::default())
+            .await
+            .unwrap();
+        assert_eq!(recent[0].chapter.chapter_name, "");
+    }
+
+    #[tokio::test]
+    async fn encodes_chapter_numbers() {
+        let client = Client::new(&format!("http://{}/", serve())).unwrap();
+        let pages = client.get_page_list(1, "Vol.1 Ch.2/3").await.unwrap();
+        assert_eq!(pages[0].url_string, "Vol.1%20Ch.2%2F3");
+    }
+
+    #[tokio::test]
+    async fn gets_single_manga_and_chapter() {
+        let client = Client::new(&format!("http://{}", serve())).unwrap();
+        let manga = client.get_manga(3).await.unwrap();
+        assert_eq!(manga.manga_id, 3);
+        let chapter = client.get_chapter(3, "Vol.1 Ch.2").await.unwrap();
+        assert_eq!(chapter.manga_id, 3);
+        assert_eq!(chapter.chapter_name, "Vol.1%20Ch.2");
+    }
+
+    #[tokio::test]
+    async fn reports_api_errors() {
+        let client = Client::new(&format!("http://{}", serve())).unwrap();
+        match client.get_chapter_list(-1).await {
+            Err(Error::Api {
+                status,
+                message,
+                code,
+                request_id,
+            }) => {
+                assert_eq!(status, 500);
+                assert_eq!(message, "Database is down");
+                assert_eq!(code.as_deref(), Some("database_unavailable"));
+                assert_eq!(request_id.as_deref(), Some("request-1"));
+            }
+            result => panic!("expected an API error, got {:?}", result),
+        }
+    }
+
+    fn new_manga() -> NewManga {
+        NewManga {
+            manga_name: "New".to_owned(),
+            creators: vec![],
+            cover_image_url: None,
+            purchase_url: None,
+            status: llrs_model::MangaStatus::Ongoing,
+            alternate_titles: vec![],
+        }
+    }
+
+    #[tokio::test]
+    async fn sends_api_token_with_writes() {
+        let client = Client::new(&format!("http://{}", serve()))
+            .unwrap()
+            .with_api_token("secret");
+        let manga = client.create_manga(&new_manga()).await.unwrap();
+        assert_eq!(manga.manga_id, 2);
+        assert_eq!(manga.manga_name, "New");
+        client.delete_chapter(2, "Vol.1 Ch.2").await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn reports_unauthorized_writes() {
+        let client = Client::new(&format!("http://{}", serve())).unwrap();
+        assert!(matches!(
+            client.create_manga(&new_manga()).await,
+            Err(Error::Api { status: 401, .. })
+        ));
+    }
+
+    #[test]
+    fn rejects_invalid_endpoints() {
+        assert!(matches!(
+            Client::new("not a url"),
+            Err(Error::InvalidEndpoint(_))
+        ));
+        assert!(matches!(
+            Client::new("mailto:llrs@example.com"),
+            Err(Error::InvalidEndpoint(_))
+        ));
+    }
+}
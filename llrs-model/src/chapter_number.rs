@@ -0,0 +1,273 @@
+use std::{
+    cmp::Ordering,
+    convert::Infallible,
+    fmt,
+    hash::{Hash, Hasher},
+    str::FromStr,
+};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// A chapter number as published, eg: `10`, `10.5`, `10a`, `Vol.2 Ch.3` or `Extra`.
+///
+/// Specials (anything without a number, like `Extra` or `Oneshot`) sort first,
+/// then chapters without a volume, then chapters by volume.
+/// Chapters compare by number and then by suffix, so `10 < 10a < 10.5 < 11`.
+/// The original text is kept, equality and serde both use it unchanged.
+#[derive(Debug, Clone)]
+pub struct ChapterNumber {
+    raw: String,
+    key: SortKey,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum SortKey {
+    Special {
+        name: String,
+    },
+    Numbered {
+        volume: Option<u32>,
+        whole: u64,
+        /// Decimal digits without trailing zeros, compares correctly as a string
+        fraction: String,
+        suffix: String,
+    },
+}
+
+const VOLUME_LABELS: &[&str] = &["volume", "vol"];
+const CHAPTER_LABELS: &[&str] = &["chapter", "ch"];
+
+impl ChapterNumber {
+    pub fn as_str(&self) -> &str {
+        &self.raw
+    }
+
+    /// Volume parsed from a `Vol.2 Ch.3` style number.
+    pub fn volume(&self) -> Option<u32> {
+        match self.key {
+            SortKey::Numbered { volume, .. } => volume,
+            SortKey::Special { .. } => None,
+        }
+    }
+
+    /// True for chapters without a number, like `Extra` or `Oneshot`.
+    pub fn is_special(&self) -> bool {
+        matches!(self.key, SortKey::Special { .. })
+    }
+}
+
+fn parse(raw: &str) -> SortKey {
+    let text = raw.trim().to_lowercase();
+    let special = || SortKey::Special { name: text.clone() };
+
+    let (volume, rest) = match strip_label(&text, VOLUME_LABELS) {
+        Some(rest) => {
+            let (digits, rest) = split_digits(rest);
+            match digits.parse::<u32>() {
+                Ok(volume) => (Some(volume), trim_separators(rest)),
+                Err(_) => return special(),
+            }
+        }
+        None => (None, text.as_str()),
+    };
+    let rest = strip_label(rest, CHAPTER_LABELS).unwrap_or(rest);
+
+    let (digits, rest) = split_digits(rest);
+    let whole = match digits.parse::<u64>() {
+        Ok(whole) => whole,
+        Err(_) => return special(),
+    };
+    let (fraction, rest) = match rest.strip_prefix('.') {
+        Some(decimals) if decimals.starts_with(|c: char| c.is_ascii_digit()) => {
+            split_digits(decimals)
+        }
+        _ => ("", rest),
+    };
+    SortKey::Numbered {
+        volume,
+        whole,
+        fraction: fraction.trim_end_matches('0').to_owned(),
+        suffix: trim_separators(rest).to_owned(),
+    }
+}
+
+/// Strips a label like `vol`/`vol.`/`vol ` when a number follows it.
+fn strip_label<'a>(text: &'a str, labels: &[&str]) -> Option<&'a str> {
+    labels.iter().find_map(|label| {
+        let rest = text
+            .strip_prefix(label)?
+            .trim_start_matches('.')
+            .trim_start();
+        if rest.starts_with(|c: char| c.is_ascii_digit()) {
+            Some(rest)
+        } else {
+            None
+        }
+    })
+}
+
+fn split_digits(text: &str) -> (&str, &str) {
+    text.split_at(
+        text.find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(text.len()),
+    )
+}
+
+fn trim_separators(text: &str) -> &str {
+    text.trim_matches(|c: char| c.is_whitespace() || c == '-' || c == '_' || c == ',')
+}
+
+impl From<&str> for ChapterNumber {
+    fn from(raw: &str) -> Self {
+        ChapterNumber {
+            raw: raw.to_owned(),
+            key: parse(raw),
+        }
+    }
+}
+
+impl From<String> for ChapterNumber {
+    fn from(raw: String) -> Self {
+        let key = parse(&raw);
+        ChapterNumber { raw, key }
+    }
+}
+
+impl FromStr for ChapterNumber {
+    type Err = Infallible;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        Ok(ChapterNumber::from(raw))
+    }
+}
+
+impl fmt::Display for ChapterNumber {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.raw)
+    }
+}
+
+impl Ord for ChapterNumber {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Falling back to the text keeps `10` and `10.0` distinct but ordered
+        self.key
+            .cmp(&other.key)
+            .then_with(|| self.raw.cmp(&other.raw))
+    }
+}
+
+impl PartialOrd for ChapterNumber {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for ChapterNumber {
+    fn eq(&self, other: &Self) -> bool {
+        self.raw == other.raw
+    }
+}
+
+impl Eq for ChapterNumber {}
+
+impl Hash for ChapterNumber {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.raw.hash(state);
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Serialize for ChapterNumber {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.raw)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for ChapterNumber {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(ChapterNumber::from)
+    }
+}
+
+#[cfg(feature = "openapi")]
+impl utoipa::PartialSchema for ChapterNumber {
+    fn schema() -> utoipa::openapi::RefOr<utoipa::openapi::schema::Schema> {
+        utoipa::openapi::schema::ObjectBuilder::new()
+            .schema_type(utoipa::openapi::schema::Type::String)
+            .description(Some(
+                "A chapter number as published, eg: `10`, `10.5`, `10a`, `Vol.2 Ch.3` or `Extra`",
+            ))
+            .into()
+    }
+}
+
+#[cfg(feature = "openapi")]
+impl utoipa::ToSchema for ChapterNumber {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(numbers: &[&str]) -> Vec<String> {
+        let mut numbers = numbers
+            .iter()
+            .map(|number| ChapterNumber::from(*number))
+            .collect::<Vec<ChapterNumber>>();
+        numbers.sort();
+        numbers.iter().map(ChapterNumber::to_string).collect()
+    }
+
+    #[test]
+    fn orders_decimals_numerically() {
+        assert_eq!(
+            sorted(&["10", "2.5", "2", "1", "2.10", "2.05"]),
+            vec!["1", "2", "2.05", "2.10", "2.5", "10"]
+        );
+    }
+
+    #[test]
+    fn orders_suffixes_after_their_chapter() {
+        assert_eq!(
+            sorted(&["11", "10b", "10.5", "10a", "10"]),
+            vec!["10", "10a", "10b", "10.5", "11"]
+        );
+    }
+
+    #[test]
+    fn orders_specials_first_by_name() {
+        assert_eq!(
+            sorted(&["1", "Oneshot", "Extra", "extra 2"]),
+            vec!["Extra", "extra 2", "Oneshot", "1"]
+        );
+        assert!(ChapterNumber::from("Oneshot").is_special());
+    }
+
+    #[test]
+    fn orders_volumes_after_plain_chapters() {
+        assert_eq!(
+            sorted(&["Vol.2 Ch.3", "Vol.1 Ch.10", "Volume 1 Chapter 2", "4"]),
+            vec!["4", "Volume 1 Chapter 2", "Vol.1 Ch.10", "Vol.2 Ch.3"]
+        );
+        assert_eq!(ChapterNumber::from("Vol.2 Ch.3").volume(), Some(2));
+        assert_eq!(ChapterNumber::from("Ch. 3").volume(), None);
+    }
+
+    #[test]
+    fn keeps_equivalent_numbers_distinct() {
+        let ten = ChapterNumber::from("10");
+        let ten_point_zero = ChapterNumber::from("10.0");
+        assert_ne!(ten, ten_point_zero);
+        assert!(ten < ten_point_zero);
+        assert!(ten_point_zero < ChapterNumber::from("10.5"));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serializes_as_the_original_string() {
+        let number: ChapterNumber = serde_json::from_str("\"Vol.2 Ch.3\"").unwrap();
+        assert_eq!(number.as_str(), "Vol.2 Ch.3");
+        assert_eq!(serde_json::to_string(&number).unwrap(), "\"Vol.2 Ch.3\"");
+    }
+}
@@ -1,7 +1,15 @@
+use std::str::FromStr;
+
 #[cfg(feature = "chrono")]
 use chrono::NaiveDateTime;
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
+#[cfg(feature = "openapi")]
+use utoipa::{IntoParams, ToSchema};
+
+mod chapter_number;
+
+pub use chapter_number::ChapterNumber;
 
 #[cfg(feature = "chrono")]
 pub type DateTimeType = NaiveDateTime;
@@ -9,9 +17,9 @@ pub type DateTimeType = NaiveDateTime;
 pub type DateTimeType = String;
 
 // Should redesign DB
-#[derive(Debug)]
-#[cfg(feature = "serde")]
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
 pub struct Manga {
     pub manga_id: i32,
     pub manga_name: String,
@@ -19,30 +27,290 @@ pub struct Manga {
     // and then this returns all associated
     pub author_names: Vec<String>,
     pub artist_names: Vec<String>,
-    pub cover_image_url: String,
-    pub purchase_url: String,
+    pub cover_image_url: Option<String>,
+    pub purchase_url: Option<String>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub status: MangaStatus,
+    /// When the manga was added, unknown for manga added before this was recorded
+    #[cfg_attr(feature = "serde", serde(default))]
+    #[cfg_attr(feature = "openapi", schema(value_type = Option<String>, example = "2021-01-01T00:00:00"))]
+    pub creation_date: Option<DateTimeType>,
+    /// When the manga or any of its chapters last changed
+    #[cfg_attr(feature = "serde", serde(default))]
+    #[cfg_attr(feature = "openapi", schema(value_type = Option<String>, example = "2021-01-01T00:00:00"))]
+    pub updated_date: Option<DateTimeType>,
 }
 
-#[derive(Debug)]
-#[cfg(feature = "serde")]
-#[derive(Serialize, Deserialize)]
+/// Publication status of a manga.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub enum MangaStatus {
+    #[default]
+    Ongoing,
+    Completed,
+    Hiatus,
+    Cancelled,
+}
+
+impl MangaStatus {
+    /// The value stored in the `Status` column.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MangaStatus::Ongoing => "Ongoing",
+            MangaStatus::Completed => "Completed",
+            MangaStatus::Hiatus => "Hiatus",
+            MangaStatus::Cancelled => "Cancelled",
+        }
+    }
+}
+
+impl FromStr for MangaStatus {
+    type Err = String;
+
+    fn from_str(status: &str) -> Result<Self, Self::Err> {
+        match status {
+            "Ongoing" => Ok(MangaStatus::Ongoing),
+            "Completed" => Ok(MangaStatus::Completed),
+            "Hiatus" => Ok(MangaStatus::Hiatus),
+            "Cancelled" => Ok(MangaStatus::Cancelled),
+            _ => Err(format!("unknown manga status {:?}", status)),
+        }
+    }
+}
+
+/// Order of a manga listing, ties are broken by `manga_id`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum MangaSort {
+    /// Alphabetical, ignoring case
+    #[default]
+    Name,
+    /// Most recently added chapter first, manga without chapters last
+    NewestChapter,
+    /// Most recently added manga first
+    CreationDate,
+}
+
+/// Which manga to list and which slice of them, every field is optional in a query string.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "openapi", derive(IntoParams))]
+#[cfg_attr(feature = "openapi", into_params(parameter_in = Query))]
+pub struct MangaListQuery {
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub sort: MangaSort,
+    /// Only manga crediting this author, ignoring case
+    pub author: Option<String>,
+    pub status: Option<MangaStatus>,
+    /// Manga to skip from the start of the listing
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub offset: u32,
+    /// Most manga to return, all remaining when `None`
+    pub limit: Option<u32>,
+}
+
+/// One slice of a manga listing with the number of manga matching its filters.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct MangaListing {
+    pub mangas: Vec<Manga>,
+    pub total: u64,
+}
+
+/// Which of the latest releases to list, eg: `?since=2021-01-01T00:00:00&limit=10`
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "openapi", derive(IntoParams))]
+#[cfg_attr(feature = "openapi", into_params(parameter_in = Query))]
+pub struct RecentChaptersQuery {
+    /// Most chapters to return, the API picks a default when `None`
+    pub limit: Option<u32>,
+    /// Only chapters released after this
+    #[cfg_attr(feature = "openapi", param(value_type = Option<String>, example = "2021-01-01T00:00:00"))]
+    pub since: Option<DateTimeType>,
+}
+
+/// Which part of a manga a search matched.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub enum SearchField {
+    Title,
+    AlternateTitle,
+    Creator,
+    Chapter,
+}
+
+impl SearchField {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SearchField::Title => "Title",
+            SearchField::AlternateTitle => "AlternateTitle",
+            SearchField::Creator => "Creator",
+            SearchField::Chapter => "Chapter",
+        }
+    }
+}
+
+impl FromStr for SearchField {
+    type Err = String;
+
+    fn from_str(field: &str) -> Result<Self, Self::Err> {
+        match field {
+            "Title" => Ok(SearchField::Title),
+            "AlternateTitle" => Ok(SearchField::AlternateTitle),
+            "Creator" => Ok(SearchField::Creator),
+            "Chapter" => Ok(SearchField::Chapter),
+            _ => Err(format!("unknown search field {:?}", field)),
+        }
+    }
+}
+
+/// A manga found by a search, along with its best matching text.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct SearchResult {
+    pub manga: Manga,
+    pub field: SearchField,
+    /// eg: the alternate title, creator name or chapter name that matched
+    pub matched_text: String,
+    /// The matching chapter when `field` is `Chapter`
+    pub chapter_number: Option<ChapterNumber>,
+    /// Higher is a better match, only meaningful within one search
+    pub score: u32,
+}
+
+/// A chapter with the manga it belongs to, as listed among the latest releases.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct RecentChapter {
+    pub manga: Manga,
+    pub chapter: Chapter,
+}
+
+/// What a creator worked on for a manga, someone who writes and draws gets one of each.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub enum CreatorRole {
+    Author,
+    Artist,
+}
+
+impl CreatorRole {
+    /// The value stored in the `Role` column.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CreatorRole::Author => "Author",
+            CreatorRole::Artist => "Artist",
+        }
+    }
+}
+
+impl FromStr for CreatorRole {
+    type Err = String;
+
+    fn from_str(role: &str) -> Result<Self, Self::Err> {
+        match role {
+            "Author" => Ok(CreatorRole::Author),
+            "Artist" => Ok(CreatorRole::Artist),
+            _ => Err(format!("unknown creator role {:?}", role)),
+        }
+    }
+}
+
+/// A credited creator of a manga, in credit order when listed.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct Creator {
+    pub creator_name: String,
+    pub role: CreatorRole,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
 pub struct Chapter {
-    pub chapter_number: String,
+    pub chapter_number: ChapterNumber,
     // not at the chapter level in current db
     // pub author_name: String,
     // pub artist_name: String,
     pub chapter_name: String,
+    #[cfg_attr(feature = "openapi", schema(value_type = String, example = "2021-01-01T00:00:00"))]
     pub creation_date: DateTimeType,
-    pub release_date: DateTimeType,
+    #[cfg_attr(feature = "openapi", schema(value_type = Option<String>, example = "2021-01-01T00:00:00"))]
+    pub release_date: Option<DateTimeType>,
+    /// When the chapter or its pages last changed
+    #[cfg_attr(feature = "serde", serde(default))]
+    #[cfg_attr(feature = "openapi", schema(value_type = Option<String>, example = "2021-01-01T00:00:00"))]
+    pub updated_date: Option<DateTimeType>,
     pub manga_id: i32,
 }
 
-#[derive(Debug)]
-#[cfg(feature = "serde")]
-#[derive(Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
 pub struct Page {
+    /// The preferred mirror
     pub url_string: String,
     pub page_number: i32,
+    /// Other mirrors of the same image to try in order when `url_string` fails
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub mirror_urls: Vec<String>,
+}
+
+impl Page {
+    /// Every mirror, preferred first.
+    pub fn urls(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.url_string.as_str()).chain(self.mirror_urls.iter().map(String::as_str))
+    }
+}
+
+/// A manga to create, or the new values for an existing one.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct NewManga {
+    pub manga_name: String,
+    /// Credited in order
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub creators: Vec<Creator>,
+    pub cover_image_url: Option<String>,
+    pub purchase_url: Option<String>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub status: MangaStatus,
+    /// Other names the manga is known by, eg: its original or translated title
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub alternate_titles: Vec<String>,
+}
+
+/// A chapter to create with its pages, or the replacement for an existing one.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct NewChapter {
+    pub chapter_number: ChapterNumber,
+    pub chapter_name: String,
+    #[cfg_attr(feature = "openapi", schema(value_type = Option<String>, example = "2021-01-01T00:00:00"))]
+    pub release_date: Option<DateTimeType>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub pages: Vec<NewPage>,
+}
+
+/// A page's image URLs, the first is preferred and the rest are mirrors.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct NewPage {
+    pub page_number: i32,
+    pub urls: Vec<String>,
 }
 
 #[cfg(test)]
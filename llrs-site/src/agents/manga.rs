@@ -1,51 +1,89 @@
-use llrs_model::{Chapter, Manga, Page};
+use llrs_client::{Client, Error as ClientError};
+use llrs_model::{
+    Chapter, Manga, MangaListQuery, MangaListing, Page, RecentChapter, RecentChaptersQuery,
+    SearchResult,
+};
 use log::*;
 use std::{
-    cell::RefCell,
     collections::{HashMap, HashSet},
     rc::Rc,
-    time::Duration,
-};
-use yew::{
-    format::{Json, Nothing},
-    services::{
-        fetch::{FetchTask, Request as FetchRequest, Response as FetchResponse},
-        FetchService, IntervalService, Task,
-    },
-    worker::*,
 };
+use wasm_bindgen_futures::spawn_local;
+use yew::worker::*;
+
+/// Results fetched per search, enough to fill the navbar dropdown
+const SEARCH_LIMIT: u32 = 8;
+
+/// Latest releases shown on the landing page
+const RECENT_CHAPTERS_LIMIT: u32 = 10;
 
 #[derive(Debug)]
 pub(crate) enum Msg {
-    FetchMangaComplete {
-        mangas: Vec<Manga>,
+    FetchMangaInfoComplete {
+        manga: Manga,
+    },
+    FetchListingComplete {
+        query: MangaListQuery,
+        listing: MangaListing,
     },
     FetchChapterComplete {
         chapters: Vec<Chapter>,
         manga_id: i32,
     },
+    FetchChapterInfoComplete {
+        chapter: Chapter,
+        chapter_number: String,
+    },
+    FetchSearchComplete {
+        query: String,
+        results: Vec<SearchResult>,
+    },
+    FetchRecentComplete {
+        chapters: Vec<RecentChapter>,
+    },
     FetchPageComplete {
         pages: Vec<Page>,
         manga_id: i32,
         chapter_number: String,
     },
-    Error(anyhow::Error),
-    CleanUpFetchTasks,
+    FetchFailed {
+        action: Action,
+        error: ClientError,
+    },
     EmitFetchComplete {
         action: Action,
     },
 }
 
+#[allow(clippy::enum_variant_names)]
 #[derive(Debug, PartialEq, Hash, Clone)]
 pub(crate) enum Action {
     GetChapterList {
         manga_id: i32,
     },
-    GetMangaList,
+    /// One manga's details, for pages that don't need the catalog
+    GetManga {
+        manga_id: i32,
+    },
+    /// One chapter's details, answered from its manga's chapter list when that's cached
+    GetChapter {
+        manga_id: i32,
+        chapter_number: String,
+    },
+    /// One slice of the catalog, cached per query
+    ListManga {
+        query: MangaListQuery,
+    },
     GetPageList {
         manga_id: i32,
         chapter_number: String,
     },
+    /// Ranked search results, cached per query
+    Search {
+        query: String,
+    },
+    /// Latest releases across every manga, fetched once
+    GetRecentChapters,
 }
 impl Eq for Action {}
 
@@ -55,18 +93,32 @@ type DataKey = (i32, String);
 pub(crate) struct MangaAgent {
     chapter_pages: HashMap<DataKey, Rc<Vec<Page>>>,
     chapters: HashMap<i32, Rc<Vec<Chapter>>>,
+    chapter_infos: HashMap<DataKey, Rc<Chapter>>,
+    mangas: HashMap<i32, Rc<Manga>>,
+    listings: HashMap<MangaListQuery, Rc<MangaListing>>,
+    search_results: HashMap<String, Rc<Vec<SearchResult>>>,
+    recent_chapters: Option<Rc<Vec<RecentChapter>>>,
     link: AgentLink<MangaAgent>,
-    fetch_tasks: HashMap<Action, FetchTask>,
-    #[allow(dead_code)]
-    clean_up_job: Box<dyn Task>,
-    manga_map: Option<Rc<HashMap<i32, Manga>>>,
+    client: Client,
+    /// Actions with a request in flight, so concurrent subscribers share one fetch
+    pending_actions: HashSet<Action>,
     subscribers_map: HashMap<Action, HashSet<HandlerId>>,
 }
 
 #[derive(Debug, Clone)]
 pub(crate) enum Response {
-    MangaMap {
-        mangas: Rc<HashMap<i32, Manga>>,
+    Manga {
+        manga_id: i32,
+        manga: Rc<Manga>,
+    },
+    /// `chapter_number` as requested, `chapter` holds its manga's ID
+    Chapter {
+        chapter_number: String,
+        chapter: Rc<Chapter>,
+    },
+    Listing {
+        query: MangaListQuery,
+        listing: Rc<MangaListing>,
     },
     Chapters {
         manga_id: i32,
@@ -77,6 +129,13 @@ pub(crate) enum Response {
         chapter_number: String,
         pages: Rc<Vec<Page>>,
     },
+    SearchResults {
+        query: String,
+        results: Rc<Vec<SearchResult>>,
+    },
+    RecentChapters {
+        chapters: Rc<Vec<RecentChapter>>,
+    },
 }
 
 impl Agent for MangaAgent {
@@ -86,66 +145,46 @@ impl Agent for MangaAgent {
     type Output = Response;
 
     fn create(link: AgentLink<Self>) -> Self {
-        let callback = link.callback(|_| Msg::CleanUpFetchTasks);
-        let clean_up_job = IntervalService::spawn(Duration::from_millis(1000), callback);
-
-        let subscribers_map: HashMap<Action, HashSet<HandlerId>> =
-            vec![(Action::GetMangaList, HashSet::new())]
-                .into_iter()
-                .collect();
+        let client =
+            Client::new(env!("LLRS_API_ENDPOINT")).expect("LLRS_API_ENDPOINT should be a URL");
         Self {
             link,
+            client,
             chapter_pages: HashMap::new(),
             chapters: HashMap::new(),
-            fetch_tasks: HashMap::new(),
-            manga_map: None,
-            clean_up_job: Box::new(clean_up_job),
-            subscribers_map,
+            chapter_infos: HashMap::new(),
+            mangas: HashMap::new(),
+            listings: HashMap::new(),
+            search_results: HashMap::new(),
+            recent_chapters: None,
+            pending_actions: HashSet::new(),
+            subscribers_map: HashMap::new(),
         }
     }
 
     fn update(&mut self, msg: Self::Message) {
         trace!("{:?}", msg);
         match msg {
-            Msg::Error(error) => error!("{}", error),
-            Msg::FetchMangaComplete { mangas } => {
-                let manga_map = mangas
-                    .into_iter()
-                    .map(|manga| (manga.manga_id, manga))
-                    .collect::<HashMap<_, _>>();
-                self.manga_map = Some(Rc::from(manga_map));
+            Msg::FetchFailed { action, error } => {
+                error!("{}", error);
+                self.pending_actions.remove(&action);
+            }
+            Msg::FetchMangaInfoComplete { manga } => {
+                let manga_id = manga.manga_id;
+                self.mangas.insert(manga_id, Rc::new(manga));
+                self.link.send_message(Msg::EmitFetchComplete {
+                    action: Action::GetManga { manga_id },
+                });
+            }
+            Msg::FetchListingComplete { query, listing } => {
+                self.listings.insert(query.clone(), Rc::new(listing));
                 self.link.send_message(Msg::EmitFetchComplete {
-                    action: Action::GetMangaList,
+                    action: Action::ListManga { query },
                 });
             }
-            Msg::CleanUpFetchTasks => self.fetch_tasks.retain(|_, task| task.is_active()),
             Msg::EmitFetchComplete { action } => {
-                let response = match action {
-                    Action::GetMangaList => {
-                        self.manga_map.as_ref().map(|mangas| Response::MangaMap {
-                            mangas: Rc::clone(&mangas),
-                        })
-                    }
-                    Action::GetChapterList { manga_id } => {
-                        self.chapters
-                            .get(&manga_id)
-                            .map(|chapters| Response::Chapters {
-                                manga_id,
-                                chapters: Rc::clone(chapters),
-                            })
-                    }
-                    Action::GetPageList {
-                        manga_id,
-                        ref chapter_number,
-                    } => self
-                        .chapter_pages
-                        .get(&(manga_id, chapter_number.to_owned()))
-                        .map(|pages| Response::Pages {
-                            manga_id,
-                            chapter_number: chapter_number.to_owned(),
-                            pages: Rc::clone(pages),
-                        }),
-                };
+                self.pending_actions.remove(&action);
+                let response = self.cached_response(&action);
                 self.respond_and_remove_subs(&action, response);
             }
             Msg::FetchChapterComplete { chapters, manga_id } => {
@@ -154,6 +193,33 @@ impl Agent for MangaAgent {
                     action: Action::GetChapterList { manga_id },
                 });
             }
+            Msg::FetchChapterInfoComplete {
+                chapter,
+                chapter_number,
+            } => {
+                // Keyed by the requested number, the API may format it differently
+                let manga_id = chapter.manga_id;
+                let key = (manga_id, chapter_number.to_owned());
+                self.chapter_infos.insert(key, Rc::new(chapter));
+                self.link.send_message(Msg::EmitFetchComplete {
+                    action: Action::GetChapter {
+                        manga_id,
+                        chapter_number,
+                    },
+                });
+            }
+            Msg::FetchSearchComplete { query, results } => {
+                self.search_results.insert(query.clone(), Rc::new(results));
+                self.link.send_message(Msg::EmitFetchComplete {
+                    action: Action::Search { query },
+                });
+            }
+            Msg::FetchRecentComplete { chapters } => {
+                self.recent_chapters = Some(Rc::new(chapters));
+                self.link.send_message(Msg::EmitFetchComplete {
+                    action: Action::GetRecentChapters,
+                });
+            }
             Msg::FetchPageComplete {
                 pages,
                 manga_id,
@@ -172,87 +238,142 @@ impl Agent for MangaAgent {
     }
 
     fn handle_input(&mut self, input: Self::Input, requester: HandlerId) {
-        let cell = Rc::new(RefCell::new(self));
-
-        if let Some(response) = get_cached_response(&cell, &input) {
-            cell.borrow_mut().link.respond(requester, response);
+        if let Some(response) = self.cached_response(&input) {
+            self.link.respond(requester, response);
         } else {
-            if cell.borrow().fetch_tasks.get(&input).is_none() {
-                let cell_ref = Rc::clone(&cell);
-                let mut get_fetch_task = get_fetch_task_closure(cell_ref, &input);
-                match get_fetch_task() {
-                    Ok(fetch_task) => {
-                        cell.borrow_mut()
-                            .fetch_tasks
-                            .insert(input.clone(), fetch_task);
-                    }
-                    Err(error) => cell.borrow_mut().link.send_message(Msg::Error(error)),
-                };
+            if self.pending_actions.insert(input.clone()) {
+                self.fetch(input.clone());
             }
-
-            cell.borrow_mut().add_subscriber(input.clone(), requester);
+            self.add_subscriber(input, requester);
         }
     }
 }
 
 impl MangaAgent {
-    fn fetch_manga_list(&mut self) -> Result<FetchTask, anyhow::Error> {
-        let request = FetchRequest::get(env!("LLRS_API_ENDPOINT")).body(Nothing)?;
-        let callback = self.link.callback(
-            |response: FetchResponse<Json<Result<Vec<Manga>, anyhow::Error>>>| {
-                let Json(data) = response.into_body();
-                match data {
-                    Ok(mangas) => Msg::FetchMangaComplete { mangas },
-                    Err(error) => Msg::Error(error),
-                }
-            },
-        );
-
-        Ok(FetchService::fetch(request, callback)?)
-    }
-
-    fn fetch_chapter_list(&mut self, manga_id: i32) -> Result<FetchTask, anyhow::Error> {
-        let request =
-            FetchRequest::get(format!("{}/manga/{}", env!("LLRS_API_ENDPOINT"), manga_id))
-                .body(Nothing)?;
-        let callback = self.link.callback(
-            move |response: FetchResponse<Json<Result<Vec<Chapter>, anyhow::Error>>>| {
-                let Json(data) = response.into_body();
-                match data {
-                    Ok(chapters) => Msg::FetchChapterComplete { chapters, manga_id },
-                    Err(error) => Msg::Error(error),
-                }
-            },
-        );
-        Ok(FetchService::fetch(request, callback)?)
-    }
-
-    fn fetch_page_list(
-        &mut self,
-        manga_id: i32,
-        chapter_number: String,
-    ) -> Result<FetchTask, anyhow::Error> {
-        let request = FetchRequest::get(format!(
-            "{}/manga/{}/{}",
-            env!("LLRS_API_ENDPOINT"),
-            manga_id,
-            chapter_number
-        ))
-        .body(Nothing)?;
-        let callback = self.link.callback(
-            move |response: FetchResponse<Json<Result<Vec<Page>, anyhow::Error>>>| {
-                let Json(data) = response.into_body();
-                match data {
-                    Ok(pages) => Msg::FetchPageComplete {
+    fn fetch(&self, action: Action) {
+        let client = self.client.clone();
+        let link = self.link.clone();
+        spawn_local(async move {
+            let result = match action.clone() {
+                Action::GetManga { manga_id } => client
+                    .get_manga(manga_id)
+                    .await
+                    .map(|manga| Msg::FetchMangaInfoComplete { manga }),
+                Action::GetChapter {
+                    manga_id,
+                    chapter_number,
+                } => client
+                    .get_chapter(manga_id, &chapter_number)
+                    .await
+                    .map(|chapter| Msg::FetchChapterInfoComplete {
+                        chapter,
+                        chapter_number,
+                    }),
+                Action::ListManga { query } => client
+                    .list_manga(&query)
+                    .await
+                    .map(|listing| Msg::FetchListingComplete { query, listing }),
+                Action::GetChapterList { manga_id } => client
+                    .get_chapter_list(manga_id)
+                    .await
+                    .map(|chapters| Msg::FetchChapterComplete { chapters, manga_id }),
+                Action::GetPageList {
+                    manga_id,
+                    chapter_number,
+                } => client
+                    .get_page_list(manga_id, &chapter_number)
+                    .await
+                    .map(|pages| Msg::FetchPageComplete {
                         pages,
                         manga_id,
-                        chapter_number: chapter_number.to_owned(),
-                    },
-                    Err(error) => Msg::Error(error),
+                        chapter_number,
+                    }),
+                Action::Search { query } => client
+                    .search(&query, Some(SEARCH_LIMIT))
+                    .await
+                    .map(|results| Msg::FetchSearchComplete { query, results }),
+                Action::GetRecentChapters => {
+                    let query = RecentChaptersQuery {
+                        limit: Some(RECENT_CHAPTERS_LIMIT),
+                        since: None,
+                    };
+                    client
+                        .get_recent_chapters(&query)
+                        .await
+                        .map(|chapters| Msg::FetchRecentComplete { chapters })
                 }
-            },
-        );
-        FetchService::fetch(request, callback)
+            };
+            link.send_message(result.unwrap_or_else(|error| Msg::FetchFailed { action, error }));
+        });
+    }
+
+    fn cached_response(&self, action: &Action) -> Option<Response> {
+        match action {
+            Action::GetManga { manga_id } => {
+                self.mangas.get(manga_id).map(|manga| Response::Manga {
+                    manga_id: *manga_id,
+                    manga: Rc::clone(manga),
+                })
+            }
+            Action::GetChapter {
+                manga_id,
+                chapter_number,
+            } => {
+                let key = (*manga_id, chapter_number.to_owned());
+                let chapter = self.chapter_infos.get(&key).cloned().or_else(|| {
+                    self.chapters.get(manga_id).and_then(|chapters| {
+                        chapters
+                            .iter()
+                            .find(|chapter| chapter.chapter_number.to_string() == *chapter_number)
+                            .map(|chapter| Rc::new(chapter.clone()))
+                    })
+                });
+                chapter.map(|chapter| Response::Chapter {
+                    chapter_number: chapter_number.to_owned(),
+                    chapter,
+                })
+            }
+            Action::ListManga { query } => {
+                self.listings.get(query).map(|listing| Response::Listing {
+                    query: query.clone(),
+                    listing: Rc::clone(listing),
+                })
+            }
+            Action::GetChapterList { manga_id } => {
+                self.chapters
+                    .get(manga_id)
+                    .map(|chapters| Response::Chapters {
+                        manga_id: *manga_id,
+                        chapters: Rc::clone(chapters),
+                    })
+            }
+            Action::GetPageList {
+                manga_id,
+                ref chapter_number,
+            } => {
+                let key = (*manga_id, chapter_number.to_owned());
+                self.chapter_pages.get(&key).map(|pages| Response::Pages {
+                    manga_id: *manga_id,
+                    chapter_number: chapter_number.to_owned(),
+                    pages: Rc::clone(pages),
+                })
+            }
+            Action::Search { query } => {
+                self.search_results
+                    .get(query)
+                    .map(|results| Response::SearchResults {
+                        query: query.clone(),
+                        results: Rc::clone(results),
+                    })
+            }
+            Action::GetRecentChapters => {
+                self.recent_chapters
+                    .as_ref()
+                    .map(|chapters| Response::RecentChapters {
+                        chapters: Rc::clone(chapters),
+                    })
+            }
+        }
     }
 
     fn respond_and_remove_subs(&mut self, action: &Action, response: Option<Response>) {
@@ -267,55 +388,7 @@ impl MangaAgent {
     }
 
     fn add_subscriber(&mut self, action: Action, requester: HandlerId) {
-        let subscribers = self.subscribers_map.entry(action).or_insert(HashSet::new());
+        let subscribers = self.subscribers_map.entry(action).or_default();
         subscribers.insert(requester);
     }
 }
-
-fn get_cached_response(agent_ref: &RefCell<&mut MangaAgent>, action: &Action) -> Option<Response> {
-    let agent = agent_ref.borrow();
-    match action {
-        Action::GetMangaList => agent.manga_map.as_ref().map(|mangas| Response::MangaMap {
-            mangas: Rc::clone(mangas),
-        }),
-        Action::GetChapterList { manga_id } => {
-            agent
-                .chapters
-                .get(&manga_id)
-                .map(|chapters| Response::Chapters {
-                    manga_id: *manga_id,
-                    chapters: Rc::clone(chapters),
-                })
-        }
-        Action::GetPageList {
-            manga_id,
-            ref chapter_number,
-        } => {
-            let key = (*manga_id, chapter_number.to_owned());
-            agent.chapter_pages.get(&key).map(|pages| Response::Pages {
-                manga_id: *manga_id,
-                chapter_number: chapter_number.to_owned(),
-                pages: Rc::clone(&pages),
-            })
-        }
-    }
-}
-
-fn get_fetch_task_closure<'a>(
-    cell: Rc<RefCell<&'a mut MangaAgent>>,
-    action: &'a Action,
-) -> Box<dyn FnMut() -> Result<FetchTask, anyhow::Error> + 'a> {
-    match &action {
-        Action::GetMangaList => Box::new(move || cell.try_borrow_mut()?.fetch_manga_list()),
-        Action::GetChapterList { manga_id } => {
-            Box::new(move || cell.try_borrow_mut()?.fetch_chapter_list(*manga_id))
-        }
-        Action::GetPageList {
-            manga_id,
-            chapter_number,
-        } => Box::new(move || {
-            cell.try_borrow_mut()?
-                .fetch_page_list(*manga_id, chapter_number.to_owned())
-        }),
-    }
-}
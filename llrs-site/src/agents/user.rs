@@ -6,7 +6,7 @@ use yew::{
     worker::{Agent, AgentLink, Context, HandlerId},
 };
 
-const READER_PREFERENCE_KEY: &'static str = "llrs.reader.view";
+const READER_PREFERENCE_KEY: &str = "llrs.reader.view";
 
 pub(crate) struct UserAgent {
     storage: Option<StorageService>,
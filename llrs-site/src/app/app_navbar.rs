@@ -1,3 +1,4 @@
+use super::search_box::SearchBox;
 use crate::{agents::manga::Response as MangaResponse, pages::ViewFormat, route::AppRoute};
 use crate::{
     agents::{
@@ -9,18 +10,21 @@ use crate::{
         navbar::Navbar,
     },
 };
-use llrs_model::Manga;
-use std::{collections::HashMap, rc::Rc};
+use llrs_model::{Chapter, Manga};
+use std::rc::Rc;
 use yew::{html::ChildrenRenderer, prelude::*};
 use yew_router::{components::RouterAnchor, switch::Permissive};
 
-const LLRS_BRAND_LOGO_URL: &'static str = env!("LLRS_BRAND_LOGO_URL");
+const LLRS_BRAND_LOGO_URL: &str = env!("LLRS_BRAND_LOGO_URL");
 
 type Anchor = RouterAnchor<AppRoute>;
 
 struct State {
     view_format: ViewFormat,
-    mangas: Option<Rc<HashMap<i32, Manga>>>,
+    /// Last manga and chapter fetched for the route, possibly for an earlier route
+    manga: Option<Rc<Manga>>,
+    /// Keyed by the route's chapter number, which may be formatted differently
+    chapter: Option<(String, Rc<Chapter>)>,
 }
 
 pub(super) enum Msg {
@@ -35,7 +39,6 @@ pub(super) struct Props {
 }
 
 pub(super) struct AppNavbar {
-    #[allow(dead_code)]
     manga_agent: Box<dyn Bridge<MangaAgent>>,
     #[allow(dead_code)]
     user_agent: Box<dyn Bridge<UserAgent>>,
@@ -51,28 +54,36 @@ impl Component for AppNavbar {
     type Properties = Props;
 
     fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
-        let mut manga_agent = MangaAgent::bridge(link.callback(Msg::MangaAgentResponse));
-        manga_agent.send(MangaAction::GetMangaList);
-
+        let manga_agent = MangaAgent::bridge(link.callback(Msg::MangaAgentResponse));
         let mut user_agent = UserAgent::bridge(link.callback(Msg::UserAgentResponse));
         user_agent.send(UserAgentAction::GetViewFormatPreference);
-        Self {
+        let mut navbar = Self {
             manga_agent,
             user_agent,
             link,
             props,
             state: State {
-                mangas: None,
+                manga: None,
+                chapter: None,
                 view_format: ViewFormat::Single,
             },
-        }
+        };
+        navbar.fetch_route_details();
+        navbar
     }
 
     fn update(&mut self, msg: Self::Message) -> ShouldRender {
         match msg {
             Msg::MangaAgentResponse(response) => match response {
-                MangaResponse::MangaMap { mangas } => {
-                    self.state.mangas = Some(mangas);
+                MangaResponse::Manga { manga, .. } => {
+                    self.state.manga = Some(manga);
+                    true
+                }
+                MangaResponse::Chapter {
+                    chapter_number,
+                    chapter,
+                } => {
+                    self.state.chapter = Some((chapter_number, chapter));
                     true
                 }
                 _ => false,
@@ -101,6 +112,7 @@ impl Component for AppNavbar {
 
     fn change(&mut self, props: Self::Properties) -> ShouldRender {
         self.props = props;
+        self.fetch_route_details();
         true
     }
 
@@ -111,6 +123,7 @@ impl Component for AppNavbar {
         html! {
             <Navbar brand_children={brand_links}>
                 <div class="navbar-start">
+                    <SearchBox />
                     {menu_start_links}
                 </div>
                 <div class="navbar-end">
@@ -124,10 +137,12 @@ impl Component for AppNavbar {
 struct BreadcrumbLink {
     route: AppRoute,
     link_text: String,
+    /// Shown on hover, eg: the chapter name
+    title: Option<String>,
 }
 
 impl AppNavbar {
-    fn get_selected_manga(&self) -> Option<&Manga> {
+    fn route_manga_id(&self) -> Option<i32> {
         match self.props.route {
             AppRoute::ChapterList { manga_id }
             | AppRoute::MangaChapterPage {
@@ -138,15 +153,64 @@ impl AppNavbar {
             | AppRoute::MangaChapter {
                 manga_id,
                 chapter_number: _,
-            } => self
-                .state
-                .mangas
-                .as_ref()
-                .map_or(None, |mangas| mangas.get(&manga_id)),
+            } => Some(manga_id),
+            _ => None,
+        }
+    }
+
+    fn route_chapter_number(&self) -> Option<&str> {
+        match &self.props.route {
+            AppRoute::MangaChapterPage {
+                manga_id: _,
+                chapter_number,
+                page_number: _,
+            }
+            | AppRoute::MangaChapter {
+                manga_id: _,
+                chapter_number,
+            } => Some(chapter_number),
+            _ => None,
+        }
+    }
+
+    /// Asks for just the route's manga and chapter, so deep links don't load the catalog
+    fn fetch_route_details(&mut self) {
+        if let Some(manga_id) = self.route_manga_id() {
+            self.manga_agent.send(MangaAction::GetManga { manga_id });
+            if let Some(chapter_number) = self.route_chapter_number() {
+                let chapter_number = chapter_number.to_owned();
+                self.manga_agent.send(MangaAction::GetChapter {
+                    manga_id,
+                    chapter_number,
+                });
+            }
+        }
+    }
+
+    fn get_selected_manga(&self) -> Option<&Manga> {
+        let manga_id = self.route_manga_id()?;
+        self.state
+            .manga
+            .as_deref()
+            .filter(|manga| manga.manga_id == manga_id)
+    }
+
+    fn get_selected_chapter(&self) -> Option<&Chapter> {
+        let manga_id = self.route_manga_id()?;
+        let chapter_number = self.route_chapter_number()?;
+        match &self.state.chapter {
+            Some((number, chapter)) if chapter.manga_id == manga_id && number == chapter_number => {
+                Some(chapter)
+            }
             _ => None,
         }
     }
 
+    fn manga_link_text(&self, manga_id: i32) -> String {
+        self.get_selected_manga()
+            .map_or(manga_id.to_string(), |manga| manga.manga_name.to_owned())
+    }
+
     fn get_menu_start_links(&self) -> Html {
         match &self.props.route {
             AppRoute::MangaChapterPage {
@@ -184,17 +248,17 @@ impl AppNavbar {
             </a>
         };
         let manga = self.get_selected_manga();
-        let manga_link = manga.as_ref().map_or(html! {}, |link| {
-            if link.purchase_url.len() > 0 {
-                html! {
-                    <a class="navbar-item" href=link.purchase_url.as_str()>
-                        {"Support the Author"}
-                    </a>
-                }
-            } else {
-                html! {}
-            }
-        });
+        let manga_link =
+            manga
+                .as_ref()
+                .map_or(html! {}, |link| match link.purchase_url.as_deref() {
+                    Some(purchase_url) if !purchase_url.is_empty() => html! {
+                        <a class="navbar-item" href=purchase_url>
+                            {"Support the Author"}
+                        </a>
+                    },
+                    _ => html! {},
+                });
         html! {
             <>
                 {manga_link}
@@ -211,70 +275,64 @@ impl AppNavbar {
             </Anchor>
         };
         // Bulma ONLY formats the text properly with anchors
-        let links =
-            match &self.props.route {
-                AppRoute::MangaList => vec![BreadcrumbLink {
+        let links = match &self.props.route {
+            AppRoute::MangaList => vec![BreadcrumbLink {
+                route: AppRoute::MangaList,
+                link_text: "llrs".to_owned(),
+                title: None,
+            }],
+            AppRoute::ChapterList { manga_id } => vec![
+                BreadcrumbLink {
                     route: AppRoute::MangaList,
                     link_text: "llrs".to_owned(),
-                }],
-                AppRoute::ChapterList { manga_id } => vec![
-                    BreadcrumbLink {
-                        route: AppRoute::MangaList,
-                        link_text: "llrs".to_owned(),
-                    },
-                    BreadcrumbLink {
-                        route: AppRoute::ChapterList {
-                            manga_id: *manga_id,
-                        },
-                        link_text: self.state.mangas.as_ref().map_or(
-                            manga_id.to_string(),
-                            |mangas| {
-                                mangas.get(&manga_id).map_or(manga_id.to_string(), |manga| {
-                                    manga.manga_name.to_owned()
-                                })
-                            },
-                        ),
-                    },
-                ],
-                AppRoute::MangaChapterPage {
-                    manga_id,
-                    chapter_number,
-                    page_number: _,
-                }
-                | AppRoute::MangaChapter {
-                    manga_id,
-                    chapter_number,
-                } => vec![
-                    BreadcrumbLink {
-                        route: AppRoute::MangaList,
-                        link_text: "llrs".to_owned(),
+                    title: None,
+                },
+                BreadcrumbLink {
+                    route: AppRoute::ChapterList {
+                        manga_id: *manga_id,
                     },
-                    BreadcrumbLink {
-                        route: AppRoute::ChapterList {
-                            manga_id: *manga_id,
-                        },
-                        link_text: self.state.mangas.as_ref().map_or(
-                            manga_id.to_string(),
-                            |mangas| {
-                                mangas.get(&manga_id).map_or(manga_id.to_string(), |manga| {
-                                    manga.manga_name.to_owned()
-                                })
-                            },
-                        ),
-                    },
-                    BreadcrumbLink {
-                        route: AppRoute::MangaChapter {
-                            manga_id: *manga_id,
-                            chapter_number: chapter_number.to_owned(),
-                        },
-                        link_text: format!("Chapter {}", chapter_number.to_owned()),
-                    },
-                ],
-                AppRoute::NotFound(Permissive(_)) => vec![BreadcrumbLink {
+                    link_text: self.manga_link_text(*manga_id),
+                    title: None,
+                },
+            ],
+            AppRoute::MangaChapterPage {
+                manga_id,
+                chapter_number,
+                page_number: _,
+            }
+            | AppRoute::MangaChapter {
+                manga_id,
+                chapter_number,
+            } => vec![
+                BreadcrumbLink {
                     route: AppRoute::MangaList,
                     link_text: "llrs".to_owned(),
-                }],
-            };
+                    title: None,
+                },
+                BreadcrumbLink {
+                    route: AppRoute::ChapterList {
+                        manga_id: *manga_id,
+                    },
+                    link_text: self.manga_link_text(*manga_id),
+                    title: None,
+                },
+                BreadcrumbLink {
+                    route: AppRoute::MangaChapter {
+                        manga_id: *manga_id,
+                        chapter_number: chapter_number.to_owned(),
+                    },
+                    link_text: format!("Chapter {}", chapter_number.to_owned()),
+                    title: self
+                        .get_selected_chapter()
+                        .map(|chapter| chapter.chapter_name.to_owned()),
+                },
+            ],
+            AppRoute::NotFound(Permissive(_)) => vec![BreadcrumbLink {
+                route: AppRoute::MangaList,
+                link_text: "llrs".to_owned(),
+                title: None,
+            }],
+        };
 
         ChildrenRenderer::new(vec![html! {
             <>
@@ -292,6 +350,8 @@ impl AppNavbar {
 fn to_route_anchor(link: BreadcrumbLink) -> Html {
     type Anchor = RouterAnchor<AppRoute>;
     html! {
-        <Anchor route=link.route>{link.link_text}</Anchor>
+        <Anchor route=link.route>
+            <span title=link.title.unwrap_or_default()>{link.link_text}</span>
+        </Anchor>
     }
 }
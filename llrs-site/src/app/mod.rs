@@ -1,4 +1,5 @@
 mod app_navbar;
+mod search_box;
 
 use crate::agents::manga::MangaAgent;
 use crate::pages::{not_found, ChapterList, MangaList, MangaPage};
@@ -0,0 +1,187 @@
+use crate::agents::manga::{Action, MangaAgent, Response};
+use crate::route::AppRoute;
+use llrs_model::{SearchField, SearchResult};
+use log::*;
+use std::{rc::Rc, time::Duration};
+use yew::{
+    prelude::*,
+    services::{timeout::TimeoutTask, TimeoutService},
+};
+use yew_router::components::RouterAnchor;
+
+/// Typing pause before searching, so each keystroke doesn't cost a request
+const SEARCH_DELAY_MILLISECONDS: u64 = 250;
+
+type Anchor = RouterAnchor<AppRoute>;
+
+struct State {
+    query: String,
+    /// Results for the trimmed `query`, `None` while they're being fetched
+    results: Option<Rc<Vec<SearchResult>>>,
+}
+
+impl State {
+    fn search_query(&self) -> &str {
+        self.query.trim()
+    }
+}
+
+pub(super) struct SearchBox {
+    link: ComponentLink<Self>,
+    manga_agent: Box<dyn Bridge<MangaAgent>>,
+    /// Dropping the pending task cancels the search it would start
+    search_task: Option<TimeoutTask>,
+    state: State,
+}
+
+#[derive(Debug)]
+pub(super) enum Msg {
+    Input(String),
+    Search,
+    AgentResponse(Response),
+    Clear,
+    Ignore,
+}
+
+impl Component for SearchBox {
+    type Message = Msg;
+    type Properties = ();
+
+    fn create(_: Self::Properties, link: ComponentLink<Self>) -> Self {
+        let manga_agent = MangaAgent::bridge(link.callback(Msg::AgentResponse));
+        Self {
+            link,
+            manga_agent,
+            search_task: None,
+            state: State {
+                query: String::new(),
+                results: None,
+            },
+        }
+    }
+
+    fn change(&mut self, _props: Self::Properties) -> ShouldRender {
+        false
+    }
+
+    fn update(&mut self, msg: Self::Message) -> ShouldRender {
+        trace!("{:?}", msg);
+        match msg {
+            Msg::Input(query) => {
+                self.state.query = query;
+                self.state.results = None;
+                self.search_task = if self.state.search_query().is_empty() {
+                    None
+                } else {
+                    Some(TimeoutService::spawn(
+                        Duration::from_millis(SEARCH_DELAY_MILLISECONDS),
+                        self.link.callback(|_| Msg::Search),
+                    ))
+                };
+            }
+            Msg::Search => {
+                self.search_task = None;
+                let query = self.state.search_query().to_owned();
+                self.manga_agent.send(Action::Search { query });
+                return false;
+            }
+            Msg::AgentResponse(response) => match response {
+                // Responses for text the user has since changed are dropped
+                Response::SearchResults { query, results }
+                    if query == self.state.search_query() =>
+                {
+                    self.state.results = Some(results);
+                }
+                _ => return false,
+            },
+            Msg::Clear => {
+                self.search_task = None;
+                self.state.query.clear();
+                self.state.results = None;
+            }
+            Msg::Ignore => return false,
+        }
+        true
+    }
+
+    fn view(&self) -> Html {
+        let on_input = self
+            .link
+            .callback(|input: InputData| Msg::Input(input.value));
+        let on_key_down = self
+            .link
+            .callback(|event: KeyboardEvent| match event.key().as_str() {
+                "Escape" => Msg::Clear,
+                _ => Msg::Ignore,
+            });
+        let dropdown_class = if self.state.results.is_some() {
+            "dropdown is-active"
+        } else {
+            "dropdown"
+        };
+        html! {
+            <div class="navbar-item">
+                <div class=dropdown_class>
+                    <div class="dropdown-trigger control">
+                        <input class="input" type="search" placeholder="Search titles, authors, chapters"
+                            value=&self.state.query oninput=on_input onkeydown=on_key_down />
+                    </div>
+                    <div class="dropdown-menu" role="menu">
+                        <div class="dropdown-content">
+                            {self.results()}
+                        </div>
+                    </div>
+                </div>
+            </div>
+        }
+    }
+}
+
+impl SearchBox {
+    fn results(&self) -> Html {
+        let results = match &self.state.results {
+            Some(results) => results,
+            None => return html! {},
+        };
+        if results.is_empty() {
+            return html! {
+                <div class="dropdown-item">{"No matches"}</div>
+            };
+        }
+        // Following a result leaves the dropdown open otherwise, the navbar isn't re-created
+        let on_click = self.link.callback(|_| Msg::Clear);
+        html! {
+            <div onclick=on_click>
+                {for results.iter().map(result_entry)}
+            </div>
+        }
+    }
+}
+
+fn result_entry(result: &SearchResult) -> Html {
+    let manga_id = result.manga.manga_id;
+    let (route, detail) = match (result.field, &result.chapter_number) {
+        (SearchField::Chapter, Some(chapter_number)) => (
+            AppRoute::MangaChapter {
+                manga_id,
+                chapter_number: chapter_number.to_string(),
+            },
+            format!("Chapter {}: {}", chapter_number, result.matched_text),
+        ),
+        (SearchField::AlternateTitle, _) => (
+            AppRoute::ChapterList { manga_id },
+            format!("Also known as {}", result.matched_text),
+        ),
+        (SearchField::Creator, _) => (
+            AppRoute::ChapterList { manga_id },
+            format!("By {}", result.matched_text),
+        ),
+        _ => (AppRoute::ChapterList { manga_id }, String::new()),
+    };
+    html! {
+        <Anchor classes="dropdown-item" route=route>
+            <p>{&result.manga.manga_name}</p>
+            <p class="is-size-7">{detail}</p>
+        </Anchor>
+    }
+}
@@ -13,9 +13,10 @@ pub(crate) struct Props {
 }
 
 #[allow(dead_code)]
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Default)]
 pub(crate) enum Separator {
     /// / - U+0002F
+    #[default]
     ForwardSlash,
     /// → - U+02192
     Arrow,
@@ -27,12 +28,6 @@ pub(crate) enum Separator {
     Succeeds,
 }
 
-impl Default for Separator {
-    fn default() -> Self {
-        Separator::ForwardSlash
-    }
-}
-
 impl Separator {
     fn class_name(&self) -> &'static str {
         match self {
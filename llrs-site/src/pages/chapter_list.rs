@@ -35,7 +35,9 @@ impl Component for ChapterList {
 
     fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
         let mut manga_agent = MangaAgent::bridge(link.callback(Msg::FetchMangaComplete));
-        manga_agent.send(MangaAction::GetMangaList);
+        manga_agent.send(MangaAction::GetManga {
+            manga_id: props.manga_id,
+        });
         manga_agent.send(MangaAction::GetChapterList {
             manga_id: props.manga_id,
         });
@@ -57,15 +59,14 @@ impl Component for ChapterList {
         trace!("{:?}", msg);
         match msg {
             Msg::FetchMangaComplete(response) => match response {
-                MangaResponse::MangaMap { mangas } => {
-                    if let Some(manga) = mangas.get(&self.props.manga_id) {
-                        self.state.cover_image_url = manga.cover_image_url.to_owned();
-                    }
+                MangaResponse::Manga { manga_id, manga } if manga_id == self.props.manga_id => {
+                    self.state.cover_image_url =
+                        manga.cover_image_url.to_owned().unwrap_or_default();
                 }
-                MangaResponse::Chapters { manga_id, chapters } => {
-                    if manga_id == self.props.manga_id {
-                        self.state.chapters = Some(chapters);
-                    }
+                MangaResponse::Chapters { manga_id, chapters }
+                    if manga_id == self.props.manga_id =>
+                {
+                    self.state.chapters = Some(chapters);
                 }
                 _ => {}
             },
@@ -104,7 +105,7 @@ impl Component for ChapterList {
     }
 }
 
-// TODO: Search bar, set is-selected for most recent chapter if same manga
+// TODO: set is-selected for most recent chapter if same manga
 impl ChapterList {
     fn chapter_entry(&self, chapter: &Chapter) -> Html {
         type Anchor = RouterAnchor<AppRoute>;
@@ -113,7 +114,7 @@ impl ChapterList {
                 <td>
                     <Anchor route=AppRoute::MangaChapter {
                         manga_id: chapter.manga_id,
-                        chapter_number: chapter.chapter_number.to_owned(),
+                        chapter_number: chapter.chapter_number.to_string(),
                     }>
                         {"Chapter "}{&chapter.chapter_number}
                     </Anchor>
@@ -121,7 +122,7 @@ impl ChapterList {
                 <td>
                     <Anchor route=AppRoute::MangaChapter {
                         manga_id: chapter.manga_id,
-                        chapter_number: chapter.chapter_number.to_owned(),
+                        chapter_number: chapter.chapter_number.to_string(),
                     }>
                     {&chapter.chapter_name}
                     </Anchor>
@@ -129,9 +130,9 @@ impl ChapterList {
                 <td>
                     <Anchor route=AppRoute::MangaChapter {
                         manga_id: chapter.manga_id,
-                        chapter_number: chapter.chapter_number.to_owned(),
+                        chapter_number: chapter.chapter_number.to_string(),
                     }>
-                    {chapter.release_date.format("%Y-%m-%d")}
+                    {chapter.release_date.map_or("".to_owned(), |date| date.format("%Y-%m-%d").to_string())}
                     </Anchor>
                 </td>
             </tr>
@@ -1,27 +1,72 @@
 use super::progress::progress_bar;
 use crate::agents::manga::{Action, MangaAgent, Response};
 use crate::route::AppRoute;
-use llrs_model::Manga;
+use llrs_model::{Manga, MangaListQuery, MangaListing, MangaSort, MangaStatus, RecentChapter};
 use log::*;
-use std::{collections::HashMap, rc::Rc};
+use std::rc::Rc;
 use yew::{prelude::*, Component, ComponentLink};
 use yew_router::components::RouterAnchor;
 
+/// Manga shown per page of the catalog, an even number fills the two column rows
+const PAGE_SIZE: u32 = 20;
+
+const SORTS: &[(MangaSort, &str, &str)] = &[
+    (MangaSort::Name, "name", "Name"),
+    (MangaSort::NewestChapter, "newest_chapter", "Latest chapter"),
+    (MangaSort::CreationDate, "creation_date", "Recently added"),
+];
+
+const STATUSES: &[MangaStatus] = &[
+    MangaStatus::Ongoing,
+    MangaStatus::Completed,
+    MangaStatus::Hiatus,
+    MangaStatus::Cancelled,
+];
+
 pub(crate) struct State {
-    mangas: Option<Rc<HashMap<i32, Manga>>>,
-    #[allow(dead_code)]
+    sort: MangaSort,
+    status: Option<MangaStatus>,
+    /// Zero based page of the catalog
+    page: u32,
+    listing: Option<Rc<MangaListing>>,
+    recent_chapters: Option<Rc<Vec<RecentChapter>>>,
     manga_agent: Box<dyn Bridge<MangaAgent>>,
 }
 
-impl State {}
+impl State {
+    fn query(&self) -> MangaListQuery {
+        MangaListQuery {
+            sort: self.sort,
+            author: None,
+            status: self.status,
+            offset: self.page * PAGE_SIZE,
+            limit: Some(PAGE_SIZE),
+        }
+    }
+
+    fn page_count(&self) -> u32 {
+        let total = self.listing.as_ref().map_or(0, |listing| listing.total);
+        total.div_ceil(u64::from(PAGE_SIZE)) as u32
+    }
+
+    fn fetch(&mut self) {
+        self.listing = None;
+        let query = self.query();
+        self.manga_agent.send(Action::ListManga { query });
+    }
+}
 
 pub(crate) struct MangaList {
+    link: ComponentLink<Self>,
     state: State,
 }
 
 #[derive(Debug)]
 pub(crate) enum Msg {
     AgentResponse(Response),
+    SetSort(MangaSort),
+    SetStatus(Option<MangaStatus>),
+    GoToPage(u32),
 }
 
 impl Component for MangaList {
@@ -30,13 +75,18 @@ impl Component for MangaList {
 
     fn create(_: Self::Properties, link: ComponentLink<Self>) -> Self {
         let mut manga_agent = MangaAgent::bridge(link.callback(Msg::AgentResponse));
-        manga_agent.send(Action::GetMangaList);
-        let state = State {
-            mangas: None,
+        manga_agent.send(Action::GetRecentChapters);
+        let mut state = State {
+            sort: MangaSort::default(),
+            status: None,
+            page: 0,
+            listing: None,
+            recent_chapters: None,
             manga_agent,
         };
+        state.fetch();
 
-        Self { state }
+        Self { link, state }
     }
 
     fn change(&mut self, _props: Self::Properties) -> ShouldRender {
@@ -47,29 +97,169 @@ impl Component for MangaList {
         trace!("{:?}", msg);
         match msg {
             Msg::AgentResponse(response) => match response {
-                Response::MangaMap { mangas } => self.state.mangas = Some(mangas),
-                _ => {}
+                // Responses for a page the user has already left are dropped
+                Response::Listing { query, listing } if query == self.state.query() => {
+                    self.state.listing = Some(listing);
+                }
+                Response::RecentChapters { chapters } => {
+                    self.state.recent_chapters = Some(chapters);
+                }
+                _ => return false,
             },
+            Msg::SetSort(sort) => {
+                self.state.sort = sort;
+                self.state.page = 0;
+                self.state.fetch();
+            }
+            Msg::SetStatus(status) => {
+                self.state.status = status;
+                self.state.page = 0;
+                self.state.fetch();
+            }
+            Msg::GoToPage(page) => {
+                self.state.page = page;
+                self.state.fetch();
+            }
         }
         true
     }
 
     fn view(&self) -> Html {
-        match &self.state.mangas {
-            Some(mangas) => {
-                let mangas = mangas
-                    .iter()
-                    .map(|(_, manga)| manga)
-                    .collect::<Vec<&Manga>>();
+        let mangas = match &self.state.listing {
+            Some(listing) => {
+                let mangas = listing.mangas.iter().collect::<Vec<&Manga>>();
                 html! {
-                    {for mangas.chunks(2).map(|chunk| column_spread(chunk))}
+                    <>
+                        {for mangas.chunks(2).map(|chunk| column_spread(chunk))}
+                        {self.pagination()}
+                    </>
                 }
             }
             None => progress_bar(),
+        };
+        html! {
+            <>
+                {self.latest_releases()}
+                {self.filters()}
+                {mangas}
+            </>
         }
     }
 }
 
+impl MangaList {
+    /// Hidden until the releases arrive, the catalog below has its own progress bar
+    fn latest_releases(&self) -> Html {
+        let chapters = match &self.state.recent_chapters {
+            Some(chapters) if !chapters.is_empty() => chapters,
+            _ => return html! {},
+        };
+        html! {
+            <section class="block">
+                <h2 class="title is-5">{"Latest releases"}</h2>
+                <table class="table is-fullwidth is-striped is-narrow">
+                    <tbody>
+                        {for chapters.iter().map(recent_chapter_entry)}
+                    </tbody>
+                </table>
+            </section>
+        }
+    }
+
+    fn filters(&self) -> Html {
+        let on_sort = self.link.callback(|change: ChangeData| {
+            let sort = match change {
+                ChangeData::Select(select) => SORTS
+                    .iter()
+                    .find(|(_, value, _)| *value == select.value())
+                    .map(|(sort, _, _)| *sort),
+                _ => None,
+            };
+            Msg::SetSort(sort.unwrap_or_default())
+        });
+        let on_status = self.link.callback(|change: ChangeData| match change {
+            ChangeData::Select(select) => Msg::SetStatus(select.value().parse().ok()),
+            _ => Msg::SetStatus(None),
+        });
+        html! {
+            <div class="level">
+                <div class="level-left">
+                    <div class="level-item select">
+                        <select onchange=on_sort>
+                            {for SORTS.iter().map(|(sort, value, label)| html! {
+                                <option value=value selected={*sort == self.state.sort}>{label}</option>
+                            })}
+                        </select>
+                    </div>
+                    <div class="level-item select">
+                        <select onchange=on_status>
+                            <option value="" selected=self.state.status.is_none()>{"Any status"}</option>
+                            {for STATUSES.iter().map(|status| html! {
+                                <option value=status.as_str() selected={Some(*status) == self.state.status}>
+                                    {status.as_str()}
+                                </option>
+                            })}
+                        </select>
+                    </div>
+                </div>
+            </div>
+        }
+    }
+
+    fn pagination(&self) -> Html {
+        let page_count = self.state.page_count();
+        if page_count < 2 {
+            return html! {};
+        }
+        let page = self.state.page;
+        let go_to = |target: u32| self.link.callback(move |_| Msg::GoToPage(target));
+        html! {
+            <nav class="pagination is-centered" role="navigation" aria-label="pagination">
+                <button class="button pagination-previous" disabled={page == 0}
+                    onclick=go_to(page.saturating_sub(1))>{"Previous"}</button>
+                <button class="button pagination-next" disabled={page + 1 >= page_count}
+                    onclick=go_to(page + 1)>{"Next"}</button>
+                <ul class="pagination-list">
+                    {for (0..page_count).map(|target| {
+                        let class = if target == page {
+                            "button pagination-link is-current"
+                        } else {
+                            "button pagination-link"
+                        };
+                        html! {
+                            <li><button class=class onclick=go_to(target)>{target + 1}</button></li>
+                        }
+                    })}
+                </ul>
+            </nav>
+        }
+    }
+}
+
+fn recent_chapter_entry(recent: &RecentChapter) -> Html {
+    type Anchor = RouterAnchor<AppRoute>;
+    let chapter = &recent.chapter;
+    let released = chapter.release_date.unwrap_or(chapter.creation_date);
+    html! {
+        <tr>
+            <td>
+                <Anchor route=AppRoute::ChapterList { manga_id: recent.manga.manga_id }>
+                    {&recent.manga.manga_name}
+                </Anchor>
+            </td>
+            <td>
+                <Anchor route=AppRoute::MangaChapter {
+                    manga_id: chapter.manga_id,
+                    chapter_number: chapter.chapter_number.to_string(),
+                }>
+                    {"Chapter "}{&chapter.chapter_number}{": "}{&chapter.chapter_name}
+                </Anchor>
+            </td>
+            <td>{released.format("%Y-%m-%d").to_string()}</td>
+        </tr>
+    }
+}
+
 /// Spreads a chunk as a set of columns
 fn column_spread(mangas: &[&Manga]) -> Html {
     html! {
@@ -80,9 +270,10 @@ fn column_spread(mangas: &[&Manga]) -> Html {
 }
 
 fn manga_entry(manga: &Manga) -> Html {
+    let cover_image_url = manga.cover_image_url.as_deref().unwrap_or("");
     html! {
         <RouterAnchor<AppRoute> route=AppRoute::ChapterList { manga_id: manga.manga_id }>
-            <img class="image-link" src=&manga.cover_image_url alt=&manga.manga_name title=&manga.manga_name />
+            <img class="image-link" src=cover_image_url alt=&manga.manga_name title=&manga.manga_name />
         </RouterAnchor<AppRoute>>
     }
 }
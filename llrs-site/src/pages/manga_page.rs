@@ -5,10 +5,10 @@ use crate::agents::{
 };
 use crate::route::AppRoute;
 use js_sys::Date;
-use llrs_model::{Chapter, Page};
+use llrs_model::{Chapter, ChapterNumber, Page};
 use log::*;
 use serde::{Deserialize, Serialize};
-use std::{cell::RefCell, collections::VecDeque, rc::Rc, time::Duration};
+use std::{collections::VecDeque, rc::Rc, time::Duration};
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
 use web_sys::{HtmlImageElement, ScrollBehavior, ScrollToOptions, Window};
@@ -42,6 +42,11 @@ pub(crate) struct State {
     prior_render_time_seconds: f64,
     prior_scroll_y: f64,
     preloader_closure: Option<Closure<dyn FnMut()>>,
+    /// Only one of the preloader's load and error handlers ever runs
+    preloader_error_closure: Option<Closure<dyn FnMut()>>,
+    /// Index into `Page::urls` of the mirror each page is shown from,
+    /// moves on whenever the reader or the preloader fails to load that mirror
+    mirror_indices: Vec<usize>,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -70,6 +75,15 @@ pub(crate) enum Msg {
     PreloadImage {
         page_index: usize,
     },
+    PreloadNextImage,
+    PreloadFailed {
+        page_index: usize,
+        mirror_index: usize,
+    },
+    ImageFailed {
+        page_index: usize,
+        mirror_index: usize,
+    },
     MangaAgentResponse(MangaAgentResponse),
     UserAgentResponse(UserAgentResponse),
     PageBack {
@@ -133,6 +147,8 @@ impl Component for MangaPage {
             prior_render_time_seconds: prior_load_date_time + 5000f64,
             prior_scroll_y: 0f64,
             preloader_closure: None,
+            preloader_error_closure: None,
+            mirror_indices: vec![],
         };
 
         Self {
@@ -174,7 +190,28 @@ impl Component for MangaPage {
     fn update(&mut self, msg: Self::Message) -> ShouldRender {
         info!("{:?}", msg);
         match msg {
-            Msg::PreloadImage { page_index } => self.preload_image_and_set_next(page_index),
+            Msg::PreloadImage { page_index } => self.preload_image(page_index),
+            Msg::PreloadNextImage => match self.state.preload_queue.pop_front() {
+                Some(page_index) => self.preload_image(page_index),
+                None => false,
+            },
+            Msg::PreloadFailed {
+                page_index,
+                mirror_index,
+            } => {
+                // The reader shows the same mirror, so it's re-rendered with the next one too
+                if self.next_mirror(page_index, mirror_index) {
+                    self.preload_image(page_index);
+                    true
+                } else {
+                    self.link.send_message(Msg::PreloadNextImage);
+                    false
+                }
+            }
+            Msg::ImageFailed {
+                page_index,
+                mirror_index,
+            } => self.next_mirror(page_index, mirror_index),
             Msg::MangaAgentResponse(response) => self.handle_manga_response(response),
             Msg::PageBack {
                 current_page_number,
@@ -227,7 +264,7 @@ impl MangaPage {
         self.props
             .page_number
             .checked_sub(1)
-            .map_or(None, |previous_pn| {
+            .and_then(|previous_pn| {
                 if previous_pn == 0 {
                     None
                 } else {
@@ -238,28 +275,25 @@ impl MangaPage {
 
     /// Returns None if it would be paging past available pages
     fn next_page_number(&self) -> Option<usize> {
-        self.props
-            .page_number
-            .checked_add(1)
-            .map_or(None, |next_pn| {
-                if next_pn
-                    > self
-                        .state
-                        .pages
-                        .as_ref()
-                        .map_or(self.props.page_number, |pages| pages.len())
-                {
-                    None
-                } else {
-                    Some(next_pn)
-                }
-            })
+        self.props.page_number.checked_add(1).and_then(|next_pn| {
+            if next_pn
+                > self
+                    .state
+                    .pages
+                    .as_ref()
+                    .map_or(self.props.page_number, |pages| pages.len())
+            {
+                None
+            } else {
+                Some(next_pn)
+            }
+        })
     }
 
     fn scroll_to_manga_page_top(&self, page_number: usize, scroll_behavior: ScrollBehavior) {
         if let Some(window) = self.window.as_ref() {
             if let Some(doc) = window.document() {
-                let mut scroll_to_options = ScrollToOptions::new();
+                let scroll_to_options = ScrollToOptions::new();
                 let element_to_scroll_to_top = match self.state.view_format {
                     ViewFormat::Single => "manga-image".to_owned(),
                     ViewFormat::Long => format!("manga-page-{}", page_number),
@@ -267,8 +301,8 @@ impl MangaPage {
                 let manga_page_top = doc
                     .get_element_by_id(element_to_scroll_to_top.as_str())
                     .map_or(0.0, |element| element.get_bounding_client_rect().top());
-                scroll_to_options.top(manga_page_top);
-                scroll_to_options.behavior(scroll_behavior);
+                scroll_to_options.set_top(manga_page_top);
+                scroll_to_options.set_behavior(scroll_behavior);
                 window.scroll_by_with_scroll_to_options(&scroll_to_options);
             }
         }
@@ -309,7 +343,7 @@ impl MangaPage {
                     match self
                         .state
                         .is_visible
-                        .get_mut(previous_page_number.checked_sub(1).unwrap_or(0))
+                        .get_mut(previous_page_number.saturating_sub(1))
                     {
                         Some(previous_page_visibility) if !*previous_page_visibility => {
                             *previous_page_visibility = true;
@@ -388,10 +422,10 @@ impl MangaPage {
                     for pages.iter()
                         .enumerate()
                         .filter(|(index, _)| *self.state.is_visible.get(*index).unwrap_or(&false))
-                        .map(|(_, page)| self.manga_page(Some(page)))
+                        .map(|(index, page)| self.manga_page(index, Some(page)))
                 },
                 ViewFormat::Single => html! {
-                    self.manga_page(pages.get(page_index))
+                    self.manga_page(page_index, pages.get(page_index))
                 },
             };
             html! {
@@ -405,13 +439,65 @@ impl MangaPage {
         }
     }
 
-    fn manga_page(&self, page: Option<&Page>) -> Html {
+    /// Index into `Page::urls` of the mirror `page_index` is currently shown from.
+    fn mirror_index(&self, page_index: usize) -> usize {
+        self.state
+            .mirror_indices
+            .get(page_index)
+            .copied()
+            .unwrap_or(0)
+    }
+
+    fn mirror_url<'a>(&self, page_index: usize, page: &'a Page) -> &'a str {
+        page.urls()
+            .nth(self.mirror_index(page_index))
+            .unwrap_or(&page.url_string)
+    }
+
+    /// Moves a page off the mirror that failed to load,
+    /// false when every mirror of the page has failed.
+    fn next_mirror(&mut self, page_index: usize, failed_mirror_index: usize) -> bool {
+        let mirror_count = match self
+            .state
+            .pages
+            .as_ref()
+            .and_then(|pages| pages.get(page_index))
+        {
+            Some(page) => page.urls().count(),
+            None => return false,
+        };
+        let mirror_index = match self.state.mirror_indices.get_mut(page_index) {
+            Some(mirror_index) => mirror_index,
+            None => return false,
+        };
+        // The reader and the preloader can both fail the same mirror, only skip it once
+        if *mirror_index != failed_mirror_index {
+            return *mirror_index < mirror_count;
+        }
+        if failed_mirror_index + 1 < mirror_count {
+            *mirror_index += 1;
+            true
+        } else {
+            error!(
+                "Every mirror of page {} failed to load",
+                page_index.saturating_add(1)
+            );
+            false
+        }
+    }
+
+    fn manga_page(&self, page_index: usize, page: Option<&Page>) -> Html {
         if let Some(page) = page {
             let current_page_number = page.page_number as usize;
             let onload_callback = match self.state.view_format {
                 ViewFormat::Single => yew::callback::Callback::noop(),
                 ViewFormat::Long => self.link.callback(|_| Msg::PageRepositioned),
             };
+            let mirror_index = self.mirror_index(page_index);
+            let onerror_callback = self.link.callback(move |_| Msg::ImageFailed {
+                page_index,
+                mirror_index,
+            });
 
             html! {
                 <div id=format!("manga-page-{}", page.page_number) class="container">
@@ -420,9 +506,10 @@ impl MangaPage {
                     <div class="forward-pager"
                         onclick=self.link.callback(move |_| Msg::PageForward { current_page_number }) />
                     <img id="manga-image"
-                         src=&page.url_string
+                         src=self.mirror_url(page_index, page)
                          alt=format!("Page {} Image", &page.page_number)
                          onload=onload_callback
+                         onerror=onerror_callback
                      />
                 </div>
             }
@@ -431,41 +518,43 @@ impl MangaPage {
         }
     }
 
-    fn preload_image_and_set_next(&mut self, page_index: usize) -> bool {
-        match self.state.pages.as_ref() {
-            Some(pages) if pages.len() > 0 => {
-                if let (Some(page), Some(image_element)) = (pages.get(page_index), &self.prefetcher)
-                {
-                    let link = self.link.clone();
-                    let load_next_page_closure =
-                        self.state.preload_queue.pop_front().map(|next_page_index| {
-                            // Once closures cleans up their resources after one call
-                            Closure::once(Box::new(move || {
-                                link.send_message(Msg::PreloadImage {
-                                    page_index: next_page_index,
-                                });
-                            }))
-                        });
-                    if let Some(closure) = &load_next_page_closure {
-                        image_element.set_onload(Some(closure.as_ref().unchecked_ref()));
-                    } else {
-                        image_element.set_onload(None);
-                    }
+    /// Loads the current mirror of `page_index` off screen,
+    /// then moves on to the next queued page or retries the next mirror.
+    fn preload_image(&mut self, page_index: usize) -> bool {
+        let url = match (self.state.pages.as_ref(), &self.prefetcher) {
+            (Some(pages), Some(_)) => match pages.get(page_index) {
+                Some(page) => self.mirror_url(page_index, page).to_owned(),
+                None => return false,
+            },
+            _ => return false,
+        };
+        let mirror_index = self.mirror_index(page_index);
+        let load_link = self.link.clone();
+        let error_link = self.link.clone();
+        // Once closures cleans up their resources after one call
+        let load_closure = Closure::once(Box::new(move || {
+            load_link.send_message(Msg::PreloadNextImage);
+        }) as Box<dyn FnOnce()>);
+        let error_closure = Closure::once(Box::new(move || {
+            error_link.send_message(Msg::PreloadFailed {
+                page_index,
+                mirror_index,
+            });
+        }) as Box<dyn FnOnce()>);
+        if let Some(image_element) = &self.prefetcher {
+            image_element.set_onload(Some(load_closure.as_ref().unchecked_ref()));
+            image_element.set_onerror(Some(error_closure.as_ref().unchecked_ref()));
+            image_element.set_src(&url);
+        }
 
-                    // To avoid a potential memory leak from using `closure.forget()`
-                    // in the case of destroying this instance before the image finishes loading,
-                    // we save the closure here so that it can get naturally cleaned up.
-                    self.state.preloader_closure = load_next_page_closure;
-                    image_element.set_src(&page.url_string);
-                    match self.state.view_format {
-                        ViewFormat::Single => false,
-                        ViewFormat::Long => true,
-                    }
-                } else {
-                    false
-                }
-            }
-            _ => false,
+        // To avoid a potential memory leak from using `closure.forget()`
+        // in the case of destroying this instance before the image finishes loading,
+        // we save the closures here so that they can get naturally cleaned up.
+        self.state.preloader_closure = Some(load_closure);
+        self.state.preloader_error_closure = Some(error_closure);
+        match self.state.view_format {
+            ViewFormat::Single => false,
+            ViewFormat::Long => true,
         }
     }
 
@@ -492,7 +581,7 @@ impl MangaPage {
                             chapter_number: self.props.chapter_number.to_owned(),
                             page_number: pages.len(),
                         })
-                    } else if pages.len() == 0 {
+                    } else if pages.is_empty() {
                         Some(AppRoute::NotFound(Permissive(Some(format!(
                             "Manga with ID {} and Chapter {} not found",
                             self.props.manga_id, self.props.chapter_number
@@ -510,7 +599,7 @@ impl MangaPage {
                 // Reset queue and load up new preloads
                 // from current page to last, then current to first
                 self.state.preload_queue.clear();
-                let starting_page_index = self.props.page_number.checked_sub(1).unwrap_or(0);
+                let starting_page_index = self.props.page_number.saturating_sub(1);
                 for page_number in starting_page_index..pages.len() {
                     self.state.preload_queue.push_back(page_number);
                 }
@@ -523,10 +612,11 @@ impl MangaPage {
                     self.link.send_message(Msg::PreloadImage { page_index });
                 }
 
+                self.state.mirror_indices = vec![0; pages.len()];
                 self.state.is_visible = pages
                     .iter()
                     .enumerate()
-                    .map(|(index, _)| self.props.page_number.checked_sub(1).unwrap_or(0) == index)
+                    .map(|(index, _)| self.props.page_number.saturating_sub(1) == index)
                     .collect();
                 self.state.pages = Some(pages);
                 if let Some(route) = route {
@@ -676,50 +766,36 @@ fn set_and_return_repositioning_handler(
     }
 }
 
+/// The chapter after the current one in `ChapterNumber` order,
+/// stays on the current chapter when it's the last.
 fn get_next_chapter_number(
     chapter_list: &Rc<Vec<Chapter>>,
     current_chapter_number: String,
 ) -> String {
-    let mut iter = chapter_list.iter();
-    let last: RefCell<Option<&Chapter>> = RefCell::new(chapter_list.first());
-    while let Some(chapter) = iter.next() {
-        if chapter.chapter_number == current_chapter_number {
-            return iter.next().map_or(
-                last.into_inner()
-                    .map_or(current_chapter_number.to_owned(), |ch| {
-                        ch.chapter_number.to_owned()
-                    }),
-                |ch| ch.chapter_number.to_owned(),
-            );
-        }
-        last.replace(Some(chapter));
-    }
-    last.into_inner()
-        .map_or(current_chapter_number.to_owned(), |ch| {
-            ch.chapter_number.to_owned()
-        })
+    let current_chapter_number = ChapterNumber::from(current_chapter_number);
+    chapter_list
+        .iter()
+        .map(|chapter| &chapter.chapter_number)
+        .filter(|chapter_number| **chapter_number > current_chapter_number)
+        .min()
+        .unwrap_or(&current_chapter_number)
+        .to_string()
 }
 
+/// The chapter before the current one in `ChapterNumber` order,
+/// stays on the current chapter when it's the first.
 fn get_previous_chapter_number(
     chapter_list: &Rc<Vec<Chapter>>,
     current_chapter_number: String,
 ) -> String {
-    let mut iter = chapter_list.iter();
-    let prev: RefCell<Option<&Chapter>> = RefCell::new(chapter_list.first());
-    while let Some(chapter) = iter.next() {
-        if chapter.chapter_number == current_chapter_number {
-            return prev
-                .into_inner()
-                .map_or(current_chapter_number.to_owned(), |ch| {
-                    ch.chapter_number.to_owned()
-                });
-        }
-        prev.replace(Some(chapter));
-    }
-    prev.into_inner()
-        .map_or(current_chapter_number.to_owned(), |ch| {
-            ch.chapter_number.to_owned()
-        })
+    let current_chapter_number = ChapterNumber::from(current_chapter_number);
+    chapter_list
+        .iter()
+        .map(|chapter| &chapter.chapter_number)
+        .filter(|chapter_number| **chapter_number < current_chapter_number)
+        .max()
+        .unwrap_or(&current_chapter_number)
+        .to_string()
 }
 
 // TODO: Make a bunch of useless traits that are implemented by default